@@ -1,5 +1,15 @@
 use eframe::egui;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::backup::{BackupManager, BufferSnapshot};
 use crate::config::EditorConfig;
+use crate::file_filter::FileFilter;
+use crate::document::encoding::FileEncoding;
+use crate::session::Session;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::document::writer;
+use crate::ui::notifications::{NotificationAction, NotificationLevel};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::workspace::{Workspace, WORKSPACE_EXTENSION};
 #[cfg(target_arch = "wasm32")]
 use std::sync::Arc;
 #[cfg(target_arch = "wasm32")]
@@ -13,17 +23,49 @@ pub struct ZenEditor {
     pub(crate) code_editor: crate::ui::editor::CodeEditor,
     config: EditorConfig,
     show_settings: bool,
+    session: Session,
+    last_seen_file: Option<std::path::PathBuf>,
+    last_seen_project: Option<std::path::PathBuf>,
+    /// The `*.zen-workspace` file the open folders came from, if any.
+    workspace_file: Option<std::path::PathBuf>,
+    #[cfg(not(target_arch = "wasm32"))]
+    backups: BackupManager,
+    #[cfg(not(target_arch = "wasm32"))]
+    pending_restores: Vec<BufferSnapshot>,
+    /// Whether the pending restores were kept by a hot exit rather than left by a crash.
+    #[cfg(not(target_arch = "wasm32"))]
+    restores_from_hot_exit: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    show_close_prompt: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    allow_close: bool,
     #[cfg(target_arch = "wasm32")]
-    pending_file_content: Arc<Mutex<Option<(String, String)>>>,
+    pending_file_content: Arc<Mutex<Option<(String, Vec<u8>)>>>,
 }
 
 impl Default for ZenEditor {
     fn default() -> Self {
         let config = EditorConfig::load();
+        #[cfg(not(target_arch = "wasm32"))]
+        let backups = BackupManager::default();
         let mut editor = Self {
             code_editor: crate::ui::editor::CodeEditor::default(),
             config,
             show_settings: false,
+            session: Session::default(),
+            last_seen_file: None,
+            last_seen_project: None,
+            workspace_file: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            pending_restores: backups.pending_restores(),
+            #[cfg(not(target_arch = "wasm32"))]
+            restores_from_hot_exit: backups.take_hot_exit(),
+            #[cfg(not(target_arch = "wasm32"))]
+            backups,
+            #[cfg(not(target_arch = "wasm32"))]
+            show_close_prompt: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            allow_close: false,
             #[cfg(target_arch = "wasm32")]
             pending_file_content: Arc::new(Mutex::new(None)),
         };
@@ -32,12 +74,177 @@ impl Default for ZenEditor {
             .find(|t| t.name == editor.config.default_theme) {
             editor.code_editor.set_theme(theme.clone());
         }
+        editor.code_editor.set_file_filter(FileFilter::new(&editor.config.files));
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            editor.code_editor.set_language_servers(editor.config.language_servers.clone());
+            editor.code_editor.set_formatters(editor.config.formatters.clone());
+        }
 
         editor
     }
 }
 
 impl ZenEditor {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let mut editor = Self::default();
+
+        if let Some(session) = cc.storage.and_then(|s| eframe::get_value::<Session>(s, Session::STORAGE_KEY)) {
+            editor.restore_session(session);
+        }
+
+        editor
+    }
+
+    fn restore_session(&mut self, session: Session) {
+        if let Some(width) = session.side_panel_width {
+            self.code_editor.side_panel_width = width;
+        }
+        self.code_editor.set_document_view_states(session.documents.clone());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let workspace = session.workspace_file.as_ref().filter(|p| p.is_file());
+            if let Some(path) = workspace {
+                self.open_workspace(path.clone());
+            } else if let Some(root) = session.project_root.as_ref().filter(|p| p.is_dir()) {
+                self.code_editor.open_project(root.clone());
+                for extra in session.extra_roots.iter().filter(|p| p.is_dir()) {
+                    self.code_editor.add_project_root(extra.clone());
+                }
+                self.code_editor.take_workspace_changed();
+            }
+            if let Some(path) = session.active_document.as_ref().filter(|p| p.is_file()) {
+                self.code_editor.load_file(path);
+            }
+        }
+
+        self.last_seen_file = self.code_editor.selected_file.clone();
+        self.last_seen_project = self.current_project();
+        self.session = session;
+    }
+
+    /// Keeps the recent project and file lists in step with whatever the editor has open.
+    fn track_recent_items(&mut self) {
+        if self.code_editor.selected_file != self.last_seen_file {
+            self.last_seen_file = self.code_editor.selected_file.clone();
+            if let Some(path) = &self.last_seen_file {
+                self.session.note_file(path);
+            }
+        }
+
+        let project = self.current_project();
+        if project != self.last_seen_project {
+            if let Some(path) = &project {
+                self.session.note_project(path);
+            }
+            self.last_seen_project = project;
+        }
+    }
+
+    /// What "Open Recent" should bring back: the workspace file, or the single project folder.
+    fn current_project(&self) -> Option<std::path::PathBuf> {
+        self.workspace_file.clone().or_else(|| self.code_editor.project_root().cloned())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_project(&mut self, path: std::path::PathBuf) {
+        self.workspace_file = None;
+        self.code_editor.open_project(path);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_workspace(&mut self, path: std::path::PathBuf) {
+        match Workspace::load(&path) {
+            Ok(workspace) => {
+                self.code_editor.open_workspace(&workspace);
+                self.code_editor.take_workspace_changed();
+                self.workspace_file = Some(path);
+            }
+            Err(e) => self.code_editor.notifications.error(e),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_workspace_dialog(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Zen workspace", &[WORKSPACE_EXTENSION])
+            .pick_file()
+        {
+            self.open_workspace(path);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_workspace_as(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Zen workspace", &[WORKSPACE_EXTENSION])
+            .set_file_name(format!("project.{}", WORKSPACE_EXTENSION))
+            .save_file()
+        {
+            match self.code_editor.workspace().save(&path) {
+                Ok(()) => self.workspace_file = Some(path),
+                Err(e) => self.code_editor.notifications.error(e),
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn add_folder_dialog(&mut self) {
+        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+            self.code_editor.add_project_root(path);
+        }
+    }
+
+    /// Writes folder additions and removals back to the open workspace file.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_workspace_if_changed(&mut self) {
+        if !self.code_editor.take_workspace_changed() {
+            return;
+        }
+        if let Some(path) = &self.workspace_file {
+            if let Err(e) = self.code_editor.workspace().save(path) {
+                self.code_editor.notifications.error(e);
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_recent_menu(&mut self, ui: &mut egui::Ui) {
+        let has_recent = !self.session.recent_projects.is_empty() || !self.session.recent_files.is_empty();
+
+        ui.add_enabled_ui(has_recent, |ui| {
+            ui.menu_button("Open Recent", |ui| {
+                for path in self.session.recent_projects.clone() {
+                    let is_workspace = Workspace::is_workspace_file(&path);
+                    let icon = if is_workspace { "🗂" } else { "📁" };
+                    if ui.button(format!("{} {}", icon, path.display())).clicked() {
+                        if is_workspace {
+                            self.open_workspace(path);
+                        } else {
+                            self.open_project(path);
+                        }
+                        ui.close();
+                    }
+                }
+                if !self.session.recent_projects.is_empty() && !self.session.recent_files.is_empty() {
+                    ui.separator();
+                }
+                for path in self.session.recent_files.clone() {
+                    if ui.button(format!("📄 {}", path.display())).clicked() {
+                        self.code_editor.load_file(&path);
+                        ui.close();
+                    }
+                }
+                ui.separator();
+                if ui.button("Clear Recently Opened").clicked() {
+                    self.session.clear_recent();
+                    ui.close();
+                }
+            });
+        });
+    }
+
     fn should_use_custom_frame(&self) -> bool {
         #[cfg(target_arch = "wasm32")]
         return false;
@@ -48,9 +255,8 @@ impl ZenEditor {
     #[cfg(target_arch = "wasm32")]
     fn handle_pending_file_operations(&mut self) {
         if let Ok(mut pending) = self.pending_file_content.try_lock() {
-            if let Some((filename, content)) = pending.take() {
-                self.code_editor.code = content;
-                self.code_editor.selected_file = Some(std::path::PathBuf::from(filename));
+            if let Some((filename, data)) = pending.take() {
+                self.code_editor.load_bytes(std::path::PathBuf::from(filename), &data);
             }
         }
     }
@@ -136,8 +342,7 @@ impl ZenEditor {
         title_ui.menu_button(egui::RichText::new("≡").monospace().size(14.0), |ui| {
             ui.menu_button("File", |ui| {
                 if ui.button("New File").clicked() {
-                    self.code_editor.code.clear();
-                    self.code_editor.selected_file = None;
+                    self.code_editor.new_file();
                     ui.close();
                 }
                 if ui.button("Open File...").clicked() {
@@ -148,6 +353,24 @@ impl ZenEditor {
                     self.open_project_dialog();
                     ui.close();
                 }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    if ui.button("Add Folder to Workspace...").clicked() {
+                        self.add_folder_dialog();
+                        ui.close();
+                    }
+                    if ui.button("Open Workspace...").clicked() {
+                        self.open_workspace_dialog();
+                        ui.close();
+                    }
+                    let has_folders = self.code_editor.project_root().is_some();
+                    if ui.add_enabled(has_folders, egui::Button::new("Save Workspace As...")).clicked() {
+                        self.save_workspace_as();
+                        ui.close();
+                    }
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                self.open_recent_menu(ui);
                 ui.separator();
                 if ui.button("Save").clicked() {
                     self.save_current_file();
@@ -159,8 +382,24 @@ impl ZenEditor {
                 }
                 ui.separator();
                 #[cfg(not(target_arch = "wasm32"))]
+                {
+                    let can_compare = self.code_editor.selected_file.is_some() && self.code_editor.is_dirty();
+                    if ui.add_enabled(can_compare, egui::Button::new("Compare with Saved")).clicked() {
+                        self.code_editor.compare_with_saved();
+                        ui.close();
+                    }
+                }
+                if ui.button("Compare with Clipboard").clicked() {
+                    self.code_editor.compare_with_clipboard(ui.ctx());
+                    ui.close();
+                }
+                ui.separator();
+                self.code_editor.encoding_menu(ui);
+                self.code_editor.line_ending_menu(ui);
+                ui.separator();
+                #[cfg(not(target_arch = "wasm32"))]
                 if ui.button("Exit").clicked() {
-                    std::process::exit(0);
+                    ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
                 }
             });
 
@@ -181,6 +420,87 @@ impl ZenEditor {
                 ui.add_enabled_ui(false, |ui| {
                     let _ = ui.button("Paste");
                 });
+                ui.separator();
+                if ui.button("Rename Symbol...").clicked() {
+                    self.code_editor.rename_symbol();
+                    ui.close();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Code Actions...").clicked() {
+                    self.code_editor.show_code_actions();
+                    ui.close();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    ui.separator();
+                    if ui.button("Format Document").clicked() {
+                        self.code_editor.format_document();
+                        ui.close();
+                    }
+                    if ui.button("Format Selection").clicked() {
+                        self.code_editor.format_selection();
+                        ui.close();
+                    }
+                }
+            });
+
+            ui.menu_button("View", |ui| {
+                if ui.button("Outline").clicked() {
+                    self.code_editor.toggle_outline();
+                    ui.close();
+                }
+                if ui.button("Problems").clicked() {
+                    self.code_editor.toggle_problems();
+                    ui.close();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Terminal").clicked() {
+                    self.code_editor.toggle_terminal(ui.ctx());
+                    ui.close();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("New Terminal").clicked() {
+                    self.code_editor.new_terminal(ui.ctx());
+                    ui.close();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Output").clicked() {
+                    self.code_editor.toggle_task_output();
+                    ui.close();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    ui.separator();
+                    if ui.button("Run Task...").clicked() {
+                        self.code_editor.show_tasks();
+                        ui.close();
+                    }
+                    if ui.add_enabled(self.code_editor.is_task_running(), egui::Button::new("Stop Task")).clicked() {
+                        self.code_editor.stop_task();
+                        ui.close();
+                    }
+                }
+                ui.separator();
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    if ui.button("Go to File...").clicked() {
+                        self.code_editor.show_go_to_file(ui.ctx());
+                        ui.close();
+                    }
+                    if ui.button("Find in Files...").clicked() {
+                        self.code_editor.show_find_in_files(ui.ctx());
+                        ui.close();
+                    }
+                }
+                if ui.button("Go to Symbol in File...").clicked() {
+                    self.code_editor.show_document_symbols();
+                    ui.close();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Go to Symbol in Workspace...").clicked() {
+                    self.code_editor.show_workspace_symbols();
+                    ui.close();
+                }
             });
 
             ui.menu_button("Settings", |ui| {
@@ -232,14 +552,70 @@ impl ZenEditor {
         if ctx.input(|i| i.modifiers.matches_exact(control_key) && i.key_pressed(egui::Key::S)) {
             self.save_current_file();
         }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if ctx.input(|i| i.modifiers.matches_exact(control_key | egui::Modifiers::SHIFT) && i.key_pressed(egui::Key::G)) {
+            self.code_editor.show_source_control();
+        }
+
+        if ctx.input(|i| i.modifiers.matches_exact(control_key | egui::Modifiers::SHIFT) && i.key_pressed(egui::Key::M)) {
+            self.code_editor.toggle_problems();
+        }
+
+        if ctx.input(|i| i.modifiers.matches_exact(control_key | egui::Modifiers::SHIFT) && i.key_pressed(egui::Key::O)) {
+            self.code_editor.show_document_symbols();
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if ctx.input(|i| i.modifiers.matches_exact(control_key) && i.key_pressed(egui::Key::P)) {
+            self.code_editor.show_go_to_file(ctx);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if ctx.input(|i| i.modifiers.matches_exact(control_key | egui::Modifiers::SHIFT) && i.key_pressed(egui::Key::F)) {
+            self.code_editor.show_find_in_files(ctx);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if ctx.input(|i| i.modifiers.matches_exact(control_key) && i.key_pressed(egui::Key::T)) {
+            self.code_editor.show_workspace_symbols();
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if ctx.input(|i| i.modifiers.matches_exact(control_key | egui::Modifiers::SHIFT) && i.key_pressed(egui::Key::B)) {
+            self.code_editor.show_tasks();
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if ctx.input(|i| i.modifiers.matches_exact(egui::Modifiers::CTRL) && i.key_pressed(egui::Key::Backtick)) {
+            self.code_editor.toggle_terminal(ctx);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if ctx.input(|i| i.modifiers.matches_exact(egui::Modifiers::SHIFT | egui::Modifiers::ALT) && i.key_pressed(egui::Key::F)) {
+            self.code_editor.format_document();
+        }
     }
 
     #[cfg(not(target_arch = "wasm32"))]
     fn save_current_file(&mut self) {
-        if let Some(path) = &self.code_editor.selected_file {
-            if let Err(e) = std::fs::write(path, &self.code_editor.code) {
-                eprintln!("Failed to save file: {}", e);
+        self.save_document(self.config.format_on_save);
+    }
+
+    /// Saves the open document, first formatting it if `format` is set and a formatter
+    /// handles it. The write then happens once the formatter is done, even if it fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_document(&mut self, format: bool) {
+        if self.code_editor.read_only {
+            self.code_editor.notifications.warning("This file is open read-only and cannot be saved");
+            return;
+        }
+
+        if let Some(path) = self.code_editor.selected_file.clone() {
+            if format && self.code_editor.format_before_save() {
+                return;
             }
+            self.write_document(&path, false);
         } else {
             self.save_file_as();
         }
@@ -250,24 +626,129 @@ impl ZenEditor {
         self.save_file_as();
     }
 
+    fn save_with_encoding(&mut self, encoding: FileEncoding) {
+        if let Err(e) = encoding.encode(&self.code_editor.code) {
+            self.code_editor.notifications.warning(format!("Not saved: {}", e));
+            return;
+        }
+
+        // Only becomes the document's encoding once it was written that way.
+        self.code_editor.set_save_encoding(Some(encoding));
+        self.save_current_file();
+    }
+
+    /// Writes the buffer to `path` through the atomic writer. Unless `overwrite` is set,
+    /// refuses to clobber a file that changed on disk since it was loaded.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write_document(&mut self, path: &std::path::Path, overwrite: bool) -> bool {
+        let bytes = match self.code_editor.encoded_contents() {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.code_editor.notifications.warning(format!("Not saved: {}", e));
+                return false;
+            }
+        };
+
+        if !overwrite && self.code_editor.changed_on_disk(path) {
+            self.code_editor.notifications.push_with_actions(
+                NotificationLevel::Warning,
+                format!("{} was changed on disk since it was opened", path.display()),
+                vec![
+                    ("Overwrite".into(), NotificationAction::OverwriteSave(path.to_path_buf())),
+                    ("Reload".into(), NotificationAction::ReloadFile(path.to_path_buf())),
+                ],
+            );
+            return false;
+        }
+
+        match writer::write_atomic(path, &bytes) {
+            Ok(()) => {
+                self.code_editor.mark_saved(path.to_path_buf());
+                true
+            }
+            Err(e) => {
+                self.code_editor.notifications.push_with_actions(
+                    NotificationLevel::Error,
+                    format!("Failed to save {}: {}", path.display(), e),
+                    vec![("Retry".into(), NotificationAction::RetrySave(path.to_path_buf()))],
+                );
+                false
+            }
+        }
+    }
+
+    fn save_config(&mut self) {
+        if let Err(e) = self.config.save() {
+            self.code_editor.notifications.error(format!("Failed to save settings: {}", e));
+        }
+    }
+
+    fn handle_notification_action(&mut self, action: NotificationAction) {
+        // Notifications with actions stay until dismissed, so the file they were about may
+        // no longer be the one open; acting then would write or replace the wrong buffer.
+        if let Some(path) = action.path() {
+            if self.code_editor.selected_file.as_deref() != Some(path) {
+                self.code_editor.notifications.warning(format!("{} is no longer open", path.display()));
+                return;
+            }
+        }
+
+        match action {
+            #[cfg(not(target_arch = "wasm32"))]
+            NotificationAction::RetrySave(path) => {
+                self.write_document(&path, false);
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            NotificationAction::OverwriteSave(path) => {
+                self.write_document(&path, true);
+            }
+            #[cfg(target_arch = "wasm32")]
+            NotificationAction::RetrySave(_) | NotificationAction::OverwriteSave(_) => {
+                self.save_file_as();
+            }
+            NotificationAction::ReloadFile(path) => {
+                self.code_editor.load_file(&path);
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            NotificationAction::UndoWorkspaceEdit => {
+                self.code_editor.undo_workspace_edit();
+            }
+            NotificationAction::Dismiss => {}
+        }
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     fn save_file_as(&mut self) {
+        if self.code_editor.read_only {
+            self.code_editor.notifications.warning("This file is open read-only and cannot be saved");
+            return;
+        }
+
         if let Some(path) = rfd::FileDialog::new()
             .add_filter("Text files", &["txt"])
             .add_filter("Rust files", &["rs"])
             .add_filter("All files", &["*"])
             .save_file() {
-            if let Err(e) = std::fs::write(&path, &self.code_editor.code) {
-                eprintln!("Failed to save file: {}", e);
-            } else {
-                self.code_editor.selected_file = Some(path);
-            }
+            self.write_document(&path, false);
+        } else {
+            self.code_editor.set_save_encoding(None);
         }
     }
 
     #[cfg(target_arch = "wasm32")]
     fn save_file_as(&mut self) {
-        let code = self.code_editor.code.clone();
+        let bytes = match self.code_editor.encoded_contents() {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.code_editor.notifications.warning(format!("Not saved: {}", e));
+                return;
+            }
+        };
+        // The browser does not tell whether the file was written, so the picked
+        // encoding is taken on right away.
+        if let Some(encoding) = self.code_editor.take_save_encoding() {
+            self.code_editor.encoding = encoding;
+        }
         wasm_bindgen_futures::spawn_local(async move {
             if let Some(handle) = rfd::AsyncFileDialog::new()
                 .add_filter("Text files", &["txt"])
@@ -276,8 +757,8 @@ impl ZenEditor {
                 .save_file()
                 .await
             {
-                if let Err(e) = handle.write(code.as_bytes()).await {
-                    eprintln!("Failed to save file: {}", e);
+                if let Err(e) = handle.write(&bytes).await {
+                    log::error!("Failed to save file: {}", e);
                 }
             }
         });
@@ -307,11 +788,10 @@ impl ZenEditor {
                 .await
             {
                 let data = handle.read().await;
-                let content = String::from_utf8_lossy(&data).to_string();
                 let filename = handle.file_name();
 
                 if let Ok(mut pending_lock) = pending.lock() {
-                    *pending_lock = Some((filename, content));
+                    *pending_lock = Some((filename, data));
                 }
             }
         });
@@ -320,7 +800,7 @@ impl ZenEditor {
     #[cfg(not(target_arch = "wasm32"))]
     fn open_project_dialog(&mut self) {
         if let Some(path) = rfd::FileDialog::new().pick_folder() {
-            self.code_editor.open_project(path);
+            self.open_project(path);
         }
     }
 
@@ -329,6 +809,131 @@ impl ZenEditor {
         eprintln!("Project opening not supported in web version");
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_restore_window(&mut self, ctx: &egui::Context) {
+        if self.pending_restores.is_empty() {
+            return;
+        }
+
+        let mut restore = None;
+        let mut discard = None;
+        let mut discard_all = false;
+
+        egui::Window::new("Restore Unsaved Changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                if self.restores_from_hot_exit {
+                    ui.label("These buffers had unsaved changes when Zen was closed:");
+                } else {
+                    ui.label("Zen did not close cleanly. These buffers had unsaved changes:");
+                }
+                ui.add_space(4.0);
+
+                for (index, snapshot) in self.pending_restores.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("📄 {}", snapshot.title()));
+                        ui.weak(snapshot.age());
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.button("Discard").clicked() {
+                                discard = Some(index);
+                            }
+                            if ui.button("Restore").clicked() {
+                                restore = Some(index);
+                            }
+                        });
+                    });
+                }
+
+                ui.separator();
+                if ui.button("Discard All").clicked() {
+                    discard_all = true;
+                }
+            });
+
+        if let Some(index) = restore {
+            let snapshot = self.pending_restores.remove(index);
+            // Removed first: a file's snapshot keeps its id, so the fresh one replaces it.
+            self.backups.remove(&snapshot.id);
+            snapshot.restore_into(&mut self.code_editor);
+            self.backups.snapshot(&self.code_editor);
+        } else if let Some(index) = discard {
+            let snapshot = self.pending_restores.remove(index);
+            self.backups.remove(&snapshot.id);
+        } else if discard_all {
+            for snapshot in self.pending_restores.drain(..) {
+                self.backups.remove(&snapshot.id);
+            }
+        }
+    }
+
+    /// Intercepts window close so unsaved changes are either kept as a hot-exit
+    /// backup or confirmed with the user.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn handle_close_request(&mut self, ctx: &egui::Context) {
+        if !ctx.input(|i| i.viewport().close_requested()) || self.allow_close {
+            return;
+        }
+
+        if self.code_editor.is_dirty() && !self.config.hot_exit {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.show_close_prompt = true;
+            return;
+        }
+
+        self.backups.snapshot(&self.code_editor);
+        if self.code_editor.is_dirty() {
+            self.backups.mark_hot_exit();
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_close_prompt(&mut self, ctx: &egui::Context) {
+        if !self.show_close_prompt {
+            return;
+        }
+
+        let name = self.code_editor.selected_file
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Untitled".to_string());
+
+        egui::Window::new("Unsaved Changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("Save changes to {} before closing?", name));
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        self.show_close_prompt = false;
+                        // Formatting would only finish after the window has closed.
+                        self.save_document(false);
+                        if !self.code_editor.is_dirty() {
+                            self.close_now(ui.ctx());
+                        }
+                    }
+                    if ui.button("Don't Save").clicked() {
+                        self.show_close_prompt = false;
+                        self.backups.discard(&self.code_editor);
+                        self.close_now(ui.ctx());
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.show_close_prompt = false;
+                    }
+                });
+            });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn close_now(&mut self, ctx: &egui::Context) {
+        self.allow_close = true;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+
     fn show_settings_window(&mut self, ctx: &egui::Context) {
         if !self.show_settings {
             return;
@@ -371,9 +976,7 @@ impl ZenEditor {
 
                     if ui.button("Set as Default Theme").clicked() {
                         self.config.default_theme = self.code_editor.theme.name.clone();
-                        if let Err(e) = self.config.save() {
-                            eprintln!("Failed to save config: {}", e);
-                        }
+                        self.save_config();
                     }
 
                     ui.separator();
@@ -387,6 +990,29 @@ impl ZenEditor {
                         new_theme.typography.font_size = font_size + 1.0;
                         self.code_editor.set_theme(new_theme);
                     }
+
+                    ui.separator();
+
+                    ui.heading("Files");
+
+                    if ui.checkbox(&mut self.config.hot_exit, "Keep unsaved changes on exit without asking").changed() {
+                        self.save_config();
+                    }
+
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui.checkbox(&mut self.config.format_on_save, "Format on save").changed() {
+                        self.save_config();
+                    }
+
+                    let mut filter_changed = false;
+                    filter_changed |= ui.checkbox(&mut self.config.files.respect_gitignore, "Respect .gitignore and .ignore files").changed();
+                    filter_changed |= ui.checkbox(&mut self.config.files.show_ignored, "Show hidden and ignored files (dimmed)").changed();
+                    ui.label(egui::RichText::new("Exclude and include patterns are set in config.json.").weak());
+
+                    if filter_changed {
+                        self.code_editor.set_file_filter(FileFilter::new(&self.config.files));
+                        self.save_config();
+                    }
                 });
             });
 
@@ -395,6 +1021,22 @@ impl ZenEditor {
 }
 
 impl eframe::App for ZenEditor {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.session.project_root = self.code_editor.project_root().cloned();
+        self.session.extra_roots = self.code_editor.project_roots().into_iter().skip(1).collect();
+        self.session.workspace_file = self.workspace_file.clone();
+        self.session.active_document = self.code_editor.selected_file.clone();
+        self.session.side_panel_width = Some(self.code_editor.side_panel_width);
+        self.session.set_documents(self.code_editor.document_view_states());
+
+        eframe::set_value(storage, Session::STORAGE_KEY, &self.session);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.code_editor.shutdown_language_servers();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         #[cfg(target_arch = "wasm32")]
         self.handle_pending_file_operations();
@@ -402,6 +1044,12 @@ impl eframe::App for ZenEditor {
         self.handle_keyboard_shortcuts(ctx);
         self.show_settings_window(ctx);
 
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.show_restore_window(ctx);
+            self.show_close_prompt(ctx);
+        }
+
         if self.should_use_custom_frame() {
             self.custom_window_frame(ctx, |app, ui| {
                 app.code_editor.ui(ui);
@@ -411,5 +1059,33 @@ impl eframe::App for ZenEditor {
                 self.code_editor.ui(ui);
             });
         }
+
+        self.track_recent_items();
+
+        if self.code_editor.take_save_before_open() {
+            self.save_current_file();
+        }
+
+        if let Some(encoding) = self.code_editor.take_pending_save_encoding() {
+            self.save_with_encoding(encoding);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = self.code_editor.take_save_after_format() {
+            if self.code_editor.selected_file.as_ref() == Some(&path) {
+                self.write_document(&path, false);
+            }
+        }
+
+        if let Some(action) = self.code_editor.notifications.render(ctx) {
+            self.handle_notification_action(action);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.save_workspace_if_changed();
+            self.backups.tick(&self.code_editor, ctx);
+            self.handle_close_request(ctx);
+        }
     }
 }
\ No newline at end of file
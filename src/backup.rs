@@ -0,0 +1,195 @@
+use crate::document::encoding::FileEncoding;
+use crate::document::line_ending::LineEnding;
+use crate::ui::editor::CodeEditor;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Left in the backups directory by a hot exit; its snapshots are not from a crash.
+const HOT_EXIT_MARKER: &str = "hot-exit";
+
+/// A copy of an unsaved buffer, written to `backups/` in the instance directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BufferSnapshot {
+    pub id: String,
+    pub path: Option<PathBuf>,
+    pub content: String,
+    pub encoding: String,
+    pub bom: bool,
+    pub line_ending: String,
+    pub saved_at: u64,
+}
+
+impl BufferSnapshot {
+    pub fn title(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => "Untitled".to_string(),
+        }
+    }
+
+    pub fn age(&self) -> String {
+        let seconds = unix_time().saturating_sub(self.saved_at);
+        match seconds {
+            0..=59 => "just now".to_string(),
+            60..=3599 => format!("{} min ago", seconds / 60),
+            3600..=86399 => format!("{} h ago", seconds / 3600),
+            _ => format!("{} days ago", seconds / 86400),
+        }
+    }
+
+    pub fn restore_into(self, editor: &mut CodeEditor) {
+        let encoding = FileEncoding::from_name(&self.encoding, self.bom).unwrap_or_default();
+        let line_ending = LineEnding::from_label(&self.line_ending).unwrap_or_default();
+        editor.restore_contents(self.path, self.content, encoding, line_ending);
+    }
+}
+
+/// Periodically snapshots the dirty buffer so it survives crashes and hot exits.
+pub struct BackupManager {
+    dir: Option<PathBuf>,
+    interval: Duration,
+    last_check: Instant,
+    last_snapshot: Option<(String, i32)>,
+    untitled_id: String,
+}
+
+impl Default for BackupManager {
+    fn default() -> Self {
+        Self {
+            dir: std::env::current_dir().ok().map(|dir| dir.join("backups")),
+            interval: Duration::from_secs(5),
+            last_check: Instant::now(),
+            last_snapshot: None,
+            untitled_id: format!("untitled-{}", unix_time()),
+        }
+    }
+}
+
+impl BackupManager {
+    /// Called every frame; writes or clears the snapshot once per interval.
+    pub fn tick(&mut self, editor: &CodeEditor, ctx: &egui::Context) {
+        if editor.is_dirty() {
+            ctx.request_repaint_after(self.interval);
+        }
+
+        if self.last_check.elapsed() < self.interval {
+            return;
+        }
+        self.last_check = Instant::now();
+        self.snapshot(editor);
+    }
+
+    /// Writes the dirty buffer to disk now, or removes its snapshot if it is clean.
+    pub fn snapshot(&mut self, editor: &CodeEditor) {
+        let id = self.buffer_id(editor);
+
+        if !editor.is_dirty() || editor.read_only {
+            if let Some((last, _)) = self.last_snapshot.take() {
+                self.remove(&last);
+            }
+            return;
+        }
+
+        let version = editor.document_version();
+        if self.last_snapshot.as_ref() == Some(&(id.clone(), version)) {
+            return;
+        }
+
+        let snapshot = BufferSnapshot {
+            id: id.clone(),
+            path: editor.selected_file.clone(),
+            content: editor.code.clone(),
+            encoding: editor.encoding.name().to_string(),
+            bom: editor.encoding.bom,
+            line_ending: editor.line_ending.label().to_string(),
+            saved_at: unix_time(),
+        };
+
+        if let Err(e) = self.write(&snapshot) {
+            log::warn!("Failed to write backup: {}", e);
+            return;
+        }
+        self.last_snapshot = Some((id, version));
+    }
+
+    /// Removes the snapshot of the current buffer, e.g. when the user discards its changes.
+    pub fn discard(&mut self, editor: &CodeEditor) {
+        let id = self.buffer_id(editor);
+        self.remove(&id);
+        self.last_snapshot = None;
+    }
+
+    /// Notes that the snapshots left now were kept on purpose by a hot exit, so the next
+    /// run does not take them for a crash.
+    pub fn mark_hot_exit(&self) {
+        if let Some(dir) = &self.dir {
+            if let Err(e) = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(dir.join(HOT_EXIT_MARKER), b"")) {
+                log::warn!("Failed to mark the hot exit: {}", e);
+            }
+        }
+    }
+
+    /// Whether the previous run ended with a hot exit, clearing the mark.
+    pub fn take_hot_exit(&self) -> bool {
+        let Some(dir) = &self.dir else {
+            return false;
+        };
+        std::fs::remove_file(dir.join(HOT_EXIT_MARKER)).is_ok()
+    }
+
+    /// Snapshots left behind by a previous run that crashed or exited with unsaved changes.
+    pub fn pending_restores(&self) -> Vec<BufferSnapshot> {
+        let Some(dir) = &self.dir else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut snapshots: Vec<BufferSnapshot> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+            .filter_map(|json| serde_json::from_str(&json).ok())
+            .collect();
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.saved_at));
+        snapshots
+    }
+
+    pub fn remove(&self, id: &str) {
+        if let Some(dir) = &self.dir {
+            let _ = std::fs::remove_file(dir.join(format!("{}.json", id)));
+        }
+    }
+
+    fn write(&self, snapshot: &BufferSnapshot) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        std::fs::create_dir_all(dir)?;
+        let json = serde_json::to_string(snapshot)?;
+        crate::document::writer::write_atomic(&dir.join(format!("{}.json", snapshot.id)), json.as_bytes())?;
+        Ok(())
+    }
+
+    fn buffer_id(&self, editor: &CodeEditor) -> String {
+        match &editor.selected_file {
+            Some(path) => {
+                use std::hash::{Hash, Hasher};
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                path.hash(&mut hasher);
+                format!("file-{:016x}", hasher.finish())
+            }
+            None => self.untitled_id.clone(),
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
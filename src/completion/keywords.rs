@@ -0,0 +1,86 @@
+/// Keywords of the language, by the file extension the editor highlights with.
+pub fn keywords(language: &str) -> &'static [&'static str] {
+    match language {
+        "rs" => &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+            "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+            "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        "c" | "h" => &[
+            "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum",
+            "extern", "float", "for", "goto", "if", "inline", "int", "long", "register", "restrict", "return",
+            "short", "signed", "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned", "void",
+            "volatile", "while",
+        ],
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => &[
+            "auto", "bool", "break", "case", "catch", "char", "class", "const", "constexpr", "continue", "default",
+            "delete", "do", "double", "else", "enum", "explicit", "extern", "false", "float", "for", "friend",
+            "if", "inline", "int", "long", "namespace", "new", "noexcept", "nullptr", "operator", "override",
+            "private", "protected", "public", "return", "short", "signed", "sizeof", "static", "struct", "switch",
+            "template", "this", "throw", "true", "try", "typedef", "typename", "union", "unsigned", "using",
+            "virtual", "void", "volatile", "while",
+        ],
+        "py" => &[
+            "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue", "def",
+            "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+            "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+        ],
+        "js" | "jsx" | "ts" | "tsx" => &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete", "do",
+            "else", "export", "extends", "false", "finally", "for", "function", "if", "import", "in",
+            "instanceof", "interface", "let", "new", "null", "return", "super", "switch", "this", "throw", "true",
+            "try", "type", "typeof", "undefined", "var", "void", "while", "yield",
+        ],
+        "go" => &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough", "for", "func",
+            "go", "goto", "if", "import", "interface", "map", "package", "range", "return", "select", "struct",
+            "switch", "type", "var",
+        ],
+        _ => &[],
+    }
+}
+
+/// Built-in snippets as (prefix, description, body in LSP snippet syntax).
+pub fn snippets(language: &str) -> &'static [(&'static str, &'static str, &'static str)] {
+    match language {
+        "rs" => &[
+            ("fn", "Function", "fn ${1:name}(${2}) {\n\t$0\n}"),
+            ("impl", "Implementation", "impl ${1:Type} {\n\t$0\n}"),
+            ("struct", "Struct", "struct ${1:Name} {\n\t$0\n}"),
+            ("enum", "Enum", "enum ${1:Name} {\n\t$0\n}"),
+            ("match", "Match expression", "match ${1:value} {\n\t${2:_} => $0,\n}"),
+            ("for", "For loop", "for ${1:item} in ${2:iter} {\n\t$0\n}"),
+            ("if", "If block", "if ${1:condition} {\n\t$0\n}"),
+            ("iflet", "If let", "if let ${1:Some(value)} = ${2:option} {\n\t$0\n}"),
+            ("test", "Test function", "#[test]\nfn ${1:name}() {\n\t$0\n}"),
+        ],
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" => &[
+            ("for", "For loop", "for (${1:int} ${2:i} = 0; $2 < ${3:count}; $2++) {\n\t$0\n}"),
+            ("if", "If block", "if (${1:condition}) {\n\t$0\n}"),
+            ("while", "While loop", "while (${1:condition}) {\n\t$0\n}"),
+            ("struct", "Struct", "struct ${1:name} {\n\t$0\n};"),
+            ("main", "Main function", "int main(int argc, char *argv[]) {\n\t$0\n\treturn 0;\n}"),
+        ],
+        "py" => &[
+            ("def", "Function", "def ${1:name}(${2}):\n\t${0:pass}"),
+            ("class", "Class", "class ${1:Name}:\n\tdef __init__(self${2}):\n\t\t${0:pass}"),
+            ("for", "For loop", "for ${1:item} in ${2:items}:\n\t${0:pass}"),
+            ("if", "If block", "if ${1:condition}:\n\t${0:pass}"),
+            ("main", "Main guard", "if __name__ == \"__main__\":\n\t${0:main()}"),
+        ],
+        "js" | "jsx" | "ts" | "tsx" => &[
+            ("function", "Function", "function ${1:name}(${2}) {\n\t$0\n}"),
+            ("arrow", "Arrow function", "(${1}) => {\n\t$0\n}"),
+            ("for", "For loop", "for (let ${1:i} = 0; $1 < ${2:items}.length; $1++) {\n\t$0\n}"),
+            ("forof", "For...of loop", "for (const ${1:item} of ${2:items}) {\n\t$0\n}"),
+            ("if", "If block", "if (${1:condition}) {\n\t$0\n}"),
+        ],
+        "go" => &[
+            ("func", "Function", "func ${1:name}(${2}) ${3}{\n\t$0\n}"),
+            ("for", "For loop", "for ${1:i} := 0; $1 < ${2:count}; $1++ {\n\t$0\n}"),
+            ("if", "If block", "if ${1:condition} {\n\t$0\n}"),
+            ("iferr", "Error check", "if err != nil {\n\treturn ${1:err}\n}"),
+        ],
+        _ => &[],
+    }
+}
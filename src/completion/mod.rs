@@ -0,0 +1,371 @@
+pub mod keywords;
+pub mod snippet;
+
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use snippet::SnippetSession;
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// How many recently edited files lend their words to completion.
+const OTHER_BUFFERS: usize = 8;
+/// Words shorter than this are quicker to type than to pick.
+const MIN_WORD_LENGTH: usize = 3;
+const MAX_MATCHES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Word,
+    Keyword,
+    Snippet,
+    Function,
+    Method,
+    Field,
+    Variable,
+    Constant,
+    Type,
+    Module,
+    Other,
+}
+
+impl CompletionKind {
+    pub fn icon(self) -> &'static str {
+        match self {
+            CompletionKind::Word => "🔤",
+            CompletionKind::Keyword => "🔑",
+            CompletionKind::Snippet => "✂",
+            CompletionKind::Function | CompletionKind::Method => "ƒ",
+            CompletionKind::Field => "#",
+            CompletionKind::Variable => "x",
+            CompletionKind::Constant => "π",
+            CompletionKind::Type => "⊞",
+            CompletionKind::Module => "📦",
+            CompletionKind::Other => "▣",
+        }
+    }
+}
+
+/// Where an item came from. Earlier sources rank first when scores tie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CompletionSource {
+    LanguageServer,
+    Snippet,
+    Keyword,
+    Buffer,
+    OtherBuffer,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub source: CompletionSource,
+    pub detail: Option<String>,
+    pub documentation: Option<String>,
+    /// What replaces the word being typed; snippet syntax when `snippet` is set.
+    pub insert_text: String,
+    pub snippet: bool,
+    /// Matched against what was typed instead of the label, when present.
+    pub filter_text: Option<String>,
+    /// Orders items with equal scores, as language servers ask.
+    pub sort_text: Option<String>,
+    /// The characters the insertion replaces when the provider says so; otherwise the
+    /// word before the cursor.
+    pub replace: Option<Range<usize>>,
+}
+
+impl CompletionItem {
+    pub fn new(label: impl Into<String>, kind: CompletionKind, source: CompletionSource) -> Self {
+        let label = label.into();
+        Self {
+            insert_text: label.clone(),
+            label,
+            kind,
+            source,
+            detail: None,
+            documentation: None,
+            snippet: false,
+            filter_text: None,
+            sort_text: None,
+            replace: None,
+        }
+    }
+}
+
+/// An item that matches what was typed, with the characters of its label that matched.
+pub struct CompletionMatch {
+    pub index: usize,
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+/// The open popup: the candidates for the word at `start` and those matching so far.
+pub struct CompletionSession {
+    /// Character index where the word being completed starts.
+    pub start: usize,
+    pub items: Vec<CompletionItem>,
+    pub matches: Vec<CompletionMatch>,
+    pub selected: usize,
+    /// The language server request whose items have yet to arrive.
+    pub pending_request: Option<i64>,
+    /// Set when the selection moved by keyboard, so the list scrolls to it.
+    pub reveal_selected: bool,
+    typed: String,
+}
+
+impl CompletionSession {
+    pub fn selected_item(&self) -> Option<&CompletionItem> {
+        self.matches.get(self.selected).map(|m| &self.items[m.index])
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+            self.reveal_selected = true;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + self.matches.len() - 1) % self.matches.len();
+            self.reveal_selected = true;
+        }
+    }
+
+    /// Ranks the items by how well they fuzzy-match `typed`.
+    fn filter(&mut self, typed: &str) {
+        self.typed = typed.to_string();
+        let matcher = SkimMatcherV2::default().smart_case();
+
+        let mut matches: Vec<CompletionMatch> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| !(item.kind == CompletionKind::Word && item.label == typed))
+            .filter_map(|(index, item)| {
+                if typed.is_empty() {
+                    return Some(CompletionMatch { index, score: 0, positions: Vec::new() });
+                }
+                let key = item.filter_text.as_deref().unwrap_or(&item.label);
+                let (score, _) = matcher.fuzzy_indices(key, typed)?;
+                let positions = matcher.fuzzy_indices(&item.label, typed).map(|(_, p)| p).unwrap_or_default();
+                Some(CompletionMatch { index, score, positions })
+            })
+            .collect();
+
+        let items = &self.items;
+        matches.sort_by(|a, b| {
+            let (x, y) = (&items[a.index], &items[b.index]);
+            b.score
+                .cmp(&a.score)
+                .then(x.source.cmp(&y.source))
+                .then_with(|| x.sort_text.as_deref().unwrap_or(&x.label).cmp(y.sort_text.as_deref().unwrap_or(&y.label)))
+        });
+        matches.truncate(MAX_MATCHES);
+
+        self.matches = matches;
+        self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+    }
+}
+
+/// The text to put in place of `range` when an item is accepted, and what to select after.
+pub struct CompletionEdit {
+    /// Character range replaced.
+    pub range: Range<usize>,
+    pub text: String,
+    /// Character range to select once inserted.
+    pub selection: Range<usize>,
+}
+
+/// Completion for the open buffer, merging words from it and recently edited files,
+/// the language's keywords and snippets, and whatever the language server suggests.
+#[derive(Default)]
+pub struct Completions {
+    pub session: Option<CompletionSession>,
+    snippet: Option<SnippetSession>,
+    /// Words of recently edited files, most recent first.
+    other_buffers: VecDeque<(PathBuf, HashSet<String>)>,
+}
+
+impl Completions {
+    /// Keeps the words of a file being switched away from.
+    pub fn remember_buffer(&mut self, path: &Path, text: &str) {
+        self.other_buffers.retain(|(other, _)| other != path);
+        self.other_buffers.push_front((path.to_path_buf(), words(text).map(str::to_string).collect()));
+        self.other_buffers.truncate(OTHER_BUFFERS);
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Opens the popup for the word ending at `cursor` with the local candidates; items
+    /// from a language server are added as they arrive.
+    pub fn start(&mut self, text: &str, cursor: usize, language: &str, path: Option<&Path>) {
+        let start = word_start(text, cursor);
+        let mut items = Vec::new();
+        let mut labels = HashSet::new();
+
+        for (prefix, description, body) in keywords::snippets(language) {
+            let mut item = CompletionItem::new(*prefix, CompletionKind::Snippet, CompletionSource::Snippet);
+            item.detail = Some(description.to_string());
+            item.documentation = Some(snippet::parse(body).text);
+            item.insert_text = body.to_string();
+            item.snippet = true;
+            items.push(item);
+        }
+        for keyword in keywords::keywords(language) {
+            labels.insert(keyword.to_string());
+            items.push(CompletionItem::new(*keyword, CompletionKind::Keyword, CompletionSource::Keyword));
+        }
+
+        let current_word = char_slice(text, start..cursor);
+        for word in words(text) {
+            if word != current_word && labels.insert(word.to_string()) {
+                items.push(CompletionItem::new(word, CompletionKind::Word, CompletionSource::Buffer));
+            }
+        }
+        for (other, words) in &self.other_buffers {
+            if Some(other.as_path()) == path {
+                continue;
+            }
+            for word in words {
+                if labels.insert(word.clone()) {
+                    let mut item = CompletionItem::new(word.as_str(), CompletionKind::Word, CompletionSource::OtherBuffer);
+                    item.detail = other.file_name().map(|name| name.to_string_lossy().to_string());
+                    items.push(item);
+                }
+            }
+        }
+
+        let mut session = CompletionSession {
+            start,
+            items,
+            matches: Vec::new(),
+            selected: 0,
+            pending_request: None,
+            reveal_selected: false,
+            typed: String::new(),
+        };
+        session.filter(current_word);
+        self.session = Some(session);
+    }
+
+    pub fn close(&mut self) {
+        self.session = None;
+    }
+
+    /// Adds what the language server answered to the popup it was asked for.
+    pub fn add_items(&mut self, request: i64, items: Vec<CompletionItem>) {
+        let Some(session) = self.session.as_mut().filter(|s| s.pending_request == Some(request)) else {
+            return;
+        };
+        session.pending_request = None;
+
+        let labels: HashSet<String> = items.iter().map(|item| item.label.clone()).collect();
+        // The server knows better what a word is, so plain words it also offers are dropped.
+        session.items.retain(|item| {
+            !matches!(item.kind, CompletionKind::Word | CompletionKind::Keyword) || !labels.contains(&item.label)
+        });
+        session.items.extend(items);
+        let typed = std::mem::take(&mut session.typed);
+        session.filter(&typed);
+        if session.matches.is_empty() {
+            self.session = None;
+        }
+    }
+
+    /// Refilters after the buffer or cursor changed, closing the popup once the cursor
+    /// leaves the word or nothing matches.
+    pub fn update(&mut self, text: &str, cursor: usize) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        if cursor < session.start || word_start(text, cursor) != session.start {
+            self.session = None;
+            return;
+        }
+        session.filter(char_slice(text, session.start..cursor));
+        if session.matches.is_empty() && session.pending_request.is_none() {
+            self.session = None;
+        }
+    }
+
+    /// Accepts the selected item, or the given match, replacing the word being typed.
+    /// Snippets have their placeholders filled in and Tab then moves between them.
+    pub fn accept(&mut self, text: &str, cursor: usize, index: Option<usize>) -> Option<CompletionEdit> {
+        let session = self.session.take()?;
+        let selected = index.unwrap_or(session.selected);
+        let item = &session.items[session.matches.get(selected)?.index];
+        // Server ranges were computed when it was asked; the word may have grown since.
+        let range = item.replace.clone().map_or(session.start..cursor, |range| range.start..range.end.max(cursor));
+
+        if !item.snippet {
+            let end = range.start + item.insert_text.chars().count();
+            return Some(CompletionEdit { range, text: item.insert_text.clone(), selection: end..end });
+        }
+
+        let indent = line_indent(text, range.start);
+        let body = item.insert_text.replace('\n', &format!("\n{}", indent));
+        let expanded = snippet::parse(&body);
+        let stops: Vec<Range<usize>> =
+            expanded.stops.iter().map(|stop| range.start + stop.start..range.start + stop.end).collect();
+        let selection = stops.first().cloned().unwrap_or(range.start..range.start);
+        let length = text.chars().count() - (range.end - range.start) + expanded.text.chars().count();
+        self.snippet = SnippetSession::new(stops, length);
+        Some(CompletionEdit { range, text: expanded.text, selection })
+    }
+
+    pub fn in_snippet(&self) -> bool {
+        self.snippet.is_some()
+    }
+
+    /// The next placeholder to select after Tab, given the buffer's length in characters.
+    pub fn next_tab_stop(&mut self, length: usize) -> Option<Range<usize>> {
+        let session = self.snippet.as_mut()?;
+        let stop = session.advance(length);
+        if stop.is_none() || session.is_last() {
+            self.snippet = None;
+        }
+        stop
+    }
+
+    pub fn leave_snippet(&mut self) {
+        self.snippet = None;
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The identifiers in `text` worth offering as completions.
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !is_word_char(c))
+        .filter(|word| word.chars().count() >= MIN_WORD_LENGTH && !word.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// The character index where the word ending at `cursor` starts.
+pub fn word_start(text: &str, cursor: usize) -> usize {
+    let before: Vec<char> = text.chars().take(cursor).collect();
+    let length = before.iter().rev().take_while(|&&c| is_word_char(c)).count();
+    before.len() - length
+}
+
+/// The byte offset of a character index, clamped to the end of the text.
+pub fn byte_offset(text: &str, index: usize) -> usize {
+    text.char_indices().nth(index).map_or(text.len(), |(offset, _)| offset)
+}
+
+fn char_slice(text: &str, range: Range<usize>) -> &str {
+    &text[byte_offset(text, range.start)..byte_offset(text, range.end)]
+}
+
+/// The leading whitespace of the line containing character `index`.
+fn line_indent(text: &str, index: usize) -> &str {
+    let offset = byte_offset(text, index);
+    let line_start = text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+    let line = &text[line_start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
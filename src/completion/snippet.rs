@@ -0,0 +1,290 @@
+use std::ops::Range;
+
+/// A snippet with its placeholders filled in with their defaults, and where they ended up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    /// Character ranges of the tab stops in the order Tab visits them. `$0`, where the
+    /// cursor ends up, comes last and is added at the end of the text if missing.
+    pub stops: Vec<Range<usize>>,
+}
+
+/// Expands LSP snippet syntax: `$1`, `${1}`, `${1:default}` (defaults may nest),
+/// `${1|one,two|}` and `$VAR` / `${VAR:default}`. Variables are not resolved and expand
+/// to their default.
+pub fn parse(body: &str) -> Snippet {
+    let mut parser = Parser { chars: body.chars().collect(), index: 0, text: String::new(), length: 0, stops: Vec::new() };
+    parser.parse_until(None);
+
+    let mut stops = parser.stops;
+    if !stops.iter().any(|(number, _)| *number == 0) {
+        stops.push((0, parser.length..parser.length));
+    }
+    // Stable, so the first occurrence of a repeated number is visited first.
+    stops.sort_by_key(|(number, _)| if *number == 0 { usize::MAX } else { *number });
+    let mut seen = Vec::new();
+    stops.retain(|(number, _)| {
+        let first = !seen.contains(number);
+        seen.push(*number);
+        first
+    });
+
+    Snippet { text: parser.text, stops: stops.into_iter().map(|(_, range)| range).collect() }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    text: String,
+    /// Characters in `text` so far.
+    length: usize,
+    stops: Vec<(usize, Range<usize>)>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn push(&mut self, c: char) {
+        self.text.push(c);
+        self.length += 1;
+    }
+
+    /// Copies text until `end` (or the end of the body), expanding what it meets on the way.
+    fn parse_until(&mut self, end: Option<char>) {
+        while let Some(c) = self.peek() {
+            if Some(c) == end {
+                return;
+            }
+            self.index += 1;
+            match c {
+                '\\' => match self.peek() {
+                    Some(escaped @ ('$' | '}' | '\\' | ',' | '|')) => {
+                        self.index += 1;
+                        self.push(escaped);
+                    }
+                    _ => self.push('\\'),
+                },
+                '$' => self.parse_dollar(),
+                c => self.push(c),
+            }
+        }
+    }
+
+    fn parse_dollar(&mut self) {
+        if let Some(number) = self.number() {
+            self.mirror(number);
+            return;
+        }
+        if self.peek() != Some('{') {
+            if self.name().is_none() {
+                self.push('$');
+            }
+            return;
+        }
+
+        let (open, text, length, stops) = (self.index, self.text.len(), self.length, self.stops.len());
+        self.index += 1;
+        if let Some(number) = self.number() {
+            let start = self.length;
+            match self.peek() {
+                Some('}') => {
+                    self.index += 1;
+                    self.mirror(number);
+                    return;
+                }
+                Some(':') => {
+                    self.index += 1;
+                    self.parse_until(Some('}'));
+                }
+                Some('|') => {
+                    self.index += 1;
+                    self.choice();
+                }
+                _ => {}
+            }
+            if self.peek() == Some('}') {
+                self.index += 1;
+                self.stops.push((number, start..self.length));
+                return;
+            }
+        } else if self.name().is_some() {
+            if self.peek() == Some(':') {
+                self.index += 1;
+                self.parse_until(Some('}'));
+            }
+            if self.peek() == Some('}') {
+                self.index += 1;
+                return;
+            }
+        }
+
+        // Not a placeholder after all, such as one left unclosed; drop what its default
+        // expanded to and keep it as written.
+        self.index = open;
+        self.text.truncate(text);
+        self.length = length;
+        self.stops.truncate(stops);
+        self.push('$');
+    }
+
+    /// An empty `$1` repeats what an earlier `${1:default}` filled in.
+    fn mirror(&mut self, number: usize) {
+        let start = self.length;
+        let earlier = self.stops.iter().find(|(n, _)| *n == number).map(|(_, range)| range.clone());
+        if let Some(range) = earlier {
+            let text: String = self.text.chars().skip(range.start).take(range.len()).collect();
+            text.chars().for_each(|c| self.push(c));
+        }
+        self.stops.push((number, start..self.length));
+    }
+
+    /// Inserts the first option of `one,two|}` and skips the rest.
+    fn choice(&mut self) {
+        let mut first = true;
+        while let Some(c) = self.peek() {
+            self.index += 1;
+            match c {
+                '|' => return,
+                ',' => first = false,
+                '\\' => {
+                    if let Some(escaped) = self.peek() {
+                        self.index += 1;
+                        if first {
+                            self.push(escaped);
+                        }
+                    }
+                }
+                c if first => self.push(c),
+                _ => {}
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.index;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.index += 1;
+        }
+        self.chars[start..self.index].iter().collect::<String>().parse().ok()
+    }
+
+    fn name(&mut self) -> Option<String> {
+        let start = self.index;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            self.index += 1;
+        }
+        (self.index > start).then(|| self.chars[start..self.index].iter().collect())
+    }
+}
+
+/// Tab stops of an inserted snippet, moved by Tab until the last one is reached.
+pub struct SnippetSession {
+    /// Absolute character ranges in the buffer.
+    stops: Vec<Range<usize>>,
+    current: usize,
+    /// Characters in the buffer when the current stop was entered, to tell how much
+    /// typing inside it moved the stops after it.
+    length: usize,
+}
+
+impl SnippetSession {
+    /// Starts at the first stop; `None` when the snippet has nothing to tab through.
+    pub fn new(stops: Vec<Range<usize>>, length: usize) -> Option<Self> {
+        (stops.len() > 1).then_some(Self { stops, current: 0, length })
+    }
+
+    /// The next stop to select, given the current length of the buffer, or `None` once
+    /// the last one was left.
+    pub fn advance(&mut self, length: usize) -> Option<Range<usize>> {
+        let delta = length as isize - self.length as isize;
+        self.length = length;
+        let shift = |position: usize| (position as isize + delta).max(0) as usize;
+
+        // Typing happened in the current stop, so only what comes after it moves.
+        let edited_end = self.stops[self.current].end;
+        for stop in &mut self.stops[self.current..] {
+            if stop.start >= edited_end {
+                *stop = shift(stop.start)..shift(stop.end);
+            } else if stop.end >= edited_end {
+                stop.end = shift(stop.end);
+            }
+        }
+
+        self.current += 1;
+        self.stops.get(self.current).cloned()
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.current + 1 >= self.stops.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops(body: &str) -> (String, Vec<Range<usize>>) {
+        let snippet = parse(body);
+        (snippet.text, snippet.stops)
+    }
+
+    #[test]
+    fn placeholders_fill_in_their_defaults() {
+        assert_eq!(stops("fn ${1:name}(${2:args}) {\n\t$0\n}"), ("fn name(args) {\n\t\n}".to_string(), vec![3..7, 8..12, 17..17]));
+        // Numbers are visited in order, wherever they are written.
+        assert_eq!(stops("$2 and $1"), (" and ".to_string(), vec![5..5, 0..0, 5..5]));
+    }
+
+    #[test]
+    fn the_final_stop_is_added_at_the_end_when_missing() {
+        assert_eq!(stops("${1:x};"), ("x;".to_string(), vec![0..1, 2..2]));
+        let plain = parse("plain");
+        assert_eq!((plain.text.as_str(), plain.stops.len(), &plain.stops[0]), ("plain", 1, &(5..5)));
+    }
+
+    #[test]
+    fn defaults_nest_and_mirrors_repeat_them() {
+        assert_eq!(stops("${1:outer ${2:inner}}"), ("outer inner".to_string(), vec![0..11, 6..11, 11..11]));
+        assert_eq!(stops("${1:a} = $1"), ("a = a".to_string(), vec![0..1, 5..5]));
+    }
+
+    #[test]
+    fn choices_insert_their_first_option() {
+        assert_eq!(stops("${1|one,two|}!"), ("one!".to_string(), vec![0..3, 4..4]));
+        assert_eq!(parse("${1|a\\,b,c|}").text, "a,b");
+    }
+
+    #[test]
+    fn variables_expand_to_their_default() {
+        assert_eq!(parse("$TM_FILENAME ${TM_SELECTED_TEXT:sel}").text, " sel");
+    }
+
+    #[test]
+    fn escapes_and_malformed_syntax_are_kept_as_text() {
+        assert_eq!(parse("\\$1 \\} \\\\ \\n").text, "$1 } \\ \\n");
+        assert_eq!(parse("cost: $").text, "cost: $");
+        assert_eq!(parse("${oops").text, "${oops");
+        assert_eq!(parse("${NAME:default").text, "${NAME:default");
+        assert_eq!(stops("${1:un${2:closed}"), ("${1:unclosed".to_string(), vec![6..12, 12..12]));
+    }
+
+    #[test]
+    fn stops_count_characters() {
+        assert_eq!(stops("é${1:ü}"), ("éü".to_string(), vec![1..2, 2..2]));
+    }
+
+    #[test]
+    fn the_session_moves_later_stops_by_what_was_typed() {
+        let mut session = SnippetSession::new(vec![3..7, 8..12, 17..17], 20).unwrap();
+        assert!(!session.is_last());
+        // "name" was replaced with "longname".
+        assert_eq!(session.advance(24), Some(12..16));
+        assert_eq!(session.advance(24), Some(21..21));
+        assert!(session.is_last());
+        assert_eq!(session.advance(24), None);
+
+        assert!(SnippetSession::new(parse("plain").stops, 5).is_none());
+    }
+}
@@ -1,14 +1,139 @@
+use crate::file_filter::FileFilterConfig;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct EditorConfig {
     pub default_theme: String,
+    /// Keep unsaved changes as backups on exit instead of asking to save them.
+    pub hot_exit: bool,
+    /// Which files the explorer shows.
+    pub files: FileFilterConfig,
+    /// Language servers started for matching files.
+    pub language_servers: Vec<LanguageServerConfig>,
+    /// Commands that format matching files, used instead of a language server.
+    pub formatters: Vec<FormatterConfig>,
+    /// Format the document before writing it whenever it is saved.
+    pub format_on_save: bool,
+}
+
+/// How to launch a language server and which files it handles.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageServerConfig {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// File extensions the server is used for, without the dot.
+    pub extensions: Vec<String>,
+    pub enabled: bool,
+    /// Sent as `initializationOptions` in the initialize request.
+    pub initialization_options: Option<serde_json::Value>,
+}
+
+impl Default for LanguageServerConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            command: String::new(),
+            args: Vec::new(),
+            extensions: Vec::new(),
+            enabled: true,
+            initialization_options: None,
+        }
+    }
+}
+
+impl LanguageServerConfig {
+    fn new(name: &str, command: &str, args: &[&str], extensions: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            extensions: extensions.iter().map(|ext| ext.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("rust-analyzer", "rust-analyzer", &[], &["rs"]),
+            Self::new("clangd", "clangd", &[], &["c", "h", "cc", "cpp", "cxx", "hpp", "hh"]),
+            Self::new("pyright", "pyright-langserver", &["--stdio"], &["py"]),
+            Self::new("typescript-language-server", "typescript-language-server", &["--stdio"], &["ts", "tsx", "js", "jsx"]),
+            Self::new("gopls", "gopls", &[], &["go"]),
+        ]
+    }
+
+    pub fn handles(&self, extension: &str) -> bool {
+        self.enabled && self.extensions.iter().any(|ext| ext.eq_ignore_ascii_case(extension))
+    }
+}
+
+/// A command that reads a file's text on stdin and writes it back formatted on stdout.
+/// It runs in the file's directory so it finds the project's own settings, and `{file}`
+/// in its arguments stands for the file's path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatterConfig {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// File extensions the formatter is used for, without the dot.
+    pub extensions: Vec<String>,
+    pub enabled: bool,
+}
+
+impl Default for FormatterConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            command: String::new(),
+            args: Vec::new(),
+            extensions: Vec::new(),
+            enabled: true,
+        }
+    }
+}
+
+impl FormatterConfig {
+    fn new(name: &str, command: &str, args: &[&str], extensions: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            extensions: extensions.iter().map(|ext| ext.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("rustfmt", "rustfmt", &["--edition", "2021", "--emit", "stdout"], &["rs"]),
+            Self::new("black", "black", &["--quiet", "-"], &["py", "pyi"]),
+            Self::new(
+                "prettier",
+                "prettier",
+                &["--stdin-filepath", "{file}"],
+                &["js", "jsx", "mjs", "cjs", "ts", "tsx", "json", "css", "scss", "html", "md", "yaml", "yml"],
+            ),
+        ]
+    }
+
+    pub fn handles(&self, extension: &str) -> bool {
+        self.enabled && self.extensions.iter().any(|ext| ext.eq_ignore_ascii_case(extension))
+    }
 }
 
 impl Default for EditorConfig {
     fn default() -> Self {
         Self {
             default_theme: "Dark".to_string(),
+            hot_exit: false,
+            files: FileFilterConfig::default(),
+            language_servers: LanguageServerConfig::defaults(),
+            formatters: FormatterConfig::defaults(),
+            format_on_save: false,
         }
     }
 }
@@ -32,8 +157,9 @@ impl EditorConfig {
                 Err(_) => return Self::default(),
             };
 
-            serde_json::from_str(&content).unwrap_or_else(|_| Self::default());
+            serde_json::from_str(&content).unwrap_or_else(|_| Self::default())
         }
+        #[cfg(target_arch = "wasm32")]
         Self::default()
     }
 
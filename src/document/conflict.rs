@@ -0,0 +1,226 @@
+use super::edit::TextEdit;
+use super::position::TextPosition;
+use similar::{DiffTag, TextDiff};
+use std::ops::Range;
+
+const OURS_MARKER: &str = "<<<<<<<";
+const BASE_MARKER: &str = "|||||||";
+const SEPARATOR: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>>";
+
+/// A merge conflict block left in a file by git. Ranges are byte offsets into the
+/// text and include the line breaks of the lines they cover.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// The whole block, from the `<<<<<<<` line through the `>>>>>>>` line.
+    pub range: Range<usize>,
+    pub ours: Range<usize>,
+    /// The common ancestor, present when the file was merged with `merge.conflictStyle=diff3`.
+    pub base: Option<Range<usize>>,
+    pub theirs: Range<usize>,
+    pub ours_label: String,
+    pub theirs_label: String,
+    /// 0-based lines of the `<<<<<<<`, `|||||||`, `=======` and `>>>>>>>` markers.
+    pub start_line: usize,
+    pub base_line: Option<usize>,
+    pub separator_line: usize,
+    pub end_line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Current,
+    Incoming,
+    Both,
+    Base,
+}
+
+impl Conflict {
+    /// The text that replaces the block for the given resolution.
+    pub fn resolved_text<'a>(&self, text: &'a str, resolution: Resolution) -> String {
+        let section = |range: &Range<usize>| -> &'a str { text.get(range.clone()).unwrap_or("") };
+        match resolution {
+            Resolution::Current => section(&self.ours).to_string(),
+            Resolution::Incoming => section(&self.theirs).to_string(),
+            Resolution::Both => format!("{}{}", section(&self.ours), section(&self.theirs)),
+            Resolution::Base => self.base.as_ref().map(section).unwrap_or("").to_string(),
+        }
+    }
+
+    /// The edit that replaces the block with `replacement`.
+    pub fn edit(&self, text: &str, replacement: &str) -> TextEdit {
+        let position = |byte: usize| TextPosition::from_char_index(text, text[..byte].chars().count());
+        TextEdit {
+            start: position(self.range.start),
+            end: position(self.range.end),
+            new_text: replacement.to_string(),
+        }
+    }
+}
+
+/// The common ancestor of each conflict, found in `base`, the whole file as it was
+/// before either side changed it, for conflicts that were not written with it.
+///
+/// The file as the current side left it is lined up with `base` line by line, and a
+/// conflict's ancestor is the lines its current side took the place of.
+pub fn base_sections(text: &str, conflicts: &[Conflict], base: &str) -> Vec<String> {
+    let mut ours = String::with_capacity(text.len());
+    let mut spans = Vec::with_capacity(conflicts.len());
+    let mut offset = 0;
+    let mut lines = 0;
+    for conflict in conflicts {
+        let before = &text[offset..conflict.range.start];
+        let section = &text[conflict.ours.clone()];
+        lines += before.matches('\n').count();
+        spans.push(lines..lines + section.matches('\n').count());
+        lines = spans.last().map_or(lines, |span| span.end);
+        ours.push_str(before);
+        ours.push_str(section);
+        offset = conflict.range.end;
+    }
+    ours.push_str(&text[offset..]);
+
+    let diff = TextDiff::from_lines(base, &ours);
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    spans
+        .into_iter()
+        .map(|span| {
+            let mut found: Option<Range<usize>> = None;
+            for op in diff.ops() {
+                let (tag, old, new) = op.as_tag_tuple();
+                let touches = if new.is_empty() {
+                    span.start <= new.start && new.start <= span.end
+                } else {
+                    new.start < span.end && span.start < new.end
+                };
+                if !touches {
+                    continue;
+                }
+                let old = if tag == DiffTag::Equal {
+                    let start = span.start.max(new.start) - new.start + old.start;
+                    let end = span.end.min(new.end) - new.start + old.start;
+                    start..end
+                } else {
+                    old
+                };
+                if old.is_empty() {
+                    continue;
+                }
+                found = Some(match found {
+                    Some(found) => found.start.min(old.start)..found.end.max(old.end),
+                    None => old,
+                });
+            }
+            found.map(|range| base_lines[range].concat()).unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Finds the complete conflict blocks in `text`. Markers that are not part of a
+/// well-formed block are left alone.
+pub fn find_conflicts(text: &str) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut open: Option<OpenConflict> = None;
+    let mut offset = 0;
+
+    for (line_number, line) in text.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+
+        if let Some(label) = marker(line, OURS_MARKER) {
+            open = Some(OpenConflict {
+                start,
+                start_line: line_number,
+                ours_label: label,
+                ours_start: offset,
+                base_start: None,
+                base_line: None,
+                separator: None,
+                separator_line: 0,
+            });
+            continue;
+        }
+
+        let Some(conflict) = &mut open else {
+            continue;
+        };
+        if conflict.separator.is_none() && conflict.base_start.is_none() && marker(line, BASE_MARKER).is_some() {
+            conflict.base_start = Some((start, offset));
+            conflict.base_line = Some(line_number);
+        } else if conflict.separator.is_none() && line.trim_end() == SEPARATOR {
+            conflict.separator = Some((start, offset));
+            conflict.separator_line = line_number;
+        } else if let (Some(theirs_label), Some((separator_start, theirs_start))) =
+            (marker(line, THEIRS_MARKER), conflict.separator)
+        {
+            let ours_end = conflict.base_start.map_or(separator_start, |(base_marker, _)| base_marker);
+            conflicts.push(Conflict {
+                range: conflict.start..offset,
+                ours: conflict.ours_start..ours_end,
+                base: conflict.base_start.map(|(_, base_start)| base_start..separator_start),
+                theirs: theirs_start..start,
+                ours_label: std::mem::take(&mut conflict.ours_label),
+                theirs_label,
+                start_line: conflict.start_line,
+                base_line: conflict.base_line,
+                separator_line: conflict.separator_line,
+                end_line: line_number,
+            });
+            open = None;
+        }
+    }
+    conflicts
+}
+
+/// Replaces a conflict block with `replacement`.
+pub fn resolve(text: &str, conflict: &Conflict, replacement: &str) -> String {
+    let mut resolved = String::with_capacity(text.len());
+    resolved.push_str(&text[..conflict.range.start]);
+    resolved.push_str(replacement);
+    resolved.push_str(&text[conflict.range.end..]);
+    resolved
+}
+
+struct OpenConflict {
+    start: usize,
+    start_line: usize,
+    ours_label: String,
+    ours_start: usize,
+    /// Start and end of the `|||||||` line.
+    base_start: Option<(usize, usize)>,
+    base_line: Option<usize>,
+    /// Start and end of the `=======` line.
+    separator: Option<(usize, usize)>,
+    separator_line: usize,
+}
+
+/// The label after a marker such as `<<<<<<< HEAD`, if `line` is that marker.
+fn marker(line: &str, marker: &str) -> Option<String> {
+    let rest = line.strip_prefix(marker)?;
+    if rest.starts_with(['<', '|', '=', '>']) {
+        return None;
+    }
+    Some(rest.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_sections_finds_the_ancestor_of_each_conflict() {
+        let base = "a\nb\nc\nd\ne\n";
+        let text = "a\n<<<<<<< HEAD\nB\n=======\nbee\n>>>>>>> topic\nc\n<<<<<<< HEAD\n=======\nE\n>>>>>>> topic\n";
+        let conflicts = find_conflicts(text);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(base_sections(text, &conflicts, base), ["b\n", "d\ne\n"]);
+    }
+
+    #[test]
+    fn edit_covers_the_whole_block() {
+        let text = "é\n<<<<<<< HEAD\nx\n=======\ny\n>>>>>>> topic\nz";
+        let conflict = &find_conflicts(text)[0];
+        let edit = conflict.edit(text, "x\n");
+        assert_eq!((edit.start, edit.end), (TextPosition::new(1, 0), TextPosition::new(6, 0)));
+    }
+}
@@ -0,0 +1,111 @@
+use super::position::TextPosition;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+impl Severity {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Severity::Error => "⊗",
+            Severity::Warning => "⚠",
+            Severity::Info | Severity::Hint => "ℹ",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+            Severity::Info => "Info",
+            Severity::Hint => "Hint",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub start: TextPosition,
+    pub end: TextPosition,
+    pub message: String,
+    /// The tool that reported it within its provider, such as `rustc` or `clippy`.
+    pub source: Option<String>,
+    pub code: Option<String>,
+}
+
+impl Diagnostic {
+    /// The message with its source and code, as shown in hovers and the Problems panel.
+    pub fn description(&self) -> String {
+        match (&self.source, &self.code) {
+            (Some(source), Some(code)) => format!("{} ({}: {})", self.message, source, code),
+            (Some(source), None) => format!("{} ({})", self.message, source),
+            (None, Some(code)) => format!("{} ({})", self.message, code),
+            (None, None) => self.message.clone(),
+        }
+    }
+}
+
+/// Problems reported for files in the workspace, by the provider that reported them.
+///
+/// A provider is a language server or an external linter, named by the caller. Each
+/// report replaces everything that provider said about the file before, so providers
+/// never clobber one another.
+#[derive(Default)]
+pub struct Diagnostics {
+    files: BTreeMap<PathBuf, HashMap<String, Vec<Diagnostic>>>,
+}
+
+impl Diagnostics {
+    pub fn set(&mut self, provider: &str, path: PathBuf, diagnostics: Vec<Diagnostic>) {
+        if diagnostics.is_empty() {
+            if let Some(providers) = self.files.get_mut(&path) {
+                providers.remove(provider);
+                if providers.is_empty() {
+                    self.files.remove(&path);
+                }
+            }
+            return;
+        }
+        self.files.entry(path).or_default().insert(provider.to_string(), diagnostics);
+    }
+
+    /// Forgets everything a provider reported, for when it stops.
+    pub fn clear_provider(&mut self, provider: &str) {
+        self.files.retain(|_, providers| {
+            providers.remove(provider);
+            !providers.is_empty()
+        });
+    }
+
+    /// The problems in a file, in order of position.
+    pub fn for_file(&self, path: &Path) -> Vec<&Diagnostic> {
+        let mut diagnostics: Vec<&Diagnostic> =
+            self.files.get(path).into_iter().flat_map(|providers| providers.values().flatten()).collect();
+        diagnostics.sort_by_key(|d| (d.start, d.severity));
+        diagnostics
+    }
+
+    /// Every file with problems, sorted by path.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, Vec<&Diagnostic>)> {
+        self.files.keys().map(|path| (path.as_path(), self.for_file(path)))
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.files
+            .values()
+            .flat_map(|providers| providers.values().flatten())
+            .filter(|d| d.severity == severity)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
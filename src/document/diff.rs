@@ -0,0 +1,143 @@
+use similar::{ChangeTag, DiffTag, TextDiff};
+use std::ops::Range;
+
+/// Which side of a comparison a line belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+    Context,
+    Removed,
+    Added,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    /// 1-based line numbers on the old and new side; `None` on the side the line is missing from.
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    /// The line without its line break.
+    pub text: String,
+    /// Byte ranges of `text` that differ from the line it replaces or is replaced by.
+    pub emphasis: Vec<Range<usize>>,
+}
+
+impl DiffLine {
+    pub fn new(kind: DiffLineKind, old_line: Option<usize>, new_line: Option<usize>, text: String) -> Self {
+        Self { kind, old_line, new_line, text, emphasis: Vec::new() }
+    }
+}
+
+/// A run of changes with surrounding context, as in a unified diff.
+#[derive(Debug, Clone, Default)]
+pub struct DiffHunk {
+    pub header: String,
+    pub lines: Vec<DiffLine>,
+    /// The hunk as unified diff text, when it came from git and can be applied as a patch.
+    pub patch: Option<String>,
+}
+
+impl DiffHunk {
+    /// Marks the changed words of lines that were edited rather than replaced. A block
+    /// of removed lines is paired line by line with the added lines right after it.
+    pub fn highlight_words(&mut self) {
+        let mut i = 0;
+        while i < self.lines.len() {
+            let removed_start = i;
+            while i < self.lines.len() && self.lines[i].kind == DiffLineKind::Removed {
+                i += 1;
+            }
+            let added_start = i;
+            while i < self.lines.len() && self.lines[i].kind == DiffLineKind::Added {
+                i += 1;
+            }
+            if added_start == removed_start && i == added_start {
+                i += 1;
+                continue;
+            }
+
+            let pairs = (added_start - removed_start).min(i - added_start);
+            for pair in 0..pairs {
+                let (old, new) = word_changes(&self.lines[removed_start + pair].text, &self.lines[added_start + pair].text);
+                self.lines[removed_start + pair].emphasis = old;
+                self.lines[added_start + pair].emphasis = new;
+            }
+        }
+    }
+}
+
+/// Diffs two texts line by line into hunks with `context` unchanged lines around each change.
+pub fn compute_hunks(old: &str, new: &str, context: usize) -> Vec<DiffHunk> {
+    let mut config = TextDiff::configure();
+    // `similar` measures its deadline with `Instant`, which the web target does not have.
+    #[cfg(not(target_arch = "wasm32"))]
+    config.timeout(std::time::Duration::from_secs(1));
+    let diff = config.diff_lines(old, new);
+
+    diff.grouped_ops(context)
+        .into_iter()
+        .map(|group| {
+            let (first, last) = (&group[0], &group[group.len() - 1]);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+
+            let lines = group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| {
+                    let kind = match change.tag() {
+                        ChangeTag::Equal => DiffLineKind::Context,
+                        ChangeTag::Delete => DiffLineKind::Removed,
+                        ChangeTag::Insert => DiffLineKind::Added,
+                    };
+                    let text = change.value().trim_end_matches(['\n', '\r']).to_string();
+                    DiffLine::new(kind, change.old_index().map(|i| i + 1), change.new_index().map(|i| i + 1), text)
+                })
+                .collect();
+
+            DiffHunk {
+                header: format!(
+                    "@@ -{},{} +{},{} @@",
+                    old_range.start + 1,
+                    old_range.len(),
+                    new_range.start + 1,
+                    new_range.len()
+                ),
+                lines,
+                patch: None,
+            }
+        })
+        .collect()
+}
+
+/// The byte ranges that differ between two versions of a line. Lines that have little
+/// in common get no ranges, since highlighting nearly every word tells the reader nothing.
+pub fn word_changes(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let diff = TextDiff::from_words(old, new);
+    if diff.ratio() < 0.3 {
+        return (Vec::new(), Vec::new());
+    }
+
+    let mut old_ranges: Vec<Range<usize>> = Vec::new();
+    let mut new_ranges: Vec<Range<usize>> = Vec::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    let push = |ranges: &mut Vec<Range<usize>>, range: Range<usize>| match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    };
+
+    for op in diff.ops() {
+        let old_len: usize = diff.iter_changes(op).filter(|c| c.tag() != ChangeTag::Insert).map(|c| c.value().len()).sum();
+        let new_len: usize = diff.iter_changes(op).filter(|c| c.tag() != ChangeTag::Delete).map(|c| c.value().len()).sum();
+        if op.tag() != DiffTag::Equal {
+            if old_len > 0 {
+                push(&mut old_ranges, old_pos..old_pos + old_len);
+            }
+            if new_len > 0 {
+                push(&mut new_ranges, new_pos..new_pos + new_len);
+            }
+        }
+        old_pos += old_len;
+        new_pos += new_len;
+    }
+    (old_ranges, new_ranges)
+}
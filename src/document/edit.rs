@@ -0,0 +1,137 @@
+use super::position::TextPosition;
+use crate::symbols::search;
+use similar::{DiffTag, TextDiff};
+use std::path::{Path, PathBuf};
+
+/// Replaces the text between two positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: TextPosition,
+    pub end: TextPosition,
+    pub new_text: String,
+}
+
+/// The edits to make to one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    pub edits: Vec<TextEdit>,
+}
+
+/// Edits to any number of files that belong together, such as a rename or a fix.
+#[derive(Debug, Clone)]
+pub struct WorkspaceEdit {
+    /// What the edit does, as shown above its preview.
+    pub label: String,
+    pub files: Vec<FileEdit>,
+}
+
+impl WorkspaceEdit {
+    pub fn new(label: impl Into<String>, files: Vec<FileEdit>) -> Self {
+        Self { label: label.into(), files }
+    }
+
+    /// Puts every file's edits under a single entry, in the order the files first appear.
+    pub fn merge_files(&mut self) {
+        let mut merged: Vec<FileEdit> = Vec::new();
+        for file in self.files.drain(..) {
+            match merged.iter_mut().find(|existing| existing.path == file.path) {
+                Some(existing) => existing.edits.extend(file.edits),
+                None => merged.push(file),
+            }
+        }
+        self.files = merged;
+    }
+}
+
+/// A file's text before and after an edit.
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub old: String,
+    pub new: String,
+}
+
+/// Makes all of `edits` to `text` at once. Positions refer to `text` as it was before any
+/// of them, so they are applied from the end; edits that overlap are refused, since
+/// there is no telling which should win.
+pub fn apply(text: &str, edits: &[TextEdit]) -> Result<String, String> {
+    let mut ranges: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .map(|edit| (edit.start.char_index(text), edit.end.char_index(text), edit.new_text.as_str()))
+        .collect();
+    // Stable, so insertions at the same position keep the order they were given in.
+    ranges.sort_by_key(|&(start, end, _)| (start, end));
+    if ranges.iter().any(|&(start, end, _)| end < start) {
+        return Err("an edit ends before it starts".to_string());
+    }
+    if ranges.windows(2).any(|pair| pair[1].0 < pair[0].1) {
+        return Err("the edits overlap".to_string());
+    }
+
+    let offsets: Vec<usize> = text.char_indices().map(|(offset, _)| offset).chain([text.len()]).collect();
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (start, end, new_text) in ranges {
+        result.push_str(&text[offsets[copied]..offsets[start]]);
+        result.push_str(new_text);
+        copied = end;
+    }
+    result.push_str(&text[offsets[copied]..]);
+    Ok(result)
+}
+
+/// Where the character at `index` of `text` ends up once `edits` are made, so the cursor
+/// stays on the same code when edits land before it.
+pub fn map_index(text: &str, edits: &[TextEdit], index: usize) -> usize {
+    let mut ranges: Vec<(usize, usize, usize)> = edits
+        .iter()
+        .map(|edit| (edit.start.char_index(text), edit.end.char_index(text), edit.new_text.chars().count()))
+        .collect();
+    ranges.sort_unstable();
+
+    let mut shift: isize = 0;
+    for (start, end, length) in ranges {
+        if end <= index {
+            shift += length as isize - (end - start) as isize;
+        } else if start < index {
+            // An edit around the index moves it to the end of the new text.
+            return (start as isize + shift) as usize + length;
+        }
+    }
+    (index as isize + shift) as usize
+}
+
+/// The edits that turn `old` into `new`, each replacing whole lines, so that a tool which
+/// rewrites a file can be applied without moving the cursor off unchanged code.
+pub fn line_changes(old: &str, new: &str) -> Vec<TextEdit> {
+    let diff = TextDiff::from_lines(old, new);
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    diff.ops()
+        .iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| TextEdit {
+            start: TextPosition::new(op.old_range().start, 0),
+            end: TextPosition::new(op.old_range().end, 0),
+            new_text: new_lines[op.new_range()].concat(),
+        })
+        .collect()
+}
+
+/// Renames every whole-word occurrence of `name` in one file's text: what a rename can
+/// do without a language server that knows which occurrences are the same symbol.
+pub fn rename_in(path: &Path, text: &str, name: &str, new_name: &str) -> FileEdit {
+    let width = name.encode_utf16().count();
+    let edits = search::references_in(path, text, name)
+        .into_iter()
+        .map(|reference| {
+            let start = reference.location.position;
+            TextEdit {
+                start,
+                end: TextPosition::new(start.line, start.column + width),
+                new_text: new_name.to_string(),
+            }
+        })
+        .collect();
+    FileEdit { path: path.to_path_buf(), edits }
+}
@@ -0,0 +1,270 @@
+use encoding_rs::Encoding;
+
+/// The on-disk encoding of a document, including whether it carried a byte order mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileEncoding {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+impl Default for FileEncoding {
+    fn default() -> Self {
+        Self::UTF8
+    }
+}
+
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: FileEncoding,
+    pub had_errors: bool,
+}
+
+impl FileEncoding {
+    pub const UTF8: Self = Self {
+        encoding: encoding_rs::UTF_8,
+        bom: false,
+    };
+
+    /// Encodings offered by the "Reopen with Encoding" and "Save with Encoding" commands.
+    pub fn choices() -> Vec<FileEncoding> {
+        vec![
+            Self::UTF8,
+            Self { encoding: encoding_rs::UTF_8, bom: true },
+            Self { encoding: encoding_rs::UTF_16LE, bom: true },
+            Self { encoding: encoding_rs::UTF_16BE, bom: true },
+            Self { encoding: encoding_rs::WINDOWS_1252, bom: false },
+            Self { encoding: encoding_rs::ISO_8859_15, bom: false },
+            Self { encoding: encoding_rs::WINDOWS_1251, bom: false },
+            Self { encoding: encoding_rs::SHIFT_JIS, bom: false },
+            Self { encoding: encoding_rs::GBK, bom: false },
+            Self { encoding: encoding_rs::EUC_KR, bom: false },
+        ]
+    }
+
+    /// Looks an encoding up by its WHATWG name, as stored in backups and sessions.
+    pub fn from_name(name: &str, bom: bool) -> Option<Self> {
+        Encoding::for_label(name.as_bytes()).map(|encoding| Self { encoding, bom })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    pub fn label(&self) -> String {
+        let name = if self.encoding == encoding_rs::WINDOWS_1252 {
+            "Latin-1 (Windows-1252)"
+        } else {
+            self.encoding.name()
+        };
+
+        if self.bom && self.encoding == encoding_rs::UTF_8 {
+            format!("{} with BOM", name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn bom_bytes(&self) -> &'static [u8] {
+        if !self.bom {
+            return &[];
+        }
+
+        if self.encoding == encoding_rs::UTF_8 {
+            b"\xEF\xBB\xBF"
+        } else if self.encoding == encoding_rs::UTF_16LE {
+            b"\xFF\xFE"
+        } else if self.encoding == encoding_rs::UTF_16BE {
+            b"\xFE\xFF"
+        } else {
+            &[]
+        }
+    }
+
+    /// Encodes `text` for writing, failing on the first character the encoding cannot represent.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = self.bom_bytes().to_vec();
+
+        if self.encoding == encoding_rs::UTF_16LE {
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            return Ok(bytes);
+        }
+        if self.encoding == encoding_rs::UTF_16BE {
+            bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            return Ok(bytes);
+        }
+
+        let (encoded, _, unmappable) = self.encoding.encode(text);
+        if unmappable {
+            return Err(self.describe_unmappable(text));
+        }
+
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+
+    fn describe_unmappable(&self, text: &str) -> String {
+        let mut buffer = [0; 4];
+        for (line_index, line) in text.lines().enumerate() {
+            for (column, ch) in line.chars().enumerate() {
+                if self.encoding.encode(ch.encode_utf8(&mut buffer)).2 {
+                    return format!(
+                        "'{}' (U+{:04X}) at line {}, column {} cannot be encoded as {}",
+                        ch,
+                        ch as u32,
+                        line_index + 1,
+                        column + 1,
+                        self.label()
+                    );
+                }
+            }
+        }
+
+        format!("The text cannot be encoded as {}", self.label())
+    }
+}
+
+/// Guesses the encoding of `bytes` from its BOM, UTF-8 validity and NUL-byte layout.
+pub fn detect(bytes: &[u8]) -> FileEncoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return FileEncoding { encoding, bom: true };
+    }
+
+    // Checked before UTF-8 validity, which ASCII text in UTF-16 passes since NUL is valid.
+    let sample = &bytes[..bytes.len().min(4096)];
+    let even_nuls = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_nuls = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    let half = sample.len() / 2;
+    if half > 0 && odd_nuls * 3 > half && even_nuls * 10 < half {
+        return FileEncoding { encoding: encoding_rs::UTF_16LE, bom: false };
+    }
+    if half > 0 && even_nuls * 3 > half && odd_nuls * 10 < half {
+        return FileEncoding { encoding: encoding_rs::UTF_16BE, bom: false };
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return FileEncoding::UTF8;
+    }
+
+    FileEncoding {
+        encoding: encoding_rs::WINDOWS_1252,
+        bom: false,
+    }
+}
+
+/// Decodes `bytes`, detecting the encoding unless one is forced.
+pub fn decode(bytes: &[u8], forced: Option<FileEncoding>) -> DecodedText {
+    let (encoding, body) = split_bom(bytes, forced);
+    let (text, had_errors) = encoding.encoding.decode_without_bom_handling(body);
+    DecodedText {
+        text: text.into_owned(),
+        encoding,
+        had_errors,
+    }
+}
+
+/// The encoding of `bytes`, detected unless one is forced, and the text after its BOM.
+pub fn split_bom(bytes: &[u8], forced: Option<FileEncoding>) -> (FileEncoding, &[u8]) {
+    let detected = detect(bytes);
+    let encoding = match forced {
+        Some(forced) => FileEncoding {
+            encoding: forced.encoding,
+            bom: detected.bom && detected.encoding == forced.encoding,
+        },
+        None => detected,
+    };
+
+    let body = match Encoding::for_bom(bytes) {
+        Some((bom_encoding, length)) if encoding.bom && bom_encoding == encoding.encoding => &bytes[length..],
+        _ => bytes,
+    };
+    (encoding, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        text.encode_utf16().flat_map(|unit| if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() }).collect()
+    }
+
+    #[test]
+    fn detects_byte_order_marks() {
+        assert_eq!(detect(b"\xEF\xBB\xBFhi"), FileEncoding { encoding: encoding_rs::UTF_8, bom: true });
+        assert_eq!(detect(b"\xFF\xFEh\0"), FileEncoding { encoding: encoding_rs::UTF_16LE, bom: true });
+        assert_eq!(detect(b"\xFE\xFF\0h"), FileEncoding { encoding: encoding_rs::UTF_16BE, bom: true });
+    }
+
+    #[test]
+    fn detects_utf16_without_a_bom_from_its_nul_bytes() {
+        let le = FileEncoding { encoding: encoding_rs::UTF_16LE, bom: false };
+        let be = FileEncoding { encoding: encoding_rs::UTF_16BE, bom: false };
+        // Plain ASCII in UTF-16 is also valid UTF-8.
+        assert_eq!(detect(&utf16("plain text\n", true)), le);
+        assert_eq!(detect(&utf16("plain text\n", false)), be);
+        assert_eq!(detect(&utf16("grüße\n", true)), le);
+    }
+
+    #[test]
+    fn falls_back_to_latin1_for_invalid_utf8() {
+        assert_eq!(detect("héllo".as_bytes()), FileEncoding::UTF8);
+        assert_eq!(detect(b"").encoding, encoding_rs::UTF_8);
+        assert_eq!(detect(b"h\xe9llo").encoding, encoding_rs::WINDOWS_1252);
+        // A stray NUL in a binary-ish file does not make it UTF-16.
+        assert_eq!(detect(b"abc\0def\xff").encoding, encoding_rs::WINDOWS_1252);
+    }
+
+    #[test]
+    fn decode_strips_the_bom_and_honors_a_forced_encoding() {
+        let decoded = decode(b"\xEF\xBB\xBFhi", None);
+        assert_eq!(decoded.text, "hi");
+        assert!(decoded.encoding.bom);
+
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(utf16("grüße", true));
+        assert_eq!(decode(&bytes, None).text, "grüße");
+
+        // Forcing the encoding the BOM names keeps it; forcing another decodes it as text.
+        let forced = decode(b"\xEF\xBB\xBFhi", Some(FileEncoding::UTF8));
+        assert_eq!((forced.text.as_str(), forced.encoding.bom), ("hi", true));
+        let latin1 = FileEncoding { encoding: encoding_rs::WINDOWS_1252, bom: false };
+        assert_eq!(decode(b"\xEF\xBB\xBFhi", Some(latin1)).text, "ï»¿hi");
+
+        let invalid = decode(b"h\xe9llo", Some(FileEncoding::UTF8));
+        assert!(invalid.had_errors);
+        assert_eq!(invalid.text, "h\u{FFFD}llo");
+    }
+
+    #[test]
+    fn encode_writes_the_bom_and_round_trips() {
+        for encoding in FileEncoding::choices() {
+            let text = "line one\nzwei\n";
+            let bytes = encoding.encode(text).unwrap();
+            let decoded = decode(&bytes, Some(encoding));
+            assert_eq!(decoded.text, text, "{}", encoding.label());
+            assert!(!decoded.had_errors);
+        }
+
+        let utf16be = FileEncoding { encoding: encoding_rs::UTF_16BE, bom: true };
+        assert_eq!(utf16be.encode("a").unwrap(), b"\xFE\xFF\0a");
+        assert_eq!(FileEncoding::UTF8.encode("a").unwrap(), b"a");
+    }
+
+    #[test]
+    fn encode_refuses_characters_the_encoding_lacks() {
+        let latin1 = FileEncoding { encoding: encoding_rs::WINDOWS_1252, bom: false };
+        assert_eq!(latin1.encode("café €").unwrap(), b"caf\xe9 \x80");
+        assert_eq!(
+            latin1.encode("ok\nab中").unwrap_err(),
+            "'中' (U+4E2D) at line 2, column 3 cannot be encoded as Latin-1 (Windows-1252)"
+        );
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for encoding in FileEncoding::choices() {
+            assert_eq!(FileEncoding::from_name(encoding.name(), encoding.bom), Some(encoding));
+        }
+        assert_eq!(FileEncoding::from_name("no-such-encoding", false), None);
+    }
+}
@@ -0,0 +1,156 @@
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl Default for LineEnding {
+    fn default() -> Self {
+        Self::native()
+    }
+}
+
+/// What a scan of a document found: the ending to keep using and whether others were mixed in.
+pub struct DetectedLineEnding {
+    pub line_ending: LineEnding,
+    pub mixed: bool,
+}
+
+impl LineEnding {
+    pub const ALL: [LineEnding; 3] = [LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr];
+
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+            LineEnding::Cr => "CR",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|ending| ending.label() == label)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    /// Converts a normalized (`\n`-only) buffer back to this ending for writing.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            LineEnding::Lf => Cow::Borrowed(text),
+            _ => Cow::Owned(text.replace('\n', self.as_str())),
+        }
+    }
+}
+
+/// Counts each kind of line ending and picks the most common one.
+/// Files without any line break get the platform default.
+pub fn detect(text: &str) -> DetectedLineEnding {
+    let bytes = text.as_bytes();
+    let (mut lf, mut crlf, mut cr) = (0usize, 0usize, 0usize);
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                crlf += 1;
+                i += 1;
+            }
+            b'\r' => cr += 1,
+            b'\n' => lf += 1,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let line_ending = if lf == 0 && crlf == 0 && cr == 0 {
+        LineEnding::native()
+    } else if crlf >= lf && crlf >= cr {
+        LineEnding::CrLf
+    } else if lf >= cr {
+        LineEnding::Lf
+    } else {
+        LineEnding::Cr
+    };
+
+    DetectedLineEnding {
+        line_ending,
+        mixed: [lf, crlf, cr].iter().filter(|count| **count > 0).count() > 1,
+    }
+}
+
+/// Rewrites every `\r\n` and lone `\r` as `\n`.
+pub fn normalize(text: String) -> String {
+    if !text.contains('\r') {
+        return text;
+    }
+
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_picks_the_most_common_ending() {
+        let detected = detect("a\r\nb\r\nc\n");
+        assert_eq!(detected.line_ending, LineEnding::CrLf);
+        assert!(detected.mixed);
+
+        let detected = detect("a\nb\n");
+        assert_eq!(detected.line_ending, LineEnding::Lf);
+        assert!(!detected.mixed);
+
+        assert_eq!(detect("a\rb\rc\n").line_ending, LineEnding::Cr);
+        // A CRLF is one ending, not a CR and an LF.
+        assert!(!detect("a\r\n\r\n").mixed);
+    }
+
+    #[test]
+    fn detect_falls_back_to_the_platform_default() {
+        let detected = detect("no line breaks");
+        assert_eq!(detected.line_ending, LineEnding::native());
+        assert!(!detected.mixed);
+        assert_eq!(detect("").line_ending, LineEnding::native());
+    }
+
+    #[test]
+    fn normalize_and_apply_round_trip() {
+        assert_eq!(normalize("a\r\nb\rc\n\r\n".to_string()), "a\nb\nc\n\n");
+        // A CR at the very end has no LF to pair with.
+        assert_eq!(normalize("a\r".to_string()), "a\n");
+
+        for ending in LineEnding::ALL {
+            let text = format!("one{0}two{0}{0}", ending.as_str());
+            let normalized = normalize(text.clone());
+            assert_eq!(normalized, "one\ntwo\n\n");
+            assert_eq!(ending.apply(&normalized), text);
+        }
+        assert!(matches!(LineEnding::Lf.apply("a\n"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn labels_round_trip() {
+        for ending in LineEnding::ALL {
+            assert_eq!(LineEnding::from_label(ending.label()), Some(ending));
+        }
+        assert_eq!(LineEnding::from_label("crlf"), None);
+    }
+}
@@ -0,0 +1,300 @@
+use crate::document::encoding::{self, FileEncoding};
+use std::borrow::Cow;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+
+/// Files above this size are loaded on a worker thread with syntax highlighting disabled.
+pub const LARGE_FILE_THRESHOLD: u64 = 2 * 1024 * 1024;
+
+/// Files above this size are memory-mapped and opened read-only, since laying out a
+/// text edit that big stalls every frame.
+pub const READ_ONLY_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// Lines longer than this are cut when shown in the read-only viewer.
+const MAX_VIEWER_LINE_LENGTH: usize = 4096;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+pub enum LoadedContent {
+    Text(String),
+    Mapped(MappedText),
+}
+
+pub struct LoadedFile {
+    pub path: PathBuf,
+    pub content: LoadedContent,
+    pub encoding: FileEncoding,
+    pub lossy: bool,
+}
+
+/// A read-only view over a file that is too big to hold in an editable buffer.
+pub struct MappedText {
+    bytes: MappedBytes,
+    encoding: FileEncoding,
+    line_starts: Vec<usize>,
+}
+
+enum MappedBytes {
+    #[cfg(not(target_arch = "wasm32"))]
+    Mapped(memmap2::Mmap),
+    #[allow(dead_code)]
+    Owned(Vec<u8>),
+}
+
+impl MappedBytes {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            MappedBytes::Mapped(mmap) => mmap,
+            MappedBytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+impl MappedText {
+    pub fn len(&self) -> usize {
+        self.bytes.as_slice().len()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    pub fn line(&self, index: usize) -> Cow<'_, str> {
+        let bytes = self.bytes.as_slice();
+        let Some(&start) = self.line_starts.get(index) else {
+            return Cow::Borrowed("");
+        };
+        let width = unit_width(self.encoding);
+        let mut end = self.line_starts.get(index + 1).copied().unwrap_or(bytes.len());
+        end -= (end - start) % width;
+        for terminator in ['\n', '\r'] {
+            if end > start && is_unit(&bytes[end - width..end], self.encoding, terminator) {
+                end -= width;
+            }
+        }
+        let end = end.min(start + MAX_VIEWER_LINE_LENGTH * width);
+
+        self.encoding.encoding.decode_without_bom_handling(&bytes[start..end]).0
+    }
+}
+
+/// Loads a file on a worker thread, reporting progress as bytes are read.
+pub struct FileLoadTask {
+    pub path: PathBuf,
+    total_bytes: u64,
+    bytes_read: Arc<AtomicU64>,
+    cancelled: Arc<AtomicBool>,
+    receiver: mpsc::Receiver<Result<LoadedFile, String>>,
+}
+
+impl FileLoadTask {
+    pub fn spawn(path: PathBuf, total_bytes: u64, forced_encoding: Option<FileEncoding>) -> Self {
+        let bytes_read = Arc::new(AtomicU64::new(0));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+
+        let worker_path = path.clone();
+        let worker_progress = bytes_read.clone();
+        let worker_cancelled = cancelled.clone();
+        std::thread::spawn(move || {
+            let result = if total_bytes > READ_ONLY_THRESHOLD {
+                map_file(&worker_path, forced_encoding, &worker_progress, &worker_cancelled)
+                    .map(|text| LoadedFile {
+                        path: worker_path.clone(),
+                        encoding: text.encoding,
+                        content: LoadedContent::Mapped(text),
+                        lossy: false,
+                    })
+            } else {
+                read_chunked(&worker_path, &worker_progress, &worker_cancelled)
+                    .map(|bytes| decode(worker_path.clone(), &bytes, forced_encoding))
+            };
+            let _ = sender.send(result);
+        });
+
+        Self {
+            path,
+            total_bytes,
+            bytes_read,
+            cancelled,
+            receiver,
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        if self.total_bytes == 0 {
+            return 1.0;
+        }
+        self.bytes_read.load(Ordering::Relaxed) as f32 / self.total_bytes as f32
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn poll(&self) -> Option<Result<LoadedFile, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err("Loader thread stopped unexpectedly".into())),
+        }
+    }
+}
+
+/// Reads a small file on the calling thread.
+pub fn load_sync(path: &Path, forced_encoding: Option<FileEncoding>) -> Result<LoadedFile, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    Ok(decode(path.to_path_buf(), &bytes, forced_encoding))
+}
+
+pub fn decode(path: PathBuf, bytes: &[u8], forced_encoding: Option<FileEncoding>) -> LoadedFile {
+    let decoded = encoding::decode(bytes, forced_encoding);
+
+    LoadedFile {
+        path,
+        content: LoadedContent::Text(decoded.text),
+        encoding: decoded.encoding,
+        lossy: decoded.had_errors,
+    }
+}
+
+fn read_chunked(path: &Path, progress: &AtomicU64, cancelled: &AtomicBool) -> Result<Vec<u8>, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let capacity = file.metadata().map(|m| m.len() as usize).unwrap_or(0);
+    let mut bytes = Vec::with_capacity(capacity);
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err("Loading cancelled".into());
+        }
+
+        let read = file.read(&mut chunk).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+        progress.fetch_add(read as u64, Ordering::Relaxed);
+    }
+
+    Ok(bytes)
+}
+
+fn map_file(
+    path: &Path,
+    forced_encoding: Option<FileEncoding>,
+    progress: &AtomicU64,
+    cancelled: &AtomicBool,
+) -> Result<MappedText, String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let bytes = {
+        let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        // SAFETY: the mapping is only ever read; a concurrent truncation by another
+        // process is the same hazard every mmap-based viewer accepts.
+        let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(|e| e.to_string())?;
+        MappedBytes::Mapped(mmap)
+    };
+    #[cfg(target_arch = "wasm32")]
+    let bytes = MappedBytes::Owned(read_chunked(path, progress, cancelled)?);
+
+    let all = bytes.as_slice();
+    let (encoding, body) = encoding::split_bom(all, forced_encoding);
+    let body_start = all.len() - body.len();
+    let width = unit_width(encoding);
+
+    let mut line_starts = vec![body_start];
+    // Chunks hold whole code units, so a newline never straddles two of them.
+    for (chunk_index, chunk) in body.chunks(CHUNK_SIZE).enumerate() {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err("Loading cancelled".into());
+        }
+
+        let offset = body_start + chunk_index * CHUNK_SIZE;
+        line_starts.extend(
+            chunk.chunks_exact(width)
+                .enumerate()
+                .filter(|(_, unit)| is_unit(unit, encoding, '\n'))
+                .map(|(i, _)| offset + (i + 1) * width),
+        );
+        progress.store((offset + chunk.len()) as u64, Ordering::Relaxed);
+    }
+
+    if line_starts.len() > 1 && line_starts.last() == Some(&all.len()) {
+        line_starts.pop();
+    }
+
+    Ok(MappedText { bytes, encoding, line_starts })
+}
+
+/// Bytes per code unit: two for UTF-16, one for UTF-8 and the single- and multi-byte
+/// encodings, which all keep ASCII line breaks as they are.
+fn unit_width(encoding: FileEncoding) -> usize {
+    if encoding.encoding == encoding_rs::UTF_16LE || encoding.encoding == encoding_rs::UTF_16BE {
+        2
+    } else {
+        1
+    }
+}
+
+fn is_unit(unit: &[u8], encoding: FileEncoding, c: char) -> bool {
+    let c = c as u8;
+    match unit {
+        [byte] => *byte == c,
+        [first, second] if encoding.encoding == encoding_rs::UTF_16LE => *first == c && *second == 0,
+        [first, second] => *first == 0 && *second == c,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(bytes: &[u8], forced: Option<FileEncoding>) -> MappedText {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.log");
+        std::fs::write(&path, bytes).unwrap();
+        map_file(&path, forced, &AtomicU64::new(0), &AtomicBool::new(false)).unwrap()
+    }
+
+    fn lines(text: &MappedText) -> Vec<String> {
+        (0..text.line_count()).map(|index| text.line(index).into_owned()).collect()
+    }
+
+    #[test]
+    fn utf8_lines_drop_their_line_breaks() {
+        let text = map("first\r\nsécond\nthird\n".as_bytes(), None);
+        assert_eq!(text.encoding, FileEncoding::UTF8);
+        assert_eq!(lines(&text), ["first", "sécond", "third"]);
+    }
+
+    #[test]
+    fn utf16_is_split_on_whole_code_units() {
+        // U+0A0D encodes as 0D 0A in UTF-16LE, which must not read as a line break.
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "a\u{0A0D}b\r\nc\n".encode_utf16() {
+            bytes.extend(unit.to_le_bytes());
+        }
+        let text = map(&bytes, None);
+        assert_eq!(text.encoding.encoding, encoding_rs::UTF_16LE);
+        assert_eq!(lines(&text), ["a\u{0A0D}b", "c"]);
+
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in "one\ntwo".encode_utf16() {
+            bytes.extend(unit.to_be_bytes());
+        }
+        assert_eq!(lines(&map(&bytes, None)), ["one", "two"]);
+    }
+
+    #[test]
+    fn latin1_is_decoded_as_detected_or_forced() {
+        let text = map(b"caf\xe9\nna\xefve\n", None);
+        assert_eq!(lines(&text), ["café", "naïve"]);
+
+        let forced = FileEncoding { encoding: encoding_rs::ISO_8859_2, bom: false };
+        assert_eq!(map(b"\xb1\n", Some(forced)).line(0), "ą");
+    }
+}
@@ -0,0 +1,9 @@
+pub mod conflict;
+pub mod diagnostics;
+pub mod diff;
+pub mod edit;
+pub mod encoding;
+pub mod line_ending;
+pub mod loader;
+pub mod position;
+pub mod writer;
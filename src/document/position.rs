@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// A place in a file as language servers report it: a 0-based line and a column
+/// counted in UTF-16 code units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct TextPosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl TextPosition {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    /// The position of the character at `index` in `text`.
+    pub fn from_char_index(text: &str, index: usize) -> Self {
+        let mut position = Self::default();
+        for c in text.chars().take(index) {
+            if c == '\n' {
+                position.line += 1;
+                position.column = 0;
+            } else {
+                position.column += c.len_utf16();
+            }
+        }
+        position
+    }
+
+    /// The character index of this position in `text`, clamped to the end of its line
+    /// and to the end of the text.
+    pub fn char_index(&self, text: &str) -> usize {
+        let mut chars = 0;
+        for (number, line) in text.split_inclusive('\n').enumerate() {
+            let content = line.strip_suffix('\n').unwrap_or(line);
+            if number == self.line {
+                return chars + char_column(content, self.column);
+            }
+            chars += line.chars().count();
+        }
+        chars
+    }
+}
+
+/// The number of characters in `line` that make up `column` UTF-16 code units.
+pub fn char_column(line: &str, column: usize) -> usize {
+    let mut units = 0;
+    for (index, c) in line.chars().enumerate() {
+        if units >= column {
+            return index;
+        }
+        units += c.len_utf16();
+    }
+    line.chars().count()
+}
+
+/// A position in some file, where navigation can jump to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub position: TextPosition,
+}
+
+impl Location {
+    pub fn new(path: impl Into<PathBuf>, position: TextPosition) -> Self {
+        Self { path: path.into(), position }
+    }
+}
+
+/// The character range of the identifier at or just before `index` in `text`.
+pub fn word_at(text: &str, index: usize) -> Option<std::ops::Range<usize>> {
+    let chars: Vec<char> = text.chars().collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    let index = if chars.get(index).is_some_and(is_word) {
+        index
+    } else if index > 0 && chars.get(index - 1).is_some_and(is_word) {
+        index - 1
+    } else {
+        return None;
+    };
+
+    let start = chars[..index].iter().rposition(|c| !is_word(c)).map_or(0, |i| i + 1);
+    let end = chars[index..].iter().position(|c| !is_word(c)).map_or(chars.len(), |i| index + i);
+    Some(start..end)
+}
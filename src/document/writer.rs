@@ -0,0 +1,91 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What the file looked like on disk when it was last loaded or saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskState {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl DiskState {
+    pub fn read(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// Writes `bytes` to a temporary file next to `path` and renames it into place,
+/// so a crash mid-write never leaves a truncated file behind.
+///
+/// If `path` is a symlink the link is kept and its target is replaced, and the
+/// target's permissions are carried over to the new file.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let target = resolve_symlink(path)?;
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = target.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "untitled".into());
+    let temp_path = dir.join(format!(".{}.zen-save-{}", file_name, std::process::id()));
+
+    let result = write_temp_file(&temp_path, &target, bytes)
+        .and_then(|_| std::fs::rename(&temp_path, &target));
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    } else {
+        sync_directory(&dir);
+    }
+
+    result
+}
+
+fn write_temp_file(temp_path: &Path, target: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(temp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+
+    if let Ok(metadata) = std::fs::metadata(target) {
+        std::fs::set_permissions(temp_path, metadata.permissions())?;
+    }
+
+    Ok(())
+}
+
+fn resolve_symlink(path: &Path) -> std::io::Result<PathBuf> {
+    const MAX_LINK_DEPTH: usize = 32;
+
+    let mut current = path.to_path_buf();
+    for _ in 0..MAX_LINK_DEPTH {
+        match std::fs::symlink_metadata(&current) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let link = std::fs::read_link(&current)?;
+                current = if link.is_absolute() {
+                    link
+                } else {
+                    current.parent().unwrap_or(Path::new("")).join(link)
+                };
+            }
+            _ => return Ok(current),
+        }
+    }
+
+    Err(std::io::Error::other(format!("Too many levels of symbolic links: {}", path.display())))
+}
+
+#[cfg(unix)]
+fn sync_directory(dir: &Path) {
+    if let Ok(dir) = std::fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_directory(_dir: &Path) {}
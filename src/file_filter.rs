@@ -0,0 +1,208 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// User-facing rules for which files show up in the project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileFilterConfig {
+    /// Honor `.gitignore`, `.ignore` and the global git excludes file.
+    pub respect_gitignore: bool,
+    /// Globs that are always hidden, matched against the name and the project-relative path.
+    pub exclude: Vec<String>,
+    /// Globs that are always shown, even when ignored or excluded.
+    pub include: Vec<String>,
+    /// Show hidden and ignored entries dimmed instead of leaving them out.
+    pub show_ignored: bool,
+}
+
+impl Default for FileFilterConfig {
+    fn default() -> Self {
+        Self {
+            respect_gitignore: true,
+            exclude: vec![
+                ".git".to_string(),
+                ".hg".to_string(),
+                ".svn".to_string(),
+                ".DS_Store".to_string(),
+            ],
+            include: Vec::new(),
+            show_ignored: false,
+        }
+    }
+}
+
+/// Compiled form of [`FileFilterConfig`], shared with the threads that list directories.
+pub struct FileFilter {
+    respect_gitignore: bool,
+    exclude: GlobSet,
+    include: GlobSet,
+    pub show_ignored: bool,
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        Self::new(&FileFilterConfig::default())
+    }
+}
+
+impl FileFilter {
+    pub fn new(config: &FileFilterConfig) -> Self {
+        Self {
+            respect_gitignore: config.respect_gitignore,
+            exclude: build_glob_set(&config.exclude),
+            include: build_glob_set(&config.include),
+            show_ignored: config.show_ignored,
+        }
+    }
+
+    /// Whether `path` should be hidden, given whether the ignore files let it through.
+    pub fn is_ignored(&self, root: &Path, path: &Path, allowed_by_ignore_files: bool) -> bool {
+        if self.matches(&self.include, root, path) {
+            return false;
+        }
+        self.matches(&self.exclude, root, path) || !allowed_by_ignore_files
+    }
+
+    /// The entries of `dir` that `.gitignore`, `.ignore` and the global excludes let
+    /// through, or `None` when ignore files are not being honored.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn allowed_entries(&self, dir: &Path) -> Option<HashSet<PathBuf>> {
+        if !self.respect_gitignore {
+            return None;
+        }
+
+        let walker = ignore::WalkBuilder::new(dir)
+            .max_depth(Some(1))
+            .hidden(false)
+            .parents(true)
+            .follow_links(false)
+            .build();
+
+        Some(
+            walker
+                .flatten()
+                .filter(|entry| entry.depth() == 1)
+                .map(ignore::DirEntry::into_path)
+                .collect(),
+        )
+    }
+
+    /// Every file under `root` that the project shows, for indexing and searching.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn files(&self, root: &Path) -> Vec<PathBuf> {
+        self.files_under(root, root)
+    }
+
+    /// Like [`Self::files`] for a folder inside `root`, such as one that was just moved
+    /// there. Patterns still apply relative to `root`, and each folder is filtered the way
+    /// the explorer filters it, so `include` brings back ignored files here too.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn files_under(&self, root: &Path, dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            let allowed = self.allowed_entries(&dir);
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let allowed_by_ignore_files = allowed.as_ref().is_none_or(|allowed| allowed.contains(&path));
+                if self.is_ignored(root, &path, allowed_by_ignore_files) {
+                    continue;
+                }
+                // Links are left alone, so a link to a folder above cannot loop.
+                match entry.file_type() {
+                    Ok(kind) if kind.is_dir() => pending.push(path),
+                    Ok(kind) if kind.is_file() => files.push(path),
+                    _ => {}
+                }
+            }
+        }
+        files
+    }
+
+    /// Whether the project shows `path`, inside `root`: neither it nor any folder above it
+    /// is excluded or left out by the ignore files. Reads every folder on the way, so it is
+    /// meant for single paths off the UI thread.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn allows(&self, root: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+        let mut current = root.to_path_buf();
+        for component in relative.components() {
+            let parent = current.clone();
+            current.push(component);
+            let allowed = self.allowed_entries(&parent).is_none_or(|entries| entries.contains(&current));
+            if self.is_ignored(root, &current, allowed) {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn allowed_entries(&self, _dir: &Path) -> Option<HashSet<PathBuf>> {
+        None
+    }
+
+    fn matches(&self, set: &GlobSet, root: &Path, path: &Path) -> bool {
+        if set.is_empty() {
+            return false;
+        }
+        if path.file_name().is_some_and(|name| set.is_match(name)) {
+            return true;
+        }
+        path.strip_prefix(root).is_ok_and(|relative| set.is_match(relative))
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => log::warn!("Ignoring invalid file pattern {:?}: {}", pattern, e),
+        }
+    }
+    builder.build().unwrap_or_else(|e| {
+        log::warn!("Failed to compile file patterns: {}", e);
+        GlobSet::empty()
+    })
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include_brings_back_ignored_files_like_the_explorer_does() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("build/nested")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\nbuild/\n").unwrap();
+        for file in ["main.rs", "debug.log", "keep.log", "build/out.rs", "build/nested/deep.rs"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+
+        let filter = FileFilter::new(&FileFilterConfig {
+            include: vec!["keep.log".to_string(), "build".to_string()],
+            ..Default::default()
+        });
+        let mut files: Vec<_> = filter.files(root).into_iter().map(|path| path.strip_prefix(root).unwrap().to_path_buf()).collect();
+        files.sort();
+        let expected: Vec<PathBuf> = [".gitignore", "build/nested/deep.rs", "build/out.rs", "keep.log", "main.rs"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(files, expected);
+        assert!(filter.allows(root, &root.join("keep.log")));
+        assert!(!filter.allows(root, &root.join("debug.log")));
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Checks a name typed into the explorer and returns the path it would have in `dir`.
+pub fn child_path(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Name cannot be empty".to_string());
+    }
+    if name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(format!("\"{}\" is not a valid file name", name));
+    }
+
+    let path = dir.join(name);
+    if path.symlink_metadata().is_ok() {
+        return Err(format!("{} already exists", path.display()));
+    }
+    Ok(path)
+}
+
+pub fn create_file(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let path = child_path(dir, name)?;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+pub fn create_dir(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let path = child_path(dir, name)?;
+    std::fs::create_dir(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+pub fn rename(path: &Path, new_name: &str) -> Result<PathBuf, String> {
+    let dir = path.parent().ok_or_else(|| format!("Cannot rename {}", path.display()))?;
+    let target = child_path(dir, new_name)?;
+    std::fs::rename(path, &target).map_err(|e| format!("Failed to rename {}: {}", path.display(), e))?;
+    Ok(target)
+}
+
+/// Moves `path` into the folder `dir`, keeping its name.
+pub fn move_into(path: &Path, dir: &Path) -> Result<PathBuf, String> {
+    if dir.starts_with(path) {
+        return Err(format!("Cannot move {} into itself", path.display()));
+    }
+    let name = path.file_name().ok_or_else(|| format!("Cannot move {}", path.display()))?;
+    let target = child_path(dir, &name.to_string_lossy())?;
+    std::fs::rename(path, &target).map_err(|e| format!("Failed to move {}: {}", path.display(), e))?;
+    Ok(target)
+}
+
+/// Copies a file or folder next to itself as "name copy.ext", "name copy 2.ext", ...
+pub fn duplicate(path: &Path) -> Result<PathBuf, String> {
+    let dir = path.parent().ok_or_else(|| format!("Cannot duplicate {}", path.display()))?;
+    let name = |s: Option<&std::ffi::OsStr>| s.map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let (stem, extension) = if path.is_dir() {
+        (name(path.file_name()), String::new())
+    } else {
+        let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        (name(path.file_stem()), extension)
+    };
+
+    let target = (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("{} copy{}", stem, extension)),
+            n => dir.join(format!("{} copy {}{}", stem, n, extension)),
+        })
+        .find(|candidate| candidate.symlink_metadata().is_err())
+        .expect("unbounded range always yields a free name");
+
+    copy_recursive(path, &target).map_err(|e| format!("Failed to duplicate {}: {}", path.display(), e))?;
+    Ok(target)
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    let kind = from.symlink_metadata()?.file_type();
+    if kind.is_symlink() {
+        return copy_link(from, to);
+    }
+    if !kind.is_dir() {
+        return std::fs::copy(from, to).map(|_| ());
+    }
+
+    std::fs::create_dir(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Re-creates a link instead of copying what it points to, which may be a folder above it.
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_link(from: &Path, to: &Path) -> std::io::Result<()> {
+    let target = std::fs::read_link(from)?;
+    if from.metadata().is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
+    }
+}
+
+pub fn move_to_trash(path: &Path) -> Result<(), String> {
+    trash::delete(path).map_err(|e| e.to_string())
+}
+
+pub fn delete_permanently(path: &Path) -> Result<(), String> {
+    let result = if path.is_dir() && !path.is_symlink() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    result.map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+}
+
+/// Opens the system file manager on the folder containing `path`, selecting it where supported.
+pub fn reveal(path: &Path) -> Result<(), String> {
+    let parent = path.parent().unwrap_or(path);
+
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(parent);
+        command
+    };
+
+    command
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to open the file manager: {}", e))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicating_a_folder_copies_links_rather_than_their_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("src");
+        std::fs::create_dir(&folder).unwrap();
+        std::fs::write(folder.join("main.rs"), "fn main() {}").unwrap();
+        std::os::unix::fs::symlink("..", folder.join("up")).unwrap();
+        std::os::unix::fs::symlink("main.rs", folder.join("alias.rs")).unwrap();
+
+        let copy = duplicate(&folder).unwrap();
+        assert_eq!(copy, dir.path().join("src copy"));
+        assert_eq!(std::fs::read_to_string(copy.join("main.rs")).unwrap(), "fn main() {}");
+        assert_eq!(std::fs::read_link(copy.join("up")).unwrap(), PathBuf::from(".."));
+        assert_eq!(std::fs::read_link(copy.join("alias.rs")).unwrap(), PathBuf::from("main.rs"));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::config::FormatterConfig;
+use crate::document::line_ending;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How long a formatter may run before it is stopped and its result thrown away.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The first configured formatter for the extension of `path`.
+pub fn formatter_for<'a>(formatters: &'a [FormatterConfig], path: &Path) -> Option<&'a FormatterConfig> {
+    let extension = path.extension()?.to_str()?;
+    formatters.iter().find(|formatter| formatter.handles(extension))
+}
+
+/// Pipes `text` through a formatter on a background thread. The receiver gets the
+/// formatted text, or what went wrong when the formatter could not be run, failed or
+/// printed nothing.
+pub fn spawn(formatter: &FormatterConfig, path: &Path, text: String, ctx: &egui::Context) -> mpsc::Receiver<Result<String, String>> {
+    let (sender, receiver) = mpsc::channel();
+    let formatter = formatter.clone();
+    let path = path.to_path_buf();
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let result = run(&formatter, &path, &text).map_err(|e| format!("{}: {}", formatter.name, e));
+        let _ = sender.send(result);
+        ctx.request_repaint();
+    });
+    receiver
+}
+
+fn run(formatter: &FormatterConfig, path: &Path, text: &str) -> Result<String, String> {
+    let file = path.to_string_lossy();
+    let mut command = Command::new(&formatter.command);
+    command
+        .args(formatter.args.iter().map(|arg| arg.replace("{file}", &file)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(dir) = path.parent().filter(|dir| dir.is_dir()) {
+        command.current_dir(dir);
+    }
+    let mut child = command.spawn().map_err(|e| format!("failed to run `{}`: {}", formatter.command, e))?;
+
+    // Written and read on their own threads, so a formatter that starts printing before
+    // it has read everything cannot fill a pipe and stall.
+    let input = text.to_string();
+    let stdin = child.stdin.take();
+    std::thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(input.as_bytes());
+        }
+    });
+    let stdout = read_to_end(child.stdout.take());
+    let stderr = read_to_end(child.stderr.take());
+
+    let status = wait(&mut child)?;
+    let stdout = stdout.recv().unwrap_or_default();
+    let stderr = String::from_utf8_lossy(&stderr.recv().unwrap_or_default()).trim().to_string();
+    if !status.success() {
+        return Err(match stderr.lines().next() {
+            Some(line) => line.to_string(),
+            None => format!("exited with {}", status),
+        });
+    }
+
+    let formatted = String::from_utf8(stdout).map_err(|_| "the output is not valid UTF-8".to_string())?;
+    if formatted.is_empty() && !text.is_empty() {
+        return Err("the formatter printed nothing".to_string());
+    }
+    Ok(line_ending::normalize(formatted))
+}
+
+fn read_to_end(pipe: Option<impl Read + Send + 'static>) -> mpsc::Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        let _ = sender.send(bytes);
+    });
+    receiver
+}
+
+/// Waits for the formatter to exit, stopping it once it runs past [`TIMEOUT`].
+fn wait(child: &mut Child) -> Result<std::process::ExitStatus, String> {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Ok(status),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("did not finish within {} seconds", TIMEOUT.as_secs()));
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
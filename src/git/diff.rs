@@ -0,0 +1,96 @@
+use similar::{DiffOp, TextDiff};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// A run of changed lines in the buffer, with the lines it replaced.
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub kind: LineChangeKind,
+    /// First buffer line of the hunk; for deletions, the line the removed text preceded.
+    pub new_start: usize,
+    pub new_len: usize,
+    pub old_lines: Vec<String>,
+    pub new_lines: Vec<String>,
+}
+
+/// The buffer compared line by line against the version in HEAD, for gutter markers.
+#[derive(Debug, Clone, Default)]
+pub struct LineDiff {
+    pub hunks: Vec<Hunk>,
+    markers: Vec<Option<usize>>,
+}
+
+impl LineDiff {
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    pub fn compute(base: &str, current: &str) -> Self {
+        let diff = TextDiff::configure().timeout(Self::TIMEOUT).diff_lines(base, current);
+        let old_lines: Vec<&str> = base.split_inclusive('\n').collect();
+        let new_lines: Vec<&str> = current.split_inclusive('\n').collect();
+        let collect = |lines: &[&str], range: std::ops::Range<usize>| -> Vec<String> {
+            lines[range].iter().map(|line| line.to_string()).collect()
+        };
+
+        let mut hunks = Vec::new();
+        for op in diff.ops() {
+            let (kind, old, new) = match *op {
+                DiffOp::Equal { .. } => continue,
+                DiffOp::Insert { old_index, new_index, new_len } => {
+                    (LineChangeKind::Added, old_index..old_index, new_index..new_index + new_len)
+                }
+                DiffOp::Delete { old_index, old_len, new_index } => {
+                    (LineChangeKind::Deleted, old_index..old_index + old_len, new_index..new_index)
+                }
+                DiffOp::Replace { old_index, old_len, new_index, new_len } => {
+                    (LineChangeKind::Modified, old_index..old_index + old_len, new_index..new_index + new_len)
+                }
+            };
+            hunks.push(Hunk {
+                kind,
+                new_start: new.start,
+                new_len: new.len(),
+                old_lines: collect(&old_lines, old),
+                new_lines: collect(&new_lines, new),
+            });
+        }
+
+        let line_count = new_lines.len().max(1);
+        let mut markers = vec![None; line_count];
+        for (index, hunk) in hunks.iter().enumerate() {
+            if hunk.kind == LineChangeKind::Deleted {
+                markers[hunk.new_start.min(line_count - 1)] = Some(index);
+            } else {
+                for marker in &mut markers[hunk.new_start..hunk.new_start + hunk.new_len] {
+                    *marker = Some(index);
+                }
+            }
+        }
+
+        Self { hunks, markers }
+    }
+
+    /// The hunk drawn next to a buffer line, if any.
+    pub fn hunk_at_line(&self, line: usize) -> Option<(usize, &Hunk)> {
+        let index = (*self.markers.get(line)?)?;
+        Some((index, &self.hunks[index]))
+    }
+
+    /// Returns `current` with one hunk put back the way it is in HEAD.
+    pub fn revert(&self, index: usize, current: &str) -> Option<String> {
+        let hunk = self.hunks.get(index)?;
+        let lines: Vec<&str> = current.split_inclusive('\n').collect();
+        let end = (hunk.new_start + hunk.new_len).min(lines.len());
+
+        let mut reverted = String::with_capacity(current.len());
+        lines[..hunk.new_start.min(lines.len())].iter().for_each(|line| reverted.push_str(line));
+        hunk.old_lines.iter().for_each(|line| reverted.push_str(line));
+        lines[end..].iter().for_each(|line| reverted.push_str(line));
+        Some(reverted)
+    }
+}
@@ -0,0 +1,512 @@
+pub mod diff;
+pub mod patch;
+pub mod status;
+
+use patch::FilePatch;
+use status::{GitStatus, RepoStatus};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+/// Runs `git` in `dir` and returns its stdout, or stderr as the error.
+pub fn run_git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("--no-optional-locks")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Like [`run_git`], feeding `input` to the command's stdin.
+fn run_git_with_input(dir: &Path, args: &[&str], input: &str) -> Result<String, String> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).map_err(|e| format!("Failed to write to git: {}", e))?;
+    }
+    let output = child.wait_with_output().map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn path_arg(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+pub fn stage(repo: &Path, path: &Path) -> Result<(), String> {
+    run_git(repo, &["add", "--", &path_arg(path)]).map(|_| ())
+}
+
+pub fn stage_all(repo: &Path) -> Result<(), String> {
+    run_git(repo, &["add", "--all"]).map(|_| ())
+}
+
+pub fn unstage(repo: &Path, path: &Path) -> Result<(), String> {
+    // `restore --staged` needs a HEAD; before the first commit the file is just removed from the index.
+    run_git(repo, &["restore", "--staged", "--", &path_arg(path)])
+        .or_else(|_| run_git(repo, &["rm", "--cached", "-q", "--", &path_arg(path)]))
+        .map(|_| ())
+}
+
+pub fn unstage_all(repo: &Path) -> Result<(), String> {
+    run_git(repo, &["reset", "-q"]).map(|_| ())
+}
+
+/// Throws away worktree changes to `path`. Untracked files are deleted.
+pub fn discard(repo: &Path, path: &Path, untracked: bool) -> Result<(), String> {
+    if !untracked {
+        return run_git(repo, &["restore", "--", &path_arg(path)]).map(|_| ());
+    }
+
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    result.map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+}
+
+/// Commits the index. Amending with an empty message keeps the previous one.
+pub fn commit(repo: &Path, message: &str, amend: bool) -> Result<(), String> {
+    let mut args = vec!["commit"];
+    if amend {
+        args.push("--amend");
+    }
+    if message.trim().is_empty() {
+        args.push("--no-edit");
+    } else {
+        args.extend(["-m", message]);
+    }
+    run_git(repo, &args).map(|_| ())
+}
+
+pub fn last_commit_message(repo: &Path) -> Option<String> {
+    run_git(repo, &["log", "-1", "--format=%B"])
+        .ok()
+        .map(|message| message.trim_end().to_string())
+}
+
+/// The changes to one file: index against HEAD when `staged`, worktree against the index otherwise.
+pub fn file_patch(repo: &Path, path: &Path, staged: bool, untracked: bool) -> Result<FilePatch, String> {
+    if untracked {
+        let contents = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        return Ok(FilePatch::untracked(&String::from_utf8_lossy(&contents)));
+    }
+
+    let path = path_arg(path);
+    let mut args = vec!["diff", "--no-color", "--no-ext-diff"];
+    if staged {
+        args.push("--cached");
+    }
+    args.extend(["--", path.as_str()]);
+    run_git(repo, &args).map(|diff| FilePatch::parse(&diff))
+}
+
+/// Applies a partial patch, e.g. to stage (`--cached`), unstage (`--cached --reverse`)
+/// or discard (`--reverse`) a single hunk.
+pub fn apply_patch(repo: &Path, patch: &str, cached: bool, reverse: bool) -> Result<(), String> {
+    let mut args = vec!["apply", "--whitespace=nowarn"];
+    if cached {
+        args.push("--cached");
+    }
+    if reverse {
+        args.push("--reverse");
+    }
+    args.push("-");
+    run_git_with_input(repo, &args, patch).map(|_| ())
+}
+
+/// The top level of the repository containing `dir`, or `None` outside a repository.
+pub fn repo_root(dir: &Path) -> Option<PathBuf> {
+    run_git(dir, &["rev-parse", "--show-toplevel"])
+        .ok()
+        .map(|output| PathBuf::from(output.trim_end_matches(['\n', '\r'])))
+}
+
+fn read_status(roots: &[PathBuf]) -> GitStatus {
+    let mut status = GitStatus::default();
+
+    for root in roots {
+        let Some(repo) = repo_root(root) else {
+            continue;
+        };
+        if status.repos.iter().any(|known| known.root == repo) {
+            continue;
+        }
+
+        let args = ["status", "--porcelain=v1", "-z", "--branch", "--ignored=matching", "--untracked-files=normal"];
+        match run_git(&repo, &args) {
+            Ok(output) => status.repos.push(RepoStatus::parse(repo, &output)),
+            Err(e) => log::warn!("git status failed in {}: {}", repo.display(), e),
+        }
+    }
+    status
+}
+
+/// The contents of `path` at `revision`, such as `HEAD` or `:1` for the common ancestor
+/// of a conflicted file, or `None` if git has no such version.
+fn read_contents(path: &Path, revision: &str) -> Option<Vec<u8>> {
+    let dir = path.parent()?;
+    let name = path.file_name()?.to_string_lossy();
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["--no-optional-locks", "show"])
+        .arg(format!("{}:./{}", revision, name))
+        .output()
+        .ok()?;
+    output.status.success().then_some(output.stdout)
+}
+
+/// A change to a repository, run in the background by [`GitMonitor::run`] since
+/// commit hooks and large patches can take a while.
+#[derive(Debug, Clone)]
+pub enum GitOperation {
+    Stage { repo: PathBuf, path: PathBuf },
+    Unstage { repo: PathBuf, path: PathBuf },
+    StageAll(PathBuf),
+    UnstageAll(PathBuf),
+    Discard { repo: PathBuf, path: PathBuf, untracked: bool },
+    Commit { repo: PathBuf, message: String, amend: bool },
+    ApplyPatch { repo: PathBuf, patch: String, cached: bool, reverse: bool },
+}
+
+impl GitOperation {
+    fn run(&self) -> Result<(), String> {
+        match self {
+            Self::Stage { repo, path } => stage(repo, path),
+            Self::Unstage { repo, path } => unstage(repo, path),
+            Self::StageAll(repo) => stage_all(repo),
+            Self::UnstageAll(repo) => unstage_all(repo),
+            Self::Discard { repo, path, untracked } => discard(repo, path, *untracked),
+            Self::Commit { repo, message, amend } => commit(repo, message, *amend),
+            Self::ApplyPatch { repo, patch, cached, reverse } => apply_patch(repo, patch, *cached, *reverse),
+        }
+    }
+}
+
+/// The changes to one file to read with [`GitMonitor::request_patch`], as [`file_patch`] takes them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchRequest {
+    pub repo: PathBuf,
+    pub path: PathBuf,
+    pub staged: bool,
+    pub untracked: bool,
+}
+
+/// Work for the thread behind [`GitMonitor::run`], which does it in order so a patch read
+/// after an operation sees its result.
+enum GitJob {
+    Operation(GitOperation),
+    Patch(PatchRequest),
+}
+
+/// What finished in the background since the last [`GitMonitor::poll`].
+#[derive(Default)]
+pub struct GitPoll {
+    pub status_changed: bool,
+    /// HEAD contents of files requested with [`GitMonitor::request_head`]; `None` if untracked.
+    pub heads: Vec<(PathBuf, Option<Vec<u8>>)>,
+    /// Common ancestors of conflicted files requested with [`GitMonitor::request_merge_base`].
+    pub merge_bases: Vec<(PathBuf, Option<Vec<u8>>)>,
+    /// Operations started with [`GitMonitor::run`] that are done, with how they went.
+    pub finished: Vec<(GitOperation, Result<(), String>)>,
+    /// Changes requested with [`GitMonitor::request_patch`].
+    pub patches: Vec<(PatchRequest, Result<FilePatch, String>)>,
+}
+
+enum GitUpdate {
+    Status(GitStatus),
+    Head(PathBuf, Option<Vec<u8>>),
+    MergeBase(PathBuf, Option<Vec<u8>>),
+    Finished(GitOperation, Result<(), String>),
+    Patch(PatchRequest, Result<FilePatch, String>),
+}
+
+/// Keeps repository status for the open roots current by rerunning `git` on a
+/// background thread whenever something changes, at most once per interval.
+pub struct GitMonitor {
+    status: Arc<GitStatus>,
+    sender: mpsc::Sender<GitUpdate>,
+    receiver: mpsc::Receiver<GitUpdate>,
+    roots: Vec<PathBuf>,
+    refresh_pending: bool,
+    in_flight: bool,
+    last_refresh: Option<Instant>,
+    /// Feeds the thread that runs operations and patch reads one after another, started with the first one.
+    jobs: Option<mpsc::Sender<GitJob>>,
+    running: usize,
+}
+
+impl Default for GitMonitor {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            status: Arc::new(GitStatus::default()),
+            sender,
+            receiver,
+            roots: Vec::new(),
+            refresh_pending: false,
+            in_flight: false,
+            last_refresh: None,
+            jobs: None,
+            running: 0,
+        }
+    }
+}
+
+impl GitMonitor {
+    const MIN_INTERVAL: Duration = Duration::from_millis(500);
+
+    pub fn status(&self) -> Arc<GitStatus> {
+        self.status.clone()
+    }
+
+    /// Follows the open roots, refreshing when they change.
+    pub fn set_roots(&mut self, roots: &[PathBuf]) {
+        if self.roots != roots {
+            self.roots = roots.to_vec();
+            self.request_refresh();
+        }
+    }
+
+    pub fn request_refresh(&mut self) {
+        self.refresh_pending = true;
+    }
+
+    /// Loads the HEAD version of a file for the gutter; it arrives through [`Self::poll`].
+    pub fn request_head(&self, path: PathBuf, ctx: &egui::Context) {
+        let sender = self.sender.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let contents = read_contents(&path, "HEAD");
+            let _ = sender.send(GitUpdate::Head(path, contents));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Loads the version of a conflicted file both sides started from, which git keeps in
+    /// the index until the conflict is resolved; it arrives through [`Self::poll`].
+    pub fn request_merge_base(&self, path: PathBuf, ctx: &egui::Context) {
+        let sender = self.sender.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let contents = read_contents(&path, ":1");
+            let _ = sender.send(GitUpdate::MergeBase(path, contents));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Queues a change to a repository. Operations run in the order they were queued and
+    /// come back through [`Self::poll`]; the status is refreshed after each one.
+    pub fn run(&mut self, operation: GitOperation, ctx: &egui::Context) {
+        if self.queue(GitJob::Operation(operation), ctx) {
+            self.running += 1;
+        }
+    }
+
+    /// Reads the changes to one file after any operations queued before it; they arrive
+    /// through [`Self::poll`].
+    pub fn request_patch(&mut self, request: PatchRequest, ctx: &egui::Context) {
+        self.queue(GitJob::Patch(request), ctx);
+    }
+
+    fn queue(&mut self, job: GitJob, ctx: &egui::Context) -> bool {
+        let sender = self.jobs.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel::<GitJob>();
+            let updates = self.sender.clone();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                for job in receiver {
+                    let update = match job {
+                        GitJob::Operation(operation) => {
+                            let result = operation.run();
+                            GitUpdate::Finished(operation, result)
+                        }
+                        GitJob::Patch(request) => {
+                            let result = file_patch(&request.repo, &request.path, request.staged, request.untracked);
+                            GitUpdate::Patch(request, result)
+                        }
+                    };
+                    if updates.send(update).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            });
+            sender
+        });
+        sender.send(job).is_ok()
+    }
+
+    /// Whether operations queued with [`Self::run`] are still going.
+    pub fn is_busy(&self) -> bool {
+        self.running > 0
+    }
+
+    /// Starts a pending refresh if one is due and collects finished work.
+    pub fn poll(&mut self, ctx: &egui::Context) -> GitPoll {
+        let mut result = GitPoll::default();
+
+        for update in self.receiver.try_iter() {
+            match update {
+                GitUpdate::Status(status) => {
+                    self.status = Arc::new(status);
+                    self.in_flight = false;
+                    result.status_changed = true;
+                }
+                GitUpdate::Head(path, contents) => result.heads.push((path, contents)),
+                GitUpdate::MergeBase(path, contents) => result.merge_bases.push((path, contents)),
+                GitUpdate::Finished(operation, outcome) => {
+                    self.running = self.running.saturating_sub(1);
+                    self.refresh_pending = true;
+                    result.finished.push((operation, outcome));
+                }
+                GitUpdate::Patch(request, patch) => result.patches.push((request, patch)),
+            }
+        }
+
+        if self.refresh_pending && !self.in_flight {
+            let due = self.last_refresh.is_none_or(|last| last.elapsed() >= Self::MIN_INTERVAL);
+            if due {
+                self.spawn_refresh(ctx);
+            } else {
+                ctx.request_repaint_after(Self::MIN_INTERVAL);
+            }
+        }
+
+        result
+    }
+
+    fn spawn_refresh(&mut self, ctx: &egui::Context) {
+        self.refresh_pending = false;
+        self.in_flight = true;
+        self.last_refresh = Some(Instant::now());
+
+        let roots = self.roots.clone();
+        let sender = self.sender.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(GitUpdate::Status(read_status(&roots)));
+            ctx.request_repaint();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    const CHANGED: &str = "one\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\ntwelve\n";
+
+    /// A repository with one committed file, changed at both ends so it diffs as two hunks.
+    fn repo() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        run_git(&root, &["init", "-q"]).unwrap();
+        run_git(&root, &["config", "user.name", "Test"]).unwrap();
+        run_git(&root, &["config", "user.email", "test@example.com"]).unwrap();
+        run_git(&root, &["config", "commit.gpgsign", "false"]).unwrap();
+        let file = root.join("numbers.txt");
+        std::fs::write(&file, ORIGINAL).unwrap();
+        stage(&root, &file).unwrap();
+        commit(&root, "Add numbers", false).unwrap();
+        std::fs::write(&file, CHANGED).unwrap();
+        (dir, file)
+    }
+
+    #[test]
+    fn file_patch_splits_worktree_changes_into_hunks() {
+        let (dir, file) = repo();
+        let patch = file_patch(dir.path(), &file, false, false).unwrap();
+        assert_eq!(patch.hunks.len(), 2);
+        assert!(patch.header.contains("numbers.txt"));
+        assert!(file_patch(dir.path(), &file, true, false).unwrap().hunks.is_empty());
+    }
+
+    #[test]
+    fn staging_and_unstaging_one_hunk() {
+        let (dir, file) = repo();
+        let patch = file_patch(dir.path(), &file, false, false).unwrap();
+
+        apply_patch(dir.path(), &patch.hunk_patch(0).unwrap(), true, false).unwrap();
+        let staged = file_patch(dir.path(), &file, true, false).unwrap();
+        assert_eq!(staged.hunks.len(), 1);
+        assert!(staged.hunks[0].lines.iter().any(|line| line.text == "one"));
+        assert_eq!(file_patch(dir.path(), &file, false, false).unwrap().hunks.len(), 1);
+
+        apply_patch(dir.path(), &staged.hunk_patch(0).unwrap(), true, true).unwrap();
+        assert!(file_patch(dir.path(), &file, true, false).unwrap().hunks.is_empty());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), CHANGED);
+    }
+
+    #[test]
+    fn discarding_one_hunk_keeps_the_other() {
+        let (dir, file) = repo();
+        let patch = file_patch(dir.path(), &file, false, false).unwrap();
+
+        apply_patch(dir.path(), &patch.hunk_patch(1).unwrap(), false, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "one\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n");
+    }
+
+    #[test]
+    fn operations_report_failures() {
+        let (dir, file) = repo();
+        let patch = file_patch(dir.path(), &file, false, false).unwrap();
+        let hunk = patch.hunk_patch(0).unwrap();
+        apply_patch(dir.path(), &hunk, false, true).unwrap();
+        // The change is gone, so taking it back again no longer applies.
+        assert!(apply_patch(dir.path(), &hunk, false, true).is_err());
+        let operation = GitOperation::Commit { repo: dir.path().to_path_buf(), message: "Nothing".into(), amend: false };
+        assert!(operation.run().is_err());
+    }
+
+    #[test]
+    fn patches_are_read_after_the_operations_queued_before_them() {
+        let (dir, file) = repo();
+        let ctx = egui::Context::default();
+        let mut monitor = GitMonitor::default();
+        let repo = dir.path().to_path_buf();
+        monitor.run(GitOperation::Stage { repo: repo.clone(), path: file.clone() }, &ctx);
+        let request = PatchRequest { repo, path: file, staged: true, untracked: false };
+        monitor.request_patch(request.clone(), &ctx);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut patches = Vec::new();
+        while patches.is_empty() {
+            assert!(Instant::now() < deadline, "timed out waiting for the patch");
+            std::thread::sleep(Duration::from_millis(10));
+            let poll = monitor.poll(&ctx);
+            assert!(poll.finished.iter().all(|(_, result)| result.is_ok()));
+            patches.extend(poll.patches);
+        }
+        let (read, patch) = patches.remove(0);
+        assert_eq!(read, request);
+        assert_eq!(patch.unwrap().hunks.len(), 2);
+        assert!(!monitor.is_busy());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::document::diff::{DiffHunk, DiffLine, DiffLineKind};
+
+/// The output of `git diff` for one file, split into hunks that can be applied one by one.
+#[derive(Debug, Clone, Default)]
+pub struct FilePatch {
+    /// The `diff --git` / `---` / `+++` lines every partial patch needs.
+    pub header: String,
+    pub hunks: Vec<DiffHunk>,
+}
+
+impl FilePatch {
+    pub fn parse(diff: &str) -> Self {
+        let mut patch = FilePatch::default();
+        let mut old_line = 0;
+        let mut new_line = 0;
+
+        for line in diff.split_inclusive('\n') {
+            if line.starts_with("@@") {
+                (old_line, new_line) = parse_hunk_header(line);
+                patch.hunks.push(DiffHunk {
+                    header: line.trim_end().to_string(),
+                    lines: Vec::new(),
+                    patch: Some(line.to_string()),
+                });
+                continue;
+            }
+
+            let Some(hunk) = patch.hunks.last_mut() else {
+                patch.header.push_str(line);
+                continue;
+            };
+            if let Some(raw) = &mut hunk.patch {
+                raw.push_str(line);
+            }
+
+            let text = line.get(1..).unwrap_or("").trim_end_matches(['\n', '\r']).to_string();
+            let diff_line = match line.as_bytes().first() {
+                Some(b'+') => {
+                    new_line += 1;
+                    DiffLine::new(DiffLineKind::Added, None, Some(new_line), text)
+                }
+                Some(b'-') => {
+                    old_line += 1;
+                    DiffLine::new(DiffLineKind::Removed, Some(old_line), None, text)
+                }
+                Some(b' ') => {
+                    old_line += 1;
+                    new_line += 1;
+                    DiffLine::new(DiffLineKind::Context, Some(old_line), Some(new_line), text)
+                }
+                // "\ No newline at end of file" and anything else git adds between lines.
+                _ => continue,
+            };
+            hunk.lines.push(diff_line);
+        }
+        patch
+    }
+
+    /// A patch for a file git does not track yet: every line is an addition.
+    pub fn untracked(contents: &str) -> Self {
+        let lines = contents
+            .lines()
+            .enumerate()
+            .map(|(index, text)| DiffLine::new(DiffLineKind::Added, None, Some(index + 1), text.to_string()))
+            .collect();
+
+        FilePatch {
+            header: String::new(),
+            hunks: vec![DiffHunk { header: "New file".to_string(), lines, patch: None }],
+        }
+    }
+
+    /// One hunk with the file header, ready for `git apply`.
+    pub fn hunk_patch(&self, index: usize) -> Option<String> {
+        let hunk = self.hunks.get(index)?.patch.as_ref()?;
+        Some(format!("{}{}", self.header, hunk))
+    }
+}
+
+/// Reads the starting line numbers out of `@@ -a,b +c,d @@`, as the numbers before the first line.
+fn parse_hunk_header(line: &str) -> (usize, usize) {
+    let mut parts = line.split_whitespace().skip(1);
+    let start = |part: Option<&str>| {
+        part.and_then(|part| part[1..].split(',').next())
+            .and_then(|n| n.parse::<usize>().ok())
+            .unwrap_or(1)
+            .saturating_sub(1)
+    };
+    let old = start(parts.next());
+    let new = start(parts.next());
+    (old, new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/notes.txt b/notes.txt
+index 3b18e51..9d6b0a2 100644
+--- a/notes.txt
++++ b/notes.txt
+@@ -1,3 +1,3 @@
+ one
+-two
++TWO
+ three
+@@ -8,2 +8,3 @@ fn section()
+ eight
+ nine
++ten
+\\ No newline at end of file
+";
+
+    #[test]
+    fn parse_splits_header_and_hunks() {
+        let patch = FilePatch::parse(DIFF);
+        assert!(patch.header.starts_with("diff --git"));
+        assert!(patch.header.ends_with("+++ b/notes.txt\n"));
+        assert_eq!(patch.hunks.len(), 2);
+        assert_eq!(patch.hunks[1].header, "@@ -8,2 +8,3 @@ fn section()");
+    }
+
+    #[test]
+    fn parse_numbers_lines_from_the_hunk_header() {
+        let patch = FilePatch::parse(DIFF);
+        let numbers: Vec<_> = patch.hunks[0].lines.iter().map(|line| (line.kind, line.old_line, line.new_line)).collect();
+        assert_eq!(
+            numbers,
+            [
+                (DiffLineKind::Context, Some(1), Some(1)),
+                (DiffLineKind::Removed, Some(2), None),
+                (DiffLineKind::Added, None, Some(2)),
+                (DiffLineKind::Context, Some(3), Some(3)),
+            ]
+        );
+        // The "no newline" marker is kept in the patch but is not a line.
+        let last = &patch.hunks[1];
+        assert_eq!(last.lines.len(), 3);
+        assert_eq!(last.lines[2].new_line, Some(10));
+        assert!(last.patch.as_ref().unwrap().ends_with("\\ No newline at end of file\n"));
+    }
+
+    #[test]
+    fn hunk_patch_puts_the_file_header_before_one_hunk() {
+        let patch = FilePatch::parse(DIFF);
+        let second = patch.hunk_patch(1).unwrap();
+        assert!(second.starts_with(&patch.header));
+        assert!(second.contains("@@ -8,2 +8,3 @@"));
+        assert!(!second.contains("@@ -1,3 +1,3 @@"));
+        assert_eq!(patch.hunk_patch(2), None);
+        assert_eq!(FilePatch::untracked("a\nb\n").hunk_patch(0), None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// How a path differs from HEAD, as far as decorations are concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Modified,
+    Added,
+    Deleted,
+    Untracked,
+    Ignored,
+    Conflicted,
+}
+
+impl FileStatus {
+    /// The letter shown next to the name in the explorer.
+    pub fn badge(&self) -> &'static str {
+        match self {
+            FileStatus::Modified => "M",
+            FileStatus::Added => "A",
+            FileStatus::Deleted => "D",
+            FileStatus::Untracked => "U",
+            FileStatus::Ignored => "I",
+            FileStatus::Conflicted => "C",
+        }
+    }
+
+    /// Which status wins when a folder contains several.
+    fn priority(&self) -> u8 {
+        match self {
+            FileStatus::Ignored => 0,
+            FileStatus::Untracked => 1,
+            FileStatus::Added => 2,
+            FileStatus::Modified | FileStatus::Deleted => 3,
+            FileStatus::Conflicted => 4,
+        }
+    }
+
+    /// Parses the two-letter code of `git status --porcelain`.
+    fn from_porcelain(code: &str) -> Option<Self> {
+        let mut chars = code.chars();
+        let (index, worktree) = (chars.next()?, chars.next()?);
+
+        let status = match (index, worktree) {
+            ('?', '?') => FileStatus::Untracked,
+            ('!', '!') => FileStatus::Ignored,
+            ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => FileStatus::Conflicted,
+            ('A' | 'R' | 'C', _) => FileStatus::Added,
+            ('D', _) | (_, 'D') => FileStatus::Deleted,
+            _ => FileStatus::Modified,
+        };
+        Some(status)
+    }
+}
+
+/// A changed path, split into what is staged in the index and what is only in the worktree.
+#[derive(Debug, Clone)]
+pub struct StatusEntry {
+    pub path: PathBuf,
+    pub staged: Option<FileStatus>,
+    pub unstaged: Option<FileStatus>,
+}
+
+impl StatusEntry {
+    fn from_porcelain(path: PathBuf, code: &str) -> Option<Self> {
+        let mut chars = code.chars();
+        let (index, worktree) = (chars.next()?, chars.next()?);
+
+        let (staged, unstaged) = match FileStatus::from_porcelain(code)? {
+            FileStatus::Ignored => return None,
+            FileStatus::Untracked => (None, Some(FileStatus::Untracked)),
+            FileStatus::Conflicted => (None, Some(FileStatus::Conflicted)),
+            _ => {
+                let side = |c: char| match c {
+                    ' ' => None,
+                    'A' | 'R' | 'C' => Some(FileStatus::Added),
+                    'D' => Some(FileStatus::Deleted),
+                    _ => Some(FileStatus::Modified),
+                };
+                (side(index), side(worktree))
+            }
+        };
+        Some(Self { path, staged, unstaged })
+    }
+}
+
+/// The status of one repository, with folder states rolled up from their contents.
+#[derive(Debug, Clone, Default)]
+pub struct RepoStatus {
+    pub root: PathBuf,
+    pub branch: Option<String>,
+    pub entries: Vec<StatusEntry>,
+    files: HashMap<PathBuf, FileStatus>,
+    directories: HashMap<PathBuf, FileStatus>,
+}
+
+impl RepoStatus {
+    /// Builds the status from the NUL-separated output of `git status --porcelain=v1 -z --branch`.
+    pub fn parse(root: PathBuf, output: &str) -> Self {
+        let mut status = Self { root, ..Default::default() };
+        let mut records = output.split('\0').filter(|record| !record.is_empty());
+
+        while let Some(record) = records.next() {
+            if let Some(branch) = record.strip_prefix("## ") {
+                let branch = branch.strip_prefix("No commits yet on ").unwrap_or(branch);
+                status.branch = branch.split("...").next().map(str::to_string);
+                continue;
+            }
+
+            let (Some(code), Some(path)) = (record.get(..2), record.get(3..)) else {
+                continue;
+            };
+            // Renames and copies are followed by the original path, which is not decorated.
+            if code.starts_with(['R', 'C']) {
+                records.next();
+            }
+            let Some(file_status) = FileStatus::from_porcelain(code) else {
+                continue;
+            };
+
+            let path = status.root.join(path.trim_end_matches('/'));
+            if let Some(entry) = StatusEntry::from_porcelain(path.clone(), code) {
+                status.entries.push(entry);
+            }
+            status.add(path, file_status);
+        }
+        status
+    }
+
+    fn add(&mut self, path: PathBuf, file_status: FileStatus) {
+        if file_status != FileStatus::Ignored {
+            for dir in path.ancestors().skip(1) {
+                if !dir.starts_with(&self.root) {
+                    break;
+                }
+                let entry = self.directories.entry(dir.to_path_buf()).or_insert(file_status);
+                if file_status.priority() > entry.priority() {
+                    *entry = file_status;
+                }
+            }
+        }
+        self.files.insert(path, file_status);
+    }
+
+    pub fn name(&self) -> String {
+        self.root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+    }
+
+    pub fn status_of(&self, path: &Path) -> Option<FileStatus> {
+        if let Some(status) = self.files.get(path).or_else(|| self.directories.get(path)) {
+            return Some(*status);
+        }
+
+        // Untracked and ignored folders are reported as a whole rather than file by file.
+        path.ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.root))
+            .find_map(|dir| self.files.get(dir))
+            .copied()
+            .filter(|status| matches!(status, FileStatus::Untracked | FileStatus::Ignored))
+    }
+}
+
+/// The status of every repository that contains one of the open root folders.
+#[derive(Debug, Clone, Default)]
+pub struct GitStatus {
+    pub repos: Vec<RepoStatus>,
+}
+
+impl GitStatus {
+    pub fn repo_for(&self, path: &Path) -> Option<&RepoStatus> {
+        self.repos
+            .iter()
+            .filter(|repo| path.starts_with(&repo.root))
+            .max_by_key(|repo| repo.root.components().count())
+    }
+
+    pub fn status_of(&self, path: &Path) -> Option<FileStatus> {
+        self.repo_for(path).and_then(|repo| repo.status_of(path))
+    }
+}
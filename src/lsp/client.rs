@@ -0,0 +1,439 @@
+use super::transport;
+use crate::config::LanguageServerConfig;
+use lsp_types::{
+    ClientCapabilities, InitializeParams, InitializeResult, ServerCapabilities, Url, WorkspaceFolder,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How long a server gets to answer `shutdown` and exit before it is killed.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerState {
+    Starting,
+    Running,
+    Stopped(Option<String>),
+}
+
+/// An error returned by the server for a request.
+#[derive(Debug, Clone)]
+pub struct ResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl std::fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+/// A message from the server that the client did not handle itself.
+pub enum ServerMessage {
+    Response { id: i64, method: String, result: Result<Value, ResponseError> },
+    Notification { method: String, params: Value },
+    /// A request the editor has to answer itself, with [`LspClient::respond`].
+    Request { id: Value, method: String, params: Value },
+    Exited(Option<String>),
+}
+
+/// What the reader thread passes back to the UI thread.
+enum Incoming {
+    Message(Value),
+    Closed(Option<String>),
+}
+
+/// One running language server, talking JSON-RPC over its stdin and stdout.
+///
+/// Writing happens on a separate thread so a slow server never blocks the UI; replies are
+/// read on another and picked up by [`LspClient::poll`].
+pub struct LspClient {
+    pub config: LanguageServerConfig,
+    child: Option<Child>,
+    outgoing: mpsc::Sender<String>,
+    incoming: mpsc::Receiver<Incoming>,
+    next_id: i64,
+    /// Methods of requests still waiting for a response, by id.
+    pending: HashMap<i64, String>,
+    state: ServerState,
+    capabilities: ServerCapabilities,
+    /// Messages sent before the server finished initializing.
+    queued: Vec<String>,
+}
+
+impl LspClient {
+    pub fn start(config: &LanguageServerConfig, roots: &[PathBuf], ctx: &egui::Context) -> Result<Self, String> {
+        let mut command = Command::new(&config.command);
+        command
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(root) = roots.first() {
+            command.current_dir(root);
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to start {} ({}): {}", config.name, config.command, e))?;
+
+        let (outgoing, outgoing_receiver) = mpsc::channel::<String>();
+        let (incoming_sender, incoming) = mpsc::channel();
+
+        if let Some(mut stdin) = child.stdin.take() {
+            std::thread::spawn(move || {
+                for message in outgoing_receiver {
+                    if transport::write_message(&mut stdin, &message).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        if let Some(stdout) = child.stdout.take() {
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stdout);
+                let closed = loop {
+                    match transport::read_message(&mut reader) {
+                        Ok(Some(body)) => match serde_json::from_str(&body) {
+                            Ok(message) => {
+                                if incoming_sender.send(Incoming::Message(message)).is_err() {
+                                    return;
+                                }
+                                ctx.request_repaint();
+                            }
+                            Err(e) => log::warn!("Ignoring malformed message from language server: {}", e),
+                        },
+                        Ok(None) => break None,
+                        Err(e) => break Some(e.to_string()),
+                    }
+                };
+                let _ = incoming_sender.send(Incoming::Closed(closed));
+                ctx.request_repaint();
+            });
+        }
+
+        if let Some(stderr) = child.stderr.take() {
+            let name = config.name.clone();
+            std::thread::spawn(move || {
+                use std::io::BufRead;
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    log::debug!("[{}] {}", name, line);
+                }
+            });
+        }
+
+        let mut client = Self {
+            config: config.clone(),
+            child: Some(child),
+            outgoing,
+            incoming,
+            next_id: 0,
+            pending: HashMap::new(),
+            state: ServerState::Starting,
+            capabilities: ServerCapabilities::default(),
+            queued: Vec::new(),
+        };
+        client.initialize(roots);
+        Ok(client)
+    }
+
+    #[allow(deprecated)]
+    fn initialize(&mut self, roots: &[PathBuf]) {
+        let folders: Vec<WorkspaceFolder> = roots
+            .iter()
+            .filter_map(|root| {
+                Some(WorkspaceFolder {
+                    uri: Url::from_directory_path(root).ok()?,
+                    name: root.file_name()?.to_string_lossy().to_string(),
+                })
+            })
+            .collect();
+
+        let params = InitializeParams {
+            process_id: Some(std::process::id()),
+            root_uri: roots.first().and_then(|root| Url::from_directory_path(root).ok()),
+            initialization_options: self.config.initialization_options.clone(),
+            capabilities: client_capabilities(),
+            workspace_folders: (!folders.is_empty()).then_some(folders),
+            client_info: Some(lsp_types::ClientInfo {
+                name: "zen".to_string(),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            }),
+            ..Default::default()
+        };
+        self.request("initialize", serde_json::to_value(params).unwrap_or(Value::Null));
+    }
+
+    pub fn state(&self) -> &ServerState {
+        &self.state
+    }
+
+    pub fn capabilities(&self) -> &ServerCapabilities {
+        &self.capabilities
+    }
+
+    pub fn request(&mut self, method: &str, params: Value) -> i64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.pending.insert(id, method.to_string());
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string();
+        if method == "initialize" {
+            self.send(message);
+        } else {
+            self.send_when_initialized(message);
+        }
+        id
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        self.send_when_initialized(json!({ "jsonrpc": "2.0", "method": method, "params": params }).to_string());
+    }
+
+    /// Nothing but `initialize` may be sent until the server has answered it.
+    fn send_when_initialized(&mut self, message: String) {
+        if self.state == ServerState::Starting {
+            self.queued.push(message);
+        } else {
+            self.send(message);
+        }
+    }
+
+    fn send(&self, message: String) {
+        let _ = self.outgoing.send(message);
+    }
+
+    pub fn respond(&self, id: Value, result: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string());
+    }
+
+    /// Handles the lifecycle messages and server requests, returning everything else.
+    pub fn poll(&mut self) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+
+        while let Ok(incoming) = self.incoming.try_recv() {
+            let message = match incoming {
+                Incoming::Message(message) => message,
+                Incoming::Closed(error) => {
+                    let error = error.unwrap_or_else(|| format!("{} exited unexpectedly", self.config.name));
+                    let error = Some(error);
+                    self.state = ServerState::Stopped(error.clone());
+                    self.reap();
+                    messages.push(ServerMessage::Exited(error));
+                    continue;
+                }
+            };
+
+            let method = message.get("method").and_then(Value::as_str).map(str::to_string);
+            let id = message.get("id").cloned();
+            match (method, id) {
+                (Some(method), Some(id)) => {
+                    if let Some(request) = self.handle_server_request(method, id, &message) {
+                        messages.push(request);
+                    }
+                }
+                (Some(method), None) => messages.push(ServerMessage::Notification {
+                    method,
+                    params: message.get("params").cloned().unwrap_or(Value::Null),
+                }),
+                (None, Some(id)) => {
+                    let Some(id) = id.as_i64() else {
+                        continue;
+                    };
+                    let Some(method) = self.pending.remove(&id) else {
+                        continue;
+                    };
+                    let result = match message.get("error") {
+                        Some(error) => Err(ResponseError {
+                            code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                            message: error.get("message").and_then(Value::as_str).unwrap_or("").to_string(),
+                        }),
+                        None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                    };
+                    if let Some(message) = self.handle_response(id, method, result) {
+                        messages.push(message);
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+
+        messages
+    }
+
+    fn handle_response(&mut self, id: i64, method: String, result: Result<Value, ResponseError>) -> Option<ServerMessage> {
+        match method.as_str() {
+            "initialize" => {
+                match result {
+                    Ok(value) => {
+                        if let Ok(initialized) = serde_json::from_value::<InitializeResult>(value) {
+                            self.capabilities = initialized.capabilities;
+                        }
+                        self.state = ServerState::Running;
+                        self.send(json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }).to_string());
+                        for message in std::mem::take(&mut self.queued) {
+                            self.send(message);
+                        }
+                    }
+                    Err(error) => {
+                        let error = format!("{} failed to initialize: {}", self.config.name, error.message);
+                        self.state = ServerState::Stopped(Some(error.clone()));
+                        return Some(ServerMessage::Exited(Some(error)));
+                    }
+                }
+                None
+            }
+            _ => Some(ServerMessage::Response { id, method, result }),
+        }
+    }
+
+    /// Answers requests the server makes of the editor. Only what servers commonly
+    /// insist on is supported; the rest get an empty result. Edits the server wants
+    /// made are passed on, since they have to be previewed before they are answered.
+    fn handle_server_request(&mut self, method: String, id: Value, message: &Value) -> Option<ServerMessage> {
+        let result = match method.as_str() {
+            "workspace/applyEdit" => {
+                let params = message.get("params").cloned().unwrap_or(Value::Null);
+                return Some(ServerMessage::Request { id, method, params });
+            }
+            "workspace/configuration" => {
+                let items = message
+                    .pointer("/params/items")
+                    .and_then(Value::as_array)
+                    .map_or(0, Vec::len);
+                Value::Array(vec![Value::Null; items])
+            }
+            _ => Value::Null,
+        };
+        self.respond(id, result);
+        None
+    }
+
+    /// Stops the server on a background thread: a running one is asked to `shutdown` and
+    /// told to `exit` once it has replied, one still starting cannot be asked yet and is
+    /// killed. Either way it is killed if it has not exited within [`SHUTDOWN_TIMEOUT`].
+    /// Returns the thread to wait on, or `None` if the server had already stopped.
+    pub fn shutdown(&mut self) -> Option<JoinHandle<()>> {
+        let mut child = self.child.take()?;
+        let running = self.state == ServerState::Running;
+        let shutdown_id = running.then(|| self.request("shutdown", Value::Null));
+        self.state = ServerState::Stopped(None);
+
+        // The reply is read here rather than by `poll`, which no longer runs for a stopped client.
+        let (_, closed) = mpsc::channel();
+        let incoming = std::mem::replace(&mut self.incoming, closed);
+        let outgoing = self.outgoing.clone();
+        Some(std::thread::spawn(move || {
+            let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+            match shutdown_id {
+                Some(id) if wait_for_reply(&incoming, id, deadline) => {
+                    let _ = outgoing.send(json!({ "jsonrpc": "2.0", "method": "exit" }).to_string());
+                }
+                _ => {
+                    let _ = child.kill();
+                }
+            }
+            drop(outgoing);
+            while Instant::now() < deadline {
+                if matches!(child.try_wait(), Ok(Some(_))) {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+        }))
+    }
+
+    /// Waits briefly for the process to exit so it does not linger as a zombie.
+    fn reap(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            std::thread::spawn(move || {
+                for _ in 0..20 {
+                    if matches!(child.try_wait(), Ok(Some(_))) {
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                let _ = child.kill();
+                let _ = child.wait();
+            });
+        }
+    }
+}
+
+/// Whether the server answers the request `id` before `deadline`, skipping whatever else
+/// it sends meanwhile.
+fn wait_for_reply(incoming: &mpsc::Receiver<Incoming>, id: i64, deadline: Instant) -> bool {
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match incoming.recv_timeout(timeout) {
+            Ok(Incoming::Message(message)) => {
+                if message.get("method").is_none() && message.get("id").and_then(Value::as_i64) == Some(id) {
+                    return true;
+                }
+            }
+            Ok(Incoming::Closed(_)) | Err(_) => return false,
+        }
+    }
+}
+
+/// A client dropped without [`LspClient::shutdown`] still stops its server, without waiting.
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+pub fn uri_for(path: &Path) -> Option<Url> {
+    Url::from_file_path(path).ok()
+}
+
+fn client_capabilities() -> ClientCapabilities {
+    serde_json::from_value(json!({
+        "general": { "positionEncodings": ["utf-16"] },
+        "textDocument": {
+            "synchronization": { "didSave": true, "dynamicRegistration": false },
+            "publishDiagnostics": { "relatedInformation": false },
+            "completion": {
+                "completionItem": { "snippetSupport": true, "documentationFormat": ["plaintext", "markdown"] },
+                "contextSupport": true,
+            },
+            "definition": { "linkSupport": true },
+            "references": {},
+            "hover": { "contentFormat": ["plaintext", "markdown"] },
+            "documentSymbol": { "hierarchicalDocumentSymbolSupport": true },
+            "rename": { "prepareSupport": false },
+            "formatting": {},
+            "rangeFormatting": {},
+            "codeAction": {
+                "codeActionLiteralSupport": {
+                    "codeActionKind": {
+                        "valueSet": ["", "quickfix", "refactor", "refactor.extract", "refactor.inline", "refactor.rewrite", "source", "source.organizeImports"],
+                    },
+                },
+                "isPreferredSupport": true,
+                "disabledSupport": true,
+                "dataSupport": true,
+                "resolveSupport": { "properties": ["edit"] },
+            },
+        },
+        "window": { "workDoneProgress": false },
+        "workspace": {
+            "workspaceFolders": true,
+            "configuration": true,
+            "applyEdit": true,
+            "didChangeWatchedFiles": { "dynamicRegistration": false },
+            "workspaceEdit": { "documentChanges": true },
+            "executeCommand": {},
+        },
+    }))
+    .unwrap_or_default()
+}
@@ -0,0 +1,347 @@
+//! Translations from protocol types into the editor's own, and back where a request
+//! has to carry them.
+
+use super::CodeAction;
+use crate::completion::{CompletionItem, CompletionKind, CompletionSource};
+use crate::document::diagnostics::{Diagnostic, Severity};
+use crate::document::edit::{FileEdit, TextEdit};
+use crate::document::line_ending;
+use crate::document::position::{Location, TextPosition};
+use crate::symbols::outline::{self, OutlineItem};
+use crate::symbols::{Symbol, SymbolKind};
+use lsp_types::{
+    CodeActionOrCommand, CompletionItemKind, CompletionResponse, CompletionTextEdit, DiagnosticSeverity,
+    DocumentChangeOperation, DocumentChanges, DocumentSymbol, DocumentSymbolResponse, Documentation,
+    GotoDefinitionResponse, HoverContents, InsertTextFormat, MarkedString, NumberOrString, OneOf, Url,
+};
+use serde_json::Value;
+
+pub fn diagnostic(diagnostic: lsp_types::Diagnostic) -> Diagnostic {
+    Diagnostic {
+        severity: match diagnostic.severity {
+            Some(DiagnosticSeverity::WARNING) => Severity::Warning,
+            Some(DiagnosticSeverity::INFORMATION) => Severity::Info,
+            Some(DiagnosticSeverity::HINT) => Severity::Hint,
+            _ => Severity::Error,
+        },
+        start: text_position(diagnostic.range.start),
+        end: text_position(diagnostic.range.end),
+        message: diagnostic.message,
+        source: diagnostic.source,
+        code: diagnostic.code.map(|code| match code {
+            NumberOrString::Number(number) => number.to_string(),
+            NumberOrString::String(code) => code,
+        }),
+    }
+}
+
+fn text_position(position: lsp_types::Position) -> TextPosition {
+    TextPosition::new(position.line as usize, position.character as usize)
+}
+
+fn lsp_position(position: TextPosition) -> lsp_types::Position {
+    lsp_types::Position::new(position.line as u32, position.column as u32)
+}
+
+/// A problem as a server would have reported it, for the context of a code action
+/// request. What the editor does not keep, such as related information, is left out.
+pub fn lsp_diagnostic(diagnostic: &Diagnostic) -> lsp_types::Diagnostic {
+    lsp_types::Diagnostic {
+        range: lsp_types::Range::new(lsp_position(diagnostic.start), lsp_position(diagnostic.end)),
+        severity: Some(match diagnostic.severity {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Info => DiagnosticSeverity::INFORMATION,
+            Severity::Hint => DiagnosticSeverity::HINT,
+        }),
+        code: diagnostic.code.clone().map(|code| match code.parse() {
+            Ok(number) => NumberOrString::Number(number),
+            Err(_) => NumberOrString::String(code),
+        }),
+        source: diagnostic.source.clone(),
+        message: diagnostic.message.clone(),
+        ..Default::default()
+    }
+}
+
+/// The edits in a `WorkspaceEdit`, from a rename reply, a code action or a
+/// `workspace/applyEdit` request. Creating, renaming and deleting files is not
+/// supported, so an edit that does any of that is refused as a whole.
+pub fn workspace_edit(result: Value) -> Result<Vec<FileEdit>, String> {
+    let edit = serde_json::from_value::<Option<lsp_types::WorkspaceEdit>>(result)
+        .map_err(|e| format!("malformed workspace edit: {}", e))?;
+    let Some(edit) = edit else {
+        return Ok(Vec::new());
+    };
+
+    let mut files = Vec::new();
+    match edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => {
+            for edit in edits {
+                files.push(file_edit(edit.text_document.uri, edit.edits)?);
+            }
+        }
+        Some(DocumentChanges::Operations(operations)) => {
+            for operation in operations {
+                match operation {
+                    DocumentChangeOperation::Edit(edit) => files.push(file_edit(edit.text_document.uri, edit.edits)?),
+                    DocumentChangeOperation::Op(_) => {
+                        return Err("the edit creates, renames or deletes files, which is not supported".to_string())
+                    }
+                }
+            }
+        }
+        None => {
+            let mut changes: Vec<(Url, Vec<lsp_types::TextEdit>)> = edit.changes.unwrap_or_default().into_iter().collect();
+            changes.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+            for (uri, edits) in changes {
+                files.push(file_edit(uri, edits.into_iter().map(OneOf::Left).collect())?);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn file_edit(uri: Url, edits: Vec<OneOf<lsp_types::TextEdit, lsp_types::AnnotatedTextEdit>>) -> Result<FileEdit, String> {
+    let path = uri.to_file_path().map_err(|_| format!("{} is not a local file", uri))?;
+    let edits = edits
+        .into_iter()
+        .map(|edit| match edit {
+            OneOf::Left(edit) => text_edit(edit),
+            OneOf::Right(annotated) => text_edit(annotated.text_edit),
+        })
+        .collect();
+    Ok(FileEdit { path, edits })
+}
+
+/// The edits in a `textDocument/formatting` or `rangeFormatting` reply.
+pub fn text_edits(result: Value) -> Result<Vec<TextEdit>, String> {
+    let edits: Option<Vec<lsp_types::TextEdit>> = serde_json::from_value(result).map_err(|e| e.to_string())?;
+    Ok(edits.unwrap_or_default().into_iter().map(text_edit).collect())
+}
+
+/// An edit with the line breaks of its text made the buffer's own.
+fn text_edit(edit: lsp_types::TextEdit) -> TextEdit {
+    TextEdit {
+        start: text_position(edit.range.start),
+        end: text_position(edit.range.end),
+        new_text: line_ending::normalize(edit.new_text),
+    }
+}
+
+/// The actions in a `textDocument/codeAction` reply, in the order the server sent them.
+pub fn code_actions(result: Value) -> Vec<CodeAction> {
+    match serde_json::from_value::<Option<Vec<Value>>>(result) {
+        Ok(actions) => actions.unwrap_or_default().into_iter().filter_map(code_action).collect(),
+        Err(e) => {
+            log::debug!("Ignoring malformed code action reply: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// One code action, or a bare command offered as one. The action is kept as sent so it
+/// can be resolved later.
+pub fn code_action(raw: Value) -> Option<CodeAction> {
+    let action = serde_json::from_value::<CodeActionOrCommand>(raw.clone())
+        .map_err(|e| log::debug!("Ignoring malformed code action: {}", e))
+        .ok()?;
+    Some(match action {
+        CodeActionOrCommand::Command(command) => CodeAction {
+            title: command.title.clone(),
+            kind: None,
+            preferred: false,
+            disabled: None,
+            edit: None,
+            command: Some(command),
+            raw: None,
+        },
+        CodeActionOrCommand::CodeAction(action) => {
+            let (edit, disabled) = match action.edit.map(|edit| workspace_edit(serde_json::to_value(edit).unwrap_or(Value::Null))) {
+                Some(Ok(edit)) => (Some(edit), action.disabled.map(|disabled| disabled.reason)),
+                Some(Err(e)) => (None, Some(e)),
+                None => (None, action.disabled.map(|disabled| disabled.reason)),
+            };
+            CodeAction {
+                title: action.title,
+                kind: action.kind.map(|kind| kind.as_str().to_string()),
+                preferred: action.is_preferred.unwrap_or(false),
+                disabled,
+                edit,
+                command: action.command,
+                raw: Some(raw),
+            }
+        }
+    })
+}
+
+/// The places in a `textDocument/definition` or `textDocument/references` reply, leaving
+/// out those that are not local files.
+pub fn locations(result: Value) -> Vec<Location> {
+    let response = match serde_json::from_value::<Option<GotoDefinitionResponse>>(result) {
+        Ok(response) => response,
+        Err(e) => {
+            log::debug!("Ignoring malformed location reply: {}", e);
+            None
+        }
+    };
+    let targets = match response {
+        Some(GotoDefinitionResponse::Scalar(location)) => vec![(location.uri, location.range.start)],
+        Some(GotoDefinitionResponse::Array(locations)) => {
+            locations.into_iter().map(|location| (location.uri, location.range.start)).collect()
+        }
+        Some(GotoDefinitionResponse::Link(links)) => links
+            .into_iter()
+            .map(|link| (link.target_uri, link.target_selection_range.start))
+            .collect(),
+        None => Vec::new(),
+    };
+    targets
+        .into_iter()
+        .filter_map(|(uri, position)| Some(Location::new(uri.to_file_path().ok()?, text_position(position))))
+        .collect()
+}
+
+/// The text of a `textDocument/hover` reply, with markdown code fences taken out since
+/// it is shown as plain text.
+pub fn hover(result: Value) -> Option<String> {
+    let hover = serde_json::from_value::<Option<lsp_types::Hover>>(result).ok()??;
+    let marked = |marked: MarkedString| match marked {
+        MarkedString::String(text) => text,
+        MarkedString::LanguageString(code) => code.value,
+    };
+    let text = match hover.contents {
+        HoverContents::Scalar(text) => marked(text),
+        HoverContents::Array(texts) => texts.into_iter().map(marked).collect::<Vec<_>>().join("\n\n"),
+        HoverContents::Markup(markup) => markup.value,
+    };
+    let text = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// The outline in a `textDocument/documentSymbol` reply. Servers that send a flat list
+/// get it nested by the symbols' ranges.
+pub fn document_symbols(result: Value) -> Vec<OutlineItem> {
+    match serde_json::from_value::<Option<DocumentSymbolResponse>>(result) {
+        Ok(Some(DocumentSymbolResponse::Nested(symbols))) => symbols.into_iter().map(outline_item).collect(),
+        Ok(Some(DocumentSymbolResponse::Flat(symbols))) => outline::nest(
+            symbols
+                .into_iter()
+                .map(|symbol| {
+                    let range = symbol.location.range;
+                    let item = Symbol {
+                        signature: symbol.name.clone(),
+                        name: symbol.name,
+                        kind: symbol_kind(symbol.kind),
+                        position: text_position(range.start),
+                    };
+                    OutlineItem::new(item, range.end.line as usize)
+                })
+                .collect(),
+        ),
+        Ok(None) => Vec::new(),
+        Err(e) => {
+            log::debug!("Ignoring malformed document symbol reply: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn outline_item(symbol: DocumentSymbol) -> OutlineItem {
+    let item = Symbol {
+        signature: symbol.detail.filter(|detail| !detail.is_empty()).unwrap_or_else(|| symbol.name.clone()),
+        name: symbol.name,
+        kind: symbol_kind(symbol.kind),
+        position: text_position(symbol.selection_range.start),
+    };
+    let mut item = OutlineItem::new(item, symbol.range.end.line as usize);
+    item.children = symbol.children.unwrap_or_default().into_iter().map(outline_item).collect();
+    item.children.sort_by_key(|child| child.symbol.position);
+    item
+}
+
+fn symbol_kind(kind: lsp_types::SymbolKind) -> SymbolKind {
+    match kind {
+        lsp_types::SymbolKind::FILE
+        | lsp_types::SymbolKind::MODULE
+        | lsp_types::SymbolKind::NAMESPACE
+        | lsp_types::SymbolKind::PACKAGE => SymbolKind::Module,
+        lsp_types::SymbolKind::FUNCTION | lsp_types::SymbolKind::OPERATOR => SymbolKind::Function,
+        lsp_types::SymbolKind::METHOD | lsp_types::SymbolKind::CONSTRUCTOR => SymbolKind::Method,
+        lsp_types::SymbolKind::STRUCT => SymbolKind::Struct,
+        lsp_types::SymbolKind::CLASS | lsp_types::SymbolKind::OBJECT => SymbolKind::Class,
+        lsp_types::SymbolKind::ENUM => SymbolKind::Enum,
+        lsp_types::SymbolKind::INTERFACE => SymbolKind::Interface,
+        lsp_types::SymbolKind::TYPE_PARAMETER => SymbolKind::Type,
+        lsp_types::SymbolKind::CONSTANT => SymbolKind::Constant,
+        lsp_types::SymbolKind::FIELD
+        | lsp_types::SymbolKind::PROPERTY
+        | lsp_types::SymbolKind::ENUM_MEMBER
+        | lsp_types::SymbolKind::KEY
+        | lsp_types::SymbolKind::EVENT => SymbolKind::Field,
+        _ => SymbolKind::Variable,
+    }
+}
+
+/// The items of a `textDocument/completion` reply. Text edit ranges become character
+/// ranges in `text`, the document as it was when completion was requested.
+pub fn completion_items(result: Value, text: &str) -> Vec<CompletionItem> {
+    let items = match serde_json::from_value::<Option<CompletionResponse>>(result) {
+        Ok(Some(CompletionResponse::Array(items))) => items,
+        Ok(Some(CompletionResponse::List(list))) => list.items,
+        Ok(None) => Vec::new(),
+        Err(e) => {
+            log::debug!("Ignoring malformed completion reply: {}", e);
+            Vec::new()
+        }
+    };
+    items.into_iter().map(|item| completion_item(item, text)).collect()
+}
+
+fn completion_item(item: lsp_types::CompletionItem, text: &str) -> CompletionItem {
+    let mut completion = CompletionItem::new(item.label.as_str(), completion_kind(item.kind), CompletionSource::LanguageServer);
+    completion.detail = item.detail;
+    completion.documentation = item.documentation.map(|documentation| match documentation {
+        Documentation::String(text) => text,
+        Documentation::MarkupContent(markup) => markup.value,
+    });
+    completion.snippet = item.insert_text_format == Some(InsertTextFormat::SNIPPET);
+    completion.filter_text = item.filter_text;
+    completion.sort_text = item.sort_text;
+
+    let (insert_text, range) = match item.text_edit {
+        Some(CompletionTextEdit::Edit(edit)) => (edit.new_text, Some(edit.range)),
+        Some(CompletionTextEdit::InsertAndReplace(edit)) => (edit.new_text, Some(edit.insert)),
+        None => (item.insert_text.unwrap_or(item.label), None),
+    };
+    completion.insert_text = insert_text;
+    completion.replace = range.map(|range| text_position(range.start).char_index(text)..text_position(range.end).char_index(text));
+    completion
+}
+
+fn completion_kind(kind: Option<CompletionItemKind>) -> CompletionKind {
+    match kind {
+        Some(CompletionItemKind::FUNCTION | CompletionItemKind::CONSTRUCTOR) => CompletionKind::Function,
+        Some(CompletionItemKind::METHOD) => CompletionKind::Method,
+        Some(CompletionItemKind::FIELD | CompletionItemKind::PROPERTY | CompletionItemKind::ENUM_MEMBER) => CompletionKind::Field,
+        Some(CompletionItemKind::VARIABLE | CompletionItemKind::VALUE) => CompletionKind::Variable,
+        Some(CompletionItemKind::CONSTANT) => CompletionKind::Constant,
+        Some(
+            CompletionItemKind::CLASS
+            | CompletionItemKind::STRUCT
+            | CompletionItemKind::ENUM
+            | CompletionItemKind::INTERFACE
+            | CompletionItemKind::TYPE_PARAMETER,
+        ) => CompletionKind::Type,
+        Some(CompletionItemKind::MODULE) => CompletionKind::Module,
+        Some(CompletionItemKind::KEYWORD) => CompletionKind::Keyword,
+        Some(CompletionItemKind::SNIPPET) => CompletionKind::Snippet,
+        Some(CompletionItemKind::TEXT) => CompletionKind::Word,
+        _ => CompletionKind::Other,
+    }
+}
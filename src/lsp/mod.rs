@@ -0,0 +1,590 @@
+pub mod client;
+pub mod convert;
+pub mod sync;
+pub mod transport;
+
+#[cfg(test)]
+mod tests;
+
+use crate::config::LanguageServerConfig;
+use crate::document::diagnostics::Diagnostic;
+use crate::document::edit::FileEdit;
+use client::{LspClient, ResponseError, ServerMessage, ServerState};
+use lsp_types::{
+    CodeActionContext, CodeActionParams, CodeActionProviderCapability, CompletionContext, CompletionParams,
+    CompletionTriggerKind, DidChangeTextDocumentParams, DidChangeWatchedFilesParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DidSaveTextDocumentParams, DocumentFormattingParams, DocumentRangeFormattingParams, DocumentSymbolParams,
+    ExecuteCommandParams, FileChangeType, FileEvent, FormattingOptions, GotoDefinitionParams, HoverParams,
+    HoverProviderCapability, OneOf, PublishDiagnosticsParams, ReferenceContext, ReferenceParams, RenameParams,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, VersionedTextDocumentIdentifier,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Something from a language server the editor may want to show or act on.
+pub enum LspEvent {
+    /// A server failed to start or exited without being asked to.
+    Stopped { server: String, error: Option<String> },
+    Message { server: String, level: lsp_types::MessageType, text: String },
+    /// The complete set of problems the server currently sees in a file.
+    Diagnostics { server: String, path: PathBuf, diagnostics: Vec<Diagnostic> },
+    Notification { server: String, method: String, params: Value },
+    Response { server: String, id: i64, method: String, result: Result<Value, ResponseError> },
+    /// A request from the server waiting for an answer through [`LspManager::respond`].
+    Request { server: String, id: Value, method: String, params: Value },
+}
+
+/// A fix or refactoring a server offers for a place in a document.
+#[derive(Debug, Clone)]
+pub struct CodeAction {
+    pub title: String,
+    /// Such as `quickfix` or `refactor.extract`.
+    pub kind: Option<String>,
+    /// Whether the server considers it the fix to go with.
+    pub preferred: bool,
+    /// Why it cannot be applied right now.
+    pub disabled: Option<String>,
+    pub edit: Option<Vec<FileEdit>>,
+    /// Run on the server once the edit, if any, is made.
+    pub command: Option<lsp_types::Command>,
+    /// The action as the server sent it, to be resolved when it came without an edit.
+    pub raw: Option<Value>,
+}
+
+/// A document the servers have been told about.
+struct OpenDocument {
+    server: String,
+    version: i32,
+    text: String,
+}
+
+/// Starts a language server per configured language on first use and keeps the
+/// documents open in the editor in sync with it.
+#[derive(Default)]
+pub struct LspManager {
+    configs: Vec<LanguageServerConfig>,
+    roots: Vec<PathBuf>,
+    clients: HashMap<String, LspClient>,
+    /// Servers that could not be started, with the reason; not retried until restarted.
+    failed: HashMap<String, String>,
+    documents: HashMap<PathBuf, OpenDocument>,
+    events: Vec<LspEvent>,
+}
+
+impl LspManager {
+    /// Replaces the server configuration, stopping servers whose settings changed.
+    pub fn set_configs(&mut self, configs: Vec<LanguageServerConfig>) {
+        if configs == self.configs {
+            return;
+        }
+        let changed: Vec<String> = self
+            .clients
+            .keys()
+            .filter(|name| configs.iter().find(|config| &config.name == *name) != self.config(name))
+            .cloned()
+            .collect();
+        self.configs = configs;
+        self.failed.clear();
+        for name in changed {
+            self.stop(&name);
+            // Reported like a crash so the editor opens its document with the new server.
+            self.events.push(LspEvent::Stopped { server: name, error: None });
+        }
+    }
+
+    /// Servers are rooted at the workspace folders, so they are restarted when those change.
+    pub fn set_roots(&mut self, roots: Vec<PathBuf>, ctx: &egui::Context) {
+        if roots == self.roots {
+            return;
+        }
+        self.roots = roots;
+        let running: Vec<String> = self.clients.keys().cloned().collect();
+        for name in running {
+            self.restart(&name, ctx);
+        }
+    }
+
+    fn config(&self, name: &str) -> Option<&LanguageServerConfig> {
+        self.configs.iter().find(|config| config.name == name)
+    }
+
+    pub fn server_for(&self, path: &Path) -> Option<&LanguageServerConfig> {
+        let extension = path.extension()?.to_str()?;
+        self.configs.iter().find(|config| config.handles(extension))
+    }
+
+    fn client_for(&mut self, path: &Path, ctx: &egui::Context) -> Option<&mut LspClient> {
+        let config = self.server_for(path)?.clone();
+        if self.failed.contains_key(&config.name) {
+            return None;
+        }
+
+        if !self.clients.contains_key(&config.name) {
+            match LspClient::start(&config, &self.roots, ctx) {
+                Ok(client) => {
+                    self.clients.insert(config.name.clone(), client);
+                }
+                Err(e) => {
+                    self.failed.insert(config.name.clone(), e.clone());
+                    self.events.push(LspEvent::Stopped { server: config.name, error: Some(e) });
+                    return None;
+                }
+            }
+        }
+        self.clients.get_mut(&config.name)
+    }
+
+    /// The running server for `path`, for sending requests about it.
+    pub fn client(&mut self, path: &Path) -> Option<&mut LspClient> {
+        let server = &self.documents.get(path)?.server;
+        self.clients.get_mut(server)
+    }
+
+    pub fn open(&mut self, path: &Path, text: &str, ctx: &egui::Context) {
+        if self.documents.contains_key(path) {
+            return;
+        }
+        let Some(uri) = client::uri_for(path) else {
+            return;
+        };
+        let language_id = language_id(path);
+        let Some(client) = self.client_for(path, ctx) else {
+            return;
+        };
+
+        client.notify(
+            "textDocument/didOpen",
+            to_value(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri,
+                    language_id,
+                    version: 0,
+                    text: text.to_string(),
+                },
+            }),
+        );
+        let server = client.config.name.clone();
+        self.documents.insert(
+            path.to_path_buf(),
+            OpenDocument { server, version: 0, text: text.to_string() },
+        );
+    }
+
+    /// Sends the edit that turns the last synced text into `text`, as a range edit when
+    /// the server accepts incremental changes and as the full text otherwise.
+    pub fn change(&mut self, path: &Path, text: &str) {
+        let (Some(document), Some(uri)) = (self.documents.get_mut(path), client::uri_for(path)) else {
+            return;
+        };
+        let Some(client) = self.clients.get_mut(&document.server) else {
+            return;
+        };
+
+        // Until the server has said how it takes changes, the whole text is sent; it is
+        // queued with the open until the server is initialized.
+        let kind = if *client.state() == ServerState::Starting { TextDocumentSyncKind::FULL } else { sync_kind(client) };
+        let changes = match kind {
+            TextDocumentSyncKind::INCREMENTAL => sync::incremental_change(&document.text, text).into_iter().collect(),
+            TextDocumentSyncKind::FULL if document.text != text => {
+                vec![TextDocumentContentChangeEvent { range: None, range_length: None, text: text.to_string() }]
+            }
+            _ => Vec::new(),
+        };
+        document.text = text.to_string();
+        if changes.is_empty() {
+            return;
+        }
+
+        document.version += 1;
+        client.notify(
+            "textDocument/didChange",
+            to_value(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier { uri, version: document.version },
+                content_changes: changes,
+            }),
+        );
+    }
+
+    pub fn save(&mut self, path: &Path) {
+        let Some(uri) = client::uri_for(path) else {
+            return;
+        };
+        if let Some(client) = self.client(path) {
+            client.notify(
+                "textDocument/didSave",
+                to_value(DidSaveTextDocumentParams { text_document: TextDocumentIdentifier { uri }, text: None }),
+            );
+        }
+    }
+
+    pub fn close(&mut self, path: &Path) {
+        let Some(uri) = client::uri_for(path) else {
+            return;
+        };
+        if let Some(client) = self.client(path) {
+            client.notify(
+                "textDocument/didClose",
+                to_value(DidCloseTextDocumentParams { text_document: TextDocumentIdentifier { uri } }),
+            );
+        }
+        self.documents.remove(path);
+    }
+
+    /// Tells every running server that files it does not have open changed on disk, such
+    /// as the ones a workspace edit wrote.
+    pub fn files_changed(&mut self, paths: &[PathBuf]) {
+        let changes: Vec<FileEvent> = paths
+            .iter()
+            .filter(|path| !self.documents.contains_key(*path))
+            .filter_map(|path| Some(FileEvent { uri: client::uri_for(path)?, typ: FileChangeType::CHANGED }))
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        for client in self.clients.values_mut() {
+            if matches!(client.state(), ServerState::Stopped(_)) {
+                continue;
+            }
+            client.notify(
+                "workspace/didChangeWatchedFiles",
+                to_value(DidChangeWatchedFilesParams { changes: changes.clone() }),
+            );
+        }
+    }
+
+    /// Asks the server of an open document for completions at a byte offset. `trigger` is
+    /// the character that was typed to ask, if any. Returns the request id to match the reply.
+    pub fn completion(&mut self, path: &Path, offset: usize, trigger: Option<char>) -> Option<i64> {
+        let text_document_position = self.position_params(path, offset)?;
+        let client = self.client(path)?;
+        client.capabilities().completion_provider.as_ref()?;
+
+        let context = CompletionContext {
+            trigger_kind: if trigger.is_some() {
+                CompletionTriggerKind::TRIGGER_CHARACTER
+            } else {
+                CompletionTriggerKind::INVOKED
+            },
+            trigger_character: trigger.map(String::from),
+        };
+        let params = CompletionParams {
+            text_document_position,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: Some(context),
+        };
+        Some(client.request("textDocument/completion", to_value(params)))
+    }
+
+    /// Asks where the symbol at a byte offset of an open document is declared.
+    pub fn definition(&mut self, path: &Path, offset: usize) -> Option<i64> {
+        let text_document_position_params = self.position_params(path, offset)?;
+        let client = self.client(path)?;
+        if !enabled(client.capabilities().definition_provider.as_ref()) {
+            return None;
+        }
+        let params = GotoDefinitionParams {
+            text_document_position_params,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Some(client.request("textDocument/definition", to_value(params)))
+    }
+
+    /// Asks for every use of the symbol at a byte offset, its declaration included.
+    pub fn references(&mut self, path: &Path, offset: usize) -> Option<i64> {
+        let text_document_position = self.position_params(path, offset)?;
+        let client = self.client(path)?;
+        if !enabled(client.capabilities().references_provider.as_ref()) {
+            return None;
+        }
+        let params = ReferenceParams {
+            text_document_position,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext { include_declaration: true },
+        };
+        Some(client.request("textDocument/references", to_value(params)))
+    }
+
+    /// Asks for the type and documentation of the symbol at a byte offset.
+    pub fn hover(&mut self, path: &Path, offset: usize) -> Option<i64> {
+        let text_document_position_params = self.position_params(path, offset)?;
+        let client = self.client(path)?;
+        match client.capabilities().hover_provider {
+            Some(HoverProviderCapability::Simple(true)) | Some(HoverProviderCapability::Options(_)) => {}
+            _ => return None,
+        }
+        let params = HoverParams { text_document_position_params, work_done_progress_params: Default::default() };
+        Some(client.request("textDocument/hover", to_value(params)))
+    }
+
+    /// Asks for the outline of an open document.
+    pub fn document_symbols(&mut self, path: &Path) -> Option<i64> {
+        let uri = client::uri_for(path)?;
+        let client = self.client(path)?;
+        if !enabled(client.capabilities().document_symbol_provider.as_ref()) {
+            return None;
+        }
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Some(client.request("textDocument/documentSymbol", to_value(params)))
+    }
+
+    /// Asks for the edits that rename the symbol at a byte offset everywhere it is used.
+    pub fn rename(&mut self, path: &Path, offset: usize, new_name: &str) -> Option<i64> {
+        let text_document_position = self.position_params(path, offset)?;
+        let client = self.client(path)?;
+        if !enabled(client.capabilities().rename_provider.as_ref()) {
+            return None;
+        }
+        let params = RenameParams {
+            text_document_position,
+            new_name: new_name.to_string(),
+            work_done_progress_params: Default::default(),
+        };
+        Some(client.request("textDocument/rename", to_value(params)))
+    }
+
+    /// Asks for the fixes and refactorings available for a byte range, telling the server
+    /// which of the problems it reported are in it.
+    pub fn code_actions(&mut self, path: &Path, range: std::ops::Range<usize>, diagnostics: &[&Diagnostic]) -> Option<i64> {
+        let uri = client::uri_for(path)?;
+        let text = &self.documents.get(path)?.text;
+        let range = lsp_types::Range::new(sync::position_at(text, range.start), sync::position_at(text, range.end));
+        let client = self.client(path)?;
+        match client.capabilities().code_action_provider {
+            Some(CodeActionProviderCapability::Simple(true)) | Some(CodeActionProviderCapability::Options(_)) => {}
+            _ => return None,
+        }
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            context: CodeActionContext {
+                diagnostics: diagnostics.iter().map(|diagnostic| convert::lsp_diagnostic(diagnostic)).collect(),
+                only: None,
+                trigger_kind: None,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Some(client.request("textDocument/codeAction", to_value(params)))
+    }
+
+    /// Asks for the edit of a code action that was sent without one, when the server
+    /// fills them in on request.
+    pub fn resolve_code_action(&mut self, path: &Path, action: &CodeAction) -> Option<i64> {
+        let raw = action.raw.clone()?;
+        let client = self.client(path)?;
+        match &client.capabilities().code_action_provider {
+            Some(CodeActionProviderCapability::Options(options)) if options.resolve_provider == Some(true) => {}
+            _ => return None,
+        }
+        Some(client.request("codeAction/resolve", raw))
+    }
+
+    /// Runs a command of a code action on the server of `path`. Commands that change
+    /// files do so by sending a `workspace/applyEdit` request back.
+    pub fn execute_command(&mut self, path: &Path, command: &lsp_types::Command) -> Option<i64> {
+        let client = self.client(path)?;
+        client.capabilities().execute_command_provider.as_ref()?;
+        let params = ExecuteCommandParams {
+            command: command.command.clone(),
+            arguments: command.arguments.clone().unwrap_or_default(),
+            work_done_progress_params: Default::default(),
+        };
+        Some(client.request("workspace/executeCommand", to_value(params)))
+    }
+
+    /// Asks for the edits that format a document, or only a byte range of it. Indentation
+    /// follows the document: tabs if any line starts with one, four spaces otherwise.
+    pub fn format(&mut self, path: &Path, range: Option<std::ops::Range<usize>>) -> Option<i64> {
+        let uri = client::uri_for(path)?;
+        let text = &self.documents.get(path)?.text;
+        let options = FormattingOptions {
+            tab_size: 4,
+            insert_spaces: !text.lines().any(|line| line.starts_with('\t')),
+            ..Default::default()
+        };
+        let range = range.map(|range| lsp_types::Range::new(sync::position_at(text, range.start), sync::position_at(text, range.end)));
+        let text_document = TextDocumentIdentifier { uri };
+        let client = self.client(path)?;
+        match range {
+            None => {
+                if !enabled(client.capabilities().document_formatting_provider.as_ref()) {
+                    return None;
+                }
+                let params = DocumentFormattingParams { text_document, options, work_done_progress_params: Default::default() };
+                Some(client.request("textDocument/formatting", to_value(params)))
+            }
+            Some(range) => {
+                if !enabled(client.capabilities().document_range_formatting_provider.as_ref()) {
+                    return None;
+                }
+                let params = DocumentRangeFormattingParams {
+                    text_document,
+                    range,
+                    options,
+                    work_done_progress_params: Default::default(),
+                };
+                Some(client.request("textDocument/rangeFormatting", to_value(params)))
+            }
+        }
+    }
+
+    /// Answers a request a server made, if it is still running.
+    pub fn respond(&mut self, server: &str, id: Value, result: Value) {
+        if let Some(client) = self.clients.get(server) {
+            client.respond(id, result);
+        }
+    }
+
+    fn position_params(&self, path: &Path, offset: usize) -> Option<TextDocumentPositionParams> {
+        let uri = client::uri_for(path)?;
+        let position = sync::position_at(&self.documents.get(path)?.text, offset);
+        Some(TextDocumentPositionParams { text_document: TextDocumentIdentifier { uri }, position })
+    }
+
+    /// Characters after which the server of `path` wants to be asked for completions, such as `.`.
+    pub fn completion_triggers(&mut self, path: &Path) -> Vec<char> {
+        self.client(path)
+            .and_then(|client| client.capabilities().completion_provider.as_ref()?.trigger_characters.clone())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|trigger| trigger.chars().next())
+            .collect()
+    }
+
+    /// Shuts a server down in the background and forgets the documents it had open, so
+    /// they are opened again with whichever server takes over.
+    fn stop(&mut self, name: &str) -> Option<std::thread::JoinHandle<()>> {
+        self.documents.retain(|_, document| document.server != name);
+        self.clients.remove(name)?.shutdown()
+    }
+
+    /// Stops a server and starts it again with the documents it had open.
+    pub fn restart(&mut self, name: &str, ctx: &egui::Context) {
+        let documents: Vec<(PathBuf, String)> = self
+            .documents
+            .iter()
+            .filter(|(_, document)| document.server == name)
+            .map(|(path, document)| (path.clone(), document.text.clone()))
+            .collect();
+        self.stop(name);
+        self.failed.remove(name);
+
+        for (path, text) in documents {
+            self.open(&path, &text, ctx);
+        }
+    }
+
+    /// Shuts every server down, waiting until each has exited or been killed.
+    pub fn shutdown_all(&mut self) {
+        let names: Vec<String> = self.clients.keys().cloned().collect();
+        let stopping: Vec<_> = names.iter().filter_map(|name| self.stop(name)).collect();
+        for handle in stopping {
+            let _ = handle.join();
+        }
+    }
+
+    /// The name and state of the server handling `path`, for the status bar.
+    pub fn status(&self, path: &Path) -> Option<(String, ServerState)> {
+        let config = self.server_for(path)?;
+        let state = match (self.clients.get(&config.name), self.failed.get(&config.name)) {
+            (Some(client), _) => client.state().clone(),
+            (None, Some(error)) => ServerState::Stopped(Some(error.clone())),
+            (None, None) => ServerState::Stopped(None),
+        };
+        Some((config.name.clone(), state))
+    }
+
+    pub fn poll(&mut self) -> Vec<LspEvent> {
+        let mut events = std::mem::take(&mut self.events);
+
+        for (name, client) in &mut self.clients {
+            for message in client.poll() {
+                let server = name.clone();
+                events.push(match message {
+                    ServerMessage::Exited(error) => LspEvent::Stopped { server, error },
+                    ServerMessage::Notification { method, params } if method == "window/showMessage" => {
+                        let Ok(message) = serde_json::from_value::<lsp_types::ShowMessageParams>(params) else {
+                            continue;
+                        };
+                        LspEvent::Message { server, level: message.typ, text: message.message }
+                    }
+                    ServerMessage::Notification { method, params } if method == "textDocument/publishDiagnostics" => {
+                        let Ok(published) = serde_json::from_value::<PublishDiagnosticsParams>(params) else {
+                            continue;
+                        };
+                        let Ok(path) = published.uri.to_file_path() else {
+                            continue;
+                        };
+                        let diagnostics = published.diagnostics.into_iter().map(convert::diagnostic).collect();
+                        LspEvent::Diagnostics { server, path, diagnostics }
+                    }
+                    ServerMessage::Notification { method, params } => LspEvent::Notification { server, method, params },
+                    ServerMessage::Response { id, method, result } => LspEvent::Response { server, id, method, result },
+                    ServerMessage::Request { id, method, params } => LspEvent::Request { server, id, method, params },
+                });
+            }
+        }
+
+        // A stopped server is dropped so the next document that needs it starts a fresh one,
+        // and its documents are forgotten so they are opened again rather than changed.
+        for event in &events {
+            if let LspEvent::Stopped { server, error } = event {
+                if self.clients.get(server).is_some_and(|client| matches!(client.state(), ServerState::Stopped(_))) {
+                    self.stop(server);
+                    if let Some(error) = error {
+                        self.failed.insert(server.clone(), error.clone());
+                    }
+                }
+            }
+        }
+
+        events
+    }
+}
+
+/// Whether a capability that is either a flag or a set of options is on.
+fn enabled<T>(capability: Option<&OneOf<bool, T>>) -> bool {
+    matches!(capability, Some(OneOf::Left(true)) | Some(OneOf::Right(_)))
+}
+
+fn sync_kind(client: &LspClient) -> TextDocumentSyncKind {
+    match &client.capabilities().text_document_sync {
+        Some(TextDocumentSyncCapability::Kind(kind)) => *kind,
+        Some(TextDocumentSyncCapability::Options(options)) => options.change.unwrap_or(TextDocumentSyncKind::NONE),
+        None => TextDocumentSyncKind::NONE,
+    }
+}
+
+/// The LSP language identifier for a file, from its extension.
+pub fn language_id(path: &Path) -> String {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_ascii_lowercase();
+    match extension.as_str() {
+        "rs" => "rust",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "py" => "python",
+        "ts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" => "javascript",
+        "jsx" => "javascriptreact",
+        "go" => "go",
+        "md" => "markdown",
+        "json" => "json",
+        "toml" => "toml",
+        other => other,
+    }
+    .to_string()
+}
+
+fn to_value(params: impl serde::Serialize) -> Value {
+    serde_json::to_value(params).unwrap_or(Value::Null)
+}
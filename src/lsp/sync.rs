@@ -0,0 +1,106 @@
+use lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+
+/// The LSP position of a byte offset. Columns count UTF-16 code units, as the protocol expects.
+pub fn position_at(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// A single change event covering everything between the common prefix and suffix of
+/// `old` and `new`, or `None` when the texts are equal.
+pub fn incremental_change(old: &str, new: &str) -> Option<TextDocumentContentChangeEvent> {
+    if old == new {
+        return None;
+    }
+
+    let mut prefix = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old.bytes().rev().zip(new.bytes().rev()).take(max_suffix).take_while(|(a, b)| a == b).count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+
+    Some(TextDocumentContentChangeEvent {
+        range: Some(Range {
+            start: position_at(old, prefix),
+            end: position_at(old, old.len() - suffix),
+        }),
+        range_length: None,
+        text: new[prefix..new.len() - suffix].to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Applies a change the way a server would, to check it turns `old` into `new`.
+    fn apply(old: &str, change: &TextDocumentContentChangeEvent) -> String {
+        let range = change.range.unwrap();
+        let offset = |position: Position| {
+            let line_start: usize = old.split_inclusive('\n').take(position.line as usize).map(str::len).sum();
+            let mut units = 0;
+            let line = &old[line_start..];
+            let column = line
+                .char_indices()
+                .find(|(_, c)| {
+                    let reached = units >= position.character;
+                    units += c.len_utf16() as u32;
+                    reached
+                })
+                .map_or(line.len(), |(index, _)| index);
+            line_start + column
+        };
+        let mut text = old.to_string();
+        text.replace_range(offset(range.start)..offset(range.end), &change.text);
+        text
+    }
+
+    #[test]
+    fn equal_texts_have_no_change() {
+        assert!(incremental_change("same\n", "same\n").is_none());
+    }
+
+    #[test]
+    fn change_covers_only_what_differs() {
+        let change = incremental_change("let x = 1;\nlet y = 2;\n", "let x = 1;\nlet yy = 2;\n").unwrap();
+        let range = change.range.unwrap();
+        assert_eq!((range.start, range.end), (Position::new(1, 5), Position::new(1, 5)));
+        assert_eq!(change.text, "y");
+    }
+
+    #[test]
+    fn columns_count_utf16_units() {
+        let old = "a🌍b\n";
+        let change = incremental_change(old, "a🌍c\n").unwrap();
+        assert_eq!(change.range.unwrap().start, Position::new(0, 3));
+        assert_eq!(apply(old, &change), "a🌍c\n");
+    }
+
+    #[test]
+    fn changes_apply_cleanly() {
+        let cases = [
+            ("hello\nworld\n", "hello\nbrave new world\n"),
+            ("abc", ""),
+            ("", "abc"),
+            ("aaaa", "aa"),
+            ("one\ntwo\nthree\n", "one\nthree\n"),
+            ("é", "e\u{301}"),
+            ("日本語", "日本人語"),
+            ("x🌍🌍y", "x🌍y"),
+        ];
+        for (old, new) in cases {
+            let change = incremental_change(old, new).unwrap();
+            assert_eq!(apply(old, &change), new, "{:?} -> {:?}", old, new);
+        }
+    }
+}
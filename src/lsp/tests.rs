@@ -0,0 +1,234 @@
+//! Runs the client against the mock server in `tests/support/mock_lsp.rs`, which `cargo
+//! test` builds as the `mock-lsp` example.
+
+use super::client::{LspClient, ServerState};
+use super::{LspEvent, LspManager};
+use crate::config::LanguageServerConfig;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+fn mock_server() -> PathBuf {
+    // Tests run from target/<profile>/deps, and examples are built in target/<profile>/examples.
+    let exe = std::env::current_exe().unwrap();
+    let profile = exe.parent().and_then(Path::parent).unwrap();
+    let path = profile.join("examples").join(format!("mock-lsp{}", std::env::consts::EXE_SUFFIX));
+    assert!(path.exists(), "{} is missing; build it with `cargo build --example mock-lsp`", path.display());
+    path
+}
+
+fn config(log: &Path, args: &[&str]) -> LanguageServerConfig {
+    LanguageServerConfig {
+        name: "mock".to_string(),
+        command: mock_server().to_string_lossy().into_owned(),
+        args: std::iter::once(log.to_string_lossy().into_owned()).chain(args.iter().map(|arg| arg.to_string())).collect(),
+        extensions: vec!["mock".to_string()],
+        ..Default::default()
+    }
+}
+
+/// What the mock server logged, as the id of the process and the entry.
+fn log_entries(log: &Path) -> Vec<(u32, String)> {
+    std::fs::read_to_string(log)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (pid, entry) = line.split_once(' ')?;
+            Some((pid.parse().ok()?, entry.to_string()))
+        })
+        .collect()
+}
+
+fn logged(log: &Path) -> Vec<String> {
+    log_entries(log).into_iter().map(|(_, entry)| entry).collect()
+}
+
+fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting until {}", what);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn start(config: &LanguageServerConfig, root: &Path) -> LspClient {
+    let mut client = LspClient::start(config, &[root.to_path_buf()], &egui::Context::default()).unwrap();
+    wait_until("the server is running", || {
+        client.poll();
+        *client.state() == ServerState::Running
+    });
+    client
+}
+
+/// The text the mock server last reported for a document.
+fn reported_text(events: &[LspEvent]) -> Option<&str> {
+    events.iter().rev().find_map(|event| match event {
+        LspEvent::Notification { method, params, .. } if method == "mock/text" => params["text"].as_str(),
+        _ => None,
+    })
+}
+
+#[test]
+fn initialize_comes_first_and_holds_back_other_messages() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("log");
+    let mut client = LspClient::start(&config(&log, &[]), &[dir.path().to_path_buf()], &egui::Context::default()).unwrap();
+    assert_eq!(*client.state(), ServerState::Starting);
+    client.notify("mock/early", Value::Null);
+
+    wait_until("the server is running", || {
+        client.poll();
+        *client.state() == ServerState::Running
+    });
+    assert!(client.capabilities().hover_provider.is_some());
+    wait_until("the early notification arrives", || logged(&log).len() >= 3);
+    assert_eq!(logged(&log), ["initialize", "initialized", "mock/early"]);
+}
+
+#[test]
+fn open_and_incremental_changes_keep_the_server_in_sync() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("log");
+    let ctx = egui::Context::default();
+    let path = dir.path().join("notes.mock");
+    let mut lsp = LspManager::default();
+    lsp.set_configs(vec![config(&log, &[])]);
+
+    lsp.open(&path, "hello\nworld\n", &ctx);
+    // Made before the server is initialized, so sent in full.
+    lsp.change(&path, "hello\nwide world\n");
+    let mut events = Vec::new();
+    wait_until("the server is running", || {
+        events.extend(lsp.poll());
+        lsp.status(&path).is_some_and(|(_, state)| state == ServerState::Running)
+    });
+
+    let edits = ["hello\nwide 🌍 world\n", "hello\nwide 🌍 world!\nbye\n", "héllo\nbye\n", ""];
+    for text in edits {
+        lsp.change(&path, text);
+    }
+    wait_until("the server has the last edit", || {
+        events.extend(lsp.poll());
+        events.iter().filter(|event| matches!(event, LspEvent::Notification { method, .. } if method == "mock/text")).count()
+            == 2 + edits.len()
+    });
+    assert_eq!(reported_text(&events), Some(""));
+
+    let entries = logged(&log);
+    assert_eq!(entries.iter().filter(|entry| *entry == "textDocument/didOpen").count(), 1);
+    assert_eq!(entries.iter().filter(|entry| *entry == "incremental").count(), edits.len());
+    assert_eq!(lsp.documents[&path].version, 1 + edits.len() as i32);
+}
+
+#[test]
+fn shutdown_waits_for_the_reply_before_exit() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("log");
+    let mut client = start(&config(&log, &["--shutdown-delay", "300"]), dir.path());
+
+    let started = Instant::now();
+    client.shutdown().unwrap().join().unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(logged(&log), ["initialize", "initialized", "shutdown", "exit", "exit 0"]);
+    assert!(client.shutdown().is_none());
+}
+
+#[test]
+fn shutdown_kills_a_server_that_does_not_reply() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("log");
+    let mut client = start(&config(&log, &["--shutdown-delay", "60000"]), dir.path());
+
+    let started = Instant::now();
+    client.shutdown().unwrap().join().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(!logged(&log).contains(&"exit".to_string()));
+}
+
+#[test]
+fn restart_reopens_documents_with_a_new_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("log");
+    let ctx = egui::Context::default();
+    let path = dir.path().join("notes.mock");
+    let mut lsp = LspManager::default();
+    lsp.set_configs(vec![config(&log, &[])]);
+    lsp.open(&path, "first\n", &ctx);
+    lsp.change(&path, "second\n");
+    wait_until("the first server has the document", || {
+        lsp.poll();
+        logged(&log).iter().any(|entry| entry == "textDocument/didChange")
+    });
+
+    lsp.restart("mock", &ctx);
+    let mut events = Vec::new();
+    wait_until("the new server has the document", || {
+        events.extend(lsp.poll());
+        log_entries(&log).iter().filter(|(_, entry)| entry == "textDocument/didOpen").count() == 2
+    });
+    wait_until("the new server reports it", || {
+        events.extend(lsp.poll());
+        reported_text(&events).is_some()
+    });
+    assert_eq!(reported_text(&events), Some("second\n"));
+
+    let entries = log_entries(&log);
+    let first = entries[0].0;
+    wait_until("the first server exits", || log_entries(&log).contains(&(first, "exit 0".to_string())));
+    assert!(entries.iter().any(|(pid, entry)| *pid != first && entry == "textDocument/didOpen"));
+}
+
+#[test]
+fn a_crashed_server_forgets_its_documents() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("log");
+    let ctx = egui::Context::default();
+    let path = dir.path().join("notes.mock");
+    let mut lsp = LspManager::default();
+    lsp.set_configs(vec![config(&log, &[])]);
+    lsp.open(&path, "text\n", &ctx);
+    wait_until("the server is running", || {
+        lsp.poll();
+        lsp.status(&path).is_some_and(|(_, state)| state == ServerState::Running)
+    });
+
+    lsp.client(&path).unwrap().notify("mock/crash", Value::Null);
+    let mut stopped = false;
+    wait_until("the crash is noticed", || {
+        stopped |= lsp.poll().iter().any(|event| matches!(event, LspEvent::Stopped { error: Some(_), .. }));
+        stopped
+    });
+    assert!(lsp.client(&path).is_none());
+    assert!(!lsp.documents.contains_key(&path));
+
+    // Opening it again after a restart reaches the new server rather than being skipped.
+    lsp.restart("mock", &ctx);
+    lsp.open(&path, "text again\n", &ctx);
+    let mut events = Vec::new();
+    wait_until("the new server has the document", || {
+        events.extend(lsp.poll());
+        reported_text(&events).is_some()
+    });
+    assert_eq!(reported_text(&events), Some("text again\n"));
+}
+
+#[test]
+fn files_written_elsewhere_are_reported_as_changed() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("log");
+    let ctx = egui::Context::default();
+    let open = dir.path().join("open.mock");
+    let mut lsp = LspManager::default();
+    lsp.set_configs(vec![config(&log, &[])]);
+    lsp.open(&open, "text\n", &ctx);
+
+    // The open document is kept in sync by its changes, so it is not reported.
+    lsp.files_changed(std::slice::from_ref(&open));
+    lsp.files_changed(&[dir.path().join("other.mock"), open]);
+    wait_until("the change is reported", || {
+        lsp.poll();
+        logged(&log).contains(&"workspace/didChangeWatchedFiles".to_string())
+    });
+    let entries = logged(&log);
+    assert_eq!(entries.iter().filter(|entry| *entry == "workspace/didChangeWatchedFiles").count(), 1);
+}
@@ -0,0 +1,88 @@
+use std::io::{self, BufRead, Write};
+
+/// Reads one `Content-Length` framed message. Returns `None` when the stream ends.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut content_length = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = content_length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message(writer: &mut impl Write, body: &str) -> io::Result<()> {
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(input: &str) -> Vec<io::Result<Option<String>>> {
+        let mut reader = io::Cursor::new(input.as_bytes().to_vec());
+        let mut messages = Vec::new();
+        loop {
+            let message = read_message(&mut reader);
+            let done = !matches!(message, Ok(Some(_)));
+            messages.push(message);
+            if done {
+                return messages;
+            }
+        }
+    }
+
+    #[test]
+    fn reads_consecutive_messages_until_the_stream_ends() {
+        let messages = read_all("Content-Length: 2\r\n\r\n{}Content-Length: 7\r\n\r\n[1,2,3]");
+        let bodies: Vec<_> = messages.into_iter().map(Result::unwrap).collect();
+        assert_eq!(bodies, [Some("{}".to_string()), Some("[1,2,3]".to_string()), None]);
+    }
+
+    #[test]
+    fn length_counts_bytes_and_other_headers_are_ignored() {
+        let body = r#"{"text":"héllo"}"#;
+        let input = format!("content-length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{}", body.len(), body);
+        assert_eq!(read_message(&mut io::Cursor::new(input)).unwrap().as_deref(), Some(body));
+    }
+
+    #[test]
+    fn malformed_messages_are_errors() {
+        let missing_length = read_message(&mut io::Cursor::new("Content-Type: x\r\n\r\n{}")).unwrap_err();
+        assert_eq!(missing_length.kind(), io::ErrorKind::InvalidData);
+        let truncated = read_message(&mut io::Cursor::new("Content-Length: 10\r\n\r\n{}")).unwrap_err();
+        assert_eq!(truncated.kind(), io::ErrorKind::UnexpectedEof);
+        let invalid_utf8 = read_message(&mut io::Cursor::new(b"Content-Length: 1\r\n\r\n\xff".to_vec())).unwrap_err();
+        assert_eq!(invalid_utf8.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn written_messages_read_back() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, "{\"id\":1}").unwrap();
+        write_message(&mut buffer, "ü").unwrap();
+        let mut reader = io::Cursor::new(buffer);
+        assert_eq!(read_message(&mut reader).unwrap().as_deref(), Some("{\"id\":1}"));
+        assert_eq!(read_message(&mut reader).unwrap().as_deref(), Some("ü"));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+}
@@ -1,8 +1,31 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
+#[cfg(not(target_arch = "wasm32"))]
+mod backup;
+mod completion;
 mod config;
+mod document;
+mod file_filter;
+#[cfg(not(target_arch = "wasm32"))]
+mod file_ops;
+#[cfg(not(target_arch = "wasm32"))]
+mod format;
+#[cfg(not(target_arch = "wasm32"))]
+mod git;
+#[cfg(not(target_arch = "wasm32"))]
+mod lsp;
+mod navigation;
+mod session;
+mod symbols;
+#[cfg(not(target_arch = "wasm32"))]
+mod tasks;
+#[cfg(not(target_arch = "wasm32"))]
+mod terminal;
 mod ui;
+#[cfg(not(target_arch = "wasm32"))]
+mod watcher;
+mod workspace;
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
@@ -22,13 +45,14 @@ fn main() -> eframe::Result {
             .with_resizable(true)
             .with_transparent(true)
             .with_icon(icon_data),
+        persist_window: true,
         ..Default::default()
     };
 
     eframe::run_native(
         "Zen",
         options,
-        Box::new(|_cc| Ok(Box::new(app::ZenEditor::default()))),
+        Box::new(|cc| Ok(Box::new(app::ZenEditor::new(cc)))),
     )
 }
 
@@ -108,7 +132,7 @@ fn main() {
                 .start(
                     canvas,
                     web_options,
-                    Box::new(|_cc| Ok(Box::new(app::ZenEditor::default()))),
+                    Box::new(|cc| Ok(Box::new(app::ZenEditor::new(cc)))),
                 )
                 .await;
 
@@ -0,0 +1,37 @@
+use crate::document::position::Location;
+
+/// How many jumps back are remembered.
+const MAX_HISTORY: usize = 100;
+
+/// Where the cursor was before each jump, for Alt+Left and Alt+Right.
+#[derive(Default)]
+pub struct NavigationHistory {
+    back: Vec<Location>,
+    forward: Vec<Location>,
+}
+
+impl NavigationHistory {
+    /// Remembers `from` as the place a jump left. A new jump forgets the way forward.
+    pub fn record(&mut self, from: Location) {
+        if self.back.last() != Some(&from) {
+            self.back.push(from);
+            if self.back.len() > MAX_HISTORY {
+                self.back.remove(0);
+            }
+        }
+        self.forward.clear();
+    }
+
+    /// The place to go back to from `current`, which becomes the way forward.
+    pub fn back(&mut self, current: Location) -> Option<Location> {
+        let location = self.back.pop()?;
+        self.forward.push(current);
+        Some(location)
+    }
+
+    pub fn forward(&mut self, current: Location) -> Option<Location> {
+        let location = self.forward.pop()?;
+        self.back.push(current);
+        Some(location)
+    }
+}
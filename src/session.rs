@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const MAX_RECENT_ITEMS: usize = 10;
+const MAX_DOCUMENT_STATES: usize = 50;
+
+/// Where the user left off in a document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentViewState {
+    pub path: PathBuf,
+    pub cursor: usize,
+    pub scroll: [f32; 2],
+}
+
+/// Everything needed to put the editor back the way it was on the next launch.
+/// Window geometry is persisted by eframe itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub project_root: Option<PathBuf>,
+    /// Folders added next to `project_root` without a workspace file.
+    pub extra_roots: Vec<PathBuf>,
+    pub workspace_file: Option<PathBuf>,
+    pub active_document: Option<PathBuf>,
+    pub documents: Vec<DocumentViewState>,
+    pub side_panel_width: Option<f32>,
+    pub recent_projects: Vec<PathBuf>,
+    pub recent_files: Vec<PathBuf>,
+}
+
+impl Session {
+    pub const STORAGE_KEY: &'static str = "zen_session";
+
+    pub fn note_project(&mut self, path: &Path) {
+        push_recent(&mut self.recent_projects, path);
+    }
+
+    pub fn note_file(&mut self, path: &Path) {
+        push_recent(&mut self.recent_files, path);
+    }
+
+    pub fn clear_recent(&mut self) {
+        self.recent_projects.clear();
+        self.recent_files.clear();
+    }
+
+    pub fn set_documents(&mut self, mut documents: Vec<DocumentViewState>) {
+        documents.truncate(MAX_DOCUMENT_STATES);
+        self.documents = documents;
+    }
+}
+
+fn push_recent(list: &mut Vec<PathBuf>, path: &Path) {
+    list.retain(|p| p != path);
+    list.insert(0, path.to_path_buf());
+    list.truncate(MAX_RECENT_ITEMS);
+}
@@ -0,0 +1,339 @@
+use super::{tags, Symbol};
+use crate::document::position::Location;
+use crate::file_filter::FileFilter;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::UNIX_EPOCH;
+
+/// Files bigger than this are not read for symbols.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// Bumped when the cache layout or the tag patterns change, so that older caches are rebuilt.
+const CACHE_VERSION: u32 = 1;
+
+/// A file's symbols as of its modification time, in nanoseconds since the epoch.
+#[derive(Serialize, Deserialize)]
+struct IndexedFile {
+    modified: u128,
+    symbols: Vec<Symbol>,
+}
+
+/// The index as saved in `symbols/` in the instance directory, one file per set of roots.
+#[derive(Serialize, Deserialize)]
+struct IndexCache {
+    version: u32,
+    roots: Vec<PathBuf>,
+    files: HashMap<PathBuf, IndexedFile>,
+}
+
+enum IndexUpdate {
+    File(PathBuf, Vec<Symbol>),
+    /// A file that is gone, left the project or no longer has symbols to read.
+    Removed(PathBuf),
+    Done,
+}
+
+/// A path that changed on disk, with the root it is in and that root's filter.
+type Change = (PathBuf, PathBuf, Arc<FileFilter>);
+
+/// The thread that re-reads changed files, in the order they changed.
+struct Updater {
+    changes: mpsc::Sender<Change>,
+    updates: mpsc::Receiver<IndexUpdate>,
+}
+
+/// The declarations in every project file the tag patterns understand, for navigating
+/// without a language server. Built on a background thread when the roots change and
+/// kept current a file at a time afterwards. The finished build is saved so the next
+/// launch only re-reads the files modified since.
+#[derive(Default)]
+pub struct SymbolIndex {
+    roots: Vec<PathBuf>,
+    /// The roots with the filters that decide which of their files are indexed.
+    filters: Vec<(PathBuf, Arc<FileFilter>)>,
+    files: HashMap<PathBuf, Vec<Symbol>>,
+    receiver: Option<mpsc::Receiver<IndexUpdate>>,
+    updater: Option<Updater>,
+    /// Counts changes, for views built from the index to tell they are out of date.
+    generation: u64,
+}
+
+impl SymbolIndex {
+    /// Rebuilds the index when the project roots are different from the ones it was built for.
+    pub fn set_roots(&mut self, roots: Vec<(PathBuf, Arc<FileFilter>)>, ctx: &egui::Context) {
+        if self.updater.is_none() {
+            self.updater = Some(Updater::spawn(ctx));
+        }
+        self.filters = roots.clone();
+        let paths: Vec<PathBuf> = roots.iter().map(|(path, _)| path.clone()).collect();
+        if paths == self.roots {
+            return;
+        }
+        self.roots = paths;
+        self.files.clear();
+
+        let (sender, receiver) = mpsc::channel();
+        self.receiver = Some(receiver);
+        self.generation += 1;
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let paths: Vec<PathBuf> = roots.iter().map(|(path, _)| path.clone()).collect();
+            let cache_path = cache_path(&paths);
+            let mut cached = cache_path.as_deref().and_then(|cache| load_cache(cache, &paths)).unwrap_or_default();
+            let mut indexed = HashMap::new();
+
+            for (root, filter) in roots {
+                for path in filter.files(&root) {
+                    if !language(&path).is_some_and(|language| tags::supports(&language)) {
+                        continue;
+                    }
+                    let Some(modified) = modified_time(&path) else {
+                        continue;
+                    };
+                    let file = match cached.remove(&path) {
+                        Some(file) if file.modified == modified => file,
+                        _ => match read_symbols(&path) {
+                            Some(symbols) => IndexedFile { modified, symbols },
+                            None => continue,
+                        },
+                    };
+                    if sender.send(IndexUpdate::File(path.clone(), file.symbols.clone())).is_err() {
+                        return;
+                    }
+                    indexed.insert(path, file);
+                }
+            }
+            let _ = sender.send(IndexUpdate::Done);
+            ctx.request_repaint();
+
+            if let Some(cache_path) = cache_path {
+                save_cache(&cache_path, IndexCache { version: CACHE_VERSION, roots: paths, files: indexed });
+            }
+        });
+    }
+
+    pub fn poll(&mut self) {
+        let updates = self.updater.as_ref().map(|updater| updater.updates.try_iter().collect::<Vec<_>>());
+        for update in updates.into_iter().flatten() {
+            self.apply(update);
+        }
+
+        let Some(receiver) = &self.receiver else {
+            return;
+        };
+        let updates: Vec<IndexUpdate> = receiver.try_iter().collect();
+        for update in updates {
+            self.apply(update);
+        }
+    }
+
+    fn apply(&mut self, update: IndexUpdate) {
+        self.generation += 1;
+        match update {
+            // Files re-read for roots that have since been closed are dropped.
+            IndexUpdate::File(path, symbols) => {
+                if self.roots.iter().any(|root| path.starts_with(root)) {
+                    self.files.insert(path, symbols);
+                }
+            }
+            IndexUpdate::Removed(path) => {
+                self.files.remove(&path);
+            }
+            IndexUpdate::Done => {
+                self.receiver = None;
+                log::debug!("Indexed symbols in {} files", self.files.len());
+            }
+        }
+    }
+
+    pub fn is_building(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Re-reads a file that changed on disk, or every file in a folder that appeared, if
+    /// the project shows it. The reading happens in the background; the symbols arrive
+    /// through [`Self::poll`].
+    pub fn update_file(&mut self, path: &Path) {
+        let Some((root, filter)) = self.filters.iter().find(|(root, _)| path.starts_with(root)) else {
+            return;
+        };
+        if let Some(updater) = &self.updater {
+            let _ = updater.changes.send((path.to_path_buf(), root.clone(), filter.clone()));
+        }
+    }
+
+    pub fn remove_file(&mut self, path: &Path) {
+        self.generation += 1;
+        self.files.retain(|file, _| !file.starts_with(path));
+    }
+
+    /// Where `name` is declared across the project, ignoring `impl` blocks.
+    pub fn definitions(&self, name: &str) -> Vec<(Location, &Symbol)> {
+        let mut found: Vec<(Location, &Symbol)> = self
+            .files
+            .iter()
+            .flat_map(|(path, symbols)| {
+                symbols
+                    .iter()
+                    .filter(|symbol| symbol.name == name && symbol.kind.is_definition())
+                    .map(move |symbol| (Location::new(path, symbol.position), symbol))
+            })
+            .collect();
+        found.sort_by(|a, b| (&a.0.path, a.0.position).cmp(&(&b.0.path, b.0.position)));
+        found
+    }
+
+    /// Every declaration in the project, ordered by name.
+    pub fn symbols(&self) -> Vec<(Location, &Symbol)> {
+        let mut symbols: Vec<(Location, &Symbol)> = self
+            .files
+            .iter()
+            .flat_map(|(path, symbols)| symbols.iter().map(move |symbol| (Location::new(path, symbol.position), symbol)))
+            .collect();
+        symbols.sort_by(|a, b| a.1.name.cmp(&b.1.name).then_with(|| a.0.path.cmp(&b.0.path)));
+        symbols
+    }
+
+    /// The indexed files, which are the ones worth searching for references.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.files.keys().cloned().collect();
+        files.sort();
+        files
+    }
+}
+
+impl Updater {
+    fn spawn(ctx: &egui::Context) -> Self {
+        let (changes, receiver) = mpsc::channel::<Change>();
+        let (sender, updates) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            for (path, root, filter) in receiver {
+                let allowed = filter.allows(&root, &path);
+                let files = if allowed && path.is_dir() { filter.files_under(&root, &path) } else { vec![path.clone()] };
+                for file in files {
+                    let update = match allowed.then(|| read_symbols(&file)).flatten() {
+                        Some(symbols) => IndexUpdate::File(file, symbols),
+                        None => IndexUpdate::Removed(file),
+                    };
+                    if sender.send(update).is_err() {
+                        return;
+                    }
+                }
+                ctx.request_repaint();
+            }
+        });
+        Self { changes, updates }
+    }
+}
+
+fn read_symbols(path: &Path) -> Option<Vec<Symbol>> {
+    let language = language(path)?;
+    if !tags::supports(&language) {
+        return None;
+    }
+    if std::fs::metadata(path).ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let text = std::fs::read_to_string(path).ok()?;
+    Some(tags::extract(&text, &language))
+}
+
+fn modified_time(path: &Path) -> Option<u128> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+/// Where the index of a set of roots is saved, named by a hash of their paths.
+fn cache_path(roots: &[PathBuf]) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    roots.hash(&mut hasher);
+    let dir = std::env::current_dir().ok()?.join("symbols");
+    Some(dir.join(format!("{:016x}.json", hasher.finish())))
+}
+
+fn load_cache(path: &Path, roots: &[PathBuf]) -> Option<HashMap<PathBuf, IndexedFile>> {
+    let bytes = std::fs::read(path).ok()?;
+    let cache: IndexCache = serde_json::from_slice(&bytes)
+        .map_err(|e| log::warn!("Ignoring unreadable symbol cache {}: {}", path.display(), e))
+        .ok()?;
+    (cache.version == CACHE_VERSION && cache.roots == roots).then_some(cache.files)
+}
+
+fn save_cache(path: &Path, cache: IndexCache) {
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| serde_json::to_vec(&cache).map_err(std::io::Error::other))
+        .and_then(|bytes| std::fs::write(path, bytes));
+    if let Err(e) = result {
+        log::warn!("Failed to save the symbol cache to {}: {}", path.display(), e);
+    }
+}
+
+/// The extension tag patterns are chosen by.
+fn language(path: &Path) -> Option<String> {
+    Some(path.extension()?.to_str()?.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_filter::FileFilterConfig;
+
+    /// An index of `root` that is kept current but was never built, so nothing is cached.
+    fn index(root: &Path) -> SymbolIndex {
+        let config = FileFilterConfig { exclude: vec!["generated".to_string()], ..Default::default() };
+        SymbolIndex {
+            roots: vec![root.to_path_buf()],
+            filters: vec![(root.to_path_buf(), Arc::new(FileFilter::new(&config)))],
+            updater: Some(Updater::spawn(&egui::Context::default())),
+            ..Default::default()
+        }
+    }
+
+    fn wait_for(index: &mut SymbolIndex, generation: u64) {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while index.generation() < generation {
+            assert!(std::time::Instant::now() < deadline, "timed out waiting for the index");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            index.poll();
+        }
+    }
+
+    fn names(index: &SymbolIndex) -> Vec<String> {
+        index.symbols().into_iter().map(|(_, symbol)| symbol.name.clone()).collect()
+    }
+
+    #[test]
+    fn renamed_folders_are_indexed_through_the_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("moved/generated")).unwrap();
+        std::fs::create_dir_all(root.join("moved/ignored")).unwrap();
+        std::fs::write(root.join(".ignore"), "ignored/\n").unwrap();
+        std::fs::write(root.join("moved/shown.rs"), "fn shown() {}\n").unwrap();
+        std::fs::write(root.join("moved/generated/excluded.rs"), "fn excluded() {}\n").unwrap();
+        std::fs::write(root.join("moved/ignored/hidden.rs"), "fn hidden() {}\n").unwrap();
+
+        let mut index = index(root);
+        index.update_file(&root.join("moved"));
+        wait_for(&mut index, 1);
+        assert_eq!(names(&index), ["shown"]);
+
+        // Single files are checked against the filter too.
+        index.update_file(&root.join("moved/ignored/hidden.rs"));
+        index.update_file(&root.join("moved/generated/excluded.rs"));
+        std::fs::write(root.join("moved/shown.rs"), "fn renamed() {}\n").unwrap();
+        index.update_file(&root.join("moved/shown.rs"));
+        wait_for(&mut index, 4);
+        assert_eq!(names(&index), ["renamed"]);
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod index;
+pub mod outline;
+pub mod search;
+pub mod tags;
+
+use crate::document::position::{Location, TextPosition};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SymbolKind {
+    Module,
+    Function,
+    Method,
+    Struct,
+    Class,
+    Enum,
+    Interface,
+    Trait,
+    Impl,
+    Type,
+    Constant,
+    Variable,
+    Field,
+    Macro,
+    Heading,
+}
+
+impl SymbolKind {
+    pub fn icon(self) -> &'static str {
+        match self {
+            SymbolKind::Module => "📦",
+            SymbolKind::Function | SymbolKind::Method => "ƒ",
+            SymbolKind::Struct | SymbolKind::Class | SymbolKind::Enum | SymbolKind::Interface | SymbolKind::Trait | SymbolKind::Type => "⊞",
+            SymbolKind::Impl => "▣",
+            SymbolKind::Constant => "π",
+            SymbolKind::Variable => "x",
+            SymbolKind::Field => "#",
+            SymbolKind::Macro => "⚙",
+            SymbolKind::Heading => "¶",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SymbolKind::Module => "module",
+            SymbolKind::Function => "function",
+            SymbolKind::Method => "method",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Class => "class",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Interface => "interface",
+            SymbolKind::Trait => "trait",
+            SymbolKind::Impl => "impl",
+            SymbolKind::Type => "type",
+            SymbolKind::Constant => "constant",
+            SymbolKind::Variable => "variable",
+            SymbolKind::Field => "field",
+            SymbolKind::Macro => "macro",
+            SymbolKind::Heading => "heading",
+        }
+    }
+
+    /// Whether the symbol declares its name, as opposed to an `impl` block naming a type
+    /// declared elsewhere.
+    pub fn is_definition(self) -> bool {
+        self != SymbolKind::Impl
+    }
+}
+
+/// A named declaration in a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the name is.
+    pub position: TextPosition,
+    /// The line it is declared on, trimmed, shown when nothing better describes it.
+    pub signature: String,
+}
+
+/// A place that mentions a symbol, with its line for the results list.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub location: Location,
+    pub preview: String,
+}
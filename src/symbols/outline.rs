@@ -0,0 +1,134 @@
+use super::{tags, Symbol, SymbolKind};
+use std::cmp::Reverse;
+
+/// A symbol of the open document with the symbols declared inside it.
+#[derive(Debug, Clone)]
+pub struct OutlineItem {
+    pub symbol: Symbol,
+    /// The last line of the symbol's body.
+    pub end_line: usize,
+    pub children: Vec<OutlineItem>,
+}
+
+impl OutlineItem {
+    pub fn new(symbol: Symbol, end_line: usize) -> Self {
+        Self { symbol, end_line, children: Vec::new() }
+    }
+
+    /// What the outline calls it: `impl` blocks go by their whole header, since they are
+    /// named after a type declared elsewhere.
+    pub fn label(&self) -> &str {
+        match self.symbol.kind {
+            SymbolKind::Impl => &self.symbol.signature,
+            _ => &self.symbol.name,
+        }
+    }
+
+    fn contains(&self, other: &OutlineItem) -> bool {
+        other.symbol.position.line >= self.symbol.position.line && other.end_line <= self.end_line
+    }
+
+    fn contains_line(&self, line: usize) -> bool {
+        (self.symbol.position.line..=self.end_line).contains(&line)
+    }
+}
+
+/// The outline the tag patterns find in `text`. Tags only mark where declarations start,
+/// so a symbol is taken to run until the next one indented as little or less, or for
+/// Markdown until the next heading of the same level or higher.
+pub fn from_tags(text: &str, language: &str) -> Vec<OutlineItem> {
+    let symbols = tags::extract(text, language);
+    let lines: Vec<&str> = text.lines().collect();
+    let depth = |symbol: &Symbol| {
+        let line = lines.get(symbol.position.line).copied().unwrap_or_default();
+        match symbol.kind {
+            SymbolKind::Heading => line.chars().take_while(|&c| c == '#').count(),
+            _ => line.chars().take_while(|c| c.is_whitespace()).map(|c| if c == '\t' { 4 } else { 1 }).sum(),
+        }
+    };
+    let depths: Vec<usize> = symbols.iter().map(depth).collect();
+    let starts: Vec<usize> = symbols.iter().map(|symbol| symbol.position.line).collect();
+    let last_line = lines.len().saturating_sub(1);
+
+    let items = symbols
+        .into_iter()
+        .enumerate()
+        .map(|(index, symbol)| {
+            let end_line = (index + 1..starts.len())
+                .find(|&next| depths[next] <= depths[index])
+                .map_or(last_line, |next| starts[next].saturating_sub(1).max(starts[index]));
+            OutlineItem::new(symbol, end_line)
+        })
+        .collect();
+
+    let mut outline = nest(items);
+    mark_methods(&mut outline, false);
+    outline
+}
+
+/// Arranges items by range: each one goes inside the nearest item that spans it.
+pub fn nest(mut items: Vec<OutlineItem>) -> Vec<OutlineItem> {
+    items.sort_by_key(|item| (item.symbol.position, Reverse(item.end_line)));
+
+    let mut roots = Vec::new();
+    let mut open: Vec<OutlineItem> = Vec::new();
+    for item in items {
+        while let Some(parent) = open.pop() {
+            if parent.contains(&item) {
+                open.push(parent);
+                break;
+            }
+            attach(&mut open, &mut roots, parent);
+        }
+        open.push(item);
+    }
+    while let Some(item) = open.pop() {
+        attach(&mut open, &mut roots, item);
+    }
+    roots
+}
+
+fn attach(open: &mut [OutlineItem], roots: &mut Vec<OutlineItem>, item: OutlineItem) {
+    match open.last_mut() {
+        Some(parent) => parent.children.push(item),
+        None => roots.push(item),
+    }
+}
+
+/// Functions declared in a type or `impl` block are its methods.
+fn mark_methods(items: &mut [OutlineItem], in_type: bool) {
+    for item in items {
+        if in_type && item.symbol.kind == SymbolKind::Function {
+            item.symbol.kind = SymbolKind::Method;
+        }
+        let is_type = matches!(
+            item.symbol.kind,
+            SymbolKind::Impl | SymbolKind::Trait | SymbolKind::Class | SymbolKind::Struct | SymbolKind::Interface
+        );
+        mark_methods(&mut item.children, is_type);
+    }
+}
+
+/// The indices leading to the innermost item that spans `line`.
+pub fn path_at(items: &[OutlineItem], line: usize) -> Vec<usize> {
+    let mut path = Vec::new();
+    let mut level = items;
+    while let Some(index) = level.iter().rposition(|item| item.contains_line(line)) {
+        path.push(index);
+        level = &level[index].children;
+    }
+    path
+}
+
+/// Every item, depth first, with the label of the item it is declared in.
+pub fn flatten(items: &[OutlineItem]) -> Vec<(&OutlineItem, Option<&str>)> {
+    fn visit<'a>(items: &'a [OutlineItem], parent: Option<&'a str>, out: &mut Vec<(&'a OutlineItem, Option<&'a str>)>) {
+        for item in items {
+            out.push((item, parent));
+            visit(&item.children, Some(item.label()), out);
+        }
+    }
+    let mut out = Vec::new();
+    visit(items, None, &mut out);
+    out
+}
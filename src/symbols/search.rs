@@ -0,0 +1,113 @@
+use super::Reference;
+use crate::document::position::{Location, TextPosition};
+use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+/// Enough to show that a name is everywhere without reading the whole project.
+const MAX_RESULTS: usize = 2000;
+
+/// Every whole-word occurrence of `name` in `files`: what "find references" can offer
+/// when no language server knows what the name refers to.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn references(files: &[PathBuf], name: &str) -> Vec<Reference> {
+    let mut found = Vec::new();
+    for path in files {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        found.extend(references_in(path, &text, name));
+        if found.len() >= MAX_RESULTS {
+            found.truncate(MAX_RESULTS);
+            break;
+        }
+    }
+    found
+}
+
+/// The whole-word occurrences of `name` in the text of one file.
+pub fn references_in(path: &Path, text: &str, name: &str) -> Vec<Reference> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut found = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        for (start, _) in line.match_indices(name) {
+            let end = start + name.len();
+            let before = line[..start].chars().next_back();
+            let after = line[end..].chars().next();
+            if before.is_some_and(is_word) || after.is_some_and(is_word) {
+                continue;
+            }
+
+            let column = line[..start].encode_utf16().count();
+            found.push(Reference {
+                location: Location::new(path, TextPosition::new(number, column)),
+                preview: line.trim().to_string(),
+            });
+            if found.len() >= MAX_RESULTS {
+                return found;
+            }
+        }
+    }
+    found
+}
+
+/// Files bigger than this are skipped by project search.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_SEARCH_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Every occurrence of `query` in the files the project shows under each root, for
+/// "Find in Files". `open` is the open document with its unsaved text, searched instead
+/// of what is on disk. Files that are not text are skipped.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn project(
+    roots: &[(PathBuf, std::sync::Arc<crate::file_filter::FileFilter>)],
+    open: Option<(&Path, &str)>,
+    query: &str,
+    match_case: bool,
+) -> Vec<Reference> {
+    let Ok(regex) = regex::RegexBuilder::new(&regex::escape(query)).case_insensitive(!match_case).build() else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for (root, filter) in roots {
+        for path in filter.files(root) {
+            let text = match open {
+                Some((open_path, text)) if open_path == path => text.to_string(),
+                _ => {
+                    if !std::fs::metadata(&path).is_ok_and(|metadata| metadata.len() <= MAX_SEARCH_FILE_SIZE) {
+                        continue;
+                    }
+                    match std::fs::read_to_string(&path) {
+                        Ok(text) if !text.contains('\0') => text,
+                        _ => continue,
+                    }
+                }
+            };
+            found.extend(matches_in(&path, &text, &regex, MAX_RESULTS - found.len()));
+            if found.len() >= MAX_RESULTS {
+                return found;
+            }
+        }
+    }
+    found
+}
+
+/// Where `regex` matches in the text of one file, at most `limit` of them.
+#[cfg(not(target_arch = "wasm32"))]
+fn matches_in(path: &Path, text: &str, regex: &regex::Regex, limit: usize) -> Vec<Reference> {
+    let mut found = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        for found_match in regex.find_iter(line) {
+            let column = line[..found_match.start()].encode_utf16().count();
+            found.push(Reference {
+                location: Location::new(path, TextPosition::new(number, column)),
+                preview: line.trim().to_string(),
+            });
+            if found.len() >= limit {
+                return found;
+            }
+        }
+    }
+    found
+}
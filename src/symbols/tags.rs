@@ -0,0 +1,157 @@
+//! ctags-style symbol extraction: declarations are found line by line with a few
+//! patterns per language. It misses what spans lines and knows nothing of scopes, but
+//! needs no language server and is fast enough to run over a whole project.
+
+use super::{Symbol, SymbolKind};
+use crate::document::position::TextPosition;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Lines longer than this are generated or minified code, not declarations.
+const MAX_LINE_LENGTH: usize = 400;
+
+struct Pattern {
+    kind: SymbolKind,
+    /// Has a `name` group.
+    regex: Regex,
+}
+
+/// Names that the looser patterns would take for declarations.
+const NOT_NAMES: &[&str] = &[
+    "if", "else", "for", "while", "do", "switch", "case", "return", "catch", "sizeof", "new", "delete", "function",
+];
+
+pub fn extract(text: &str, language: &str) -> Vec<Symbol> {
+    let Some(patterns) = patterns(language) else {
+        return Vec::new();
+    };
+    let comment = line_comment(language);
+
+    let mut symbols = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if line.len() > MAX_LINE_LENGTH || comment.is_some_and(|comment| trimmed.starts_with(comment)) {
+            continue;
+        }
+        let Some((kind, name)) = patterns.iter().find_map(|pattern| {
+            let name = pattern.regex.captures(line)?.name("name")?;
+            (!NOT_NAMES.contains(&name.as_str())).then_some((pattern.kind, name))
+        }) else {
+            continue;
+        };
+
+        symbols.push(Symbol {
+            name: name.as_str().trim().to_string(),
+            kind,
+            position: TextPosition::new(number, line[..name.start()].encode_utf16().count()),
+            signature: trimmed.trim_end().trim_end_matches('{').trim_end().to_string(),
+        });
+    }
+    symbols
+}
+
+/// Whether symbols can be extracted from files with this extension.
+pub fn supports(language: &str) -> bool {
+    patterns(language).is_some()
+}
+
+fn line_comment(language: &str) -> Option<&'static str> {
+    match language {
+        "py" => Some("#"),
+        "md" | "markdown" => None,
+        _ => Some("//"),
+    }
+}
+
+fn patterns(language: &str) -> Option<&'static [Pattern]> {
+    static RUST: OnceLock<Vec<Pattern>> = OnceLock::new();
+    static C: OnceLock<Vec<Pattern>> = OnceLock::new();
+    static PYTHON: OnceLock<Vec<Pattern>> = OnceLock::new();
+    static SCRIPT: OnceLock<Vec<Pattern>> = OnceLock::new();
+    static GO: OnceLock<Vec<Pattern>> = OnceLock::new();
+    static MARKDOWN: OnceLock<Vec<Pattern>> = OnceLock::new();
+
+    let patterns = match language {
+        "rs" => RUST.get_or_init(|| {
+            const VISIBILITY: &str = r"^\s*(?:pub(?:\([^)]*\))?\s+)?";
+            compile(&[
+                (SymbolKind::Function, &format!(r#"{VISIBILITY}(?:default\s+)?(?:const\s+)?(?:async\s+)?(?:unsafe\s+)?(?:extern\s+"[^"]*"\s+)?fn\s+(?P<name>\w+)"#)),
+                (SymbolKind::Struct, &format!(r"{VISIBILITY}(?:struct|union)\s+(?P<name>\w+)")),
+                (SymbolKind::Enum, &format!(r"{VISIBILITY}enum\s+(?P<name>\w+)")),
+                (SymbolKind::Trait, &format!(r"{VISIBILITY}(?:unsafe\s+)?trait\s+(?P<name>\w+)")),
+                (SymbolKind::Type, &format!(r"{VISIBILITY}type\s+(?P<name>\w+)")),
+                (SymbolKind::Module, &format!(r"{VISIBILITY}mod\s+(?P<name>\w+)")),
+                (SymbolKind::Constant, &format!(r"{VISIBILITY}(?:const|static)\s+(?:mut\s+)?(?P<name>\w+)\s*:")),
+                (SymbolKind::Macro, r"^\s*macro_rules!\s*(?P<name>\w+)"),
+                (SymbolKind::Impl, r"^\s*(?:unsafe\s+)?impl(?:\s*<[^>]*>)?\s+(?:[^{]*?\s+for\s+)?(?P<name>[\w:]+)"),
+            ])
+        }),
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" => C.get_or_init(|| {
+            compile(&[
+                (SymbolKind::Macro, r"^\s*#\s*define\s+(?P<name>\w+)"),
+                (SymbolKind::Module, r"^\s*namespace\s+(?P<name>\w+)"),
+                (SymbolKind::Class, r"^\s*(?:template\s*<.*>\s*)?class\s+(?P<name>\w+)\s*(?:final\s*)?(?::|\{|$)"),
+                (SymbolKind::Struct, r"^\s*(?:typedef\s+)?(?:struct|union)\s+(?P<name>\w+)\s*(?:\{|$)"),
+                (SymbolKind::Enum, r"^\s*(?:typedef\s+)?enum\s+(?:class\s+)?(?P<name>\w+)"),
+                (SymbolKind::Type, r"^\s*\}\s*(?P<name>\w+)\s*;"),
+                (SymbolKind::Type, r"^\s*typedef\s+[^;{]*?\b(?P<name>\w+)\s*;"),
+                (SymbolKind::Type, r"^\s*using\s+(?P<name>\w+)\s*="),
+                (SymbolKind::Function, r"^(?:[\w:<>,*&]+[\s*&]+)+(?P<name>[\w:~]+)\s*\([^;]*$"),
+            ])
+        }),
+        "py" => PYTHON.get_or_init(|| {
+            compile(&[
+                (SymbolKind::Function, r"^\s*(?:async\s+)?def\s+(?P<name>\w+)"),
+                (SymbolKind::Class, r"^\s*class\s+(?P<name>\w+)"),
+                (SymbolKind::Constant, r"^(?P<name>[A-Z_][A-Z0-9_]*)\s*(?::[^=]*)?="),
+            ])
+        }),
+        "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" => SCRIPT.get_or_init(|| {
+            const EXPORT: &str = r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?";
+            compile(&[
+                (SymbolKind::Function, &format!(r"{EXPORT}(?:async\s+)?function\s*\*?\s*(?P<name>[\w$]+)")),
+                (SymbolKind::Class, &format!(r"{EXPORT}(?:abstract\s+)?class\s+(?P<name>[\w$]+)")),
+                (SymbolKind::Interface, &format!(r"{EXPORT}interface\s+(?P<name>[\w$]+)")),
+                (SymbolKind::Enum, &format!(r"{EXPORT}(?:const\s+)?enum\s+(?P<name>[\w$]+)")),
+                (SymbolKind::Type, &format!(r"{EXPORT}type\s+(?P<name>[\w$]+)\s*(?:<[^>]*>\s*)?=")),
+                (SymbolKind::Module, &format!(r"{EXPORT}namespace\s+(?P<name>[\w$.]+)")),
+                (
+                    SymbolKind::Function,
+                    &format!(r"{EXPORT}(?:const|let|var)\s+(?P<name>[\w$]+)\s*(?::[^=]*)?=\s*(?:async\s+)?(?:function\b|(?:\([^)]*\)|[\w$]+)\s*(?::[^=]*)?=>)"),
+                ),
+                (SymbolKind::Constant, &format!(r"{EXPORT}const\s+(?P<name>[A-Z_][A-Z0-9_$]*)\s*(?::[^=]*)?=")),
+                (
+                    SymbolKind::Method,
+                    r"^\s+(?:(?:public|private|protected|static|async|readonly|override|get|set)\s+)*(?P<name>[\w$]+)\s*(?:<[^>]*>)?\([^)]*\)\s*(?::\s*[^{;]+)?\{\s*$",
+                ),
+            ])
+        }),
+        "go" => GO.get_or_init(|| {
+            compile(&[
+                (SymbolKind::Method, r"^func\s+\([^)]*\)\s*(?P<name>\w+)"),
+                (SymbolKind::Function, r"^func\s+(?P<name>\w+)"),
+                (SymbolKind::Struct, r"^type\s+(?P<name>\w+)\s+struct\b"),
+                (SymbolKind::Interface, r"^type\s+(?P<name>\w+)\s+interface\b"),
+                (SymbolKind::Type, r"^type\s+(?P<name>\w+)"),
+                (SymbolKind::Constant, r"^const\s+(?P<name>\w+)"),
+                (SymbolKind::Variable, r"^var\s+(?P<name>\w+)"),
+            ])
+        }),
+        "md" | "markdown" => MARKDOWN.get_or_init(|| compile(&[(SymbolKind::Heading, r"^#{1,6}\s+(?P<name>.+?)\s*#*\s*$")])),
+        _ => return None,
+    };
+    Some(patterns)
+}
+
+fn compile(patterns: &[(SymbolKind, &str)]) -> Vec<Pattern> {
+    patterns
+        .iter()
+        .filter_map(|(kind, pattern)| match Regex::new(pattern) {
+            Ok(regex) => Some(Pattern { kind: *kind, regex }),
+            Err(e) => {
+                log::warn!("Invalid symbol pattern {:?}: {}", pattern, e);
+                None
+            }
+        })
+        .collect()
+}
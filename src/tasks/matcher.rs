@@ -0,0 +1,335 @@
+use crate::document::diagnostics::{Diagnostic, Severity};
+use crate::document::position::TextPosition;
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+/// How a task's problem matcher is written in `tasks.json`: the name of a built-in
+/// one, or a regular expression with the groups that hold each part of a problem.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum MatcherConfig {
+    Named(String),
+    Pattern(PatternConfig),
+}
+
+/// A custom matcher. The group numbers are 1-based; without a column group problems
+/// point at the start of the line, and without a severity group they are errors.
+/// Columns are taken to count characters.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PatternConfig {
+    pub regexp: String,
+    #[serde(default = "default_file")]
+    pub file: usize,
+    #[serde(default = "default_line")]
+    pub line: usize,
+    pub column: Option<usize>,
+    pub severity: Option<usize>,
+    #[serde(default = "default_message")]
+    pub message: usize,
+    /// Reported as the source of the problems, such as `eslint`.
+    pub source: Option<String>,
+}
+
+fn default_file() -> usize {
+    1
+}
+
+fn default_line() -> usize {
+    2
+}
+
+fn default_message() -> usize {
+    3
+}
+
+/// What the columns a tool prints count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnUnit {
+    /// Characters, as rustc counts them. gcc counts display columns and clang bytes,
+    /// which are the same for the ASCII lines they mostly point into.
+    Characters,
+    /// UTF-16 code units, as JavaScript tools count them and [`TextPosition`] does.
+    Utf16,
+}
+
+/// A problem found in a line of output, at a path as the tool printed it. Its column
+/// counts `column_unit` until [`Problem::convert_column`] converts it.
+pub struct Problem {
+    pub path: String,
+    pub diagnostic: Diagnostic,
+    pub column_unit: ColumnUnit,
+}
+
+impl Problem {
+    /// Converts a column counted in characters to UTF-16 code units, given the text of
+    /// the line it is on.
+    pub fn convert_column(&mut self, line: &str) {
+        if self.column_unit == ColumnUnit::Utf16 {
+            return;
+        }
+        let column = line.chars().take(self.diagnostic.start.column).map(char::len_utf16).sum();
+        self.diagnostic.start.column = column;
+        self.diagnostic.end.column = column;
+        self.column_unit = ColumnUnit::Utf16;
+    }
+}
+
+/// Picks problems out of a task's output one line at a time.
+pub enum ProblemMatcher {
+    /// `error[E0308]: message` followed by `--> file:line:col`, as cargo and rustc print them.
+    Rustc {
+        /// The last heading seen, waiting for the location under it.
+        heading: Option<(Option<Severity>, String, Option<String>)>,
+    },
+    /// `file:line:col: error: message`, as gcc, clang and many other tools print them.
+    Gcc,
+    /// `file(line,col): error TS2304: message`, or `file:line:col - error ...` when pretty.
+    Tsc,
+    Pattern(Box<Pattern>),
+}
+
+pub struct Pattern {
+    regex: Regex,
+    config: PatternConfig,
+}
+
+impl ProblemMatcher {
+    pub fn new(config: &MatcherConfig) -> Result<Self, String> {
+        match config {
+            MatcherConfig::Named(name) => match name.as_str() {
+                "rustc" | "cargo" => Ok(Self::Rustc { heading: None }),
+                "gcc" | "clang" => Ok(Self::Gcc),
+                "tsc" => Ok(Self::Tsc),
+                _ => Err(format!("unknown problem matcher `{}`", name)),
+            },
+            MatcherConfig::Pattern(config) => {
+                let regex = Regex::new(&config.regexp).map_err(|e| format!("invalid problem matcher pattern: {}", e))?;
+                Ok(Self::Pattern(Box::new(Pattern { regex, config: config.clone() })))
+            }
+        }
+    }
+
+    pub fn feed(&mut self, line: &str) -> Option<Problem> {
+        match self {
+            Self::Rustc { heading } => rustc(heading, line),
+            Self::Gcc => {
+                static GCC: OnceLock<Regex> = OnceLock::new();
+                let regex = GCC.get_or_init(|| {
+                    Regex::new(r"^(.+?):(\d+):(?:(\d+):)? (?:fatal )?(error|warning|note): (.*)$").unwrap()
+                });
+                let captures = regex.captures(line)?;
+                let severity = severity(&captures[4])?;
+                problem(&captures[1], &captures[2], captures.get(3).map(|c| c.as_str()), severity, &captures[5], Some("gcc"), None)
+            }
+            Self::Tsc => {
+                static TSC: OnceLock<Regex> = OnceLock::new();
+                let regex = TSC.get_or_init(|| {
+                    Regex::new(r"^(.+?)(?:\((\d+),(\d+)\):|:(\d+):(\d+) -) (error|warning) (TS\d+): (.*)$").unwrap()
+                });
+                let captures = regex.captures(line)?;
+                let line_number = captures.get(2).or(captures.get(4))?.as_str();
+                let column = captures.get(3).or(captures.get(5)).map(|c| c.as_str());
+                let severity = severity(&captures[6])?;
+                problem(&captures[1], line_number, column, severity, &captures[8], Some("tsc"), Some(&captures[7]))
+                    .map(|problem| Problem { column_unit: ColumnUnit::Utf16, ..problem })
+            }
+            Self::Pattern(pattern) => {
+                let captures = pattern.regex.captures(line)?;
+                let config = &pattern.config;
+                let group = |index: usize| captures.get(index).map(|c| c.as_str());
+                let severity = match config.severity.and_then(group) {
+                    Some(severity) => self::severity(severity)?,
+                    None => Severity::Error,
+                };
+                problem(
+                    group(config.file)?,
+                    group(config.line)?,
+                    config.column.and_then(group),
+                    severity,
+                    group(config.message)?,
+                    config.source.as_deref(),
+                    None,
+                )
+            }
+        }
+    }
+}
+
+fn rustc(heading: &mut Option<(Option<Severity>, String, Option<String>)>, line: &str) -> Option<Problem> {
+    static HEADING: OnceLock<Regex> = OnceLock::new();
+    static LOCATION: OnceLock<Regex> = OnceLock::new();
+    let heading_regex = HEADING.get_or_init(|| Regex::new(r"^(error|warning|note|help)(?:\[(\w+)\])?: (.*)$").unwrap());
+    let location_regex = LOCATION.get_or_init(|| Regex::new(r"^\s*--> (.+?):(\d+):(\d+)$").unwrap());
+
+    if let Some(captures) = heading_regex.captures(line) {
+        // Notes and help are remembered too, so their locations are not taken for the error's.
+        let code = captures.get(2).map(|c| c.as_str().to_string());
+        *heading = Some((severity(&captures[1]).filter(|s| *s != Severity::Info), captures[3].to_string(), code));
+        return None;
+    }
+    let captures = location_regex.captures(line)?;
+    let (severity, message, code) = heading.take()?;
+    problem(&captures[1], &captures[2], Some(&captures[3]), severity?, &message, Some("rustc"), code.as_deref())
+}
+
+fn severity(text: &str) -> Option<Severity> {
+    match text.to_ascii_lowercase().as_str() {
+        "error" | "fatal" => Some(Severity::Error),
+        "warning" | "warn" => Some(Severity::Warning),
+        "note" | "info" | "help" => Some(Severity::Info),
+        _ => None,
+    }
+}
+
+/// A problem from the printed parts, whose line and column count from 1 and whose
+/// column counts characters.
+fn problem(
+    path: &str,
+    line: &str,
+    column: Option<&str>,
+    severity: Severity,
+    message: &str,
+    source: Option<&str>,
+    code: Option<&str>,
+) -> Option<Problem> {
+    let line = line.parse::<usize>().ok()?.saturating_sub(1);
+    let column = column.and_then(|column| column.parse::<usize>().ok()).unwrap_or(1).saturating_sub(1);
+    let position = TextPosition::new(line, column);
+    Some(Problem {
+        path: path.trim().to_string(),
+        diagnostic: Diagnostic {
+            severity,
+            start: position,
+            end: position,
+            message: message.trim().to_string(),
+            source: source.map(str::to_string),
+            code: code.map(str::to_string),
+        },
+        column_unit: ColumnUnit::Characters,
+    })
+}
+
+/// The matchers a task's output is run through, one result per line at most.
+pub fn compile(configs: &[MatcherConfig]) -> Result<Vec<ProblemMatcher>, String> {
+    configs.iter().map(ProblemMatcher::new).collect()
+}
+
+/// Feeds a line to every matcher, since the stateful ones need to see all of them, and
+/// keeps the first problem found.
+pub fn feed(matchers: &mut [ProblemMatcher], line: &str) -> Option<Problem> {
+    matchers.iter_mut().map(|matcher| matcher.feed(line)).fold(None, |found, problem| found.or(problem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, output: &str) -> Vec<Problem> {
+        let mut matcher = ProblemMatcher::new(&MatcherConfig::Named(name.to_string())).unwrap();
+        output.lines().filter_map(|line| matcher.feed(line)).collect()
+    }
+
+    fn summary(problem: &Problem) -> (&str, usize, usize, Severity, &str) {
+        let diagnostic = &problem.diagnostic;
+        (&problem.path, diagnostic.start.line, diagnostic.start.column, diagnostic.severity, &diagnostic.message)
+    }
+
+    #[test]
+    fn rustc_takes_the_location_under_each_heading() {
+        let output = "\
+error[E0308]: mismatched types
+  --> src/main.rs:4:18
+   |
+4  |     let x: i32 = \"five\";
+   |            ---   ^^^^^^ expected `i32`, found `&str`
+   |            |
+   |            expected due to this
+note: the function is defined here
+  --> src/lib.rs:10:4
+help: consider using `parse`
+  --> src/main.rs:4:24
+warning: unused variable: `y`
+ --> src/util.rs:2:9
+  |
+  = note: `#[warn(unused_variables)]` on by default
+error: aborting due to 1 previous error
+";
+        let problems = run("rustc", output);
+        assert_eq!(problems.len(), 2);
+        assert_eq!(summary(&problems[0]), ("src/main.rs", 3, 17, Severity::Error, "mismatched types"));
+        assert_eq!(problems[0].diagnostic.code.as_deref(), Some("E0308"));
+        assert_eq!(summary(&problems[1]), ("src/util.rs", 1, 8, Severity::Warning, "unused variable: `y`"));
+        assert_eq!(problems[1].diagnostic.code, None);
+    }
+
+    #[test]
+    fn gcc_with_and_without_a_column() {
+        let output = "\
+main.c: In function 'main':
+main.c:5:12: error: 'y' undeclared (first use in this function)
+main.c:9: warning: implicit declaration of function 'foo'
+In file included from main.c:1:
+include/util.h:3:1: fatal error: missing.h: No such file or directory
+main.c:5:12: note: each undeclared identifier is reported only once
+";
+        let problems = run("gcc", output);
+        let summaries: Vec<_> = problems.iter().map(summary).collect();
+        assert_eq!(
+            summaries,
+            [
+                ("main.c", 4, 11, Severity::Error, "'y' undeclared (first use in this function)"),
+                ("main.c", 8, 0, Severity::Warning, "implicit declaration of function 'foo'"),
+                ("include/util.h", 2, 0, Severity::Error, "missing.h: No such file or directory"),
+                ("main.c", 4, 11, Severity::Info, "each undeclared identifier is reported only once"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tsc_plain_and_pretty() {
+        let output = "\
+src/app.ts(12,5): error TS2304: Cannot find name 'foo'.
+src/app.ts:3:1 - warning TS6133: 'x' is declared but its value is never read.
+Found 2 errors.
+";
+        let problems = run("tsc", output);
+        assert_eq!(summary(&problems[0]), ("src/app.ts", 11, 4, Severity::Error, "Cannot find name 'foo'."));
+        assert_eq!(problems[0].diagnostic.code.as_deref(), Some("TS2304"));
+        assert_eq!(problems[0].column_unit, ColumnUnit::Utf16);
+        assert_eq!(summary(&problems[1]).3, Severity::Warning);
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    fn custom_patterns_use_their_groups() {
+        let config: MatcherConfig = serde_json::from_str(
+            r#"{ "regexp": "^(\\w+) (.+):(\\d+):(\\d+) (.*)$", "severity": 1, "file": 2, "line": 3, "column": 4, "message": 5, "source": "lint" }"#,
+        )
+        .unwrap();
+        let mut matcher = ProblemMatcher::new(&config).unwrap();
+        let problem = matcher.feed("warn lib/a.js:7:3 no-unused-vars").unwrap();
+        assert_eq!(summary(&problem), ("lib/a.js", 6, 2, Severity::Warning, "no-unused-vars"));
+        assert_eq!(problem.diagnostic.source.as_deref(), Some("lint"));
+        // A severity the matcher does not know drops the line.
+        assert!(matcher.feed("bogus lib/a.js:7:3 oops").is_none());
+
+        let defaults: MatcherConfig = serde_json::from_str(r#"{ "regexp": "^(.+):(\\d+) (.*)$" }"#).unwrap();
+        let problem = ProblemMatcher::new(&defaults).unwrap().feed("x.py:2 bad").unwrap();
+        assert_eq!(summary(&problem), ("x.py", 1, 0, Severity::Error, "bad"));
+
+        let invalid: MatcherConfig = serde_json::from_str(r#"{ "regexp": "(" }"#).unwrap();
+        assert!(ProblemMatcher::new(&invalid).is_err());
+        assert!(ProblemMatcher::new(&MatcherConfig::Named("nope".to_string())).is_err());
+    }
+
+    #[test]
+    fn character_columns_are_converted_to_utf16() {
+        let mut problem = run("rustc", "error: bad\n --> src/main.rs:1:8\n").remove(0);
+        assert_eq!(problem.column_unit, ColumnUnit::Characters);
+        problem.convert_column("let 🌍 = x;");
+        assert_eq!(problem.diagnostic.start.column, 8);
+        assert_eq!(problem.diagnostic.end.column, 8);
+        assert_eq!(problem.column_unit, ColumnUnit::Utf16);
+    }
+}
@@ -0,0 +1,331 @@
+pub mod matcher;
+
+use crate::document::diagnostics::Diagnostic;
+use crate::document::position::Location;
+use crate::workspace::normalize;
+use matcher::{ColumnUnit, MatcherConfig, ProblemMatcher};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, OnceLock};
+
+/// Output lines kept per run; older ones are dropped first.
+const MAX_LINES: usize = 20_000;
+
+/// A named command from a `tasks.json`, such as a build or a test run.
+///
+/// In `command`, `cwd` and `env`, `${workspace}` stands for the project root and
+/// `${file}` for the open file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TaskConfig {
+    pub label: String,
+    /// Run by the shell, so it can have arguments, pipes and `&&`.
+    pub command: String,
+    /// Where it runs, relative to the project root; the root itself by default.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Built-in matchers by name (`rustc`, `gcc`, `tsc`) or custom patterns that turn
+    /// lines of output into problems.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub problem_matcher: Vec<MatcherConfig>,
+}
+
+#[derive(Deserialize)]
+struct TasksFile {
+    #[serde(default)]
+    tasks: Vec<TaskConfig>,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<MatcherConfig>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(MatcherConfig),
+        Many(Vec<MatcherConfig>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(config) => vec![config],
+        OneOrMany::Many(configs) => configs,
+    })
+}
+
+/// A task with the project it was defined in.
+#[derive(Debug, Clone)]
+pub struct Task {
+    pub config: TaskConfig,
+    /// The root whose `.zen/tasks.json` defined it, or `None` for a task from the
+    /// `tasks.json` next to the editor's config, which runs in the first project root.
+    pub root: Option<PathBuf>,
+}
+
+/// The tasks of every project root, then the ones defined next to the editor's
+/// config, with what was wrong with any file that could not be read.
+pub fn load(roots: &[PathBuf]) -> (Vec<Task>, Vec<String>) {
+    let mut tasks = Vec::new();
+    let mut errors = Vec::new();
+    let files = roots
+        .iter()
+        .map(|root| (root.join(".zen").join("tasks.json"), Some(root.clone())))
+        .chain(std::env::current_dir().ok().map(|dir| (dir.join("tasks.json"), None)));
+    for (path, root) in files {
+        if !path.is_file() {
+            continue;
+        }
+        let file = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str::<TasksFile>(&content).map_err(|e| e.to_string()));
+        match file {
+            Ok(file) => tasks.extend(file.tasks.into_iter().map(|config| Task { config, root: root.clone() })),
+            Err(e) => errors.push(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+    (tasks, errors)
+}
+
+/// A line of a task's output, with where it points when a problem matcher found one.
+pub struct OutputLine {
+    pub text: String,
+    pub problem: Option<(Location, Diagnostic)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    Running,
+    /// The exit code, or `None` when a signal ended it.
+    Exited(Option<i32>),
+    Stopped,
+}
+
+/// A task that was started, with its output so far.
+pub struct TaskRun {
+    pub task: Task,
+    /// Where it runs; relative paths in its output are resolved against it.
+    pub dir: PathBuf,
+    root: PathBuf,
+    pub lines: VecDeque<OutputLine>,
+    pub status: TaskStatus,
+    /// Problems found so far by file, to report to the editor's diagnostics.
+    pub problems: BTreeMap<PathBuf, Vec<Diagnostic>>,
+    matchers: Vec<ProblemMatcher>,
+    child: Child,
+    receiver: mpsc::Receiver<String>,
+    output_ended: bool,
+    stopped: bool,
+}
+
+impl TaskRun {
+    /// Starts a task in the shell. `root` stands in for the project root of tasks that
+    /// were not defined in one, and `file` is the open file. Output is read on
+    /// background threads that wake `ctx` whenever there is more.
+    pub fn start(task: Task, root: Option<&Path>, file: Option<&Path>, ctx: &egui::Context) -> Result<Self, String> {
+        let root = task
+            .root
+            .clone()
+            .or_else(|| root.map(Path::to_path_buf))
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        let matchers = matcher::compile(&task.config.problem_matcher).map_err(|e| format!("{}: {}", task.config.label, e))?;
+        let expand = |text: &str| {
+            let file = file.map(|file| file.to_string_lossy().into_owned()).unwrap_or_default();
+            text.replace("${workspace}", &root.to_string_lossy()).replace("${file}", &file)
+        };
+        let dir = match &task.config.cwd {
+            Some(cwd) => normalize(&root.join(expand(cwd))),
+            None => root.clone(),
+        };
+
+        let mut command = shell(&expand(&task.config.command));
+        command
+            .current_dir(&dir)
+            .envs(task.config.env.iter().map(|(name, value)| (name, expand(value))))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Its own process group, so stopping it also stops what the shell started.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", task.config.label, e))?;
+
+        let (sender, receiver) = mpsc::channel();
+        read_lines(child.stdout.take(), sender.clone(), ctx);
+        read_lines(child.stderr.take(), sender, ctx);
+
+        Ok(Self {
+            task,
+            dir,
+            root,
+            lines: VecDeque::new(),
+            status: TaskStatus::Running,
+            problems: BTreeMap::new(),
+            matchers,
+            child,
+            receiver,
+            output_ended: false,
+            stopped: false,
+        })
+    }
+
+    pub fn label(&self) -> &str {
+        &self.task.config.label
+    }
+
+    /// Takes in the output printed since the last call and returns the files whose
+    /// problems changed.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        // The files problems point into, read once per call to convert their columns.
+        let mut files: HashMap<PathBuf, Option<String>> = HashMap::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(text) => {
+                    let problem = matcher::feed(&mut self.matchers, &text).map(|mut problem| {
+                        let path = self.resolve(&problem.path);
+                        if problem.column_unit != ColumnUnit::Utf16 && problem.diagnostic.start.column > 0 {
+                            let contents = files.entry(path.clone()).or_insert_with(|| std::fs::read_to_string(&path).ok());
+                            if let Some(line) = contents.as_deref().and_then(|text| text.lines().nth(problem.diagnostic.start.line)) {
+                                problem.convert_column(line);
+                            }
+                        }
+                        let location = Location::new(path.clone(), problem.diagnostic.start);
+                        self.problems.entry(path.clone()).or_default().push(problem.diagnostic.clone());
+                        if !changed.contains(&path) {
+                            changed.push(path);
+                        }
+                        (location, problem.diagnostic)
+                    });
+                    if self.lines.len() == MAX_LINES {
+                        self.lines.pop_front();
+                    }
+                    self.lines.push_back(OutputLine { text, problem });
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.output_ended = true;
+                    break;
+                }
+            }
+        }
+        if self.output_ended && self.status == TaskStatus::Running {
+            match self.child.try_wait() {
+                Ok(Some(_)) if self.stopped => self.status = TaskStatus::Stopped,
+                Ok(Some(status)) => self.status = TaskStatus::Exited(status.code()),
+                Ok(None) => {}
+                Err(_) => self.status = TaskStatus::Exited(None),
+            }
+        }
+        changed
+    }
+
+    /// Whether the output has ended but the task has not been seen to exit yet, so it
+    /// is worth polling again shortly.
+    pub fn is_exiting(&self) -> bool {
+        self.output_ended && self.status == TaskStatus::Running
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status == TaskStatus::Running
+    }
+
+    /// Stops the task and everything it started.
+    pub fn stop(&mut self) {
+        if !self.is_running() {
+            return;
+        }
+        self.stopped = true;
+        #[cfg(unix)]
+        if let Ok(pid) = i32::try_from(self.child.id()) {
+            // SAFETY: `kill` only sends a signal; the negative pid names the task's own group.
+            unsafe {
+                libc::kill(-pid, libc::SIGTERM);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = self.child.kill();
+    }
+
+    /// The path a tool printed, made absolute. Relative paths are tried against where
+    /// the task ran and then against the project root, since cargo prints them relative
+    /// to the workspace even when run in a member.
+    fn resolve(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            return normalize(path);
+        }
+        let in_dir = normalize(&self.dir.join(path));
+        if in_dir.exists() {
+            return in_dir;
+        }
+        let in_root = normalize(&self.root.join(path));
+        if in_root.exists() {
+            in_root
+        } else {
+            in_dir
+        }
+    }
+}
+
+impl Drop for TaskRun {
+    fn drop(&mut self) {
+        if self.is_running() {
+            self.stop();
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell(command_line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+#[cfg(windows)]
+fn shell(command_line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(command_line);
+    command
+}
+
+/// Sends each line of a pipe without its line ending or color codes.
+fn read_lines(pipe: Option<impl Read + Send + 'static>, sender: mpsc::Sender<String>, ctx: &egui::Context) {
+    let Some(pipe) = pipe else {
+        return;
+    };
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut bytes = Vec::new();
+        loop {
+            bytes.clear();
+            match reader.read_until(b'\n', &mut bytes) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&bytes);
+                    let line = line.trim_end_matches(['\n', '\r']);
+                    // A progress line redrawn in place shows as what it ended up saying.
+                    let line = line.rsplit('\r').next().unwrap_or_default();
+                    if sender.send(strip_escapes(line)).is_err() {
+                        return;
+                    }
+                    ctx.request_repaint();
+                }
+            }
+        }
+        ctx.request_repaint();
+    });
+}
+
+fn strip_escapes(line: &str) -> String {
+    static ESCAPE: OnceLock<Regex> = OnceLock::new();
+    let regex = ESCAPE.get_or_init(|| Regex::new(r"\x1b(?:\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(?:\x07|\x1b\\)?|[@-Z\\-_])").unwrap());
+    regex.replace_all(line, "").into_owned()
+}
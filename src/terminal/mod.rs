@@ -0,0 +1,175 @@
+pub mod screen;
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use screen::Screen;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::mpsc;
+
+/// How much output one [`Terminal::poll`] parses, so that a program printing without
+/// pause, like `yes`, cannot keep a frame from ending.
+const MAX_BYTES_PER_POLL: usize = 256 * 1024;
+
+/// A shell running on a pseudo-terminal, with the screen its output draws.
+pub struct Terminal {
+    pub screen: Screen,
+    parser: vte::Parser,
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    receiver: mpsc::Receiver<Vec<u8>>,
+    shell: String,
+    /// Set once the terminal's output has ended, which is when the shell is exiting.
+    output_ended: bool,
+    /// The shell's exit code once it has ended.
+    pub exit_code: Option<u32>,
+}
+
+impl Terminal {
+    /// Starts the user's shell in `dir`. Output is read on a background thread that
+    /// wakes `ctx` whenever there is more.
+    pub fn spawn(dir: &Path, rows: usize, columns: usize, ctx: &egui::Context) -> Result<Self, String> {
+        let pair = native_pty_system()
+            .openpty(pty_size(rows, columns))
+            .map_err(|e| format!("Failed to open a terminal: {}", e))?;
+        let mut command = CommandBuilder::new_default_prog();
+        command.cwd(dir);
+        command.env("TERM", "xterm-256color");
+        command.env("COLORTERM", "truecolor");
+        let shell = command.get_shell();
+        let child = pair
+            .slave
+            .spawn_command(command)
+            .map_err(|e| format!("Failed to start {}: {}", shell, e))?;
+        // Only the shell holds the terminal's other end, so reads end when it exits.
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+        let writer = pair.master.take_writer().map_err(|e| e.to_string())?;
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let mut buffer = [0; 8192];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(count) => {
+                        if sender.send(buffer[..count].to_vec()).is_err() {
+                            return;
+                        }
+                        ctx.request_repaint();
+                    }
+                }
+            }
+            drop(sender);
+            ctx.request_repaint();
+        });
+
+        let shell = Path::new(&shell).file_name().map_or(shell.clone(), |name| name.to_string_lossy().into_owned());
+        Ok(Self {
+            screen: Screen::new(rows, columns),
+            parser: vte::Parser::new(),
+            master: pair.master,
+            writer,
+            child,
+            receiver,
+            shell,
+            output_ended: false,
+            exit_code: None,
+        })
+    }
+
+    /// Draws what the shell printed since the last call and answers its queries. Stops
+    /// after [`MAX_BYTES_PER_POLL`] and returns whether output is left for the next call.
+    pub fn poll(&mut self) -> bool {
+        let mut parsed = 0;
+        let more = loop {
+            if parsed >= MAX_BYTES_PER_POLL {
+                break true;
+            }
+            match self.receiver.try_recv() {
+                Ok(bytes) => {
+                    parsed += bytes.len();
+                    self.parser.advance(&mut self.screen, &bytes);
+                }
+                Err(mpsc::TryRecvError::Empty) => break false,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.output_ended = true;
+                    break false;
+                }
+            }
+        };
+        if self.output_ended && self.exit_code.is_none() {
+            self.exit_code = match self.child.try_wait() {
+                Ok(status) => status.map(|status| status.exit_code()),
+                Err(_) => Some(1),
+            };
+        }
+        let responses = self.screen.take_responses();
+        if !responses.is_empty() {
+            self.write(&responses);
+        }
+        more
+    }
+
+    /// Whether the output has ended but the shell has not been seen to exit yet, so it
+    /// is worth polling again shortly.
+    pub fn is_exiting(&self) -> bool {
+        self.output_ended && self.exit_code.is_none()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        if self.exit_code.is_some() {
+            return;
+        }
+        if let Err(e) = self.writer.write_all(bytes).and_then(|_| self.writer.flush()) {
+            log::warn!("Failed to write to the terminal: {}", e);
+        }
+    }
+
+    /// Types `text` into the shell, marked as a paste when the program asked for that.
+    pub fn paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\r").replace('\n', "\r");
+        if self.screen.bracketed_paste {
+            // A pasted end marker would let the rest of the text run as typed keys.
+            let text = text.replace("\x1b[201~", "");
+            self.write(format!("\x1b[200~{}\x1b[201~", text).as_bytes());
+        } else {
+            self.write(text.as_bytes());
+        }
+    }
+
+    /// Resizes the screen and tells the shell, which passes it on to what it runs.
+    pub fn resize(&mut self, rows: usize, columns: usize) {
+        if self.screen.size() == (rows.max(1), columns.max(1)) {
+            return;
+        }
+        self.screen.resize(rows, columns);
+        if let Err(e) = self.master.resize(pty_size(rows, columns)) {
+            log::warn!("Failed to resize the terminal: {}", e);
+        }
+    }
+
+    /// The title the program set, or else the shell's name.
+    pub fn title(&self) -> &str {
+        self.screen.title.as_deref().filter(|title| !title.is_empty()).unwrap_or(&self.shell)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // Hangs up on the shell, and kills it when it does not exit on that.
+        if self.exit_code.is_none() && self.child.kill().is_ok() {
+            let _ = self.child.wait();
+        }
+    }
+}
+
+fn pty_size(rows: usize, columns: usize) -> PtySize {
+    PtySize {
+        rows: rows.clamp(1, u16::MAX as usize) as u16,
+        cols: columns.clamp(1, u16::MAX as usize) as u16,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
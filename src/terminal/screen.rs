@@ -0,0 +1,637 @@
+use std::collections::VecDeque;
+use vte::{Params, Perform};
+
+/// Lines kept once they scroll off the top of the screen.
+const SCROLLBACK: usize = 10_000;
+const TAB_WIDTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Color {
+    /// The terminal's own text or background color.
+    #[default]
+    Default,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// How a character is drawn, as set by SGR sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Style {
+    pub foreground: Color,
+    pub background: Color,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub c: char,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { c: ' ', style: Style::default() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Cursor {
+    row: usize,
+    column: usize,
+    style: Style,
+}
+
+/// The character grid of a terminal and the lines that scrolled off it, kept up to date
+/// by feeding a `vte::Parser` the program's output with the screen as its performer.
+/// Understands the VT100 and xterm sequences shells and full-screen programs rely on.
+/// Every character takes one cell; wide and combining characters are not measured.
+pub struct Screen {
+    rows: usize,
+    columns: usize,
+    /// Lines that scrolled off the top, oldest first.
+    scrollback: VecDeque<Vec<Cell>>,
+    grid: Vec<Vec<Cell>>,
+    /// The main screen while a full-screen program has the alternate one.
+    main_grid: Option<Vec<Vec<Cell>>>,
+    cursor: Cursor,
+    saved_cursor: Cursor,
+    /// Set after a character lands in the last column, so that the next one wraps.
+    wrap_pending: bool,
+    /// The rows line feeds scroll, as set by DECSTBM.
+    scroll_region: std::ops::Range<usize>,
+    autowrap: bool,
+    pub cursor_visible: bool,
+    /// Arrow keys send `ESC O` sequences instead of `ESC [`.
+    pub application_cursor: bool,
+    /// Pasted text is wrapped in markers so the program can tell it was not typed.
+    pub bracketed_paste: bool,
+    pub title: Option<String>,
+    /// Answers to queries from the program, to be written back to it.
+    responses: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(rows: usize, columns: usize) -> Self {
+        let rows = rows.max(1);
+        let columns = columns.max(1);
+        Self {
+            rows,
+            columns,
+            scrollback: VecDeque::new(),
+            grid: vec![vec![Cell::default(); columns]; rows],
+            main_grid: None,
+            cursor: Cursor::default(),
+            saved_cursor: Cursor::default(),
+            wrap_pending: false,
+            scroll_region: 0..rows,
+            autowrap: true,
+            cursor_visible: true,
+            application_cursor: false,
+            bracketed_paste: false,
+            title: None,
+            responses: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.rows, self.columns)
+    }
+
+    /// Changes the size of the grid. Lines are cut or padded rather than rewrapped; when
+    /// the screen gets shorter, lines leave from the bottom unless the cursor is on them,
+    /// and then from the top into the scrollback.
+    pub fn resize(&mut self, rows: usize, columns: usize) {
+        let rows = rows.max(1);
+        let columns = columns.max(1);
+        if (rows, columns) == (self.rows, self.columns) {
+            return;
+        }
+
+        for line in self.grid.iter_mut().chain(self.main_grid.iter_mut().flatten()) {
+            line.resize(columns, Cell::default());
+        }
+        while self.grid.len() > rows {
+            if self.cursor.row + 1 < self.grid.len() {
+                self.grid.pop();
+            } else {
+                let line = self.grid.remove(0);
+                if self.main_grid.is_none() {
+                    self.push_scrollback(line);
+                }
+                self.cursor.row -= 1;
+            }
+        }
+        self.grid.resize(rows, vec![Cell::default(); columns]);
+        if let Some(grid) = &mut self.main_grid {
+            grid.resize(rows, vec![Cell::default(); columns]);
+        }
+
+        self.rows = rows;
+        self.columns = columns;
+        self.scroll_region = 0..rows;
+        self.cursor.row = self.cursor.row.min(rows - 1);
+        self.cursor.column = self.cursor.column.min(columns - 1);
+        self.saved_cursor.row = self.saved_cursor.row.min(rows - 1);
+        self.saved_cursor.column = self.saved_cursor.column.min(columns - 1);
+        self.wrap_pending = false;
+    }
+
+    /// The scrollback lines followed by the screen's.
+    pub fn line_count(&self) -> usize {
+        self.scrollback.len() + self.rows
+    }
+
+    /// A line by its index among [`Self::line_count`]. Scrollback lines keep the width
+    /// the screen had when they scrolled off.
+    pub fn line(&self, index: usize) -> &[Cell] {
+        match index.checked_sub(self.scrollback.len()) {
+            None => &self.scrollback[index],
+            Some(row) => self.grid.get(row).map_or(&[], Vec::as_slice),
+        }
+    }
+
+    /// The line and column of the cursor, in the numbering of [`Self::line`].
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.scrollback.len() + self.cursor.row, self.cursor.column)
+    }
+
+    /// The text from one line and column up to another, without the spaces that pad
+    /// the end of each line.
+    pub fn text(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let mut lines = Vec::new();
+        for index in start.0..=end.0.min(self.line_count().saturating_sub(1)) {
+            let line = self.line(index);
+            let from = if index == start.0 { start.1.min(line.len()) } else { 0 };
+            let to = if index == end.0 { end.1.min(line.len()) } else { line.len() };
+            let text: String = line[from..to.max(from)].iter().map(|cell| cell.c).collect();
+            lines.push(text.trim_end().to_string());
+        }
+        lines.join("\n")
+    }
+
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    fn blank_line(&self) -> Vec<Cell> {
+        // Erased cells keep the current background, as terminals do.
+        let style = Style { background: self.cursor.style.background, ..Style::default() };
+        vec![Cell { c: ' ', style }; self.columns]
+    }
+
+    fn push_scrollback(&mut self, line: Vec<Cell>) {
+        if self.scrollback.len() == SCROLLBACK {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(line);
+    }
+
+    fn put(&mut self, c: char) {
+        if self.wrap_pending && self.autowrap {
+            self.cursor.column = 0;
+            self.line_feed();
+        }
+        self.wrap_pending = false;
+        let cell = Cell { c, style: self.cursor.style };
+        self.grid[self.cursor.row][self.cursor.column] = cell;
+        if self.cursor.column + 1 < self.columns {
+            self.cursor.column += 1;
+        } else {
+            self.wrap_pending = true;
+        }
+    }
+
+    fn line_feed(&mut self) {
+        if self.cursor.row + 1 == self.scroll_region.end {
+            self.scroll_up(1);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        if self.cursor.row == self.scroll_region.start {
+            self.scroll_down(1);
+        } else {
+            self.cursor.row = self.cursor.row.saturating_sub(1);
+        }
+    }
+
+    /// Moves the lines of the scroll region up, into the scrollback when the region
+    /// starts at the top of the main screen.
+    fn scroll_up(&mut self, count: usize) {
+        let region = self.scroll_region.clone();
+        for _ in 0..count.min(region.len()) {
+            let line = self.grid.remove(region.start);
+            if region.start == 0 && self.main_grid.is_none() {
+                self.push_scrollback(line);
+            }
+            self.grid.insert(region.end - 1, self.blank_line());
+        }
+    }
+
+    fn scroll_down(&mut self, count: usize) {
+        let region = self.scroll_region.clone();
+        for _ in 0..count.min(region.len()) {
+            self.grid.remove(region.end - 1);
+            self.grid.insert(region.start, self.blank_line());
+        }
+    }
+
+    /// Inserts or deletes lines at the cursor, moving the rest of the scroll region.
+    fn shift_lines(&mut self, count: usize, insert: bool) {
+        if !self.scroll_region.contains(&self.cursor.row) {
+            return;
+        }
+        let region = self.cursor.row..self.scroll_region.end;
+        for _ in 0..count.min(region.len()) {
+            if insert {
+                self.grid.remove(region.end - 1);
+                self.grid.insert(region.start, self.blank_line());
+            } else {
+                self.grid.remove(region.start);
+                self.grid.insert(region.end - 1, self.blank_line());
+            }
+        }
+        self.cursor.column = 0;
+    }
+
+    fn erase(&mut self, row: usize, columns: std::ops::Range<usize>) {
+        let blank = self.blank_line()[0];
+        let line = &mut self.grid[row];
+        let end = columns.end.min(line.len());
+        for cell in &mut line[columns.start.min(end)..end] {
+            *cell = blank;
+        }
+    }
+
+    fn erase_display(&mut self, mode: u16) {
+        let (row, column) = (self.cursor.row, self.cursor.column);
+        match mode {
+            0 => {
+                self.erase(row, column..self.columns);
+                for row in row + 1..self.rows {
+                    self.erase(row, 0..self.columns);
+                }
+            }
+            1 => {
+                for row in 0..row {
+                    self.erase(row, 0..self.columns);
+                }
+                self.erase(row, 0..column + 1);
+            }
+            2 | 3 => {
+                for row in 0..self.rows {
+                    self.erase(row, 0..self.columns);
+                }
+                if mode == 3 {
+                    self.scrollback.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn move_to(&mut self, row: usize, column: usize) {
+        self.cursor.row = row.min(self.rows - 1);
+        self.cursor.column = column.min(self.columns - 1);
+        self.wrap_pending = false;
+    }
+
+    fn set_mode(&mut self, mode: u16, on: bool) {
+        match mode {
+            1 => self.application_cursor = on,
+            7 => self.autowrap = on,
+            25 => self.cursor_visible = on,
+            47 | 1047 | 1049 => {
+                if on == self.main_grid.is_some() {
+                    return;
+                }
+                if on {
+                    if mode == 1049 {
+                        self.saved_cursor = self.cursor;
+                    }
+                    let blank = vec![vec![Cell::default(); self.columns]; self.rows];
+                    self.main_grid = Some(std::mem::replace(&mut self.grid, blank));
+                } else {
+                    if let Some(grid) = self.main_grid.take() {
+                        self.grid = grid;
+                    }
+                    if mode == 1049 {
+                        self.cursor = self.saved_cursor;
+                    }
+                }
+                self.scroll_region = 0..self.rows;
+                self.wrap_pending = false;
+            }
+            2004 => self.bracketed_paste = on,
+            _ => log::trace!("Ignoring terminal mode {} ({})", mode, on),
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params: &Params) {
+        let style = &mut self.cursor.style;
+        if params.is_empty() {
+            *style = Style::default();
+            return;
+        }
+        let mut params = params.iter();
+        while let Some(param) = params.next() {
+            match param[0] {
+                0 => *style = Style::default(),
+                1 => style.bold = true,
+                2 => style.dim = true,
+                3 => style.italic = true,
+                4 => style.underline = true,
+                7 => style.inverse = true,
+                22 => {
+                    style.bold = false;
+                    style.dim = false;
+                }
+                23 => style.italic = false,
+                24 => style.underline = false,
+                27 => style.inverse = false,
+                code @ 30..=37 => style.foreground = Color::Indexed(code as u8 - 30),
+                38 => style.foreground = extended_color(param, &mut params).unwrap_or_default(),
+                39 => style.foreground = Color::Default,
+                code @ 40..=47 => style.background = Color::Indexed(code as u8 - 40),
+                48 => style.background = extended_color(param, &mut params).unwrap_or_default(),
+                49 => style.background = Color::Default,
+                code @ 90..=97 => style.foreground = Color::Indexed(code as u8 - 90 + 8),
+                code @ 100..=107 => style.background = Color::Indexed(code as u8 - 100 + 8),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The color of a `38` or `48` parameter, given either as its own subparameters
+/// (`38:5:n`, `38:2::r:g:b`) or as the parameters that follow (`38;5;n`, `38;2;r;g;b`).
+fn extended_color<'a>(param: &[u16], rest: &mut impl Iterator<Item = &'a [u16]>) -> Option<Color> {
+    if param.len() > 1 {
+        return match param[1] {
+            5 => Some(Color::Indexed(*param.get(2)? as u8)),
+            2 if param.len() >= 5 => {
+                let [r, g, b] = [param[param.len() - 3], param[param.len() - 2], param[param.len() - 1]];
+                Some(Color::Rgb(r as u8, g as u8, b as u8))
+            }
+            _ => None,
+        };
+    }
+    match rest.next()?[0] {
+        5 => Some(Color::Indexed(rest.next()?[0] as u8)),
+        2 => {
+            let r = rest.next()?[0];
+            let g = rest.next()?[0];
+            let b = rest.next()?[0];
+            Some(Color::Rgb(r as u8, g as u8, b as u8))
+        }
+        _ => None,
+    }
+}
+
+impl Perform for Screen {
+    fn print(&mut self, c: char) {
+        self.put(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            // Backspace
+            0x08 => {
+                self.cursor.column = self.cursor.column.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            b'\t' => {
+                let next = (self.cursor.column / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.cursor.column = next.min(self.columns - 1);
+            }
+            b'\n' | 0x0b | 0x0c => {
+                self.line_feed();
+                self.wrap_pending = false;
+            }
+            b'\r' => {
+                self.cursor.column = 0;
+                self.wrap_pending = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        let values: Vec<u16> = params.iter().map(|param| param[0]).collect();
+        // The parameter at `index`, where missing and zero both mean `default`.
+        let arg = |index: usize, default: usize| match values.get(index) {
+            Some(&value) if value > 0 => value as usize,
+            _ => default,
+        };
+        let (row, column) = (self.cursor.row, self.cursor.column);
+
+        match (intermediates, action) {
+            ([b'?'], 'h') | ([b'?'], 'l') => {
+                for &mode in &values {
+                    self.set_mode(mode, action == 'h');
+                }
+            }
+            ([], 'A') => self.move_to(row.saturating_sub(arg(0, 1)), column),
+            ([], 'B') | ([], 'e') => self.move_to(row + arg(0, 1), column),
+            ([], 'C') | ([], 'a') => self.move_to(row, column + arg(0, 1)),
+            ([], 'D') => self.move_to(row, column.saturating_sub(arg(0, 1))),
+            ([], 'E') => self.move_to(row + arg(0, 1), 0),
+            ([], 'F') => self.move_to(row.saturating_sub(arg(0, 1)), 0),
+            ([], 'G') | ([], '`') => self.move_to(row, arg(0, 1) - 1),
+            ([], 'H') | ([], 'f') => self.move_to(arg(0, 1) - 1, arg(1, 1) - 1),
+            ([], 'd') => self.move_to(arg(0, 1) - 1, column),
+            ([], 'J') => self.erase_display(values.first().copied().unwrap_or(0)),
+            ([], 'K') => match values.first().copied().unwrap_or(0) {
+                0 => self.erase(row, column..self.columns),
+                1 => self.erase(row, 0..column + 1),
+                2 => self.erase(row, 0..self.columns),
+                _ => {}
+            },
+            ([], 'L') => self.shift_lines(arg(0, 1), true),
+            ([], 'M') => self.shift_lines(arg(0, 1), false),
+            ([], '@') => {
+                let line = &mut self.grid[row];
+                for _ in 0..arg(0, 1).min(self.columns - column) {
+                    line.insert(column, Cell::default());
+                }
+                line.truncate(self.columns);
+            }
+            ([], 'P') => {
+                let line = &mut self.grid[row];
+                let count = arg(0, 1).min(self.columns - column);
+                line.drain(column..column + count);
+                line.resize(self.columns, Cell::default());
+            }
+            ([], 'X') => self.erase(row, column..column + arg(0, 1)),
+            ([], 'S') => self.scroll_up(arg(0, 1)),
+            ([], 'T') => self.scroll_down(arg(0, 1)),
+            ([], 'r') => {
+                let top = arg(0, 1) - 1;
+                let bottom = arg(1, self.rows).min(self.rows);
+                if top < bottom {
+                    self.scroll_region = top..bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            ([], 'm') => self.select_graphic_rendition(params),
+            ([], 's') => self.saved_cursor = self.cursor,
+            ([], 'u') => {
+                self.cursor = self.saved_cursor;
+                self.wrap_pending = false;
+            }
+            ([], 'n') => match values.first() {
+                Some(5) => self.responses.extend_from_slice(b"\x1b[0n"),
+                Some(6) => self.responses.extend(format!("\x1b[{};{}R", row + 1, column + 1).bytes()),
+                _ => {}
+            },
+            // Device attributes: a VT102, and for the secondary query a version of zero.
+            ([], 'c') => self.responses.extend_from_slice(b"\x1b[?6c"),
+            ([b'>'], 'c') => self.responses.extend_from_slice(b"\x1b[>0;0;0c"),
+            _ => log::trace!("Ignoring CSI {:?} {:?} {}", values, intermediates, action),
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        if !intermediates.is_empty() {
+            // Character set designations; everything is drawn as Unicode.
+            return;
+        }
+        match byte {
+            b'7' => self.saved_cursor = self.cursor,
+            b'8' => {
+                self.cursor = self.saved_cursor;
+                self.wrap_pending = false;
+            }
+            b'D' => self.line_feed(),
+            b'E' => {
+                self.cursor.column = 0;
+                self.line_feed();
+            }
+            b'M' => self.reverse_index(),
+            b'c' => {
+                let (rows, columns) = (self.rows, self.columns);
+                let scrollback = std::mem::take(&mut self.scrollback);
+                *self = Self::new(rows, columns);
+                self.scrollback = scrollback;
+            }
+            _ => {}
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        if let [b"0" | b"2", title, ..] = params {
+            self.title = Some(String::from_utf8_lossy(title).into_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(rows: usize, columns: usize, output: &str) -> Screen {
+        let mut screen = Screen::new(rows, columns);
+        feed(&mut screen, output);
+        screen
+    }
+
+    fn feed(screen: &mut Screen, output: &str) {
+        vte::Parser::new().advance(screen, output.as_bytes());
+    }
+
+    fn lines(screen: &Screen) -> Vec<String> {
+        (0..screen.line_count())
+            .map(|index| screen.line(index).iter().map(|cell| cell.c).collect::<String>().trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn cursor_movement_stays_on_the_screen() {
+        let mut screen = run(5, 10, "\x1b[3;5Hx");
+        assert_eq!(screen.line(2)[4].c, 'x');
+        assert_eq!(screen.cursor(), (2, 5));
+
+        feed(&mut screen, "\x1b[10A");
+        assert_eq!(screen.cursor(), (0, 5));
+        feed(&mut screen, "\x1b[99C");
+        assert_eq!(screen.cursor(), (0, 9));
+        feed(&mut screen, "\x1b[2D");
+        assert_eq!(screen.cursor(), (0, 7));
+        feed(&mut screen, "\x1b[G\t");
+        assert_eq!(screen.cursor(), (0, 8));
+        feed(&mut screen, "\x08\x1b[6n");
+        assert_eq!(screen.take_responses(), b"\x1b[1;8R");
+
+        // The character after the last column goes to the next line.
+        feed(&mut screen, "\x1b[1;9Habc");
+        assert_eq!(lines(&screen)[..2], ["        ab", "c"]);
+    }
+
+    #[test]
+    fn sgr_sets_and_resets_styles() {
+        let screen = run(1, 10, "\x1b[1;31mA\x1b[38;5;200mB\x1b[48;2;1;2;3mC\x1b[38:2::4:5:6mD\x1b[0;92mE\x1b[mF");
+        let style = |column: usize| screen.line(0)[column].style;
+
+        assert_eq!(style(0), Style { bold: true, foreground: Color::Indexed(1), ..Style::default() });
+        assert_eq!(style(1).foreground, Color::Indexed(200));
+        assert_eq!(style(2).background, Color::Rgb(1, 2, 3));
+        assert_eq!(style(3).foreground, Color::Rgb(4, 5, 6));
+        assert!(style(3).bold);
+        assert_eq!(style(4), Style { foreground: Color::Indexed(10), ..Style::default() });
+        assert_eq!(style(5), Style::default());
+    }
+
+    #[test]
+    fn scrolling_regions_keep_the_lines_outside_them() {
+        let mut screen = run(5, 10, "1\r\n2\r\n3\r\n4\r\n5\x1b[2;4r");
+        assert_eq!(screen.cursor(), (0, 0));
+
+        // A line feed on the last row of the region scrolls only the region.
+        feed(&mut screen, "\x1b[4;1H\n");
+        assert_eq!(lines(&screen), ["1", "3", "4", "", "5"]);
+
+        // A reverse index on its first row scrolls it back down.
+        feed(&mut screen, "\x1b[2;1H\x1bM");
+        assert_eq!(lines(&screen), ["1", "", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn lines_scrolled_off_the_top_go_to_the_scrollback() {
+        let screen = run(2, 10, "a\r\nb\r\nc");
+        assert_eq!(lines(&screen), ["a", "b", "c"]);
+        assert_eq!(screen.cursor(), (2, 1));
+    }
+
+    #[test]
+    fn resize_cuts_lines_and_keeps_the_cursor_line() {
+        let mut screen = run(3, 4, "ab\r\ncd\r\nef");
+        screen.resize(2, 1);
+        assert_eq!(lines(&screen), ["a", "c", "e"]);
+        assert_eq!(screen.cursor(), (2, 0));
+
+        screen.resize(4, 3);
+        assert_eq!(lines(&screen), ["a", "c", "e", "", ""]);
+
+        // Lines below the cursor leave first.
+        let mut screen = run(3, 4, "x\x1b[H");
+        screen.resize(1, 4);
+        assert_eq!(lines(&screen), ["x"]);
+    }
+
+    #[test]
+    fn the_alternate_screen_restores_the_main_one() {
+        let mut screen = run(2, 5, "main");
+        feed(&mut screen, "\x1b[?1049h\x1b[Halt\r\n\r\n\r\n");
+        assert_eq!(lines(&screen), ["", ""]);
+
+        feed(&mut screen, "\x1b[?1049l");
+        assert_eq!(lines(&screen), ["main", ""]);
+        assert_eq!(screen.cursor(), (0, 4));
+    }
+}
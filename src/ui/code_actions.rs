@@ -0,0 +1,220 @@
+use crate::lsp::CodeAction;
+use crate::ui::notifications::Notifications;
+use crate::ui::palette::PaletteResult;
+use crate::ui::theme::ZenTheme;
+
+const WIDTH: f32 = 380.0;
+const HEIGHT: f32 = 280.0;
+/// How long the cursor has to rest before the code actions at it are looked up.
+const DELAY: f64 = 0.3;
+
+/// The icon shown for a kind of code action.
+pub fn icon(kind: Option<&str>) -> &'static str {
+    match kind {
+        Some(kind) if kind.starts_with("refactor") => "🔧",
+        Some(kind) if kind.starts_with("source") => "⚙",
+        _ => "💡",
+    }
+}
+
+/// The fixes and refactorings for the cursor position, listed under it. Actions the
+/// server marked as disabled are greyed out with the reason on hover.
+pub struct CodeActionMenu {
+    pub actions: Vec<CodeAction>,
+    selected: usize,
+    reveal_selected: bool,
+}
+
+impl CodeActionMenu {
+    /// Starts on the action the server prefers, or else the first that can be applied.
+    pub fn new(actions: Vec<CodeAction>) -> Self {
+        let selected = actions
+            .iter()
+            .position(|action| action.preferred && action.disabled.is_none())
+            .or_else(|| actions.iter().position(|action| action.disabled.is_none()))
+            .unwrap_or(0);
+        Self { actions, selected, reveal_selected: true }
+    }
+
+    /// Takes the keys the menu uses before the text edit sees them.
+    pub fn handle_keys(&mut self, ctx: &egui::Context) -> Option<PaletteResult> {
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            )
+        });
+        if escape {
+            return Some(PaletteResult::Closed);
+        }
+        if up {
+            self.step(false);
+        }
+        if down {
+            self.step(true);
+        }
+        let enabled = self.actions.get(self.selected).is_some_and(|action| action.disabled.is_none());
+        (enter && enabled).then_some(PaletteResult::Picked(self.selected))
+    }
+
+    /// Moves the selection to the next or previous action that can be applied.
+    fn step(&mut self, forward: bool) {
+        let enabled = |index: &usize| self.actions[*index].disabled.is_none();
+        let next = if forward {
+            (self.selected + 1..self.actions.len()).find(enabled)
+        } else {
+            (0..self.selected).rev().find(enabled)
+        };
+        if let Some(next) = next {
+            self.selected = next;
+            self.reveal_selected = true;
+        }
+    }
+
+    /// Draws the list with its top left at `anchor`.
+    pub fn show(&mut self, ctx: &egui::Context, anchor: egui::Pos2, theme: &ZenTheme) -> Option<PaletteResult> {
+        let mut result = None;
+        let font = egui::FontId::monospace(theme.typography.code_font_size);
+
+        let area = egui::Area::new(egui::Id::new("code_action_menu"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(anchor)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(WIDTH);
+                    egui::ScrollArea::vertical().max_height(HEIGHT).show(ui, |ui| {
+                        ui.spacing_mut().item_spacing.y = 0.0;
+                        for (index, action) in self.actions.iter().enumerate() {
+                            let text = egui::RichText::new(format!("{} {}", icon(action.kind.as_deref()), action.title)).font(font.clone());
+                            let button = egui::Button::selectable(index == self.selected, text);
+                            let mut response = ui.add_enabled(action.disabled.is_none(), button);
+                            if let Some(reason) = &action.disabled {
+                                response = response.on_disabled_hover_text(reason);
+                            }
+                            if index == self.selected && self.reveal_selected {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                result = Some(PaletteResult::Picked(index));
+                            }
+                        }
+                    });
+                    self.reveal_selected = false;
+                });
+            });
+
+        let clicked_outside = ctx.input(|i| {
+            i.pointer.any_pressed() && i.pointer.interact_pos().is_some_and(|pos| !area.response.rect.contains(pos))
+        });
+        if clicked_outside && result.is_none() {
+            return Some(PaletteResult::Closed);
+        }
+        result
+    }
+}
+
+/// The code actions at the cursor, looked up once it has rested there, for the lightbulb
+/// in the gutter and the Ctrl+. menu.
+#[derive(Default)]
+pub struct CodeActions {
+    /// The cursor and buffer version the actions are for, and when the cursor got there.
+    position: Option<(usize, i32)>,
+    since: f64,
+    requested: bool,
+    request: Option<i64>,
+    actions: Vec<CodeAction>,
+    /// Set when the menu was asked for before the actions came in.
+    show_when_ready: bool,
+    menu: Option<CodeActionMenu>,
+}
+
+impl CodeActions {
+    /// Opens the menu once the actions are known.
+    pub fn show_when_ready(&mut self) {
+        self.show_when_ready = true;
+    }
+
+    /// Keeps the actions in step with the cursor at `position`: forgotten when it moves,
+    /// and opened in the menu once they are in and it is wanted. Returns true when they are
+    /// to be asked for, once the cursor has rested or straight away when the menu is
+    /// wanted; the request goes to [`Self::requested`].
+    pub fn update(&mut self, ctx: &egui::Context, position: (usize, i32), notifications: &mut Notifications) -> bool {
+        let now = ctx.input(|i| i.time);
+        if self.position != Some(position) {
+            *self = Self {
+                position: Some(position),
+                since: now,
+                show_when_ready: self.show_when_ready,
+                ..Default::default()
+            };
+        }
+
+        if self.requested {
+            if self.show_when_ready && self.request.is_none() {
+                self.open_menu(notifications);
+            }
+            return false;
+        }
+        let waited = now - self.since;
+        if waited < DELAY && !self.show_when_ready {
+            ctx.request_repaint_after_secs((DELAY - waited) as f32);
+            return false;
+        }
+        self.requested = true;
+        true
+    }
+
+    /// The id of the request for the actions, or none when there is nothing to ask.
+    pub fn requested(&mut self, request: Option<i64>) {
+        self.request = request;
+    }
+
+    pub fn is_response(&self, id: i64) -> bool {
+        self.request == Some(id)
+    }
+
+    pub fn receive(&mut self, actions: Vec<CodeAction>, notifications: &mut Notifications) {
+        self.request = None;
+        self.actions = actions;
+        if self.show_when_ready {
+            self.open_menu(notifications);
+        }
+    }
+
+    /// Whether any action at the cursor can be applied, which shows the lightbulb.
+    pub fn any_enabled(&self) -> bool {
+        self.actions.iter().any(|action| action.disabled.is_none())
+    }
+
+    pub fn open_menu(&mut self, notifications: &mut Notifications) {
+        self.show_when_ready = false;
+        if self.actions.is_empty() {
+            notifications.info("No code actions available here");
+            return;
+        }
+        self.menu = Some(CodeActionMenu::new(self.actions.clone()));
+    }
+
+    /// Takes the keys of the open menu. Returns the action picked with them.
+    pub fn handle_keys(&mut self, ctx: &egui::Context) -> Option<CodeAction> {
+        let result = self.menu.as_mut()?.handle_keys(ctx)?;
+        self.picked(result)
+    }
+
+    /// Draws the open menu at `anchor`. Returns the action picked in it.
+    pub fn show_menu(&mut self, ctx: &egui::Context, anchor: egui::Pos2, theme: &ZenTheme) -> Option<CodeAction> {
+        let result = self.menu.as_mut()?.show(ctx, anchor, theme)?;
+        self.picked(result)
+    }
+
+    /// Closes the menu, giving the action that was picked in it.
+    fn picked(&mut self, result: PaletteResult) -> Option<CodeAction> {
+        let menu = self.menu.take()?;
+        match result {
+            PaletteResult::Picked(index) => menu.actions.into_iter().nth(index),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::completion::CompletionSession;
+use crate::ui::theme::ZenTheme;
+use egui::text::{LayoutJob, TextFormat};
+use egui::Color32;
+
+const LIST_WIDTH: f32 = 320.0;
+const LIST_HEIGHT: f32 = 240.0;
+const DOCUMENTATION_WIDTH: f32 = 340.0;
+
+/// Draws the completion list with its top left at `anchor`, and the selected item's
+/// details beside it. Returns the match that was clicked.
+pub fn show(ctx: &egui::Context, anchor: egui::Pos2, session: &mut CompletionSession, theme: &ZenTheme) -> Option<usize> {
+    if session.matches.is_empty() {
+        return None;
+    }
+    let mut clicked = None;
+
+    egui::Area::new(egui::Id::new("completion_popup"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(anchor)
+        .show(ctx, |ui| {
+            ui.horizontal_top(|ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(LIST_WIDTH);
+                    egui::ScrollArea::vertical().max_height(LIST_HEIGHT).show(ui, |ui| {
+                        ui.spacing_mut().item_spacing.y = 0.0;
+                        for (row, candidate) in session.matches.iter().enumerate() {
+                            let item = &session.items[candidate.index];
+                            let job = row_layout(ui, theme, item.kind.icon(), &item.label, &candidate.positions, item.detail.as_deref());
+                            let selected = row == session.selected;
+                            let response = ui.add_sized([LIST_WIDTH, 0.0], egui::Button::selectable(selected, job));
+                            if selected && session.reveal_selected {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                clicked = Some(row);
+                            }
+                        }
+                    });
+                });
+                session.reveal_selected = false;
+
+                let Some(item) = session.selected_item() else {
+                    return;
+                };
+                if item.documentation.is_none() && item.detail.is_none() {
+                    return;
+                }
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(DOCUMENTATION_WIDTH);
+                    if let Some(detail) = &item.detail {
+                        ui.label(egui::RichText::new(detail).monospace());
+                    }
+                    if let Some(documentation) = &item.documentation {
+                        if item.detail.is_some() {
+                            ui.separator();
+                        }
+                        egui::ScrollArea::vertical().id_salt("completion_documentation").max_height(LIST_HEIGHT).show(ui, |ui| {
+                            ui.label(documentation);
+                        });
+                    }
+                });
+            });
+        });
+
+    clicked
+}
+
+/// The kind icon, the label with the characters that matched in bold color, and the detail faded.
+fn row_layout(ui: &egui::Ui, theme: &ZenTheme, icon: &str, label: &str, matched: &[usize], detail: Option<&str>) -> LayoutJob {
+    let font = egui::FontId::monospace(theme.typography.code_font_size);
+    let plain = TextFormat::simple(font.clone(), ui.visuals().text_color());
+    let [r, g, b] = theme.syntax.keyword;
+    let highlight = TextFormat::simple(font.clone(), Color32::from_rgb(r, g, b));
+    let faded = TextFormat::simple(font.clone(), ui.visuals().weak_text_color());
+
+    let mut job = LayoutJob::default();
+    job.append(icon, 0.0, faded.clone());
+    job.append(" ", 0.0, plain.clone());
+    for (index, c) in label.chars().enumerate() {
+        let format = if matched.contains(&index) { highlight.clone() } else { plain.clone() };
+        job.append(c.encode_utf8(&mut [0; 4]), 0.0, format);
+    }
+    if let Some(detail) = detail {
+        let detail = detail.lines().next().unwrap_or_default();
+        job.append(detail, 12.0, faded);
+    }
+    job.wrap.max_rows = 1;
+    job.wrap.break_anywhere = true;
+    job.wrap.max_width = LIST_WIDTH - 16.0;
+    job
+}
@@ -0,0 +1,441 @@
+use crate::document::diff::{self, DiffHunk, DiffLine, DiffLineKind};
+use crate::ui::theme::ZenTheme;
+use egui::{Color32, FontId, Rect};
+
+/// Unchanged lines shown around each change when comparing texts.
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLayout {
+    SideBySide,
+    Inline,
+}
+
+/// Something that can be done to a single hunk, offered as a button on its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkAction {
+    Stage,
+    Unstage,
+    Discard,
+}
+
+impl HunkAction {
+    fn label(&self) -> &'static str {
+        match self {
+            HunkAction::Stage => "Stage Hunk",
+            HunkAction::Unstage => "Unstage Hunk",
+            HunkAction::Discard => "Discard Hunk",
+        }
+    }
+}
+
+pub enum DiffViewAction {
+    Close,
+    /// The button offered with [`DiffView::with_confirm`] was clicked.
+    Confirm,
+    Hunk(usize, HunkAction),
+}
+
+/// One visual row: a hunk header, or a line (inline) or pair of lines (side by side).
+enum DiffRow {
+    Header(usize),
+    Line(usize, usize),
+    Pair(usize, Option<usize>, Option<usize>),
+}
+
+/// Shows a set of hunks inline or side by side, in the colors of the active theme.
+///
+/// Both sides are drawn in the same rows of one scroll area, so they always scroll
+/// together; long lines scroll sideways with a shared offset.
+pub struct DiffView {
+    pub title: String,
+    /// Names of the old and new side, shown above the columns.
+    labels: Option<(String, String)>,
+    /// The label of a button that accepts the changes shown, for previews.
+    confirm: Option<String>,
+    hunks: Vec<DiffHunk>,
+    hunk_actions: Vec<HunkAction>,
+    /// The hunk whose discard button was pressed and is waiting for confirmation.
+    confirm_discard: Option<usize>,
+    layout: DiffLayout,
+    rows: Vec<DiffRow>,
+    /// The rows where a run of changed lines starts, for next/previous navigation.
+    change_rows: Vec<usize>,
+    current_change: Option<usize>,
+    scroll_to_row: Option<usize>,
+    scroll_x: f32,
+    longest_line: usize,
+}
+
+impl DiffView {
+    pub fn new(title: impl Into<String>, hunks: Vec<DiffHunk>, hunk_actions: Vec<HunkAction>) -> Self {
+        let mut view = Self {
+            title: title.into(),
+            labels: None,
+            confirm: None,
+            hunks: Vec::new(),
+            hunk_actions,
+            confirm_discard: None,
+            layout: DiffLayout::SideBySide,
+            rows: Vec::new(),
+            change_rows: Vec::new(),
+            current_change: None,
+            scroll_to_row: None,
+            scroll_x: 0.0,
+            longest_line: 0,
+        };
+        view.set_hunks(hunks);
+        view
+    }
+
+    /// Compares two texts line by line, with changed words highlighted.
+    pub fn compare(title: impl Into<String>, old_label: &str, new_label: &str, old: &str, new: &str) -> Self {
+        let mut view = Self::new(title, diff::compute_hunks(old, new, CONTEXT_LINES), Vec::new());
+        view.labels = Some((old_label.to_string(), new_label.to_string()));
+        view
+    }
+
+    /// Compares several files at once, with each hunk headed by the name of its file.
+    pub fn compare_files<'a>(title: impl Into<String>, files: impl IntoIterator<Item = (String, &'a str, &'a str)>) -> Self {
+        let hunks = files
+            .into_iter()
+            .flat_map(|(name, old, new)| {
+                diff::compute_hunks(old, new, CONTEXT_LINES).into_iter().map(move |mut hunk| {
+                    hunk.header = format!("{}  {}", name, hunk.header);
+                    hunk
+                })
+            })
+            .collect();
+        Self::new(title, hunks, Vec::new())
+    }
+
+    /// Turns the view into a preview of changes that are made only if the button with
+    /// `label` is clicked; closing it cancels them.
+    pub fn with_confirm(mut self, label: impl Into<String>) -> Self {
+        self.confirm = Some(label.into());
+        self
+    }
+
+    /// Swaps in new hunks, e.g. after staging one, keeping the layout.
+    pub fn set_hunks(&mut self, mut hunks: Vec<DiffHunk>) {
+        for hunk in &mut hunks {
+            hunk.highlight_words();
+        }
+        self.longest_line = hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .map(|line| line.text.chars().count())
+            .max()
+            .unwrap_or(0);
+        self.hunks = hunks;
+        self.confirm_discard = None;
+        self.build_rows();
+    }
+
+    fn build_rows(&mut self) {
+        self.rows.clear();
+
+        for (hunk_index, hunk) in self.hunks.iter().enumerate() {
+            self.rows.push(DiffRow::Header(hunk_index));
+
+            if self.layout == DiffLayout::Inline {
+                self.rows.extend((0..hunk.lines.len()).map(|line| DiffRow::Line(hunk_index, line)));
+                continue;
+            }
+
+            // Side by side: context lines sit on both sides; a block of removals is paired
+            // row by row with the additions that follow it.
+            let lines = &hunk.lines;
+            let mut i = 0;
+            while i < lines.len() {
+                if lines[i].kind == DiffLineKind::Context {
+                    self.rows.push(DiffRow::Pair(hunk_index, Some(i), Some(i)));
+                    i += 1;
+                    continue;
+                }
+
+                let removed_start = i;
+                while i < lines.len() && lines[i].kind == DiffLineKind::Removed {
+                    i += 1;
+                }
+                let added_start = i;
+                while i < lines.len() && lines[i].kind == DiffLineKind::Added {
+                    i += 1;
+                }
+
+                let removed = added_start - removed_start;
+                let added = i - added_start;
+                for row in 0..removed.max(added) {
+                    self.rows.push(DiffRow::Pair(
+                        hunk_index,
+                        (row < removed).then_some(removed_start + row),
+                        (row < added).then_some(added_start + row),
+                    ));
+                }
+            }
+        }
+
+        self.change_rows.clear();
+        let mut in_change = false;
+        for (index, row) in self.rows.iter().enumerate() {
+            let changed = match *row {
+                DiffRow::Header(_) => false,
+                DiffRow::Line(hunk, line) => self.hunks[hunk].lines[line].kind != DiffLineKind::Context,
+                DiffRow::Pair(hunk, left, _) => left.is_none_or(|i| self.hunks[hunk].lines[i].kind != DiffLineKind::Context),
+            };
+            if changed && !in_change {
+                self.change_rows.push(index);
+            }
+            in_change = changed;
+        }
+        self.current_change = None;
+    }
+
+    fn go_to_change(&mut self, forward: bool) {
+        if self.change_rows.is_empty() {
+            return;
+        }
+        let last = self.change_rows.len() - 1;
+        let next = match (self.current_change, forward) {
+            (None, true) => 0,
+            (None, false) => last,
+            (Some(current), true) => (current + 1).min(last),
+            (Some(current), false) => current.saturating_sub(1),
+        };
+        self.current_change = Some(next);
+        self.scroll_to_row = Some(self.change_rows[next]);
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, theme: &ZenTheme) -> Option<DiffViewAction> {
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            ui.strong(&self.title);
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let close = if self.confirm.is_some() { "Cancel" } else { "Close" };
+                if ui.button(close).clicked() {
+                    action = Some(DiffViewAction::Close);
+                }
+                if let Some(confirm) = &self.confirm {
+                    if ui.button(confirm).clicked() {
+                        action = Some(DiffViewAction::Confirm);
+                    }
+                }
+                let mut layout = self.layout;
+                ui.selectable_value(&mut layout, DiffLayout::Inline, "Inline");
+                ui.selectable_value(&mut layout, DiffLayout::SideBySide, "Side by Side");
+                if layout != self.layout {
+                    self.layout = layout;
+                    self.build_rows();
+                }
+
+                ui.separator();
+                let has_changes = !self.change_rows.is_empty();
+                if ui.add_enabled(has_changes, egui::Button::new("⏷")).on_hover_text("Next Change (F7)").clicked() {
+                    self.go_to_change(true);
+                }
+                if ui.add_enabled(has_changes, egui::Button::new("⏶")).on_hover_text("Previous Change (Shift+F7)").clicked() {
+                    self.go_to_change(false);
+                }
+                match self.current_change {
+                    Some(current) => ui.weak(format!("{} of {} changes", current + 1, self.change_rows.len())),
+                    None => ui.weak(format!("{} changes", self.change_rows.len())),
+                };
+            });
+        });
+
+        if ui.input(|i| i.key_pressed(egui::Key::F7)) {
+            let forward = !ui.input(|i| i.modifiers.shift);
+            self.go_to_change(forward);
+        }
+
+        if let Some((old_label, new_label)) = &self.labels {
+            ui.horizontal(|ui| match self.layout {
+                DiffLayout::SideBySide => {
+                    let half = ui.available_width() / 2.0;
+                    ui.add_sized([half, ui.spacing().interact_size.y], egui::Label::new(egui::RichText::new(old_label).weak()).truncate());
+                    ui.add(egui::Label::new(egui::RichText::new(new_label).weak()).truncate());
+                }
+                DiffLayout::Inline => {
+                    ui.weak(format!("{}  ↔  {}", old_label, new_label));
+                }
+            });
+        }
+        ui.separator();
+
+        if self.hunks.is_empty() {
+            ui.weak("No changes");
+            return action;
+        }
+
+        let font = FontId::monospace(theme.typography.code_font_size);
+        let row_height = ui.spacing().interact_size.y.max(ui.fonts(|f| f.row_height(&font)));
+        let char_width = ui.fonts(|f| f.glyph_width(&font, '9'));
+
+        // Rows are laid out without spacing, which `show_rows` reads from the enclosing ui.
+        ui.spacing_mut().item_spacing.y = 0.0;
+        let mut scroll_area = egui::ScrollArea::vertical().auto_shrink([false; 2]);
+        if let Some(row) = self.scroll_to_row.take() {
+            scroll_area = scroll_area.vertical_scroll_offset(row.saturating_sub(2) as f32 * row_height);
+        }
+
+        let mut confirm_discard = self.confirm_discard;
+        let output = scroll_area.show_rows(ui, row_height, self.rows.len(), |ui, range| {
+            let painter = LinePainter { theme, font: &font, char_width, scroll_x: self.scroll_x };
+            for row in &self.rows[range] {
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), row_height), egui::Sense::hover());
+                match *row {
+                    DiffRow::Header(hunk) => {
+                        if let Some(hunk_action) = self.render_header(ui, rect, hunk, &font, &mut confirm_discard) {
+                            action = Some(DiffViewAction::Hunk(hunk, hunk_action));
+                        }
+                    }
+                    DiffRow::Line(hunk, line) => {
+                        let line = &self.hunks[hunk].lines[line];
+                        painter.paint(ui, rect, Some(line), &[line.old_line, line.new_line]);
+                    }
+                    DiffRow::Pair(hunk, left, right) => {
+                        let lines = &self.hunks[hunk].lines;
+                        let (left_rect, right_rect) = rect.split_left_right_at_fraction(0.5);
+                        let left = left.map(|i| &lines[i]);
+                        let right = right.map(|i| &lines[i]);
+                        painter.paint(ui, left_rect, left, &[left.and_then(|l| l.old_line)]);
+                        painter.paint(ui, right_rect, right, &[right.and_then(|l| l.new_line)]);
+                    }
+                }
+            }
+        });
+        self.confirm_discard = confirm_discard;
+
+        // Sideways scrolling moves the text of both sides while the line numbers stay put.
+        if ui.rect_contains_pointer(output.inner_rect) {
+            let delta = ui.input(|i| i.smooth_scroll_delta.x);
+            let max_scroll = self.longest_line as f32 * char_width;
+            self.scroll_x = (self.scroll_x - delta).clamp(0.0, max_scroll);
+        }
+
+        action
+    }
+
+    /// Draws a hunk's header with its actions; discarding asks for confirmation first.
+    fn render_header(
+        &self,
+        ui: &mut egui::Ui,
+        rect: Rect,
+        hunk: usize,
+        font: &FontId,
+        confirm_discard: &mut Option<usize>,
+    ) -> Option<HunkAction> {
+        let mut clicked = None;
+        ui.painter().rect_filled(rect, 0.0, ui.visuals().faint_bg_color);
+
+        let mut header_ui = ui.new_child(
+            egui::UiBuilder::new()
+                .max_rect(rect.shrink2(egui::vec2(4.0, 0.0)))
+                .layout(egui::Layout::left_to_right(egui::Align::Center)),
+        );
+        header_ui.label(egui::RichText::new(&self.hunks[hunk].header).font(font.clone()).weak());
+        if self.hunks[hunk].patch.is_some() {
+            header_ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if *confirm_discard == Some(hunk) {
+                    let discard = egui::RichText::new(HunkAction::Discard.label()).color(ui.visuals().error_fg_color);
+                    if ui.small_button(discard).clicked() {
+                        *confirm_discard = None;
+                        clicked = Some(HunkAction::Discard);
+                    }
+                    if ui.small_button("Cancel").clicked() {
+                        *confirm_discard = None;
+                    }
+                    return;
+                }
+                for hunk_action in self.hunk_actions.iter().rev() {
+                    if !ui.small_button(hunk_action.label()).clicked() {
+                        continue;
+                    }
+                    if *hunk_action == HunkAction::Discard {
+                        *confirm_discard = Some(hunk);
+                    } else {
+                        clicked = Some(*hunk_action);
+                    }
+                }
+            });
+        }
+        clicked
+    }
+}
+
+/// Draws diff lines with their background, line numbers, change sign and changed words.
+struct LinePainter<'a> {
+    theme: &'a ZenTheme,
+    font: &'a FontId,
+    char_width: f32,
+    scroll_x: f32,
+}
+
+impl LinePainter<'_> {
+    /// Paints one line clipped to `rect`. Inline rows pass both the old and new line number;
+    /// side-by-side halves pass only their own. A missing line leaves a shaded gap.
+    fn paint(&self, ui: &egui::Ui, rect: Rect, line: Option<&DiffLine>, numbers: &[Option<usize>]) {
+        let painter = ui.painter_at(rect);
+        let tint = |[r, g, b]: [u8; 3], alpha: u8| Color32::from_rgba_unmultiplied(r, g, b, alpha);
+
+        let Some(line) = line else {
+            painter.rect_filled(rect, 0.0, ui.visuals().faint_bg_color);
+            return;
+        };
+
+        let (color, sign) = match line.kind {
+            DiffLineKind::Context => (None, " "),
+            DiffLineKind::Removed => (Some(self.theme.git.deleted), "-"),
+            DiffLineKind::Added => (Some(self.theme.git.added), "+"),
+        };
+        if let Some(color) = color {
+            painter.rect_filled(rect, 0.0, tint(color, 40));
+        }
+
+        let number_width = self.char_width * 5.0;
+        let muted = self.theme.text_color().linear_multiply(0.5);
+        let mut x = rect.left() + 4.0;
+
+        for number in numbers {
+            let text = number.map(|n| n.to_string()).unwrap_or_default();
+            painter.text(
+                egui::pos2(x + number_width, rect.center().y),
+                egui::Align2::RIGHT_CENTER,
+                text,
+                self.font.clone(),
+                muted,
+            );
+            x += number_width + 6.0;
+        }
+        painter.text(egui::pos2(x, rect.center().y), egui::Align2::LEFT_CENTER, sign, self.font.clone(), muted);
+        x += self.char_width * 2.0;
+
+        let mut job = egui::text::LayoutJob::default();
+        let text_color = self.theme.text_color();
+        let mut append = |text: &str, background: Color32| {
+            job.append(
+                text,
+                0.0,
+                egui::TextFormat { font_id: self.font.clone(), color: text_color, background, ..Default::default() },
+            );
+        };
+        let mut pos = 0;
+        for range in &line.emphasis {
+            let (Some(before), Some(changed)) = (line.text.get(pos..range.start), line.text.get(range.clone())) else {
+                break;
+            };
+            append(before, Color32::TRANSPARENT);
+            append(changed, color.map(|c| tint(c, 110)).unwrap_or_default());
+            pos = range.end;
+        }
+        append(line.text.get(pos..).unwrap_or(""), Color32::TRANSPARENT);
+
+        let galley = painter.layout_job(job);
+        let text_rect = Rect::from_min_max(egui::pos2(x, rect.top()), rect.max);
+        let top = rect.center().y - galley.size().y / 2.0;
+        painter
+            .with_clip_rect(text_rect)
+            .galley(egui::pos2(x - self.scroll_x, top), galley, text_color);
+    }
+}
@@ -1,7 +1,178 @@
 use crate::app::ZenView;
+use crate::completion::{self, Completions};
+use crate::document::conflict::{self, Conflict, Resolution};
+use crate::document::diagnostics::{Diagnostic, Diagnostics, Severity};
+use crate::document::edit::{self, TextEdit, WorkspaceEdit};
+use crate::document::encoding::FileEncoding;
+use crate::document::line_ending::{self, LineEnding};
+use crate::document::position::{self, Location, TextPosition};
+use crate::document::loader::{self, FileLoadTask, LoadedContent, LoadedFile, MappedText};
+use crate::document::writer::DiskState;
+use crate::file_filter::FileFilter;
+use crate::navigation::NavigationHistory;
+use crate::session::DocumentViewState;
+use crate::symbols::outline;
+use crate::symbols::{search, tags, Reference};
+use crate::ui::completion_popup;
+use crate::ui::diff_view::{DiffView, DiffViewAction};
+use crate::ui::merge_view::{MergeAction, MergeView};
+use crate::ui::notifications::Notifications;
+use crate::ui::outline::{OutlinePanel, OutlineSource};
+use crate::ui::palette::{Palette, PaletteItem, PaletteResult};
+use crate::ui::problems::{display_path, ProblemsPanel};
+use crate::ui::references::ReferencesPanel;
+use crate::ui::refactor::{OpenBuffer, PendingEdit, PreviewResult, Refactoring};
 use crate::ui::theme::ZenTheme;
 use crate::ui::tree::FileExplorer;
-use std::path::PathBuf;
+use crate::workspace::{Workspace, WorkspaceFolder};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::file_ops;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::git::diff::{Hunk, LineChangeKind, LineDiff};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::config::{FormatterConfig, LanguageServerConfig};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::git_diff::GitDiff;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::format::{FormatRequest, Formatting, OpenDocument};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::git::{GitMonitor, GitOperation, PatchRequest};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::lsp::client::ServerState;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::lsp::{convert, CodeAction, LspEvent, LspManager};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::symbols::index::SymbolIndex;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::code_actions::CodeActions;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::refactor::RefactorRequest;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::source_control::{ScmAction, SourceControlPanel};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::tasks::Task;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::task_output::{TaskOutputAction, TaskOutputPanel};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::terminal::{TerminalAction, TerminalPanel};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::tree::ExplorerCommand;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::notifications::{NotificationAction, NotificationLevel};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::watcher::{FileWatcher, FsEvent};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc;
+
+/// How long the mouse pointer has to rest on a word before its documentation is looked up.
+const HOVER_DELAY: f32 = 0.4;
+/// How long "Compare with Clipboard" waits for the clipboard contents before giving up.
+const CLIPBOARD_TIMEOUT: f64 = 0.5;
+
+/// A delete from the explorer waiting for confirmation. If moving to the trash
+/// failed, the prompt offers to delete permanently instead.
+#[cfg(not(target_arch = "wasm32"))]
+struct PendingDelete {
+    path: PathBuf,
+    trash_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BottomPanel {
+    Problems,
+    References,
+    #[cfg(not(target_arch = "wasm32"))]
+    Terminal,
+    #[cfg(not(target_arch = "wasm32"))]
+    Output,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum LookupKind {
+    Definition,
+    References,
+}
+
+/// A definition or references request waiting on a language server, with the name it
+/// is about in case the server has no answer.
+#[cfg(not(target_arch = "wasm32"))]
+struct PendingLookup {
+    id: i64,
+    kind: LookupKind,
+    name: String,
+}
+
+/// What an open palette lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PaletteKind {
+    DocumentSymbols,
+    #[cfg(not(target_arch = "wasm32"))]
+    WorkspaceSymbols,
+    #[cfg(not(target_arch = "wasm32"))]
+    Files,
+    #[cfg(not(target_arch = "wasm32"))]
+    Tasks,
+}
+
+/// The project's symbols as the workspace symbol palette lists them.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct WorkspaceSymbols {
+    /// The index generation the entries were built from.
+    generation: Option<u64>,
+    entries: (Vec<PaletteItem>, Vec<Location>),
+}
+
+/// A file to open in place of a buffer with unsaved edits, waiting for the user to save
+/// or discard them.
+struct PendingOpen {
+    path: PathBuf,
+    encoding: Option<FileEncoding>,
+    /// Where to go in the file once it is open, when it was reached by navigation.
+    location: Option<Location>,
+    /// Set once the user chose to save first; the file opens when the save went through.
+    saving: bool,
+}
+
+/// What was clicked in the gutter.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+enum GutterClick {
+    /// The change marker of a hunk.
+    Hunk(usize),
+    Lightbulb,
+}
+
+/// The word under the resting mouse pointer and what is known about it.
+struct SymbolHover {
+    /// Character range of the word, in the buffer version it was looked up in.
+    range: Range<usize>,
+    version: i32,
+    name: String,
+    request: Option<i64>,
+    text: Option<String>,
+}
+
+/// The project's files as the Go to File palette lists them, listed again on a
+/// background thread each time it opens.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct ProjectFiles {
+    receiver: Option<mpsc::Receiver<Vec<PathBuf>>>,
+    entries: (Vec<PaletteItem>, Vec<Location>),
+}
+
+/// The Find in Files prompt, which keeps the last query for next time.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct SearchPrompt {
+    open: bool,
+    focus: bool,
+    query: String,
+    match_case: bool,
+}
 
 pub struct CodeEditor {
     language: String,
@@ -11,8 +182,112 @@ pub struct CodeEditor {
     pub theme: ZenTheme,
     pub available_themes: Vec<ZenTheme>,
     pub selected_theme_index: usize,
+    pub notifications: Notifications,
+    /// Problems reported by language servers and linters, shown in the editor and the Problems panel.
+    pub diagnostics: Diagnostics,
+    bottom_panel: Option<BottomPanel>,
+    problems: ProblemsPanel,
+    references: ReferencesPanel,
+    navigation: NavigationHistory,
+    /// Declarations across the project, for navigating without a language server.
+    #[cfg(not(target_arch = "wasm32"))]
+    symbol_index: SymbolIndex,
+    #[cfg(not(target_arch = "wasm32"))]
+    pending_lookup: Option<PendingLookup>,
+    /// Occurrences found by searching the project files for a name.
+    #[cfg(not(target_arch = "wasm32"))]
+    reference_search: Option<mpsc::Receiver<Vec<Reference>>>,
+    hover: Option<SymbolHover>,
+    show_outline: bool,
+    outline: OutlinePanel,
+    palette: Option<(PaletteKind, Palette)>,
+    #[cfg(not(target_arch = "wasm32"))]
+    workspace_symbols: WorkspaceSymbols,
+    #[cfg(not(target_arch = "wasm32"))]
+    project_files: ProjectFiles,
+    #[cfg(not(target_arch = "wasm32"))]
+    search_prompt: SearchPrompt,
+    refactoring: Refactoring,
+    #[cfg(not(target_arch = "wasm32"))]
+    code_actions: CodeActions,
+    #[cfg(not(target_arch = "wasm32"))]
+    formatting: Formatting,
+    #[cfg(not(target_arch = "wasm32"))]
+    terminals: TerminalPanel,
+    #[cfg(not(target_arch = "wasm32"))]
+    task_output: TaskOutputPanel,
+    pub read_only: bool,
+    pub encoding: FileEncoding,
+    pub line_ending: LineEnding,
+    disk_state: Option<DiskState>,
+    notified_disk_state: Option<DiskState>,
+    #[cfg(not(target_arch = "wasm32"))]
+    watcher: Option<FileWatcher>,
+    #[cfg(not(target_arch = "wasm32"))]
+    watcher_started: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    pending_delete: Option<PendingDelete>,
+    #[cfg(not(target_arch = "wasm32"))]
+    git: GitMonitor,
+    /// The open file as committed in HEAD, the base for gutter markers.
+    #[cfg(not(target_arch = "wasm32"))]
+    head_text: Option<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    head_path: Option<PathBuf>,
+    #[cfg(not(target_arch = "wasm32"))]
+    line_diff: Option<LineDiff>,
+    #[cfg(not(target_arch = "wasm32"))]
+    line_diff_version: i32,
+    #[cfg(not(target_arch = "wasm32"))]
+    open_hunk: Option<usize>,
+    #[cfg(not(target_arch = "wasm32"))]
+    show_source_control: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    source_control: SourceControlPanel,
+    diff_view: Option<DiffView>,
+    #[cfg(not(target_arch = "wasm32"))]
+    git_diff: GitDiff,
+    /// When the clipboard contents for "Compare with Clipboard" were requested, in egui's
+    /// input time, while waiting for them.
+    clipboard_compare_requested: Option<f64>,
+    conflicts: Vec<Conflict>,
+    conflicts_version: i32,
+    /// Whether the open file had conflict markers, so it can be shown as resolved once they are gone.
+    had_conflicts: bool,
+    /// The file "Mark as Resolved" is staging in the background.
+    #[cfg(not(target_arch = "wasm32"))]
+    resolving: Option<PathBuf>,
+    merge_view: Option<MergeView>,
+    #[cfg(not(target_arch = "wasm32"))]
+    lsp: LspManager,
+    /// The document the language servers know as open, and the buffer version they last saw.
+    #[cfg(not(target_arch = "wasm32"))]
+    lsp_document: Option<PathBuf>,
+    #[cfg(not(target_arch = "wasm32"))]
+    lsp_version: i32,
+    completion: Completions,
+    workspace_changed: bool,
+    pending_save_encoding: Option<FileEncoding>,
+    /// The encoding picked with "Save with Encoding", which becomes the document's once
+    /// the write succeeds.
+    save_encoding: Option<FileEncoding>,
+    /// A file (or this one in another encoding) waiting to replace unsaved edits.
+    pending_open: Option<PendingOpen>,
+    save_before_open: bool,
+    large_file_mode: bool,
+    large_file_view: Option<MappedText>,
+    loading: Option<FileLoadTask>,
     show_line_numbers: bool,
+    pub side_panel_width: f32,
+    cursor: usize,
+    scroll_offset: egui::Vec2,
+    pending_cursor: Option<usize>,
+    /// Character range to select on the next frame, taking precedence over `pending_cursor`.
+    pending_selection: Option<Range<usize>>,
+    pending_scroll: Option<egui::Vec2>,
+    view_states: HashMap<PathBuf, DocumentViewState>,
     document_version: i32,
+    saved_version: i32,
     cached_line_height: Option<f32>,
     cached_max_line_width: Option<f32>,
     cached_layout_job: Option<egui::text::LayoutJob>,
@@ -30,123 +305,3063 @@ impl Default for CodeEditor {
             theme: ZenTheme::default(),
             available_themes: themes,
             selected_theme_index: 0,
+            notifications: Notifications::default(),
+            diagnostics: Diagnostics::default(),
+            bottom_panel: None,
+            problems: ProblemsPanel::default(),
+            references: ReferencesPanel::default(),
+            navigation: NavigationHistory::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            symbol_index: SymbolIndex::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            pending_lookup: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            reference_search: None,
+            hover: None,
+            show_outline: false,
+            outline: OutlinePanel::default(),
+            palette: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            workspace_symbols: WorkspaceSymbols::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            project_files: ProjectFiles::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            search_prompt: SearchPrompt::default(),
+            refactoring: Refactoring::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            code_actions: CodeActions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            formatting: Formatting::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            #[cfg(not(target_arch = "wasm32"))]
+            #[cfg(not(target_arch = "wasm32"))]
+            terminals: TerminalPanel::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            task_output: TaskOutputPanel::default(),
+            read_only: false,
+            encoding: FileEncoding::default(),
+            line_ending: LineEnding::default(),
+            disk_state: None,
+            notified_disk_state: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            watcher: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            watcher_started: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            pending_delete: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            git: GitMonitor::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            head_text: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            head_path: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            line_diff: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            line_diff_version: -1,
+            #[cfg(not(target_arch = "wasm32"))]
+            open_hunk: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            show_source_control: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            source_control: SourceControlPanel::default(),
+            diff_view: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            git_diff: GitDiff::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            clipboard_compare_requested: None,
+            conflicts: Vec::new(),
+            conflicts_version: -1,
+            had_conflicts: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            resolving: None,
+            merge_view: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            lsp: LspManager::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            lsp_document: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            lsp_version: -1,
+            completion: Completions::default(),
+            workspace_changed: false,
+            pending_save_encoding: None,
+            save_encoding: None,
+            pending_open: None,
+            save_before_open: false,
+            large_file_mode: false,
+            large_file_view: None,
+            loading: None,
             show_line_numbers: true,
+            side_panel_width: 200.0,
+            cursor: 0,
+            scroll_offset: egui::Vec2::ZERO,
+            pending_cursor: None,
+            pending_selection: None,
+            pending_scroll: None,
+            view_states: HashMap::new(),
             document_version: 0,
+            saved_version: 0,
             cached_line_height: None,
             cached_max_line_width: None,
             cached_layout_job: None,
             cache_version: -1,
         }
     }
-}
+}
+
+impl CodeEditor {
+    pub(crate) const TEXT_EDIT_ID: &'static str = "zen_code_editor";
+
+    pub fn open_project(&mut self, path: PathBuf) {
+        self.file_explorer.open_project(path.clone());
+    }
+
+    pub fn set_file_filter(&mut self, filter: FileFilter) {
+        self.file_explorer.set_filter(filter);
+    }
+
+    pub fn open_workspace(&mut self, workspace: &Workspace) {
+        self.file_explorer.open_workspace(workspace.folders.clone());
+    }
+
+    /// Adds a folder to the explorer next to the ones already open.
+    pub fn add_project_root(&mut self, path: PathBuf) {
+        if self.file_explorer.add_root(WorkspaceFolder { path, ..Default::default() }) {
+            self.workspace_changed = true;
+        }
+    }
+
+    pub fn workspace(&self) -> Workspace {
+        Workspace { folders: self.file_explorer.workspace_folders() }
+    }
+
+    /// Whether roots were added or removed since the last call, so the workspace file can be updated.
+    pub fn take_workspace_changed(&mut self) -> bool {
+        std::mem::take(&mut self.workspace_changed)
+    }
+
+    /// The first root folder; the project, when only one folder is open.
+    pub fn project_root(&self) -> Option<&PathBuf> {
+        self.file_explorer.roots.first().map(|root| &root.node.path)
+    }
+
+    pub fn project_roots(&self) -> Vec<PathBuf> {
+        self.file_explorer.root_paths()
+    }
+
+    /// Cursor and scroll positions of every document opened this session, most recent first.
+    pub fn document_view_states(&mut self) -> Vec<DocumentViewState> {
+        self.remember_view_state();
+        let mut states: Vec<DocumentViewState> = self.view_states.values().cloned().collect();
+        if let Some(active) = &self.selected_file {
+            states.sort_by_key(|state| state.path != *active);
+        }
+        states
+    }
+
+    pub fn set_document_view_states(&mut self, states: Vec<DocumentViewState>) {
+        self.view_states = states.into_iter()
+            .map(|state| (state.path.clone(), state))
+            .collect();
+    }
+
+    fn remember_view_state(&mut self) {
+        if let Some(path) = &self.selected_file {
+            self.view_states.insert(path.clone(), DocumentViewState {
+                path: path.clone(),
+                cursor: self.cursor,
+                scroll: [self.scroll_offset.x, self.scroll_offset.y],
+            });
+        }
+    }
+
+    fn restore_view_state(&mut self, path: &Path) {
+        let state = self.view_states.get(path).cloned().unwrap_or_default();
+        self.cursor = state.cursor;
+        self.scroll_offset = egui::vec2(state.scroll[0], state.scroll[1]);
+        self.pending_cursor = Some(state.cursor);
+        self.pending_scroll = Some(self.scroll_offset);
+    }
+
+    pub fn set_theme(&mut self, theme: ZenTheme) {
+        self.theme = theme;
+        if let Some(index) = self.available_themes.iter().position(|t| t.name == self.theme.name) {
+            self.selected_theme_index = index;
+        }
+
+        self.cached_layout_job = None;
+        self.cached_line_height = None;
+    }
+
+    /// Opens `path`, asking first to save or discard unsaved edits.
+    pub fn load_file(&mut self, path: &Path) {
+        self.open_or_ask(path.to_path_buf(), None, None);
+    }
+
+    /// Reloads the document decoded as `encoding`, asking first when that would drop
+    /// unsaved edits.
+    pub fn reopen_with_encoding(&mut self, encoding: FileEncoding) {
+        if let Some(path) = self.selected_file.clone() {
+            self.open_or_ask(path, Some(encoding), None);
+        }
+    }
+
+    fn open_or_ask(&mut self, path: PathBuf, encoding: Option<FileEncoding>, location: Option<Location>) {
+        if self.is_dirty() {
+            self.pending_open = Some(PendingOpen { path, encoding, location, saving: false });
+            return;
+        }
+        self.load_file_with_encoding(&path, encoding);
+    }
+
+    /// Whether the user asked to save the buffer before another file replaces it. The app
+    /// saves it, and the file opens once the buffer is clean.
+    pub fn take_save_before_open(&mut self) -> bool {
+        std::mem::take(&mut self.save_before_open)
+    }
+
+    /// Whether a save is still on its way, which it is while formatting before one.
+    fn save_in_progress(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.formatting.saving();
+        #[cfg(target_arch = "wasm32")]
+        false
+    }
+
+    fn render_open_prompt(&mut self, ui: &egui::Ui) {
+        let Some(pending) = &self.pending_open else {
+            return;
+        };
+
+        if pending.saving {
+            if self.save_before_open || self.save_in_progress() {
+                return;
+            }
+            // The save either went through or failed with a notification saying why.
+            let pending = self.pending_open.take().unwrap();
+            if !self.is_dirty() {
+                self.open_pending(ui, pending);
+            }
+            return;
+        }
+
+        let name = self.selected_file.as_ref().map_or("Untitled".to_string(), |path| {
+            path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string()
+        });
+        let consequence = match pending.encoding {
+            Some(encoding) if self.selected_file.as_ref() == Some(&pending.path) => {
+                format!("Reopening it as {} discards them unless you save first.", encoding.label())
+            }
+            _ => format!(
+                "Opening {} discards them unless you save first.",
+                pending.path.file_name().unwrap_or(pending.path.as_os_str()).to_string_lossy()
+            ),
+        };
+        let mut save = false;
+        let mut discard = false;
+        let mut cancelled = false;
+
+        egui::Window::new("Unsaved Changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!("{} has unsaved changes.", name));
+                ui.label(consequence);
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    save = ui.add_enabled(!self.read_only, egui::Button::new("Save")).clicked();
+                    discard = ui.button("Discard").clicked();
+                    cancelled = ui.button("Cancel").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape));
+                });
+            });
+
+        if cancelled {
+            self.pending_open = None;
+        } else if save {
+            self.save_before_open = true;
+            if let Some(pending) = &mut self.pending_open {
+                pending.saving = true;
+            }
+        } else if discard {
+            if let Some(pending) = self.pending_open.take() {
+                self.open_pending(ui, pending);
+            }
+        }
+    }
+
+    fn open_pending(&mut self, ui: &egui::Ui, pending: PendingOpen) {
+        self.load_file_with_encoding(&pending.path, pending.encoding);
+        if let Some(location) = pending.location {
+            self.jump_to(ui, &location);
+        }
+    }
+
+    /// Asks the app to save the current document in `encoding`.
+    pub fn request_save_with_encoding(&mut self, encoding: FileEncoding) {
+        self.pending_save_encoding = Some(encoding);
+    }
+
+    pub fn take_pending_save_encoding(&mut self) -> Option<FileEncoding> {
+        self.pending_save_encoding.take()
+    }
+
+    /// Makes the next save write `encoding`, or the document's own again for `None`.
+    pub fn set_save_encoding(&mut self, encoding: Option<FileEncoding>) {
+        self.save_encoding = encoding;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn take_save_encoding(&mut self) -> Option<FileEncoding> {
+        self.save_encoding.take()
+    }
+
+    /// Encodes the buffer with the document's line ending and encoding, or the one picked
+    /// for the next save, ready to be written to disk.
+    pub fn encoded_contents(&self) -> Result<Vec<u8>, String> {
+        self.save_encoding.unwrap_or(self.encoding).encode(&self.line_ending.apply(&self.code))
+    }
+
+    /// Whether `path` was modified by someone else since this document was loaded or saved.
+    pub fn changed_on_disk(&self, path: &Path) -> bool {
+        if self.selected_file.as_deref() != Some(path) {
+            return false;
+        }
+
+        match self.disk_state {
+            Some(state) => DiskState::read(path) != Some(state),
+            None => false,
+        }
+    }
+
+    pub fn mark_saved(&mut self, path: PathBuf) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.lsp.save(&path);
+        self.disk_state = DiskState::read(&path);
+        self.selected_file = Some(path);
+        self.saved_version = self.document_version;
+        if let Some(encoding) = self.save_encoding.take() {
+            self.encoding = encoding;
+        }
+    }
+
+    /// Whether the buffer has edits that have not been written to disk.
+    pub fn is_dirty(&self) -> bool {
+        self.document_version != self.saved_version
+    }
+
+    pub fn document_version(&self) -> i32 {
+        self.document_version
+    }
+
+    /// Replaces the buffer with recovered contents, leaving it marked as unsaved.
+    pub fn restore_contents(
+        &mut self,
+        path: Option<PathBuf>,
+        content: String,
+        encoding: FileEncoding,
+        line_ending: LineEnding,
+    ) {
+        self.new_file();
+        if let Some(path) = &path {
+            self.disk_state = DiskState::read(path);
+            self.detect_language(path);
+        }
+        self.selected_file = path;
+        self.code = content;
+        self.encoding = encoding;
+        self.line_ending = line_ending;
+        self.document_version += 1;
+        self.invalidate_caches();
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.line_ending != line_ending {
+            self.line_ending = line_ending;
+            self.document_version += 1;
+        }
+    }
+
+    fn load_file_with_encoding(&mut self, path: &PathBuf, encoding: Option<FileEncoding>) {
+        let size = match std::fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                self.notifications.error(format!("Failed to open {}: {}", path.display(), e));
+                return;
+            }
+        };
+
+        if let Some(task) = self.loading.take() {
+            task.cancel();
+        }
+
+        if size > loader::LARGE_FILE_THRESHOLD {
+            self.loading = Some(FileLoadTask::spawn(path.clone(), size, encoding));
+            return;
+        }
+
+        match loader::load_sync(path, encoding) {
+            Ok(file) => self.apply_loaded_file(file),
+            Err(e) => self.notifications.error(format!("Failed to open {}: {}", path.display(), e)),
+        }
+    }
+
+    fn apply_loaded_file(&mut self, file: LoadedFile) {
+        if let Some(previous) = self.selected_file.as_ref().filter(|_| !self.large_file_mode) {
+            self.completion.remember_buffer(previous, &self.code);
+        }
+        self.completion.close();
+        self.completion.leave_snippet();
+
+        let size = match &file.content {
+            LoadedContent::Text(text) => text.len(),
+            LoadedContent::Mapped(view) => view.len(),
+        };
+        self.large_file_mode = size as u64 > loader::LARGE_FILE_THRESHOLD;
+        self.read_only = file.lossy;
+        self.encoding = file.encoding;
+        self.save_encoding = None;
+
+        match file.content {
+            LoadedContent::Text(text) => {
+                let detected = line_ending::detect(&text);
+                if detected.mixed {
+                    self.notifications.warning(format!(
+                        "{} has mixed line endings; they will be saved as {}",
+                        file.path.display(),
+                        detected.line_ending.label()
+                    ));
+                }
+                self.line_ending = detected.line_ending;
+                self.code = line_ending::normalize(text);
+                self.large_file_view = None;
+            }
+            LoadedContent::Mapped(view) => {
+                self.code.clear();
+                self.large_file_view = Some(view);
+                self.read_only = true;
+                self.notifications.info(format!(
+                    "{} is too large to edit and was opened read-only",
+                    file.path.display()
+                ));
+            }
+        }
+
+        if file.lossy {
+            self.notifications.warning(format!(
+                "{} contains bytes that are not valid {} and was opened read-only",
+                file.path.display(),
+                file.encoding.label()
+            ));
+        }
+
+        self.remember_view_state();
+        self.restore_view_state(&file.path);
+        self.disk_state = DiskState::read(&file.path);
+        self.notified_disk_state = None;
+        self.selected_file = Some(file.path.clone());
+        self.document_version += 1;
+        self.saved_version = self.document_version;
+        self.had_conflicts = false;
+        self.merge_view = None;
+        self.invalidate_caches();
+        self.detect_language(&file.path);
+    }
+
+    fn detect_language(&mut self, path: &Path) {
+        if let Some(name) = path.file_name() {
+            if name.to_string_lossy().eq_ignore_ascii_case("CMakeLists.txt") {
+                self.language = "c".into();
+            } else if let Some(ext) = path.extension() {
+                self.language = ext.to_string_lossy().to_string();
+            }
+        }
+    }
+
+    /// Opens content that was read outside the native file system, such as a browser upload.
+    #[cfg(target_arch = "wasm32")]
+    pub fn load_bytes(&mut self, path: PathBuf, bytes: &[u8]) {
+        self.new_file();
+        self.apply_loaded_file(loader::decode(path, bytes, None));
+    }
+
+    pub fn new_file(&mut self) {
+        if let Some(task) = self.loading.take() {
+            task.cancel();
+        }
+
+        self.remember_view_state();
+        self.completion.close();
+        self.completion.leave_snippet();
+        self.cursor = 0;
+        self.scroll_offset = egui::Vec2::ZERO;
+        self.pending_cursor = Some(0);
+        self.pending_scroll = Some(egui::Vec2::ZERO);
+
+        self.code.clear();
+        self.selected_file = None;
+        self.disk_state = None;
+        self.read_only = false;
+        self.encoding = FileEncoding::default();
+        self.save_encoding = None;
+        self.line_ending = LineEnding::default();
+        self.large_file_mode = false;
+        self.large_file_view = None;
+        self.document_version += 1;
+        self.saved_version = self.document_version;
+        self.had_conflicts = false;
+        self.merge_view = None;
+        self.invalidate_caches();
+    }
+
+    /// Feeds file watcher events into the tree and the open buffer, starting the watcher on first use.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_fs_events(&mut self, ctx: &egui::Context) {
+        if !self.watcher_started {
+            self.watcher_started = true;
+            self.watcher = FileWatcher::new(ctx.clone())
+                .map_err(|e| log::warn!("File watching disabled: {}", e))
+                .ok();
+        }
+
+        let roots = self.project_roots();
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+
+        watcher.watch_roots(&roots);
+        watcher.watch_document(self.selected_file.as_deref());
+
+        let events = watcher.drain();
+        if !events.is_empty() {
+            self.git.request_refresh();
+        }
+        self.file_explorer.apply_fs_events(&events);
+        for event in &events {
+            match event {
+                FsEvent::Created(path) | FsEvent::Modified(path) => self.symbol_index.update_file(path),
+                FsEvent::Removed(path) => self.symbol_index.remove_file(path),
+                FsEvent::Renamed(old, new) => {
+                    self.symbol_index.remove_file(old);
+                    self.symbol_index.update_file(new);
+                }
+            }
+        }
+        self.handle_document_events(&events);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_language_servers(&mut self, configs: Vec<LanguageServerConfig>) {
+        self.lsp.set_configs(configs);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_formatters(&mut self, formatters: Vec<FormatterConfig>) {
+        self.formatting.set_formatters(formatters);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn shutdown_language_servers(&mut self) {
+        self.lsp.shutdown_all();
+    }
+
+    /// Tells the language servers about the open document: opened when the editor switches
+    /// to it, closed when it switches away, and every edit in between.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn sync_language_server(&mut self, ctx: &egui::Context) {
+        self.lsp.set_roots(self.project_roots(), ctx);
+
+        let editable = self.loading.is_none() && self.large_file_view.is_none() && !self.large_file_mode;
+        let document = self.selected_file.clone().filter(|_| editable);
+
+        if self.lsp_document != document {
+            if let Some(previous) = self.lsp_document.take() {
+                self.lsp.close(&previous);
+            }
+            if let Some(path) = &document {
+                self.lsp.open(path, &self.code, ctx);
+            }
+            self.lsp_document = document;
+            self.lsp_version = self.document_version;
+        } else if let Some(path) = self.lsp_document.as_ref().filter(|_| self.lsp_version != self.document_version) {
+            self.lsp.change(path, &self.code);
+            self.lsp_version = self.document_version;
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_language_servers(&mut self, ui: &egui::Ui) {
+        for event in self.lsp.poll() {
+            match event {
+                LspEvent::Stopped { server, error } => {
+                    self.diagnostics.clear_provider(&server);
+                    // The server's documents were forgotten; the open one is opened again with
+                    // whichever server takes over, once there is one.
+                    self.lsp_document = None;
+                    if let Some(error) = error {
+                        self.notifications.error(error);
+                    }
+                }
+                LspEvent::Message { server, level, text } => {
+                    let text = format!("{}: {}", server, text);
+                    match level {
+                        lsp_types::MessageType::ERROR => self.notifications.error(text),
+                        lsp_types::MessageType::WARNING => self.notifications.warning(text),
+                        _ => self.notifications.info(text),
+                    }
+                }
+                LspEvent::Diagnostics { server, path, diagnostics } => self.diagnostics.set(&server, path, diagnostics),
+                LspEvent::Notification { server, method, params } => {
+                    log::trace!("Unhandled {} from {}: {}", method, server, params);
+                }
+                LspEvent::Response { server, id, method, result } if method == "textDocument/completion" => {
+                    let items = result.map_or_else(
+                        |e| {
+                            log::debug!("Completion request {} to {} failed: {}", id, server, e);
+                            Vec::new()
+                        },
+                        |result| convert::completion_items(result, &self.code),
+                    );
+                    self.completion.add_items(id, items);
+                }
+                LspEvent::Response { id, result, .. } if self.pending_lookup.as_ref().is_some_and(|lookup| lookup.id == id) => {
+                    if let Some(lookup) = self.pending_lookup.take() {
+                        self.finish_lookup(ui, lookup, result.map_err(|e| e.to_string()));
+                    }
+                }
+                LspEvent::Response { server, id, result, .. } if self.hover.as_ref().is_some_and(|hover| hover.request == Some(id)) => {
+                    let name = self.hover.as_ref().map(|hover| hover.name.clone()).unwrap_or_default();
+                    let text = result
+                        .map_err(|e| log::debug!("Hover request {} to {} failed: {}", id, server, e))
+                        .ok()
+                        .and_then(convert::hover)
+                        .or_else(|| self.describe(&name));
+                    if let Some(hover) = &mut self.hover {
+                        hover.request = None;
+                        hover.text = text;
+                    }
+                }
+                LspEvent::Response { server, id, result, .. }
+                    if self.outline.request.as_ref().is_some_and(|(request, _)| *request == id) =>
+                {
+                    let Some((_, source)) = self.outline.request.take() else {
+                        continue;
+                    };
+                    let items = match result {
+                        Ok(result) => convert::document_symbols(result),
+                        Err(e) => {
+                            log::debug!("Document symbol request {} to {} failed: {}", id, server, e);
+                            outline::from_tags(&self.code, &self.language)
+                        }
+                    };
+                    self.outline.set_items(items, source);
+                }
+                LspEvent::Response { id, result, .. } if self.code_actions.is_response(id) => {
+                    let actions = result
+                        .map_err(|e| log::debug!("Code action request {} failed: {}", id, e))
+                        .map(convert::code_actions)
+                        .unwrap_or_default();
+                    self.code_actions.receive(actions, &mut self.notifications);
+                }
+                LspEvent::Response { id, result, .. } if self.formatting.is_server_request(id) => {
+                    let Some(request) = self.formatting.take_server_request(id) else {
+                        continue;
+                    };
+                    let formatted = result
+                        .map_err(|e| e.message)
+                        .and_then(convert::text_edits)
+                        .and_then(|edits| edit::apply(&self.code, &edits));
+                    self.finish_format(ui.ctx(), request, formatted);
+                }
+                LspEvent::Response { id, result, .. } if self.refactoring.is_response(id) => {
+                    if let Some(request) = self.refactoring.take_request(id) {
+                        self.finish_refactor_request(request, result.map_err(|e| e.message));
+                    }
+                }
+                LspEvent::Request { server, id, method, params } if method == "workspace/applyEdit" => {
+                    self.server_edit(server, id, params);
+                }
+                LspEvent::Request { server, id, method, .. } => {
+                    log::debug!("Unhandled {} request from {}", method, server);
+                    self.lsp.respond(&server, id, serde_json::Value::Null);
+                }
+                LspEvent::Response { server, id, method, result } => match result {
+                    Ok(_) => log::trace!("Unhandled response {} to {} from {}", id, method, server),
+                    Err(e) => log::debug!("{} request {} to {} failed: {}", method, id, server, e),
+                },
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restart_language_server(&mut self, name: &str, ctx: &egui::Context) {
+        self.lsp.restart(name, ctx);
+        self.diagnostics.clear_provider(name);
+        // Reopen the current document in case the server had failed before it was opened.
+        self.lsp_document = None;
+    }
+
+    /// The language server of the open file and its state, with a menu to restart it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn language_server_status(&mut self, ui: &mut egui::Ui) {
+        let Some((name, state)) = self.selected_file.as_ref().and_then(|path| self.lsp.status(path)) else {
+            return;
+        };
+        let (icon, detail) = match &state {
+            ServerState::Starting => ("◌", "starting".to_string()),
+            ServerState::Running => ("●", "running".to_string()),
+            ServerState::Stopped(Some(error)) => ("⚠", error.clone()),
+            ServerState::Stopped(None) => ("○", "not running".to_string()),
+        };
+
+        let response = ui.menu_button(egui::RichText::new(format!("{} {}", icon, name)).weak(), |ui| {
+            if ui.button("Restart Language Server").clicked() {
+                self.restart_language_server(&name, ui.ctx());
+                ui.close();
+            }
+        });
+        response.response.on_hover_text(detail);
+    }
+
+    /// Picks up new repository status for the explorer and the HEAD version of the open file.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_git(&mut self, ctx: &egui::Context) {
+        self.git.set_roots(&self.project_roots());
+        let poll = self.git.poll(ctx);
+        let status_changed = poll.status_changed;
+
+        if status_changed {
+            self.file_explorer.set_git_status(self.git.status());
+        }
+        // A commit or checkout changes HEAD, so the base is reloaded with every status update.
+        if status_changed || self.selected_file != self.head_path {
+            self.head_path = self.selected_file.clone();
+            match &self.head_path {
+                Some(path) => self.git.request_head(path.clone(), ctx),
+                None => self.head_text = None,
+            }
+        }
+
+        self.finish_git_operations(poll.finished, ctx);
+        for (request, patch) in poll.patches {
+            self.git_diff.show(request, patch, &mut self.diff_view, &mut self.notifications);
+        }
+
+        for (path, contents) in poll.merge_bases {
+            let (Some(view), Some(bytes)) = (&mut self.merge_view, contents) else {
+                continue;
+            };
+            if self.selected_file.as_ref() == Some(&path) {
+                let text = crate::document::encoding::decode(&bytes, Some(self.encoding)).text;
+                view.set_base_file(line_ending::normalize(text));
+            }
+        }
+
+        for (path, contents) in poll.heads {
+            if self.selected_file.as_ref() != Some(&path) {
+                continue;
+            }
+            let head_text = contents.map(|bytes| {
+                line_ending::normalize(crate::document::encoding::decode(&bytes, Some(self.encoding)).text)
+            });
+            if head_text != self.head_text {
+                self.head_text = head_text;
+                self.line_diff = None;
+                self.open_hunk = None;
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn update_line_diff(&mut self) {
+        let Some(base) = self.head_text.as_ref().filter(|_| !self.large_file_mode) else {
+            self.line_diff = None;
+            return;
+        };
+        if self.line_diff.is_none() || self.line_diff_version != self.document_version {
+            self.line_diff = Some(LineDiff::compute(base, &self.code));
+            self.line_diff_version = self.document_version;
+        }
+    }
+
+    /// Puts one changed region of the buffer back to its HEAD version.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn revert_hunk(&mut self, index: usize) {
+        let Some(reverted) = self.line_diff.as_ref().and_then(|diff| diff.revert(index, &self.code)) else {
+            return;
+        };
+        self.code = reverted;
+        self.document_version += 1;
+        self.invalidate_caches();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_hunk_window(&mut self, ctx: &egui::Context) {
+        let Some(index) = self.open_hunk else {
+            return;
+        };
+        let Some(hunk) = self.line_diff.as_ref().and_then(|diff| diff.hunks.get(index)) else {
+            self.open_hunk = None;
+            return;
+        };
+
+        let mut open = true;
+        let mut revert = false;
+        egui::Window::new(format!("Change at line {}", hunk.new_start + 1))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                self.hunk_preview(ui, hunk);
+                ui.add_space(4.0);
+                ui.add_enabled_ui(!self.read_only, |ui| {
+                    revert = ui.button("Revert Change").clicked();
+                });
+            });
+
+        if revert {
+            self.revert_hunk(index);
+        }
+        if revert || !open {
+            self.open_hunk = None;
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn hunk_preview(&self, ui: &mut egui::Ui, hunk: &Hunk) {
+        const MAX_PREVIEW_LINES: usize = 20;
+        let font = egui::FontId::monospace(self.theme.typography.code_font_size);
+        let lines = |prefix: &str, lines: &[String], kind: LineChangeKind, ui: &mut egui::Ui| {
+            for line in lines.iter().take(MAX_PREVIEW_LINES) {
+                ui.label(
+                    egui::RichText::new(format!("{} {}", prefix, line.trim_end_matches(['\n', '\r'])))
+                        .font(font.clone())
+                        .color(self.theme.git.change_color(kind)),
+                );
+            }
+            if lines.len() > MAX_PREVIEW_LINES {
+                ui.weak(format!("… {} more lines", lines.len() - MAX_PREVIEW_LINES));
+            }
+        };
+
+        ui.spacing_mut().item_spacing.y = 0.0;
+        lines("-", &hunk.old_lines, LineChangeKind::Deleted, ui);
+        lines("+", &hunk.new_lines, LineChangeKind::Added, ui);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn show_source_control(&mut self) {
+        self.show_source_control = true;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn run_scm_action(&mut self, action: ScmAction, ctx: &egui::Context) {
+        let operation = match action {
+            ScmAction::Stage { repo, path } => GitOperation::Stage { repo, path },
+            ScmAction::Unstage { repo, path } => GitOperation::Unstage { repo, path },
+            ScmAction::StageAll(repo) => GitOperation::StageAll(repo),
+            ScmAction::UnstageAll(repo) => GitOperation::UnstageAll(repo),
+            ScmAction::Discard { repo, path, untracked } => GitOperation::Discard { repo, path, untracked },
+            ScmAction::Commit { repo, message, amend } => GitOperation::Commit { repo, message, amend },
+            ScmAction::OpenDiff { repo, path, staged, untracked } => {
+                self.git_diff.open(PatchRequest { repo, path, staged, untracked }, &mut self.git, ctx);
+                return;
+            }
+        };
+        self.git.run(operation, ctx);
+    }
+
+    /// Reports git operations that finished in the background and reloads the open diff,
+    /// whose hunks they may have moved.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn finish_git_operations(&mut self, finished: Vec<(GitOperation, Result<(), String>)>, ctx: &egui::Context) {
+        if finished.is_empty() {
+            return;
+        }
+        for (operation, result) in finished {
+            match (operation, result) {
+                (GitOperation::Commit { repo, .. }, Ok(())) => self.source_control.committed(&repo),
+                (GitOperation::Stage { path, .. }, result) if self.resolving.as_ref() == Some(&path) => {
+                    self.resolving = None;
+                    if let Err(e) = result {
+                        self.notifications.error(e);
+                        continue;
+                    }
+                    if self.selected_file.as_ref() == Some(&path) {
+                        self.had_conflicts = false;
+                        self.merge_view = None;
+                    }
+                    self.notifications.info(format!("Marked {} as resolved", path.display()));
+                }
+                (_, Ok(())) => {}
+                (GitOperation::ApplyPatch { .. }, Err(e)) => {
+                    self.notifications.error(format!("Failed to apply the change: {}", e))
+                }
+                (_, Err(e)) => self.notifications.error(e),
+            }
+        }
+        if !self.git_diff.reload(&mut self.git, ctx) {
+            self.diff_view = None;
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn handle_document_events(&mut self, events: &[FsEvent]) {
+        let Some(path) = self.selected_file.clone() else {
+            return;
+        };
+
+        // The file itself or one of its folders may have been renamed or moved.
+        let renamed_to = events.iter().find_map(|event| match event {
+            FsEvent::Renamed(from, to) if !path.exists() => path
+                .strip_prefix(from)
+                .ok()
+                .map(|rest| if rest.as_os_str().is_empty() { to.clone() } else { to.join(rest) }),
+            _ => None,
+        });
+        if let Some(to) = renamed_to {
+            if let Some(mut state) = self.view_states.remove(&path) {
+                state.path = to.clone();
+                self.view_states.insert(to.clone(), state);
+            }
+            self.disk_state = DiskState::read(&to);
+            self.detect_language(&to);
+            self.file_explorer.selected_file = Some(to.clone());
+            self.selected_file = Some(to);
+            return;
+        }
+
+        let removed = events.iter().any(|event| matches!(event, FsEvent::Removed(p) if path.starts_with(p)));
+        if removed && !path.exists() {
+            self.notifications.warning(format!(
+                "{} was deleted on disk; saving will recreate it",
+                path.display()
+            ));
+            return;
+        }
+
+        let changed = events.iter().any(|event| match event {
+            FsEvent::Created(p) | FsEvent::Modified(p) | FsEvent::Renamed(_, p) => *p == path,
+            FsEvent::Removed(_) => false,
+        });
+        if changed {
+            self.check_disk_changes(&path);
+        }
+    }
+
+    /// Carries out file operations requested from the explorer's context menu or by dragging.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn run_explorer_commands(&mut self) {
+        for command in self.file_explorer.take_commands() {
+            let result = match command {
+                ExplorerCommand::NewFile { dir, name } => file_ops::create_file(&dir, &name).map(|path| {
+                    self.file_explorer.open_file(path.clone());
+                    vec![FsEvent::Created(path)]
+                }),
+                ExplorerCommand::NewFolder { dir, name } => {
+                    file_ops::create_dir(&dir, &name).map(|path| vec![FsEvent::Created(path)])
+                }
+                ExplorerCommand::Rename { path, name } => {
+                    file_ops::rename(&path, &name).map(|to| vec![FsEvent::Renamed(path, to)])
+                }
+                ExplorerCommand::Move { path, into } => {
+                    file_ops::move_into(&path, &into).map(|to| vec![FsEvent::Renamed(path, to)])
+                }
+                ExplorerCommand::Duplicate(path) => file_ops::duplicate(&path).map(|copy| vec![FsEvent::Created(copy)]),
+                ExplorerCommand::Delete(path) => {
+                    self.pending_delete = Some(PendingDelete { path, trash_error: None });
+                    Ok(Vec::new())
+                }
+                ExplorerCommand::Reveal(path) => file_ops::reveal(&path).map(|_| Vec::new()),
+                ExplorerCommand::RemoveRoot(path) => {
+                    self.file_explorer.remove_root(&path);
+                    self.workspace_changed = true;
+                    Ok(Vec::new())
+                }
+                ExplorerCommand::Compare(old, new) => self.compare_files(&old, &new).map(|_| Vec::new()),
+            };
+
+            match result {
+                Ok(events) => self.apply_local_fs_events(&events),
+                Err(e) => self.notifications.error(e),
+            }
+        }
+    }
+
+    /// Updates the tree and the open buffer right away for a change made from the
+    /// explorer, rather than waiting for the watcher to report it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_local_fs_events(&mut self, events: &[FsEvent]) {
+        self.file_explorer.apply_fs_events(events);
+        self.handle_document_events(events);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_delete_prompt(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending_delete else {
+            return;
+        };
+
+        let name = pending.path.file_name().unwrap_or(pending.path.as_os_str()).to_string_lossy().to_string();
+        let mut confirmed = false;
+        let mut cancelled = false;
+
+        egui::Window::new("Delete")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                match &pending.trash_error {
+                    None => {
+                        ui.label(format!("Move {} to the trash?", name));
+                    }
+                    Some(error) => {
+                        ui.label(format!("{} could not be moved to the trash: {}", name, error));
+                        ui.label("Delete it permanently? This cannot be undone.");
+                    }
+                }
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    let label = if pending.trash_error.is_none() { "Move to Trash" } else { "Delete Permanently" };
+                    confirmed = ui.button(label).clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+
+        if cancelled {
+            self.pending_delete = None;
+        }
+        if !confirmed {
+            return;
+        }
+
+        let Some(pending) = self.pending_delete.take() else {
+            return;
+        };
+        let result = match pending.trash_error {
+            None => file_ops::move_to_trash(&pending.path),
+            Some(_) => file_ops::delete_permanently(&pending.path),
+        };
+
+        match result {
+            Ok(()) => self.apply_local_fs_events(&[FsEvent::Removed(pending.path)]),
+            Err(e) if pending.trash_error.is_none() => {
+                self.pending_delete = Some(PendingDelete { path: pending.path, trash_error: Some(e) });
+            }
+            Err(e) => self.notifications.error(e),
+        }
+    }
+
+    /// Reloads a clean buffer whose file changed on disk, or asks before discarding local edits.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn check_disk_changes(&mut self, path: &Path) {
+        let current = DiskState::read(path);
+        if current.is_none() || current == self.disk_state || self.loading.is_some() {
+            return;
+        }
+
+        if !self.is_dirty() {
+            self.load_file(path);
+            return;
+        }
+
+        if self.notified_disk_state == current {
+            return;
+        }
+        self.notified_disk_state = current;
+        self.notifications.push_with_actions(
+            NotificationLevel::Warning,
+            format!("{} changed on disk and you have unsaved edits", path.display()),
+            vec![
+                ("Reload".into(), NotificationAction::ReloadFile(path.to_path_buf())),
+                ("Keep Mine".into(), NotificationAction::Dismiss),
+            ],
+        );
+    }
+
+    fn poll_loading(&mut self, ctx: &egui::Context) {
+        let Some(task) = &self.loading else {
+            return;
+        };
+
+        match task.poll() {
+            Some(Ok(file)) => {
+                self.loading = None;
+                self.apply_loaded_file(file);
+            }
+            Some(Err(e)) => {
+                let path = task.path.clone();
+                self.loading = None;
+                self.notifications.error(format!("Failed to open {}: {}", path.display(), e));
+            }
+            None => ctx.request_repaint_after(std::time::Duration::from_millis(50)),
+        }
+    }
+
+    fn invalidate_caches(&mut self) {
+        self.cached_line_height = None;
+        self.cached_max_line_width = None;
+        self.cached_layout_job = None;
+        self.cache_version = self.document_version;
+    }
+
+    fn get_line_height(&mut self, ui: &egui::Ui) -> f32 {
+        if self.cached_line_height.is_none() || self.cache_version != self.document_version {
+            self.cached_line_height = Some(
+                ui.fonts(|f| f.row_height(&egui::FontId::monospace(self.theme.typography.code_font_size)))
+            );
+            self.cache_version = self.document_version;
+        }
+        self.cached_line_height.unwrap()
+    }
+
+    fn get_max_line_width(&mut self, ui: &egui::Ui) -> f32 {
+        if self.cached_max_line_width.is_none() || self.cache_version != self.document_version {
+            let max_lines_to_check = 1000;
+            let lines: Vec<&str> = self.code.lines().take(max_lines_to_check).collect();
+
+            self.cached_max_line_width = Some(
+                lines.iter()
+                    .map(|line| {
+                        ui.fonts(|f| f.layout_no_wrap(
+                            line.to_string(),
+                            egui::FontId::monospace(self.theme.typography.code_font_size),
+                            egui::Color32::WHITE
+                        ).size().x)
+                    })
+                    .fold(0.0, f32::max)
+                    .max(800.0)
+            );
+            self.cache_version = self.document_version;
+        }
+        self.cached_max_line_width.unwrap()
+    }
+
+    fn get_highlighted_layout(&mut self, text: &str) -> egui::text::LayoutJob {
+        if self.cached_layout_job.is_none() || self.cache_version != self.document_version {
+            let mut layout_job = if self.large_file_mode {
+                egui::text::LayoutJob::simple(
+                    text.to_owned(),
+                    egui::FontId::monospace(self.theme.typography.code_font_size),
+                    self.theme.text_color(),
+                    f32::INFINITY,
+                )
+            } else {
+                self.theme.highlight_code(text, &self.language)
+            };
+            layout_job.wrap.max_width = f32::INFINITY;
+            self.cached_layout_job = Some(layout_job);
+            self.cache_version = self.document_version;
+        }
+        self.cached_layout_job.as_ref().unwrap().clone()
+    }
+}
+
+impl ZenView for CodeEditor {
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        self.theme.apply_to_context(ui.ctx());
+        self.poll_clipboard_compare(ui.ctx());
+
+        egui::TopBottomPanel::bottom("status_bar")
+            .show_separator_line(false)
+            .show_inside(ui, |ui| {
+                self.render_status_bar(ui);
+            });
+
+        let side_panel = egui::SidePanel::left("file_tree")
+            .resizable(true)
+            .default_width(self.side_panel_width)
+            .width_range(150.0..=400.0)
+            .show_animated_inside(ui, !self.file_explorer.roots.is_empty(), |ui| {
+                self.render_side_panel(ui);
+            });
+        if let Some(panel) = side_panel {
+            self.side_panel_width = panel.response.rect.width();
+        }
+
+        if self.show_outline {
+            egui::SidePanel::right("outline")
+                .resizable(true)
+                .default_width(240.0)
+                .width_range(160.0..=480.0)
+                .show_inside(ui, |ui| {
+                    self.render_outline_panel(ui);
+                });
+        }
+
+        if self.bottom_panel.is_some() {
+            egui::TopBottomPanel::bottom("bottom_panel")
+                .resizable(true)
+                .default_height(180.0)
+                .show_inside(ui, |ui| {
+                    self.render_bottom_panel(ui);
+                });
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.run_explorer_commands();
+            self.render_delete_prompt(ui.ctx());
+        }
+        self.render_rename_prompt(ui.ctx());
+        #[cfg(not(target_arch = "wasm32"))]
+        self.render_search_prompt(ui.ctx());
+        self.render_open_prompt(ui);
+
+        if let Some(path) = self.file_explorer.take_pending_file() {
+            self.load_file(&path);
+        }
+
+        self.poll_loading(ui.ctx());
+        #[cfg(not(target_arch = "wasm32"))]
+        self.poll_fs_events(ui.ctx());
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.poll_git(ui.ctx());
+            self.render_hunk_window(ui.ctx());
+            self.sync_language_server(ui.ctx());
+            self.poll_language_servers(ui);
+            self.poll_symbols(ui.ctx());
+            self.update_code_actions(ui.ctx());
+            self.update_format(ui.ctx());
+            self.terminals.poll(ui.ctx());
+            self.task_output.poll(ui.ctx(), &mut self.diagnostics);
+        }
+
+        self.update_conflicts();
+        if self.show_outline || self.palette.is_some() {
+            self.refresh_outline();
+        }
+        self.show_palette(ui);
+
+        egui::CentralPanel::default().show_inside(ui, |ui| {
+            if self.refactoring.is_previewing() {
+                self.render_edit_preview(ui);
+                return;
+            }
+            if self.diff_view.is_some() {
+                self.render_diff_panel(ui);
+                return;
+            }
+            if self.merge_view.is_some() {
+                self.render_merge_panel(ui);
+                return;
+            }
+
+            if self.loading.is_some() {
+                self.render_loading_panel(ui);
+            } else if self.large_file_view.is_some() {
+                self.render_large_file_panel(ui);
+            } else {
+                self.render_editor_panel(ui);
+            }
+        });
+    }
+}
+
+impl CodeEditor {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_side_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.show_source_control, false, "Files");
+            ui.selectable_value(&mut self.show_source_control, true, "Source Control");
+        });
+        ui.separator();
+
+        if !self.show_source_control {
+            self.file_explorer.render(ui, &self.theme.git);
+            return;
+        }
+
+        let status = self.git.status();
+        let actions = self.source_control.render(ui, &status, &self.theme.git, self.git.is_busy());
+        for action in actions {
+            self.run_scm_action(action, ui.ctx());
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn render_side_panel(&mut self, ui: &mut egui::Ui) {
+        self.file_explorer.render(ui, &self.theme.git);
+    }
+
+    fn render_diff_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(view) = &mut self.diff_view else {
+            return;
+        };
+
+        match view.render(ui, &self.theme) {
+            Some(DiffViewAction::Close) => {
+                self.diff_view = None;
+                #[cfg(not(target_arch = "wasm32"))]
+                self.git_diff.close();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Some(DiffViewAction::Hunk(index, action)) => {
+                if let Some(operation) = self.git_diff.hunk_operation(index, action) {
+                    self.git.run(operation, ui.ctx());
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            Some(DiffViewAction::Hunk(..)) => {}
+            // Only the preview of a workspace edit asks to be confirmed.
+            Some(DiffViewAction::Confirm) | None => {}
+        }
+    }
+
+    fn open_comparison(&mut self, view: DiffView) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.git_diff.close();
+        self.diff_view = Some(view);
+    }
+
+    fn document_name(&self) -> String {
+        self.selected_file
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Untitled".to_string())
+    }
+
+    /// Compares the buffer with the file as it was last saved.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compare_with_saved(&mut self) {
+        let Some(path) = self.selected_file.clone() else {
+            return;
+        };
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                let saved = line_ending::normalize(crate::document::encoding::decode(&bytes, Some(self.encoding)).text);
+                let name = self.document_name();
+                let view = DiffView::compare(format!("{} (Saved ↔ Editor)", name), "Saved", "Editor", &saved, &self.code);
+                self.open_comparison(view);
+            }
+            Err(e) => self.notifications.error(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn compare_files(&mut self, old: &Path, new: &Path) -> Result<(), String> {
+        let read = |path: &Path| {
+            std::fs::read(path)
+                .map(|bytes| line_ending::normalize(crate::document::encoding::decode(&bytes, None).text))
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+        };
+        let (old_text, new_text) = (read(old)?, read(new)?);
+
+        let name = |path: &Path| path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let title = format!("{} ↔ {}", name(old), name(new));
+        let view = DiffView::compare(title, &old.display().to_string(), &new.display().to_string(), &old_text, &new_text);
+        self.open_comparison(view);
+        Ok(())
+    }
+
+    /// Asks the platform for the clipboard contents; the comparison opens once they arrive as a paste event.
+    pub fn compare_with_clipboard(&mut self, ctx: &egui::Context) {
+        self.clipboard_compare_requested = Some(ctx.input(|i| i.time));
+        ctx.send_viewport_cmd(egui::ViewportCommand::RequestPaste);
+        ctx.request_repaint();
+    }
+
+    /// Takes the requested clipboard contents out of the input before the text editor can paste them.
+    ///
+    /// An empty or unreadable clipboard sends no paste at all, so the request is given up
+    /// after a short while; otherwise the next Ctrl+V would be taken for the comparison.
+    fn poll_clipboard_compare(&mut self, ctx: &egui::Context) {
+        let Some(requested) = self.clipboard_compare_requested else {
+            return;
+        };
+        let pasted = ctx.input_mut(|input| {
+            let index = input.events.iter().position(|event| matches!(event, egui::Event::Paste(_)))?;
+            match input.events.remove(index) {
+                egui::Event::Paste(text) => Some(text),
+                _ => None,
+            }
+        });
+        let Some(text) = pasted else {
+            let waited = ctx.input(|i| i.time) - requested;
+            if waited > CLIPBOARD_TIMEOUT {
+                self.clipboard_compare_requested = None;
+                self.notifications.warning("The clipboard is empty or holds no text");
+            } else {
+                ctx.request_repaint_after(std::time::Duration::from_secs_f64(CLIPBOARD_TIMEOUT - waited));
+            }
+            return;
+        };
+
+        self.clipboard_compare_requested = None;
+        let clipboard = line_ending::normalize(text);
+        let name = self.document_name();
+        let view = DiffView::compare(format!("{} (Clipboard ↔ Editor)", name), "Clipboard", "Editor", &clipboard, &self.code);
+        self.open_comparison(view);
+    }
+
+    fn update_conflicts(&mut self) {
+        if self.conflicts_version == self.document_version {
+            return;
+        }
+        self.conflicts_version = self.document_version;
+        self.conflicts = if self.large_file_mode { Vec::new() } else { conflict::find_conflicts(&self.code) };
+        self.had_conflicts |= !self.conflicts.is_empty();
+    }
+
+    /// Replaces a conflict block with `replacement` in one undoable step.
+    fn resolve_conflict(&mut self, ctx: &egui::Context, index: usize, replacement: &str) {
+        let Some(conflict) = self.conflicts.get(index) else {
+            return;
+        };
+        let edit = conflict.edit(&self.code, replacement);
+        let text = conflict::resolve(&self.code, conflict, replacement);
+        self.replace_buffer(ctx, text, &[edit]);
+        self.update_conflicts();
+    }
+
+    fn accept_conflict(&mut self, ctx: &egui::Context, index: usize, resolution: Resolution) {
+        if let Some(conflict) = self.conflicts.get(index) {
+            let replacement = conflict.resolved_text(&self.code, resolution);
+            self.resolve_conflict(ctx, index, &replacement);
+        }
+    }
+
+    /// Resolves every conflict in the file the same way, last first so earlier ranges stay
+    /// valid, as a single step that Ctrl+Z takes back.
+    fn accept_all_conflicts(&mut self, ctx: &egui::Context, resolution: Resolution) {
+        let mut text = self.code.clone();
+        let mut edits = Vec::with_capacity(self.conflicts.len());
+        for conflict in self.conflicts.iter().rev() {
+            let replacement = conflict.resolved_text(&self.code, resolution);
+            edits.push(conflict.edit(&self.code, &replacement));
+            text = conflict::resolve(&text, conflict, &replacement);
+        }
+        if edits.is_empty() {
+            return;
+        }
+        self.replace_buffer(ctx, text, &edits);
+        self.update_conflicts();
+    }
+
+    /// Moves the cursor to the next or previous conflict, wrapping around the file.
+    fn go_to_conflict(&mut self, ui: &egui::Ui, forward: bool) {
+        if self.conflicts.is_empty() {
+            return;
+        }
+        let cursor_line = self.code.chars().take(self.cursor).filter(|&c| c == '\n').count();
+        let target = if forward {
+            self.conflicts.iter().find(|c| c.start_line > cursor_line).or(self.conflicts.first())
+        } else {
+            self.conflicts.iter().rev().find(|c| c.start_line < cursor_line).or(self.conflicts.last())
+        };
+        let Some((start, line)) = target.map(|c| (c.range.start, c.start_line)) else {
+            return;
+        };
+        let cursor = self.code[..start].chars().count();
+        self.reveal(ui, cursor, line);
+    }
+
+    /// Puts the cursor at a character index and scrolls its line into view, a few lines from the top.
+    fn reveal(&mut self, ui: &egui::Ui, cursor: usize, line: usize) {
+        let line_height = self.get_line_height(ui);
+        self.pending_cursor = Some(cursor);
+        self.pending_scroll = Some(egui::vec2(self.scroll_offset.x, line.saturating_sub(3) as f32 * line_height));
+    }
+
+    /// Opens the file of `location` unless it is already open and moves the cursor there,
+    /// remembering where it was for [`Self::navigate_back`].
+    pub fn go_to_location(&mut self, ui: &egui::Ui, location: Location) {
+        if let Some(current) = self.current_location().filter(|current| *current != location) {
+            self.navigation.record(current);
+        }
+        self.jump_to(ui, &location);
+    }
+
+    fn jump_to(&mut self, ui: &egui::Ui, location: &Location) {
+        let path = &location.path;
+        if self.selected_file.as_ref() != Some(path) {
+            if self.is_dirty() {
+                self.open_or_ask(path.clone(), None, Some(location.clone()));
+                return;
+            }
+            self.load_file(path);
+        }
+        // Files too large to edit load in the background and have no cursor to move.
+        if self.selected_file.as_ref() != Some(path) || self.large_file_view.is_some() {
+            return;
+        }
+
+        self.diff_view = None;
+        self.merge_view = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        self.git_diff.close();
+        let cursor = location.position.char_index(&self.code);
+        self.reveal(ui, cursor, location.position.line);
+    }
+
+    fn current_location(&self) -> Option<Location> {
+        let path = self.selected_file.clone()?;
+        Some(Location::new(path, TextPosition::from_char_index(&self.code, self.cursor)))
+    }
+
+    pub fn navigate_back(&mut self, ui: &egui::Ui) {
+        let Some(current) = self.current_location() else {
+            return;
+        };
+        if let Some(location) = self.navigation.back(current) {
+            self.jump_to(ui, &location);
+        }
+    }
+
+    pub fn navigate_forward(&mut self, ui: &egui::Ui) {
+        let Some(current) = self.current_location() else {
+            return;
+        };
+        if let Some(location) = self.navigation.forward(current) {
+            self.jump_to(ui, &location);
+        }
+    }
+
+    pub fn toggle_problems(&mut self) {
+        self.bottom_panel = match self.bottom_panel {
+            Some(BottomPanel::Problems) => None,
+            _ => Some(BottomPanel::Problems),
+        };
+    }
+
+    /// Shows the terminals, starting one in the project root when there is none yet.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn toggle_terminal(&mut self, ctx: &egui::Context) {
+        if self.bottom_panel == Some(BottomPanel::Terminal) {
+            self.bottom_panel = None;
+            return;
+        }
+        self.bottom_panel = Some(BottomPanel::Terminal);
+        if self.terminals.is_empty() {
+            self.new_terminal(ctx);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new_terminal(&mut self, ctx: &egui::Context) {
+        let dir = self
+            .project_root()
+            .cloned()
+            .or_else(dirs::home_dir)
+            .unwrap_or_else(|| PathBuf::from("."));
+        match self.terminals.open(&dir, ctx) {
+            Ok(()) => self.bottom_panel = Some(BottomPanel::Terminal),
+            Err(e) => self.notifications.error(e),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn toggle_task_output(&mut self) {
+        self.bottom_panel = match self.bottom_panel {
+            Some(BottomPanel::Output) => None,
+            _ => Some(BottomPanel::Output),
+        };
+    }
+
+    /// Opens the palette listing the tasks defined for the project.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn show_tasks(&mut self) {
+        if self.task_output.load(&self.project_roots(), &mut self.notifications) {
+            self.palette = Some((PaletteKind::Tasks, Palette::new("Run task")));
+        }
+    }
+
+    /// Runs a task with its output in the Output panel.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn run_task(&mut self, ctx: &egui::Context, task: Task) {
+        let root = self.project_root().cloned();
+        match self.task_output.start(task, root.as_deref(), self.selected_file.as_deref(), &mut self.diagnostics, ctx) {
+            Ok(()) => self.bottom_panel = Some(BottomPanel::Output),
+            Err(e) => self.notifications.error(e),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn stop_task(&mut self) {
+        self.task_output.stop();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn is_task_running(&self) -> bool {
+        self.task_output.is_running()
+    }
+
+    /// The Problems, References, Terminal and Output panels below the editor, one at a
+    /// time behind tabs.
+    fn render_bottom_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(mut panel) = self.bottom_panel else {
+            return;
+        };
+        let mut close = false;
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut panel, BottomPanel::Problems, "Problems");
+            ui.selectable_value(&mut panel, BottomPanel::References, "References");
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.selectable_value(&mut panel, BottomPanel::Terminal, "Terminal");
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.selectable_value(&mut panel, BottomPanel::Output, "Output");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                close = ui.small_button("🗙").on_hover_text("Close").clicked();
+            });
+        });
+        self.bottom_panel = (!close).then_some(panel);
+
+        let roots = self.project_roots();
+        let clicked = match panel {
+            BottomPanel::Problems => self.problems.render(ui, &self.theme, &self.diagnostics, &roots),
+            BottomPanel::References => self.references.render(ui, &roots),
+            #[cfg(not(target_arch = "wasm32"))]
+            BottomPanel::Terminal => {
+                match self.terminals.render(ui, &self.theme) {
+                    Some(TerminalAction::New) => self.new_terminal(ui.ctx()),
+                    Some(TerminalAction::Closed) => self.bottom_panel = None,
+                    None => {}
+                }
+                None
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            BottomPanel::Output => match self.task_output.render(ui, &self.theme, &roots) {
+                Some(TaskOutputAction::GoTo(location)) => Some(location),
+                Some(TaskOutputAction::Stop) => {
+                    self.stop_task();
+                    None
+                }
+                Some(TaskOutputAction::Rerun(task)) => {
+                    self.run_task(ui.ctx(), *task);
+                    None
+                }
+                None => None,
+            },
+        };
+        if let Some(location) = clicked {
+            self.go_to_location(ui, location);
+        }
+    }
+
+    /// The identifier at a character index.
+    fn word_at(&self, index: usize) -> Option<(Range<usize>, String)> {
+        let range = position::word_at(&self.code, index)?;
+        let word = self.code.chars().skip(range.start).take(range.len()).collect();
+        Some((range, word))
+    }
+
+    /// Brings the language server up to date with the buffer and sends it a request about
+    /// the character at `index`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn request_at(&mut self, index: usize, request: fn(&mut LspManager, &Path, usize) -> Option<i64>) -> Option<i64> {
+        let path = self.lsp_document.clone()?;
+        self.lsp.change(&path, &self.code);
+        self.lsp_version = self.document_version;
+        request(&mut self.lsp, &path, completion::byte_offset(&self.code, index))
+    }
+
+    /// Jumps to the declaration of the identifier at `index`, asking the language server
+    /// when there is one and the symbol index otherwise.
+    fn go_to_definition(&mut self, ui: &egui::Ui, index: usize) {
+        let Some((_, name)) = self.word_at(index) else {
+            return;
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(id) = self.request_at(index, LspManager::definition) {
+            self.pending_lookup = Some(PendingLookup { id, kind: LookupKind::Definition, name });
+            return;
+        }
+        let definitions = self.tag_definitions(&name);
+        self.show_definitions(ui, &name, definitions);
+    }
+
+    /// Declarations of `name` found by the tag patterns, in the open buffer and the project.
+    fn tag_definitions(&self, name: &str) -> Vec<Reference> {
+        let mut found: Vec<Reference> = Vec::new();
+        if let Some(path) = &self.selected_file {
+            found.extend(
+                tags::extract(&self.code, &self.language)
+                    .into_iter()
+                    .filter(|symbol| symbol.name == name && symbol.kind.is_definition())
+                    .map(|symbol| Reference { location: Location::new(path, symbol.position), preview: symbol.signature }),
+            );
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        found.extend(
+            self.symbol_index
+                .definitions(name)
+                .into_iter()
+                // The open buffer was read above and may be ahead of the file on disk.
+                .filter(|(location, _)| self.selected_file.as_ref() != Some(&location.path))
+                .map(|(location, symbol)| Reference { location, preview: symbol.signature.clone() }),
+        );
+        found
+    }
+
+    /// Goes to the only definition, or lists them when there are several.
+    fn show_definitions(&mut self, ui: &egui::Ui, name: &str, definitions: Vec<Reference>) {
+        match definitions.len() {
+            #[cfg(not(target_arch = "wasm32"))]
+            0 if self.symbol_index.is_building() => {
+                self.notifications.info(format!("No definition found for `{}` yet, the project is still being indexed", name))
+            }
+            0 => self.notifications.info(format!("No definition found for `{}`", name)),
+            1 => {
+                let location = definitions.into_iter().next().map(|definition| definition.location);
+                if let Some(location) = location {
+                    self.go_to_location(ui, location);
+                }
+            }
+            _ => {
+                self.references.start(format!("definitions of `{}`", name));
+                self.references.add_results(definitions);
+                self.references.finish();
+                self.bottom_panel = Some(BottomPanel::References);
+            }
+        }
+    }
+
+    /// Lists every use of the identifier at `index` in the References panel, from the
+    /// language server when there is one and by searching the project files otherwise.
+    fn find_references(&mut self, ctx: &egui::Context, index: usize) {
+        let Some((_, name)) = self.word_at(index) else {
+            return;
+        };
+        self.references.start(format!("references to `{}`", name));
+        self.bottom_panel = Some(BottomPanel::References);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(id) = self.request_at(index, LspManager::references) {
+            self.pending_lookup = Some(PendingLookup { id, kind: LookupKind::References, name });
+            return;
+        }
+        self.search_references(ctx, &name);
+    }
+
+    /// Whole-word occurrences of `name` in the open buffer, then in the other project files.
+    fn search_references(&mut self, ctx: &egui::Context, name: &str) {
+        if let Some(path) = &self.selected_file {
+            self.references.add_results(search::references_in(path, &self.code, name));
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let files: Vec<PathBuf> = self
+                .symbol_index
+                .files()
+                .into_iter()
+                .filter(|file| self.selected_file.as_ref() != Some(file))
+                .collect();
+            let (sender, receiver) = mpsc::channel();
+            let name = name.to_string();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let _ = sender.send(search::references(&files, &name));
+                ctx.request_repaint();
+            });
+            self.reference_search = Some(receiver);
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = ctx;
+            self.references.finish();
+        }
+    }
+
+    /// Shows what a language server found for a definition or references request, falling
+    /// back to the symbol index when it failed or knew of no definition.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn finish_lookup(&mut self, ui: &egui::Ui, lookup: PendingLookup, result: Result<serde_json::Value, String>) {
+        let locations = result
+            .map_err(|e| log::debug!("Request {} for `{}` failed: {}", lookup.id, lookup.name, e))
+            .ok()
+            .map(convert::locations);
+        match lookup.kind {
+            LookupKind::Definition => {
+                let definitions = match locations.filter(|locations| !locations.is_empty()) {
+                    Some(locations) => self.with_previews(locations),
+                    None => self.tag_definitions(&lookup.name),
+                };
+                self.show_definitions(ui, &lookup.name, definitions);
+            }
+            LookupKind::References => match locations {
+                Some(locations) => {
+                    let references = self.with_previews(locations);
+                    self.references.add_results(references);
+                    self.references.finish();
+                }
+                None => self.search_references(ui.ctx(), &lookup.name),
+            },
+        }
+    }
+
+    /// Pairs each location with its line, read from the open buffer or from disk.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn with_previews(&self, locations: Vec<Location>) -> Vec<Reference> {
+        let mut files: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        locations
+            .into_iter()
+            .map(|location| {
+                let lines = files.entry(location.path.clone()).or_insert_with(|| {
+                    let text = if self.selected_file.as_ref() == Some(&location.path) {
+                        self.code.clone()
+                    } else {
+                        std::fs::read_to_string(&location.path).unwrap_or_default()
+                    };
+                    text.lines().map(|line| line.trim().to_string()).collect()
+                });
+                let preview = lines.get(location.position.line).cloned().unwrap_or_default();
+                Reference { location, preview }
+            })
+            .collect()
+    }
+
+    /// Keeps the symbol index on the project roots and collects project search results.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_symbols(&mut self, ctx: &egui::Context) {
+        self.symbol_index.set_roots(self.file_explorer.filtered_roots(), ctx);
+        self.symbol_index.poll();
+
+        let Some(receiver) = &self.reference_search else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(references) => {
+                self.references.add_results(references);
+                self.references.finish();
+                self.reference_search = None;
+            }
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.references.finish();
+                self.reference_search = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+        }
+    }
+
+    /// Looks up the word under the mouse pointer once the pointer has rested on it.
+    fn update_hover(&mut self, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput) {
+        let Some(pointer) = output.response.hover_pos() else {
+            self.hover = None;
+            return;
+        };
+        let resting = ui.input(|i| i.pointer.time_since_last_movement());
+        if resting < HOVER_DELAY {
+            self.hover = None;
+            ui.ctx().request_repaint_after_secs(HOVER_DELAY - resting);
+            return;
+        }
+
+        let index = output.galley.cursor_from_pos(pointer - output.galley_pos).index;
+        let Some((range, name)) = self.word_at(index) else {
+            self.hover = None;
+            return;
+        };
+        if self.hover.as_ref().is_some_and(|hover| hover.range == range && hover.version == self.document_version) {
+            return;
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let request = self.request_at(index, LspManager::hover);
+        #[cfg(target_arch = "wasm32")]
+        let request = None;
+        let text = if request.is_none() { self.describe(&name) } else { None };
+        self.hover = Some(SymbolHover { range, version: self.document_version, name, request, text });
+    }
+
+    /// What the tag patterns know about `name`: the lines it is declared on.
+    fn describe(&self, name: &str) -> Option<String> {
+        const MAX_SIGNATURES: usize = 5;
+        let definitions = self.tag_definitions(name);
+        if definitions.is_empty() {
+            return None;
+        }
+        let mut signatures: Vec<String> = definitions.into_iter().map(|definition| definition.preview).collect();
+        signatures.dedup();
+        signatures.truncate(MAX_SIGNATURES);
+        Some(signatures.join("\n"))
+    }
+
+    pub fn toggle_outline(&mut self) {
+        self.show_outline = !self.show_outline;
+    }
+
+    /// Rebuilds the outline after the document changed, from the language server when
+    /// it offers one and from the tag patterns otherwise.
+    fn refresh_outline(&mut self) {
+        let Some(path) = self.selected_file.clone().filter(|_| self.large_file_view.is_none()) else {
+            self.outline.clear();
+            return;
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let server_running = self.lsp.status(&path).is_some_and(|(_, state)| state == ServerState::Running);
+        #[cfg(target_arch = "wasm32")]
+        let server_running = false;
+        let source = OutlineSource { path, version: self.document_version, server_running };
+        if self.outline.source.as_ref() == Some(&source) {
+            return;
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.lsp_document.as_ref() == Some(&source.path) {
+            if self.outline.request.is_some() {
+                return;
+            }
+            self.lsp.change(&source.path, &self.code);
+            self.lsp_version = self.document_version;
+            if let Some(id) = self.lsp.document_symbols(&source.path) {
+                self.outline.request = Some((id, source));
+                return;
+            }
+        }
+        self.outline.set_items(outline::from_tags(&self.code, &self.language), source);
+    }
+
+    fn render_outline_panel(&mut self, ui: &mut egui::Ui) {
+        let cursor_line = self.code.chars().take(self.cursor).filter(|&c| c == '\n').count();
+        if let Some(position) = self.outline.render(ui, cursor_line) {
+            if let Some(path) = self.selected_file.clone() {
+                self.go_to_location(ui, Location::new(path, position));
+            }
+        }
+    }
+
+    /// Opens the palette listing the open document's symbols.
+    pub fn show_document_symbols(&mut self) {
+        self.palette = Some((PaletteKind::DocumentSymbols, Palette::new("Go to symbol in file")));
+    }
+
+    /// Opens the palette listing every symbol in the project.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn show_workspace_symbols(&mut self) {
+        let hint = if self.symbol_index.is_building() {
+            "Go to symbol in workspace (indexing...)"
+        } else {
+            "Go to symbol in workspace"
+        };
+        self.palette = Some((PaletteKind::WorkspaceSymbols, Palette::new(hint)));
+    }
+
+    /// Opens the palette listing every file the explorer shows, across all roots.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn show_go_to_file(&mut self, ctx: &egui::Context) {
+        let roots = self.file_explorer.filtered_roots();
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let mut files: Vec<PathBuf> = roots.iter().flat_map(|(root, filter)| filter.files(root)).collect();
+            files.sort();
+            let _ = sender.send(files);
+            ctx.request_repaint();
+        });
+        self.project_files.receiver = Some(receiver);
+        self.palette = Some((PaletteKind::Files, Palette::new("Go to file")));
+    }
+
+    /// Builds the Go to File entries once the listing is in.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn refresh_project_files(&mut self) {
+        let Some(files) = self.project_files.receiver.as_ref().and_then(|receiver| receiver.try_recv().ok()) else {
+            return;
+        };
+        self.project_files.receiver = None;
+        let roots = self.project_roots();
+        self.project_files.entries = files
+            .into_iter()
+            .map(|path| {
+                let entry = PaletteItem {
+                    icon: "📄",
+                    label: file_name(&path),
+                    detail: display_path(&path, &roots),
+                };
+                (entry, Location::new(path, TextPosition::default()))
+            })
+            .unzip();
+    }
+
+    /// Opens the Find in Files prompt, filled in with the selected text when there is
+    /// some on a single line.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn show_find_in_files(&mut self, ctx: &egui::Context) {
+        let selected = egui::TextEdit::load_state(ctx, egui::Id::new(Self::TEXT_EDIT_ID))
+            .and_then(|state| state.cursor.char_range())
+            .map(|range| {
+                let [start, end] = range.sorted_cursors();
+                self.code.chars().skip(start.index).take(end.index - start.index).collect::<String>()
+            })
+            .filter(|text| !text.is_empty() && !text.contains('\n'));
+        if let Some(selected) = selected {
+            self.search_prompt.query = selected;
+        }
+        self.search_prompt.open = true;
+        self.search_prompt.focus = true;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_search_prompt(&mut self, ctx: &egui::Context) {
+        let prompt = &mut self.search_prompt;
+        if !prompt.open {
+            return;
+        }
+
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new("Find in Files")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 48.0))
+            .show(ctx, |ui| {
+                let output = egui::TextEdit::singleline(&mut prompt.query)
+                    .hint_text("Search the project")
+                    .desired_width(320.0)
+                    .show(ui);
+                if prompt.focus {
+                    prompt.focus = false;
+                    output.response.request_focus();
+                    let mut state = output.state;
+                    let all = egui::text::CCursorRange::two(
+                        egui::text::CCursor::new(0),
+                        egui::text::CCursor::new(prompt.query.chars().count()),
+                    );
+                    state.cursor.set_char_range(Some(all));
+                    state.store(ui.ctx(), output.response.id);
+                }
+                confirmed = output.response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                cancelled = ui.input(|i| i.key_pressed(egui::Key::Escape));
+                ui.checkbox(&mut prompt.match_case, "Match case");
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    confirmed |= ui.button("Search").clicked();
+                    cancelled |= ui.button("Cancel").clicked();
+                });
+            });
+
+        if cancelled {
+            prompt.open = false;
+        }
+        if confirmed && !prompt.query.is_empty() {
+            prompt.open = false;
+            let query = prompt.query.clone();
+            let match_case = prompt.match_case;
+            self.find_in_files(ctx, query, match_case);
+        }
+    }
+
+    /// Searches the files the explorer shows in every root, listing the matches in the
+    /// References panel.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn find_in_files(&mut self, ctx: &egui::Context, query: String, match_case: bool) {
+        self.references.start(format!("matches of `{}`", query));
+        self.bottom_panel = Some(BottomPanel::References);
+
+        let roots = self.file_explorer.filtered_roots();
+        let open = self.selected_file.clone().filter(|_| !self.large_file_mode).map(|path| (path, self.code.clone()));
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let open = open.as_ref().map(|(path, text)| (path.as_path(), text.as_str()));
+            let _ = sender.send(search::project(&roots, open, &query, match_case));
+            ctx.request_repaint();
+        });
+        self.reference_search = Some(receiver);
+    }
+
+    fn show_palette(&mut self, ui: &egui::Ui) {
+        let Some((kind, _)) = &self.palette else {
+            return;
+        };
+        let (items, locations) = match *kind {
+            PaletteKind::DocumentSymbols => self.document_symbol_entries(),
+            #[cfg(not(target_arch = "wasm32"))]
+            PaletteKind::WorkspaceSymbols => {
+                self.refresh_workspace_symbols();
+                std::mem::take(&mut self.workspace_symbols.entries)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            PaletteKind::Files => {
+                self.refresh_project_files();
+                std::mem::take(&mut self.project_files.entries)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            PaletteKind::Tasks => (self.task_output.palette_items(&self.project_roots()), Vec::new()),
+        };
+        let Some((kind, palette)) = &mut self.palette else {
+            return;
+        };
+        let kind = *kind;
+        let result = palette.show(ui.ctx(), &self.theme, &items);
+
+        match result {
+            #[cfg(not(target_arch = "wasm32"))]
+            Some(PaletteResult::Picked(index)) if kind == PaletteKind::Tasks => {
+                self.palette = None;
+                if let Some(task) = self.task_output.task(index) {
+                    self.run_task(ui.ctx(), task);
+                }
+            }
+            Some(PaletteResult::Picked(index)) => {
+                self.palette = None;
+                if let Some(location) = locations.get(index) {
+                    self.go_to_location(ui, location.clone());
+                }
+            }
+            Some(PaletteResult::Closed) => self.palette = None,
+            None => {}
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        match kind {
+            PaletteKind::WorkspaceSymbols => self.workspace_symbols.entries = (items, locations),
+            PaletteKind::Files => self.project_files.entries = (items, locations),
+            _ => {}
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = kind;
+    }
+
+    /// The open document's symbols for the palette, each with its parent as the detail.
+    fn document_symbol_entries(&self) -> (Vec<PaletteItem>, Vec<Location>) {
+        let Some(path) = &self.selected_file else {
+            return (Vec::new(), Vec::new());
+        };
+        outline::flatten(&self.outline.items)
+            .into_iter()
+            .map(|(item, parent)| {
+                let entry = PaletteItem {
+                    icon: item.symbol.kind.icon(),
+                    label: item.label().to_string(),
+                    detail: parent.unwrap_or_default().to_string(),
+                };
+                (entry, Location::new(path, item.symbol.position))
+            })
+            .unzip()
+    }
+
+    /// Rebuilds the workspace symbol list after the index changed. While the index is
+    /// being built the list is only rebuilt once it is done, to keep typing responsive.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn refresh_workspace_symbols(&mut self) {
+        let generation = self.symbol_index.generation();
+        let stale = self.workspace_symbols.generation != Some(generation);
+        if !stale || (self.symbol_index.is_building() && self.workspace_symbols.generation.is_some()) {
+            return;
+        }
+
+        let roots = self.project_roots();
+        self.workspace_symbols.generation = Some(generation);
+        self.workspace_symbols.entries = self
+            .symbol_index
+            .symbols()
+            .into_iter()
+            .filter(|(_, symbol)| symbol.kind.is_definition())
+            .map(|(location, symbol)| {
+                let entry = PaletteItem {
+                    icon: symbol.kind.icon(),
+                    label: symbol.name.clone(),
+                    detail: format!("{}:{}", display_path(&location.path, &roots), location.position.line + 1),
+                };
+                (entry, location)
+            })
+            .unzip();
+    }
+
+    /// F12, Shift+F12 and Alt+Left/Right.
+    fn handle_navigation_keys(&mut self, ui: &egui::Ui) {
+        // Shift+F12 first, since a plain F12 shortcut would also match it.
+        let (references, definition, back, forward) = ui.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::SHIFT, egui::Key::F12),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::F12),
+                i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowLeft),
+                i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowRight),
+            )
+        });
+        if references {
+            self.find_references(ui.ctx(), self.cursor);
+        }
+        if definition {
+            self.go_to_definition(ui, self.cursor);
+        }
+        if back {
+            self.navigate_back(ui);
+        }
+        if forward {
+            self.navigate_forward(ui);
+        }
+    }
+
+    /// F2, Ctrl+. and the keys of the code action menu.
+    fn handle_refactor_keys(&mut self, ui: &egui::Ui) {
+        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::F2)) {
+            self.rename_symbol();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Period)) {
+                self.show_code_actions();
+            }
+            if let Some(action) = self.code_actions.handle_keys(ui.ctx()) {
+                self.apply_code_action(action, true);
+            }
+        }
+    }
+
+    /// Asks for a new name for the identifier at the cursor.
+    pub fn rename_symbol(&mut self) {
+        if self.read_only {
+            self.notifications.warning("This file is open read-only");
+            return;
+        }
+        let Some((range, name)) = self.word_at(self.cursor) else {
+            self.notifications.info("Place the cursor on a name to rename it");
+            return;
+        };
+        self.refactoring.start_rename(range.start, self.document_version, name);
+    }
+
+    fn render_rename_prompt(&mut self, ctx: &egui::Context) {
+        if let Some(rename) = self.refactoring.render_rename_prompt(ctx, self.document_version) {
+            self.rename(rename.index, &rename.name, &rename.new_name);
+        }
+    }
+
+    /// Renames the identifier at `index`: everywhere it is used when the language server
+    /// can rename, and otherwise its whole-word occurrences in the open file.
+    fn rename(&mut self, index: usize, name: &str, new_name: &str) {
+        let label = format!("Rename `{}` to `{}`", name, new_name);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = self.lsp_document.clone() {
+            self.lsp.change(&path, &self.code);
+            self.lsp_version = self.document_version;
+            if let Some(id) = self.lsp.rename(&path, completion::byte_offset(&self.code, index), new_name) {
+                self.refactoring.wait_for(id, RefactorRequest::Rename(label));
+                return;
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = index;
+
+        let Some(path) = self.selected_file.clone() else {
+            self.notifications.info("Save the file to rename in it");
+            return;
+        };
+        let file = edit::rename_in(&path, &self.code, name, new_name);
+        self.preview_or_report(WorkspaceEdit::new(format!("{} in this file", label), vec![file]));
+    }
+
+    /// Previews an edit, or says why it cannot be made. Returns whether the preview opened.
+    fn preview_or_report(&mut self, edit: WorkspaceEdit) -> bool {
+        let label = edit.label.clone();
+        match self.preview_edit(edit) {
+            Ok(true) => true,
+            Ok(false) => {
+                self.notifications.info(format!("{} makes no changes", label));
+                false
+            }
+            Err(e) => {
+                self.notifications.error(format!("{} failed: {}", label, e));
+                false
+            }
+        }
+    }
+
+    /// Shows what an edit changes in the diff view, to be applied or cancelled. Returns
+    /// false when it changes nothing.
+    fn preview_edit(&mut self, edit: WorkspaceEdit) -> Result<bool, String> {
+        let buffer = OpenBuffer {
+            path: self.selected_file.as_deref().filter(|_| self.large_file_view.is_none()),
+            code: &self.code,
+            read_only: self.read_only,
+        };
+        self.refactoring.preview(edit, &buffer, &self.project_roots())
+    }
+
+    fn render_edit_preview(&mut self, ui: &mut egui::Ui) {
+        match self.refactoring.render_preview(ui, &self.theme) {
+            Some(PreviewResult::Apply(pending)) => {
+                let result = self.make_edit(ui.ctx(), &pending);
+                if let Err(e) = &result {
+                    self.notifications.error(format!("{} failed: {}", pending.label, e));
+                }
+                self.finish_edit(pending, result);
+            }
+            Some(PreviewResult::Cancel(pending)) => self.finish_edit(pending, Err("the edit was cancelled".to_string())),
+            None => {}
+        }
+    }
+
+    /// Makes a previewed edit. The open buffer changes in one step that Ctrl+Z takes back.
+    /// Other files are written to disk rather than opened as edited buffers, since there is
+    /// only ever one buffer; the notification offers to undo them instead, and the language
+    /// servers are told they changed. Nothing is changed if any file was modified since the
+    /// preview.
+    fn make_edit(&mut self, ctx: &egui::Context, pending: &PendingEdit) -> Result<(), String> {
+        let buffer_change = pending.buffer_change();
+        if let Some((change, _)) = buffer_change {
+            if self.selected_file.as_ref() != Some(&change.path) || self.code != change.old {
+                return Err(format!("{} changed since the preview", change.path.display()));
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let written = self.refactoring.write_files(pending)?;
+            if !written.is_empty() {
+                for path in &written {
+                    self.symbol_index.update_file(path);
+                }
+                self.lsp.files_changed(&written);
+                self.notifications.push_with_actions(
+                    NotificationLevel::Info,
+                    format!("{}: saved changes to {} files", pending.label, written.len()),
+                    vec![("Undo".into(), NotificationAction::UndoWorkspaceEdit)],
+                );
+            }
+        }
+
+        if let Some((change, edits)) = buffer_change {
+            self.replace_buffer(ctx, change.new.clone(), edits);
+        }
+        Ok(())
+    }
+
+    /// Tells the language server how an edit it asked for went, and runs the command of
+    /// the code action it came from once it is made.
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn finish_edit(&mut self, pending: PendingEdit, result: Result<(), String>) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some((server, id)) = pending.reply {
+                let reply = match &result {
+                    Ok(()) => serde_json::json!({ "applied": true }),
+                    Err(e) => serde_json::json!({ "applied": false, "failureReason": e }),
+                };
+                self.lsp.respond(&server, id, reply);
+            }
+            if let (Ok(()), Some((path, command))) = (&result, pending.command) {
+                self.run_command(&path, command);
+            }
+        }
+    }
+
+    /// Replaces the buffer with the result of `edits` in one step that Ctrl+Z takes back,
+    /// keeping the cursor on the same code.
+    fn replace_buffer(&mut self, ctx: &egui::Context, text: String, edits: &[TextEdit]) {
+        let id = egui::Id::new(Self::TEXT_EDIT_ID);
+        let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+        let before = state.cursor.char_range().unwrap_or_default();
+        let cursor = edit::map_index(&self.code, edits, self.cursor);
+        let after = egui::text::CCursorRange::one(egui::text::CCursor::new(cursor));
+
+        let mut undoer = state.undoer();
+        undoer.add_undo(&(before, self.code.clone()));
+        undoer.add_undo(&(after, text.clone()));
+        state.set_undoer(undoer);
+        state.cursor.set_char_range(Some(after));
+        state.store(ctx, id);
+
+        self.code = text;
+        self.cursor = cursor;
+        self.document_version += 1;
+        self.invalidate_caches();
+    }
+
+    /// Puts back the files the last workspace edit wrote, unless they changed since.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn undo_workspace_edit(&mut self) {
+        let restored = self.refactoring.undo(&mut self.notifications);
+        for path in &restored {
+            self.symbol_index.update_file(path);
+        }
+        self.lsp.files_changed(&restored);
+    }
+
+    /// A language server asking for an edit to be made, usually while running a command.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn server_edit(&mut self, server: String, id: serde_json::Value, params: serde_json::Value) {
+        let label = params
+            .get("label")
+            .and_then(serde_json::Value::as_str)
+            .map_or_else(|| format!("Edit from {}", server), str::to_string);
+        let edit = params.get("edit").cloned().unwrap_or_default();
+        let result = convert::workspace_edit(edit).and_then(|files| self.preview_edit(WorkspaceEdit::new(label.clone(), files)));
+        match result {
+            Ok(true) => {
+                if let Some(pending) = self.refactoring.pending_edit() {
+                    pending.reply = Some((server, id));
+                }
+            }
+            Ok(false) => self.lsp.respond(&server, id, serde_json::json!({ "applied": true })),
+            Err(e) => {
+                self.notifications.error(format!("{} failed: {}", label, e));
+                self.lsp.respond(&server, id, serde_json::json!({ "applied": false, "failureReason": e }));
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn finish_refactor_request(&mut self, request: RefactorRequest, result: Result<serde_json::Value, String>) {
+        match (request, result) {
+            (RefactorRequest::Rename(label), Ok(result)) => match convert::workspace_edit(result) {
+                Ok(files) => {
+                    self.preview_or_report(WorkspaceEdit::new(label, files));
+                }
+                Err(e) => self.notifications.error(format!("{} failed: {}", label, e)),
+            },
+            (RefactorRequest::Rename(label), Err(e)) => self.notifications.error(format!("{} failed: {}", label, e)),
+            (RefactorRequest::Resolve, result) => match result.ok().and_then(convert::code_action) {
+                Some(action) => self.apply_code_action(action, false),
+                None => self.notifications.error("The language server did not provide the code action's changes"),
+            },
+            (RefactorRequest::Command(_), Ok(_)) => {}
+            (RefactorRequest::Command(title), Err(e)) => self.notifications.error(format!("{} failed: {}", title, e)),
+        }
+    }
 
-impl CodeEditor {
-    pub fn open_project(&mut self, path: PathBuf) {
-        self.file_explorer.open_project(path.clone());
+    /// Opens the menu of code actions at the cursor once they are known.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn show_code_actions(&mut self) {
+        self.code_actions.show_when_ready();
     }
 
-    pub fn set_theme(&mut self, theme: ZenTheme) {
-        self.theme = theme;
-        if let Some(index) = self.available_themes.iter().position(|t| t.name == self.theme.name) {
-            self.selected_theme_index = index;
+    /// Keeps the code actions in step with the cursor, asking for them once it has rested.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn update_code_actions(&mut self, ctx: &egui::Context) {
+        let position = (self.cursor, self.document_version);
+        if self.code_actions.update(ctx, position, &mut self.notifications) {
+            let request = self.request_code_actions(ctx);
+            self.code_actions.requested(request);
         }
+    }
 
-        self.cached_layout_job = None;
-        self.cached_line_height = None;
+    /// Asks the language server for the code actions at the selection, passing along the
+    /// problems on the cursor line for it to offer fixes for.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn request_code_actions(&mut self, ctx: &egui::Context) -> Option<i64> {
+        let path = self.lsp_document.clone()?;
+        let selection = egui::TextEdit::load_state(ctx, egui::Id::new(Self::TEXT_EDIT_ID))
+            .and_then(|state| state.cursor.char_range())
+            .map_or(self.cursor..self.cursor, |range| {
+                let [start, end] = range.sorted_cursors();
+                start.index..end.index
+            });
+        let line = TextPosition::from_char_index(&self.code, self.cursor).line;
+        let problems: Vec<&Diagnostic> = self
+            .diagnostics
+            .for_file(&path)
+            .into_iter()
+            .filter(|diagnostic| (diagnostic.start.line..=diagnostic.end.line).contains(&line))
+            .collect();
+
+        self.lsp.change(&path, &self.code);
+        self.lsp_version = self.document_version;
+        let range = completion::byte_offset(&self.code, selection.start)..completion::byte_offset(&self.code, selection.end);
+        self.lsp.code_actions(&path, range, &problems)
     }
 
-    pub fn load_file(&mut self, path: &PathBuf) {
-        if let Ok(content) = std::fs::read_to_string(path) {
-            self.code = content;
-            self.selected_file = Some(path.clone());
-            self.document_version += 1;
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_code_action_menu(&mut self, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput) {
+        let cursor = egui::text::CCursor::new(self.cursor);
+        let anchor = output.galley.pos_from_cursor(cursor).translate(output.galley_pos.to_vec2()).left_bottom();
+        if let Some(action) = self.code_actions.show_menu(ui.ctx(), anchor, &self.theme) {
+            self.apply_code_action(action, true);
+        }
+    }
 
-            self.invalidate_caches();
+    /// Previews a code action's edit, first asking the server for it when the action came
+    /// without one, or runs its command when that is all it has.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_code_action(&mut self, action: CodeAction, resolve: bool) {
+        let Some(path) = self.lsp_document.clone() else {
+            return;
+        };
+        if let Some(reason) = action.disabled {
+            self.notifications.info(format!("{}: {}", action.title, reason));
+            return;
+        }
 
-            if let Some(name) = path.file_name() {
-                if name.to_string_lossy().eq_ignore_ascii_case("CMakeLists.txt") {
-                    self.language = "c".into();
-                } else if let Some(ext) = path.extension() {
-                    self.language = ext.to_string_lossy().to_string();
+        if let Some(files) = action.edit {
+            let label = action.title.clone();
+            match self.preview_edit(WorkspaceEdit::new(action.title, files)) {
+                Ok(true) => {
+                    if let Some(pending) = self.refactoring.pending_edit() {
+                        pending.command = action.command.map(|command| (path, command));
+                    }
                 }
+                Ok(false) => match action.command {
+                    Some(command) => self.run_command(&path, command),
+                    None => self.notifications.info(format!("{} makes no changes", label)),
+                },
+                Err(e) => self.notifications.error(format!("{} failed: {}", label, e)),
+            }
+            return;
+        }
+
+        if resolve && action.command.is_none() {
+            if let Some(id) = self.lsp.resolve_code_action(&path, &action) {
+                self.refactoring.wait_for(id, RefactorRequest::Resolve);
+                return;
             }
         }
+        match action.command {
+            Some(command) => self.run_command(&path, command),
+            None => self.notifications.info(format!("{} makes no changes", action.title)),
+        }
     }
 
-    fn invalidate_caches(&mut self) {
-        self.cached_line_height = None;
-        self.cached_max_line_width = None;
-        self.cached_layout_job = None;
-        self.cache_version = self.document_version;
+    /// Runs a code action's command on the language server of `path`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn run_command(&mut self, path: &Path, command: lsp_types::Command) {
+        match self.lsp.execute_command(path, &command) {
+            Some(id) => self.refactoring.wait_for(id, RefactorRequest::Command(command.title)),
+            None => self.notifications.warning(format!("{}: the language server cannot run commands", command.title)),
+        }
     }
 
-    fn get_line_height(&mut self, ui: &egui::Ui) -> f32 {
-        if self.cached_line_height.is_none() || self.cache_version != self.document_version {
-            self.cached_line_height = Some(
-                ui.fonts(|f| f.row_height(&egui::FontId::monospace(self.theme.typography.code_font_size)))
-            );
-            self.cache_version = self.document_version;
+    /// Formats the open document with its formatter or language server.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn format_document(&mut self) {
+        self.request_format(false, false);
+    }
+
+    /// Formats the selected lines. Formatters that only take whole files format the whole
+    /// document, and only their changes to the selected lines are kept.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn format_selection(&mut self) {
+        self.request_format(true, false);
+    }
+
+    /// Formats the document ahead of saving it. Returns false when nothing formats it, and
+    /// otherwise [`Self::take_save_after_format`] says when to go on with the save.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn format_before_save(&mut self) -> bool {
+        self.request_format(false, true)
+    }
+
+    /// The document to save now that it has been formatted.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn take_save_after_format(&mut self) -> Option<PathBuf> {
+        self.formatting.take_save_after()
+    }
+
+    /// Queues formatting of the open document, unless nothing can format it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn request_format(&mut self, selection: bool, save_after: bool) -> bool {
+        let path = self.editable_file();
+        let has_server = path.is_some() && self.lsp_document == path;
+        let document = OpenDocument { path: path.as_deref(), code: &self.code, version: self.document_version };
+        self.formatting.request(&document, has_server, selection, save_after, &mut self.notifications)
+    }
+
+    /// Starts a queued formatting and takes the result of a formatter command.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn update_format(&mut self, ctx: &egui::Context) {
+        let path = self.editable_file();
+        let document = OpenDocument { path: path.as_deref(), code: &self.code, version: self.document_version };
+        let lsp = &mut self.lsp;
+        let mut synced = false;
+        let finished = self.formatting.update(ctx, &document, &mut self.notifications, |path, bytes| {
+            lsp.change(path, document.code);
+            synced = true;
+            lsp.format(path, bytes)
+        });
+        if synced {
+            self.lsp_version = self.document_version;
+        }
+        if let Some((request, formatted)) = finished {
+            self.finish_format(ctx, request, formatted);
         }
-        self.cached_line_height.unwrap()
     }
 
-    fn get_max_line_width(&mut self, ui: &egui::Ui) -> f32 {
-        if self.cached_max_line_width.is_none() || self.cache_version != self.document_version {
-            let max_lines_to_check = 1000;
-            let lines: Vec<&str> = self.code.lines().take(max_lines_to_check).collect();
+    #[cfg(not(target_arch = "wasm32"))]
+    fn finish_format(&mut self, ctx: &egui::Context, request: FormatRequest, formatted: Result<String, String>) {
+        let path = self.editable_file();
+        let document = OpenDocument { path: path.as_deref(), code: &self.code, version: self.document_version };
+        if let Some((text, edits)) = self.formatting.finish(request, formatted, &document, &mut self.notifications) {
+            self.replace_buffer(ctx, text, &edits);
+        }
+    }
 
-            self.cached_max_line_width = Some(
-                lines.iter()
-                    .map(|line| {
-                        ui.fonts(|f| f.layout_no_wrap(
-                            line.to_string(),
-                            egui::FontId::monospace(self.theme.typography.code_font_size),
-                            egui::Color32::WHITE
-                        ).size().x)
-                    })
-                    .fold(0.0, f32::max)
-                    .max(800.0)
+    /// The open file, unless it is only being viewed.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn editable_file(&self) -> Option<PathBuf> {
+        self.selected_file.clone().filter(|_| !self.read_only && self.large_file_view.is_none())
+    }
+
+    /// Underlines the problems in the open file with squiggles.
+    /// Returns the problems under the mouse pointer.
+    fn paint_diagnostics(&self, ui: &mut egui::Ui, output: &egui::text_edit::TextEditOutput) -> Vec<&Diagnostic> {
+        let Some(path) = &self.selected_file else {
+            return Vec::new();
+        };
+        let mut problems = self.diagnostics.for_file(path);
+        if problems.is_empty() {
+            return Vec::new();
+        }
+        // The most severe are painted last so they end up on top.
+        problems.sort_by_key(|d| std::cmp::Reverse(d.severity));
+
+        let lines: Vec<&str> = self.code.split('\n').collect();
+        let painter = ui.painter_at(output.text_clip_rect);
+        let pointer = output.response.hover_pos();
+        let mut hovered = Vec::new();
+
+        for diagnostic in problems {
+            let color = self.theme.diagnostic_color(diagnostic.severity);
+            let mut is_hovered = false;
+            for line in diagnostic.start.line..=diagnostic.end.line {
+                let (Some(row), Some(text)) = (output.galley.rows.get(line), lines.get(line)) else {
+                    break;
+                };
+                let from = if line == diagnostic.start.line { position::char_column(text, diagnostic.start.column) } else { 0 };
+                let to = if line == diagnostic.end.line {
+                    position::char_column(text, diagnostic.end.column)
+                } else {
+                    text.chars().count()
+                };
+                // Empty ranges still get a short squiggle so they can be seen and hovered.
+                let left = output.galley_pos.x + row.pos.x + row.x_offset(from);
+                let right = (output.galley_pos.x + row.pos.x + row.x_offset(to.max(from + 1))).max(left + 6.0);
+                let rect = egui::Rect::from_x_y_ranges(left..=right, row.rect().translate(output.galley_pos.to_vec2()).y_range());
+
+                paint_squiggle(&painter, left, right, rect.bottom() - 1.5, color);
+                is_hovered |= pointer.is_some_and(|pointer| rect.contains(pointer));
+            }
+            if is_hovered {
+                hovered.push(diagnostic);
+            }
+        }
+
+        hovered.sort_by_key(|d| d.severity);
+        hovered
+    }
+
+    /// The tooltip for what is under the mouse pointer: problems first, then the symbol's
+    /// type and documentation.
+    fn show_hover(&self, ui: &egui::Ui, problems: &[&Diagnostic]) {
+        let symbol = self.hover.as_ref().and_then(|hover| hover.text.as_deref());
+        if problems.is_empty() && symbol.is_none() {
+            return;
+        }
+        egui::Tooltip::always_open(ui.ctx().clone(), ui.layer_id(), ui.id().with("editor_hover"), egui::PopupAnchor::Pointer)
+            .gap(12.0)
+            .show(|ui| {
+                ui.set_max_width(520.0);
+                self.problem_list(ui, problems);
+                if let Some(text) = symbol {
+                    if !problems.is_empty() {
+                        ui.separator();
+                    }
+                    ui.label(egui::RichText::new(text).monospace());
+                }
+            });
+    }
+
+    fn problem_list(&self, ui: &mut egui::Ui, problems: &[&Diagnostic]) {
+        for diagnostic in problems {
+            ui.horizontal_top(|ui| {
+                let color = self.theme.diagnostic_color(diagnostic.severity);
+                ui.label(egui::RichText::new(diagnostic.severity.icon()).color(color));
+                ui.label(diagnostic.description());
+            });
+        }
+    }
+
+    /// The strip above the editor while a file has conflicts, and once they are all resolved.
+    fn render_conflict_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if self.conflicts.is_empty() {
+                ui.label("✔ All conflicts are resolved");
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.small_button("Mark as Resolved").clicked() {
+                    self.mark_resolved(ui.ctx());
+                }
+                return;
+            }
+
+            let [r, g, b] = self.theme.git.conflicted;
+            let summary = format!("⚠ {} merge conflicts", self.conflicts.len());
+            ui.label(egui::RichText::new(summary).color(egui::Color32::from_rgb(r, g, b)));
+            if ui.small_button("⏶").on_hover_text("Previous Conflict").clicked() {
+                self.go_to_conflict(ui, false);
+            }
+            if ui.small_button("⏷").on_hover_text("Next Conflict").clicked() {
+                self.go_to_conflict(ui, true);
+            }
+            ui.separator();
+            ui.add_enabled_ui(!self.read_only, |ui| {
+                if ui.small_button("Accept All Current").clicked() {
+                    self.accept_all_conflicts(ui.ctx(), Resolution::Current);
+                }
+                if ui.small_button("Accept All Incoming").clicked() {
+                    self.accept_all_conflicts(ui.ctx(), Resolution::Incoming);
+                }
+            });
+            if ui.small_button("Open Merge View").clicked() {
+                self.open_merge_view(ui.ctx());
+            }
+        });
+    }
+
+    /// Tints the sides of each conflict block and puts its accept actions on the `<<<<<<<` line.
+    fn paint_conflicts(&mut self, ui: &mut egui::Ui, output: &egui::text_edit::TextEditOutput) {
+        let tint = |[r, g, b]: [u8; 3], alpha: u8| egui::Color32::from_rgba_unmultiplied(r, g, b, alpha);
+        let row_rect = |line: usize| {
+            output.galley.rows.get(line).map(|row| {
+                let rect = row.rect().translate(output.galley_pos.to_vec2());
+                egui::Rect::from_x_y_ranges(output.text_clip_rect.x_range(), rect.y_range())
+            })
+        };
+        let painter = ui.painter_at(output.text_clip_rect);
+        let git = &self.theme.git;
+
+        let mut accepted = None;
+        for (index, conflict) in self.conflicts.iter().enumerate() {
+            let ours_end = conflict.base_line.unwrap_or(conflict.separator_line);
+            for line in conflict.start_line..=conflict.end_line {
+                let color = if line == conflict.start_line
+                    || line == conflict.end_line
+                    || line == conflict.separator_line
+                    || Some(line) == conflict.base_line
+                {
+                    tint(git.conflicted, 45)
+                } else if line < ours_end {
+                    tint(git.added, 30)
+                } else if line > conflict.separator_line {
+                    tint(git.modified, 30)
+                } else {
+                    tint(git.ignored, 30)
+                };
+                if let Some(rect) = row_rect(line) {
+                    painter.rect_filled(rect, 0.0, color);
+                }
+            }
+
+            let Some(marker_rect) = row_rect(conflict.start_line) else {
+                continue;
+            };
+            if self.read_only || !ui.is_rect_visible(marker_rect) {
+                continue;
+            }
+            let mut actions = ui.new_child(
+                egui::UiBuilder::new()
+                    .max_rect(marker_rect.shrink2(egui::vec2(8.0, 0.0)))
+                    .layout(egui::Layout::right_to_left(egui::Align::Center)),
             );
-            self.cache_version = self.document_version;
+            actions.spacing_mut().button_padding.y = 0.0;
+            for (label, resolution) in [
+                ("Accept Both", Resolution::Both),
+                ("Accept Incoming", Resolution::Incoming),
+                ("Accept Current", Resolution::Current),
+            ] {
+                if actions.small_button(label).clicked() {
+                    accepted = Some((index, resolution));
+                }
+            }
+        }
+
+        if let Some((index, resolution)) = accepted {
+            self.accept_conflict(ui.ctx(), index, resolution);
         }
-        self.cached_max_line_width.unwrap()
     }
 
-    fn get_highlighted_layout(&mut self, text: &str) -> egui::text::LayoutJob {
-        if self.cached_layout_job.is_none() || self.cache_version != self.document_version {
-            let mut layout_job = self.theme.highlight_code(text, &self.language);
-            layout_job.wrap.max_width = f32::INFINITY;
-            self.cached_layout_job = Some(layout_job);
-            self.cache_version = self.document_version;
+    /// Opens the merge view, loading the common ancestor from git when the conflict
+    /// markers do not include it.
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn open_merge_view(&mut self, ctx: &egui::Context) {
+        self.merge_view = Some(MergeView::default());
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.selected_file {
+            if self.conflicts.iter().any(|conflict| conflict.base.is_none()) {
+                self.git.request_merge_base(path.clone(), ctx);
+            }
         }
-        self.cached_layout_job.as_ref().unwrap().clone()
     }
-}
 
-impl ZenView for CodeEditor {
-    fn ui(&mut self, ui: &mut egui::Ui) {
-        self.theme.apply_to_context(ui.ctx());
+    fn render_merge_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(view) = &mut self.merge_view else {
+            return;
+        };
 
-        egui::SidePanel::left("file_tree")
-            .resizable(true)
-            .default_width(200.0)
-            .width_range(150.0..=400.0)
-            .show_animated_inside(ui, self.file_explorer.root.is_some(), |ui| {
-                self.file_explorer.render(ui);
+        match view.render(ui, &self.theme, &self.code, &self.conflicts, self.read_only) {
+            Some(MergeAction::Close) => self.merge_view = None,
+            Some(MergeAction::Resolve(index, text)) => self.resolve_conflict(ui.ctx(), index, &text),
+            #[cfg(not(target_arch = "wasm32"))]
+            Some(MergeAction::MarkResolved) => self.mark_resolved(ui.ctx()),
+            #[cfg(target_arch = "wasm32")]
+            Some(MergeAction::MarkResolved) => {}
+            None => {}
+        }
+    }
+
+    /// Stages the open file, which is how git records that its conflicts are resolved.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn mark_resolved(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.selected_file.clone() else {
+            return;
+        };
+        if self.is_dirty() {
+            self.notifications.warning("Save the file before marking it as resolved");
+            return;
+        }
+        let status = self.git.status();
+        let Some(repo) = status.repo_for(&path) else {
+            self.notifications.warning(format!("{} is not in a git repository", path.display()));
+            return;
+        };
+
+        let operation = GitOperation::Stage { repo: repo.root.clone(), path: path.clone() };
+        self.resolving = Some(path);
+        self.git.run(operation, ctx);
+    }
+
+    /// Takes the keys the completion popup and snippet placeholders use before the text edit
+    /// sees them, and returns the character typed this frame, if any.
+    fn handle_completion_keys(&mut self, ui: &mut egui::Ui) -> Option<char> {
+        let typed = ui.input(|i| {
+            i.events.iter().rev().find_map(|event| match event {
+                egui::Event::Text(text) => text.chars().last(),
+                _ => None,
+            })
+        });
+        let consume = |modifiers, key| ui.input_mut(|i| i.consume_key(modifiers, key));
+
+        if consume(egui::Modifiers::COMMAND, egui::Key::Space) {
+            self.start_completion(None);
+            return None;
+        }
+
+        if let Some(session) = self.completion.session.as_mut().filter(|s| !s.matches.is_empty()) {
+            if consume(egui::Modifiers::NONE, egui::Key::ArrowDown) {
+                session.select_next();
+            } else if consume(egui::Modifiers::NONE, egui::Key::ArrowUp) {
+                session.select_previous();
+            } else if consume(egui::Modifiers::NONE, egui::Key::Escape) {
+                self.completion.close();
+            } else if consume(egui::Modifiers::NONE, egui::Key::Enter) || consume(egui::Modifiers::NONE, egui::Key::Tab) {
+                self.accept_completion(None);
+                return None;
+            }
+            return typed;
+        }
+
+        if self.completion.in_snippet() {
+            if consume(egui::Modifiers::NONE, egui::Key::Tab) {
+                let length = self.code.chars().count();
+                self.pending_selection = self.completion.next_tab_stop(length);
+            } else if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.completion.leave_snippet();
+            }
+        }
+        typed
+    }
+
+    /// Opens the completion popup at the cursor, or refilters it, after an edit. Typing a
+    /// word opens it, as does a character the language server asks to be triggered by.
+    fn completion_typed(&mut self, typed: Option<char>) {
+        if self.completion.is_open() {
+            self.completion.update(&self.code, self.cursor);
+            return;
+        }
+        let Some(typed) = typed else {
+            return;
+        };
+
+        let word = self.cursor - completion::word_start(&self.code, self.cursor);
+        if word >= 2 && (typed.is_alphanumeric() || typed == '_') {
+            self.start_completion(None);
+            return;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = self.lsp_document.clone() {
+            if self.lsp.completion_triggers(&path).contains(&typed) {
+                self.start_completion(Some(typed));
+            }
+        }
+    }
+
+    fn start_completion(&mut self, trigger: Option<char>) {
+        self.completion.start(&self.code, self.cursor, &self.language, self.selected_file.as_deref());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = self.lsp_document.clone() {
+            // The server has to see this frame's edit before it is asked about it.
+            self.lsp.change(&path, &self.code);
+            self.lsp_version = self.document_version;
+            let offset = completion::byte_offset(&self.code, self.cursor);
+            let request = self.lsp.completion(&path, offset, trigger);
+            if let Some(session) = &mut self.completion.session {
+                session.pending_request = request;
+            }
+        }
+
+        if self.completion.session.as_ref().is_some_and(|s| s.matches.is_empty() && s.pending_request.is_none()) {
+            self.completion.close();
+        }
+    }
+
+    fn accept_completion(&mut self, index: Option<usize>) {
+        let Some(edit) = self.completion.accept(&self.code, self.cursor, index) else {
+            return;
+        };
+        let start = completion::byte_offset(&self.code, edit.range.start);
+        let end = completion::byte_offset(&self.code, edit.range.end);
+        self.code.replace_range(start..end, &edit.text);
+        self.document_version += 1;
+        self.invalidate_caches();
+        self.pending_selection = Some(edit.selection);
+    }
+
+    fn render_status_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.style_mut().visuals.widgets.inactive.weak_bg_fill = egui::Color32::TRANSPARENT;
+
+            if let Some(path) = &self.selected_file {
+                ui.weak(path.display().to_string());
+            }
+            if self.read_only {
+                ui.weak("🔒 Read-only");
+            }
+            if !self.conflicts.is_empty() {
+                let [r, g, b] = self.theme.git.conflicted;
+                let text = egui::RichText::new(format!("⚠ {} conflicts", self.conflicts.len()));
+                ui.label(text.color(egui::Color32::from_rgb(r, g, b)));
+            }
+
+            let errors = self.diagnostics.count(Severity::Error);
+            let warnings = self.diagnostics.count(Severity::Warning);
+            let summary = egui::RichText::new(format!("{} {}  {} {}", Severity::Error.icon(), errors, Severity::Warning.icon(), warnings));
+            let summary = if errors + warnings == 0 { summary.weak() } else { summary };
+            if ui.add(egui::Button::new(summary).frame(false)).on_hover_text("Problems").clicked() {
+                self.toggle_problems();
+            }
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                #[cfg(not(target_arch = "wasm32"))]
+                self.language_server_status(ui);
+                ui.weak(self.language.as_str());
+                ui.menu_button(egui::RichText::new(self.line_ending.label()).weak(), |ui| {
+                    self.line_ending_menu(ui);
+                });
+                ui.menu_button(egui::RichText::new(self.encoding.label()).weak(), |ui| {
+                    self.encoding_menu(ui);
+                });
             });
+        });
+    }
 
-        if let Some(path) = self.file_explorer.take_pending_file() {
-            self.load_file(&path);
+    /// "Reopen with Encoding" and "Save with Encoding" submenus, shared by the status bar and File menu.
+    pub fn encoding_menu(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(self.selected_file.is_some() && self.large_file_view.is_none(), |ui| {
+            ui.menu_button("Reopen with Encoding...", |ui| {
+                for encoding in FileEncoding::choices() {
+                    if ui.selectable_label(encoding == self.encoding, encoding.label()).clicked() {
+                        self.reopen_with_encoding(encoding);
+                        ui.close();
+                    }
+                }
+            });
+        });
+        ui.add_enabled_ui(!self.read_only, |ui| {
+            ui.menu_button("Save with Encoding...", |ui| {
+                for encoding in FileEncoding::choices() {
+                    if ui.selectable_label(encoding == self.encoding, encoding.label()).clicked() {
+                        self.request_save_with_encoding(encoding);
+                        ui.close();
+                    }
+                }
+            });
+        });
+    }
+
+    /// Converts the document between line endings; the change is applied when it is next saved.
+    pub fn line_ending_menu(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(!self.read_only, |ui| {
+            ui.menu_button("Convert Line Endings", |ui| {
+                for ending in LineEnding::ALL {
+                    if ui.selectable_label(ending == self.line_ending, ending.label()).clicked() {
+                        self.set_line_ending(ending);
+                        ui.close();
+                    }
+                }
+            });
+        });
+    }
+
+    fn render_loading_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(task) = &self.loading else {
+            return;
+        };
+
+        let name = task.path.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let progress = task.progress();
+        let mut cancel = false;
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 3.0);
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!("Loading {}...", name));
+            });
+            ui.add(
+                egui::ProgressBar::new(progress)
+                    .desired_width(320.0)
+                    .show_percentage(),
+            );
+            cancel = ui.button("Cancel").clicked();
+        });
+
+        if cancel {
+            if let Some(task) = self.loading.take() {
+                task.cancel();
+            }
         }
+    }
 
-        egui::CentralPanel::default().show_inside(ui, |ui| {
-            self.render_editor_panel(ui);
+    fn render_large_file_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(view) = &self.large_file_view else {
+            return;
+        };
+
+        let font = egui::FontId::monospace(self.theme.typography.code_font_size);
+        let line_height = ui.fonts(|f| f.row_height(&font));
+        let digits = view.line_count().to_string().len();
+        let number_color = self.theme.text_color().linear_multiply(0.6);
+        let text_color = self.theme.text_color();
+
+        self.create_editor_frame().show(ui, |ui| {
+            ui.style_mut().spacing.item_spacing.y = 0.0;
+            egui::ScrollArea::both()
+                .auto_shrink([false; 2])
+                .show_rows(ui, line_height, view.line_count(), |ui, rows| {
+                    for row in rows {
+                        ui.horizontal(|ui| {
+                            if self.show_line_numbers {
+                                ui.label(
+                                    egui::RichText::new(format!("{:>width$} ", row + 1, width = digits))
+                                        .font(font.clone())
+                                        .color(number_color),
+                                );
+                            }
+                            ui.add(
+                                egui::Label::new(
+                                    egui::RichText::new(view.line(row))
+                                        .font(font.clone())
+                                        .color(text_color),
+                                )
+                                .extend(),
+                            );
+                        });
+                    }
+                });
         });
     }
-}
 
-impl CodeEditor {
     fn render_editor_panel(&mut self, ui: &mut egui::Ui) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.update_line_diff();
+        if self.had_conflicts {
+            self.render_conflict_bar(ui);
+        }
+
         let line_count = self.code.lines().count().max(1);
         let line_height = self.get_line_height(ui);
         let line_number_width = self.calculate_line_number_width(ui, line_count);
@@ -154,11 +3369,15 @@ impl CodeEditor {
         let frame = self.create_editor_frame();
 
         frame.show(ui, |ui| {
-            egui::ScrollArea::both()
-                .auto_shrink([false; 2])
-                .show(ui, |ui| {
-                    self.render_editor_content(ui, line_count, line_height, line_number_width);
-                });
+            let mut scroll_area = egui::ScrollArea::both().auto_shrink([false; 2]);
+            if let Some(offset) = self.pending_scroll.take() {
+                scroll_area = scroll_area.scroll_offset(offset);
+            }
+
+            let output = scroll_area.show(ui, |ui| {
+                self.render_editor_content(ui, line_count, line_height, line_number_width);
+            });
+            self.scroll_offset = output.state.offset;
         });
     }
 
@@ -184,7 +3403,13 @@ impl CodeEditor {
     fn render_editor_content(&mut self, ui: &mut egui::Ui, line_count: usize, line_height: f32, line_number_width: f32) {
         ui.horizontal_top(|ui| {
             if self.show_line_numbers {
-                self.render_line_numbers(ui, line_count, line_height, line_number_width);
+                match self.render_line_numbers(ui, line_count, line_height, line_number_width) {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    Some(GutterClick::Hunk(index)) => self.open_hunk = Some(index),
+                    #[cfg(not(target_arch = "wasm32"))]
+                    Some(GutterClick::Lightbulb) => self.code_actions.open_menu(&mut self.notifications),
+                    _ => {}
+                }
             }
 
             let (editor_width, editor_height) = self.calculate_editor_dimensions(ui, line_count, line_height);
@@ -192,16 +3417,17 @@ impl CodeEditor {
         });
     }
 
-    fn render_line_numbers(&self, ui: &mut egui::Ui, line_count: usize, line_height: f32, line_number_width: f32) {
+    /// Draws the gutter and returns the change marker or lightbulb that was clicked, if any.
+    fn render_line_numbers(&self, ui: &mut egui::Ui, line_count: usize, line_height: f32, line_number_width: f32) -> Option<GutterClick> {
         ui.allocate_ui_with_layout(
             egui::vec2(line_number_width, line_height * line_count as f32),
             egui::Layout::top_down(egui::Align::RIGHT),
             |ui| {
                 self.draw_line_number_background(ui);
                 self.configure_line_number_spacing(ui);
-                self.draw_line_numbers(ui, line_count, line_height, line_number_width);
+                self.draw_line_numbers(ui, line_count, line_height, line_number_width)
             }
-        );
+        ).inner
     }
 
     #[allow(deprecated)]
@@ -221,9 +3447,28 @@ impl CodeEditor {
         ui.style_mut().spacing.button_padding.y = 0.0;
     }
 
-    fn draw_line_numbers(&self, ui: &mut egui::Ui, line_count: usize, line_height: f32, line_number_width: f32) {
+    fn draw_line_numbers(&self, ui: &mut egui::Ui, line_count: usize, line_height: f32, line_number_width: f32) -> Option<GutterClick> {
+        #[cfg_attr(target_arch = "wasm32", allow(unused_mut))]
+        let mut clicked = None;
+        // The lightbulb takes the place of the problem icon on the cursor line.
+        #[cfg(not(target_arch = "wasm32"))]
+        let lightbulb_line = self
+            .code_actions
+            .any_enabled()
+            .then(|| self.code.chars().take(self.cursor).filter(|&c| c == '\n').count());
+        #[cfg(target_arch = "wasm32")]
+        let lightbulb_line: Option<usize> = None;
+
+        let mut problems: HashMap<usize, Vec<&Diagnostic>> = HashMap::new();
+        if let Some(path) = &self.selected_file {
+            for diagnostic in self.diagnostics.for_file(path) {
+                problems.entry(diagnostic.start.line).or_default().push(diagnostic);
+            }
+        }
+
         for line_num in 1..=line_count {
-            ui.allocate_ui_with_layout(
+            #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+            let row = ui.allocate_ui_with_layout(
                 egui::vec2(line_number_width - 8.0, line_height),
                 egui::Layout::right_to_left(egui::Align::Center),
                 |ui| {
@@ -234,7 +3479,91 @@ impl CodeEditor {
                     );
                 },
             );
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(index) = self.draw_change_marker(ui, row.response.rect, line_num - 1) {
+                clicked = Some(GutterClick::Hunk(index));
+            }
+            if lightbulb_line == Some(line_num - 1) {
+                #[cfg(not(target_arch = "wasm32"))]
+                if self.draw_lightbulb(ui, row.response.rect) {
+                    clicked = Some(GutterClick::Lightbulb);
+                }
+            } else if let Some(problems) = problems.get(&(line_num - 1)) {
+                self.draw_problem_icon(ui, row.response.rect, line_num - 1, problems);
+            }
         }
+
+        clicked
+    }
+
+    /// Paints the lightbulb that shows code actions are available; returns whether it was clicked.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn draw_lightbulb(&self, ui: &mut egui::Ui, row: egui::Rect) -> bool {
+        let size = self.theme.typography.code_font_size * 0.8;
+        let center = egui::pos2(row.left() + 6.0 + size / 2.0, row.center().y);
+        ui.painter().text(
+            center,
+            egui::Align2::CENTER_CENTER,
+            "💡",
+            egui::FontId::proportional(size),
+            self.theme.diagnostic_color(Severity::Warning),
+        );
+
+        let icon = egui::Rect::from_center_size(center, egui::vec2(size, row.height()));
+        ui.interact(icon, ui.id().with("lightbulb"), egui::Sense::click())
+            .on_hover_cursor(egui::CursorIcon::PointingHand)
+            .on_hover_text("Show Code Actions (Ctrl+.)")
+            .clicked()
+    }
+
+    /// Paints the icon of the most severe problem starting on a gutter row; hovering lists them all.
+    fn draw_problem_icon(&self, ui: &mut egui::Ui, row: egui::Rect, line: usize, problems: &[&Diagnostic]) {
+        let Some(severity) = problems.iter().map(|d| d.severity).min() else {
+            return;
+        };
+        let size = self.theme.typography.code_font_size * 0.8;
+        let center = egui::pos2(row.left() + 6.0 + size / 2.0, row.center().y);
+        ui.painter().text(
+            center,
+            egui::Align2::CENTER_CENTER,
+            severity.icon(),
+            egui::FontId::proportional(size),
+            self.theme.diagnostic_color(severity),
+        );
+
+        let icon = egui::Rect::from_center_size(center, egui::vec2(size, row.height()));
+        ui.interact(icon, ui.id().with(("problem_icon", line)), egui::Sense::hover())
+            .on_hover_ui(|ui| self.problem_list(ui, problems));
+    }
+
+    /// Paints the added/modified/deleted marker for a gutter row. Hovering previews
+    /// the change; clicking returns its hunk so it can be reverted.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn draw_change_marker(&self, ui: &mut egui::Ui, row: egui::Rect, line: usize) -> Option<usize> {
+        let (index, hunk) = self.line_diff.as_ref()?.hunk_at_line(line)?;
+        let color = self.theme.git.change_color(hunk.kind);
+        let left = row.left() + 1.0;
+
+        let marker = if hunk.kind == LineChangeKind::Deleted {
+            let top = if hunk.new_start > line { row.bottom() } else { row.top() };
+            ui.painter().add(egui::Shape::convex_polygon(
+                vec![egui::pos2(left, top - 4.0), egui::pos2(left + 6.0, top), egui::pos2(left, top + 4.0)],
+                color,
+                egui::Stroke::NONE,
+            ));
+            egui::Rect::from_min_max(egui::pos2(left, top - 4.0), egui::pos2(left + 6.0, top + 4.0))
+        } else {
+            let bar = egui::Rect::from_min_size(egui::pos2(left, row.top()), egui::vec2(3.0, row.height()));
+            ui.painter().rect_filled(bar, 0.0, color);
+            bar
+        };
+
+        let response = ui
+            .interact(marker.expand2(egui::vec2(3.0, 0.0)), ui.id().with(("change_marker", line)), egui::Sense::click())
+            .on_hover_cursor(egui::CursorIcon::PointingHand)
+            .on_hover_ui(|ui| self.hunk_preview(ui, hunk));
+        response.clicked().then_some(index)
     }
 
     fn calculate_editor_dimensions(&mut self, ui: &egui::Ui, line_count: usize, line_height: f32) -> (f32, f32) {
@@ -251,29 +3580,129 @@ impl CodeEditor {
         editor_width: f32,
         editor_height: f32,
     ) {
+        let text_edit_id = egui::Id::new(Self::TEXT_EDIT_ID);
+        self.handle_navigation_keys(ui);
+        self.handle_refactor_keys(ui);
+        let typed = if ui.memory(|m| m.has_focus(text_edit_id)) && !self.read_only {
+            self.handle_completion_keys(ui)
+        } else {
+            None
+        };
+
         let desired_rows = self.code.lines().count().max(1);
         let code_for_highlighting = self.code.clone();
 
         let layout_job = self.get_highlighted_layout(&code_for_highlighting);
-        let response = ui.add_sized(
-            [editor_width, editor_height],
-            egui::TextEdit::multiline(&mut self.code)
-                .font(egui::TextStyle::Monospace)
-                .code_editor()
-                .desired_rows(desired_rows)
-                .desired_width(editor_width)
-                .layouter(&mut |ui: &egui::Ui, _buf: &dyn egui::TextBuffer, _wrap_width: f32| {
-                    ui.fonts(|f| f.layout_job(layout_job.clone()))
-                }),
-        );
+        let mut layouter = |ui: &egui::Ui, _buf: &dyn egui::TextBuffer, _wrap_width: f32| {
+            ui.fonts(|f| f.layout_job(layout_job.clone()))
+        };
+
+        let pending_cursor = self.pending_cursor.take().map(|cursor| cursor..cursor);
+        if let Some(selection) = self.pending_selection.take().or(pending_cursor) {
+            let mut state = egui::TextEdit::load_state(ui.ctx(), text_edit_id).unwrap_or_default();
+            let length = self.code.chars().count();
+            let cursor = |index: usize| egui::text::CCursor::new(index.min(length));
+            state.cursor.set_char_range(Some(egui::text::CCursorRange::two(cursor(selection.start), cursor(selection.end))));
+            state.store(ui.ctx(), text_edit_id);
+        }
+
+        // Laid out like `add_sized`, but keeping the output so decorations can find line positions.
+        let layout = egui::Layout::centered_and_justified(ui.layout().main_dir());
+        let output = if self.read_only {
+            ui.allocate_ui_with_layout(egui::vec2(editor_width, editor_height), layout, |ui| {
+                egui::TextEdit::multiline(&mut self.code.as_str())
+                    .id(text_edit_id)
+                    .font(egui::TextStyle::Monospace)
+                    .code_editor()
+                    .desired_rows(desired_rows)
+                    .desired_width(editor_width)
+                    .layouter(&mut layouter)
+                    .show(ui)
+            })
+        } else {
+            ui.allocate_ui_with_layout(egui::vec2(editor_width, editor_height), layout, |ui| {
+                egui::TextEdit::multiline(&mut self.code)
+                    .id(text_edit_id)
+                    .font(egui::TextStyle::Monospace)
+                    .code_editor()
+                    .desired_rows(desired_rows)
+                    .desired_width(editor_width)
+                    .layouter(&mut layouter)
+                    .show(ui)
+            })
+        }
+        .inner;
+        let response = output.response.clone();
 
         if response.changed() {
+            if self.code.contains('\r') {
+                self.code = line_ending::normalize(std::mem::take(&mut self.code));
+            }
             self.document_version += 1;
             self.invalidate_caches();
         }
 
-        if !response.has_focus() && response.hovered() {
+        if let Some(range) = egui::TextEdit::load_state(ui.ctx(), text_edit_id)
+            .and_then(|state| state.cursor.char_range())
+        {
+            self.cursor = range.primary.index;
+        }
+
+        if response.changed() {
+            self.completion_typed(typed);
+        } else {
+            self.completion.update(&self.code, self.cursor);
+        }
+        if response.clicked() && ui.input(|i| i.modifiers.command) {
+            self.go_to_definition(ui, self.cursor);
+        }
+
+        if let Some(session) = &mut self.completion.session {
+            let start = egui::text::CCursor::new(session.start);
+            let anchor = output.galley.pos_from_cursor(start).translate(output.galley_pos.to_vec2()).left_bottom();
+            if let Some(index) = completion_popup::show(ui.ctx(), anchor, session, &self.theme) {
+                self.accept_completion(Some(index));
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.show_code_action_menu(ui, &output);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let terminal_focused = self.terminals.has_focus() && self.bottom_panel == Some(BottomPanel::Terminal);
+        #[cfg(target_arch = "wasm32")]
+        let terminal_focused = false;
+        if !response.has_focus() && response.hovered() && self.palette.is_none() && !self.refactoring.is_renaming() && !terminal_focused {
             response.request_focus();
         }
+
+        if !self.conflicts.is_empty() && !response.changed() {
+            self.paint_conflicts(ui, &output);
+        }
+        if !response.changed() {
+            self.update_hover(ui, &output);
+            let problems = self.paint_diagnostics(ui, &output);
+            self.show_hover(ui, &problems);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string()
+}
+
+/// A wavy underline from `left` to `right` around `y`.
+fn paint_squiggle(painter: &egui::Painter, left: f32, right: f32, y: f32, color: egui::Color32) {
+    const STEP: f32 = 2.0;
+    let mut points = Vec::new();
+    let mut x = left;
+    let mut up = true;
+    while x < right {
+        points.push(egui::pos2(x, if up { y - 1.0 } else { y + 1.0 }));
+        x += STEP;
+        up = !up;
     }
-}
\ No newline at end of file
+    points.push(egui::pos2(right, y));
+    painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+}
@@ -0,0 +1,242 @@
+use crate::completion;
+use crate::config::FormatterConfig;
+use crate::document::edit::{self, TextEdit};
+use crate::document::position::TextPosition;
+use crate::format;
+use crate::ui::editor::{file_name, CodeEditor};
+use crate::ui::notifications::Notifications;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// Formatting of the open document, asked for by a command or before a save.
+pub struct FormatRequest {
+    path: PathBuf,
+    version: i32,
+    selection: bool,
+    /// Save the document once formatting is done, whether it worked or not.
+    save_after: bool,
+    /// The selected lines, when a formatter that only formats whole files was used on a
+    /// selection and the changes outside them are to be left out.
+    lines: Option<Range<usize>>,
+    /// Started on the next frame, which has the selection and a context to wake.
+    job: Option<FormatJob>,
+}
+
+enum FormatJob {
+    /// The formatted text from a configured command.
+    Command(mpsc::Receiver<Result<String, String>>),
+    /// The id of a formatting request to the language server.
+    Server(i64),
+}
+
+/// The document in the editor, as formatting needs to see it.
+pub struct OpenDocument<'a> {
+    /// The file open for editing, if any.
+    pub path: Option<&'a Path>,
+    pub code: &'a str,
+    pub version: i32,
+}
+
+/// The configured formatters and the formatting that is under way.
+#[derive(Default)]
+pub struct Formatting {
+    formatters: Vec<FormatterConfig>,
+    request: Option<FormatRequest>,
+    save_after: Option<PathBuf>,
+}
+
+impl Formatting {
+    pub fn set_formatters(&mut self, formatters: Vec<FormatterConfig>) {
+        self.formatters = formatters;
+    }
+
+    /// Whether a save waits for the formatting under way.
+    pub fn saving(&self) -> bool {
+        self.request.as_ref().is_some_and(|request| request.save_after)
+    }
+
+    /// The document to save now that it has been formatted.
+    pub fn take_save_after(&mut self) -> Option<PathBuf> {
+        self.save_after.take()
+    }
+
+    /// Queues formatting of the open document, unless nothing can format it: no formatter
+    /// is configured and no language server has it open. Commands say why; saves go ahead
+    /// quietly.
+    pub fn request(
+        &mut self,
+        document: &OpenDocument<'_>,
+        has_server: bool,
+        selection: bool,
+        save_after: bool,
+        notifications: &mut Notifications,
+    ) -> bool {
+        if let Some(request) = &mut self.request {
+            if save_after {
+                request.save_after = true;
+                return true;
+            }
+            notifications.info("The document is already being formatted");
+            return false;
+        }
+
+        let Some(path) = document.path else {
+            if !save_after {
+                notifications.info("Only files that are open for editing can be formatted");
+            }
+            return false;
+        };
+        if format::formatter_for(&self.formatters, path).is_none() && !has_server {
+            if !save_after {
+                notifications.info(format!("No formatter is configured for {}", file_name(path)));
+            }
+            return false;
+        }
+
+        self.request = Some(FormatRequest {
+            path: path.to_path_buf(),
+            version: document.version,
+            selection,
+            save_after,
+            lines: None,
+            job: None,
+        });
+        true
+    }
+
+    pub fn is_server_request(&self, id: i64) -> bool {
+        matches!(self.request, Some(FormatRequest { job: Some(FormatJob::Server(job)), .. }) if job == id)
+    }
+
+    /// The formatting the language server answered with response `id`, if it was one.
+    pub fn take_server_request(&mut self, id: i64) -> Option<FormatRequest> {
+        if !self.is_server_request(id) {
+            return None;
+        }
+        self.request.take()
+    }
+
+    /// Starts a queued formatting and takes the result of a formatter command. Documents
+    /// without a formatter command go to the language server through `format_with_server`,
+    /// which gets the selected bytes and returns the id of its request.
+    pub fn update(
+        &mut self,
+        ctx: &egui::Context,
+        document: &OpenDocument<'_>,
+        notifications: &mut Notifications,
+        format_with_server: impl FnOnce(&Path, Option<Range<usize>>) -> Option<i64>,
+    ) -> Option<(FormatRequest, Result<String, String>)> {
+        let request = self.request.as_ref()?;
+        match &request.job {
+            None => self.start(ctx, document, notifications, format_with_server),
+            Some(FormatJob::Command(receiver)) => match receiver.try_recv() {
+                Ok(formatted) => self.request.take().map(|request| (request, formatted)),
+                Err(mpsc::TryRecvError::Empty) => None,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    let request = self.request.take()?;
+                    Some((request, Err("the formatter stopped unexpectedly".to_string())))
+                }
+            },
+            Some(FormatJob::Server(_)) => None,
+        }
+    }
+
+    fn start(
+        &mut self,
+        ctx: &egui::Context,
+        document: &OpenDocument<'_>,
+        notifications: &mut Notifications,
+        format_with_server: impl FnOnce(&Path, Option<Range<usize>>) -> Option<i64>,
+    ) -> Option<(FormatRequest, Result<String, String>)> {
+        let mut request = self.request.take()?;
+        if document.path != Some(request.path.as_path()) {
+            return None;
+        }
+
+        let mut range = None;
+        if request.selection {
+            let selection = egui::TextEdit::load_state(ctx, egui::Id::new(CodeEditor::TEXT_EDIT_ID))
+                .and_then(|state| state.cursor.char_range())
+                .map(|range| {
+                    let [start, end] = range.sorted_cursors();
+                    start.index..end.index
+                })
+                .filter(|selection| !selection.is_empty());
+            let Some(selection) = selection else {
+                notifications.info("Select the lines to format");
+                return None;
+            };
+            range = Some(selection);
+        }
+
+        if let Some(formatter) = format::formatter_for(&self.formatters, &request.path) {
+            request.lines = range.map(|range| {
+                let first = TextPosition::from_char_index(document.code, range.start).line;
+                let last = TextPosition::from_char_index(document.code, range.end).line;
+                first..last + 1
+            });
+            request.job = Some(FormatJob::Command(format::spawn(formatter, &request.path, document.code.to_string(), ctx)));
+            self.request = Some(request);
+            return None;
+        }
+
+        let bytes = range.map(|range| completion::byte_offset(document.code, range.start)..completion::byte_offset(document.code, range.end));
+        match format_with_server(&request.path, bytes) {
+            Some(id) => {
+                request.job = Some(FormatJob::Server(id));
+                self.request = Some(request);
+                None
+            }
+            None => {
+                let what = if request.selection { "a selection" } else { "documents" };
+                Some((request, Err(format!("the language server cannot format {}", what))))
+            }
+        }
+    }
+
+    /// The formatted document and the edits that make it, to go in the buffer as one undo
+    /// step, unless the document changed in the meantime. Failures leave it as it was.
+    pub fn finish(
+        &mut self,
+        request: FormatRequest,
+        formatted: Result<String, String>,
+        document: &OpenDocument<'_>,
+        notifications: &mut Notifications,
+    ) -> Option<(String, Vec<TextEdit>)> {
+        if request.save_after {
+            self.save_after = Some(request.path.clone());
+        }
+        let formatted = match formatted {
+            Ok(formatted) => formatted,
+            // Nothing formats this file after all; the save goes ahead without a word.
+            Err(_) if request.save_after && request.job.is_none() => return None,
+            Err(e) => {
+                notifications.error(format!("Formatting {} failed: {}", file_name(&request.path), e));
+                return None;
+            }
+        };
+        if document.path != Some(request.path.as_path()) || document.version != request.version {
+            notifications.warning(format!(
+                "{} changed while it was being formatted, so the result was discarded",
+                file_name(&request.path)
+            ));
+            return None;
+        }
+
+        let mut edits = edit::line_changes(document.code, &formatted);
+        if let Some(lines) = &request.lines {
+            edits.retain(|edit| edit.start.line < lines.end && (edit.end.line > lines.start || edit.start.line >= lines.start));
+        }
+        if edits.is_empty() {
+            return None;
+        }
+        match edit::apply(document.code, &edits) {
+            Ok(text) => Some((text, edits)),
+            Err(e) => {
+                notifications.error(format!("Formatting {} failed: {}", file_name(&request.path), e));
+                None
+            }
+        }
+    }
+}
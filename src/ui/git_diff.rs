@@ -0,0 +1,98 @@
+use crate::git::patch::FilePatch;
+use crate::git::{GitMonitor, GitOperation, PatchRequest};
+use crate::ui::diff_view::{DiffView, HunkAction};
+use crate::ui::notifications::Notifications;
+
+/// The file change shown in the diff view, kept so hunks can be staged and the view reloaded.
+struct ShownPatch {
+    request: PatchRequest,
+    patch: FilePatch,
+}
+
+/// The changes to one file that Source Control opened in the diff view.
+#[derive(Default)]
+pub struct GitDiff {
+    shown: Option<ShownPatch>,
+    /// The diff to show once the worker has read it; a newer request replaces it.
+    requested: Option<PatchRequest>,
+}
+
+impl GitDiff {
+    /// Reads the changes to one file in the background, to be shown by [`Self::show`].
+    pub fn open(&mut self, request: PatchRequest, git: &mut GitMonitor, ctx: &egui::Context) {
+        self.requested = Some(request.clone());
+        git.request_patch(request, ctx);
+    }
+
+    pub fn close(&mut self) {
+        self.shown = None;
+        self.requested = None;
+    }
+
+    /// Reads the changes shown in the diff view again, keeping the view and its layout.
+    /// Returns false when the diff closed instead because the file is gone.
+    pub fn reload(&mut self, git: &mut GitMonitor, ctx: &egui::Context) -> bool {
+        let Some(shown) = &self.shown else {
+            return true;
+        };
+        // Discarding an untracked file deletes it, leaving nothing to show.
+        if shown.request.untracked && !shown.request.path.exists() {
+            self.close();
+            return false;
+        }
+        let request = shown.request.clone();
+        self.open(request, git, ctx);
+        true
+    }
+
+    /// Shows the changes to one file in the diff view, or reloads them if it is already shown.
+    pub fn show(
+        &mut self,
+        request: PatchRequest,
+        patch: Result<FilePatch, String>,
+        diff_view: &mut Option<DiffView>,
+        notifications: &mut Notifications,
+    ) {
+        if self.requested.as_ref() != Some(&request) {
+            return;
+        }
+        self.requested = None;
+        let patch = match patch {
+            Ok(patch) => patch,
+            Err(e) => {
+                notifications.error(format!("Failed to diff {}: {}", request.path.display(), e));
+                return;
+            }
+        };
+
+        let hunks = patch.hunks.clone();
+        let reloading = diff_view.is_some()
+            && self.shown.as_ref().is_some_and(|shown| shown.request.path == request.path && shown.request.staged == request.staged);
+        match diff_view {
+            Some(view) if reloading => view.set_hunks(hunks),
+            _ => {
+                let name = request.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let (title, actions) = match (request.staged, request.untracked) {
+                    (true, _) => (format!("{} (Staged)", name), vec![HunkAction::Unstage]),
+                    (false, true) => (format!("{} (Untracked)", name), Vec::new()),
+                    (false, false) => (format!("{} (Working Tree)", name), vec![HunkAction::Stage, HunkAction::Discard]),
+                };
+                *diff_view = Some(DiffView::new(title, hunks, actions));
+            }
+        }
+        self.shown = Some(ShownPatch { request, patch });
+    }
+
+    /// The operation that stages, unstages or discards one hunk of the shown diff by
+    /// applying it as a patch.
+    pub fn hunk_operation(&self, index: usize, action: HunkAction) -> Option<GitOperation> {
+        let shown = self.shown.as_ref()?;
+        let patch = shown.patch.hunk_patch(index)?;
+        let (cached, reverse) = match action {
+            HunkAction::Stage => (true, false),
+            HunkAction::Unstage => (true, true),
+            HunkAction::Discard => (false, true),
+        };
+        Some(GitOperation::ApplyPatch { repo: shown.request.repo.clone(), patch, cached, reverse })
+    }
+}
@@ -0,0 +1,166 @@
+use crate::document::conflict::{self, Conflict, Resolution};
+use crate::ui::theme::ZenTheme;
+use egui::Color32;
+
+pub enum MergeAction {
+    Close,
+    /// Replace the conflict at this index with the given text.
+    Resolve(usize, String),
+    /// Stage the file so git considers it resolved.
+    MarkResolved,
+}
+
+/// Three-way view of the conflicts in the open buffer: base, current and incoming side
+/// by side, and an editable result for the conflict being resolved.
+#[derive(Default)]
+pub struct MergeView {
+    current: usize,
+    result: String,
+    /// The conflict `result` was filled in for, so edits survive until it is applied.
+    result_for: Option<Conflict>,
+    /// The whole file as it was before either side changed it, from git's index, for
+    /// conflicts written without their common ancestor.
+    base_file: Option<String>,
+    /// Each conflict's part of `base_file`, worked out again when the conflicts change.
+    bases: Vec<String>,
+    bases_for: Vec<Conflict>,
+}
+
+impl MergeView {
+    pub fn set_base_file(&mut self, base: String) {
+        self.base_file = Some(base);
+        self.bases_for.clear();
+    }
+
+    pub fn render(
+        &mut self,
+        ui: &mut egui::Ui,
+        theme: &ZenTheme,
+        text: &str,
+        conflicts: &[Conflict],
+        read_only: bool,
+    ) -> Option<MergeAction> {
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            ui.strong("Merge Conflicts");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("Close").clicked() {
+                    action = Some(MergeAction::Close);
+                }
+                if conflicts.is_empty() {
+                    return;
+                }
+                ui.separator();
+                if ui.add_enabled(self.current + 1 < conflicts.len(), egui::Button::new("⏷")).on_hover_text("Next Conflict").clicked() {
+                    self.current += 1;
+                }
+                if ui.add_enabled(self.current > 0, egui::Button::new("⏶")).on_hover_text("Previous Conflict").clicked() {
+                    self.current -= 1;
+                }
+                ui.weak(format!("Conflict {} of {}", self.current + 1, conflicts.len()));
+            });
+        });
+        ui.separator();
+
+        if conflicts.is_empty() {
+            ui.vertical_centered(|ui| {
+                ui.add_space(24.0);
+                ui.label("✔ All conflicts are resolved");
+                ui.add_space(8.0);
+                if cfg!(not(target_arch = "wasm32")) && ui.button("Mark as Resolved").clicked() {
+                    action = Some(MergeAction::MarkResolved);
+                }
+            });
+            return action;
+        }
+
+        self.current = self.current.min(conflicts.len() - 1);
+        let conflict = &conflicts[self.current];
+        if self.result_for.as_ref() != Some(conflict) {
+            self.result = conflict.resolved_text(text, Resolution::Current);
+            self.result_for = Some(conflict.clone());
+        }
+
+        if let Some(base_file) = &self.base_file {
+            if self.bases_for != conflicts {
+                self.bases = conflict::base_sections(text, conflicts, base_file);
+                self.bases_for = conflicts.to_vec();
+            }
+        }
+        let loaded_base = self.base_file.as_ref().and_then(|_| self.bases.get(self.current)).cloned();
+
+        let section = |range: &std::ops::Range<usize>| text.get(range.clone()).unwrap_or("");
+        let tint = |[r, g, b]: [u8; 3]| Color32::from_rgba_unmultiplied(r, g, b, 30);
+        let font = egui::FontId::monospace(theme.typography.code_font_size);
+        let pane_height = (ui.available_height() * 0.45).max(120.0);
+
+        ui.columns(3, |columns| {
+            let base_label = "Base";
+            match (&conflict.base, &loaded_base) {
+                (Some(base), _) => text_pane(&mut columns[0], base_label, section(base), None, &font, pane_height),
+                (None, Some(base)) => text_pane(&mut columns[0], base_label, base, None, &font, pane_height),
+                (None, None) => {
+                    columns[0].strong(base_label);
+                    columns[0].weak("Not recorded in the file. Merge with merge.conflictStyle=diff3 to see the common ancestor.");
+                }
+            }
+            let ours_label = format!("Current ({})", conflict.ours_label);
+            text_pane(&mut columns[1], &ours_label, section(&conflict.ours), Some(tint(theme.git.added)), &font, pane_height);
+            let theirs_label = format!("Incoming ({})", conflict.theirs_label);
+            text_pane(&mut columns[2], &theirs_label, section(&conflict.theirs), Some(tint(theme.git.modified)), &font, pane_height);
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.strong("Result");
+            ui.add_enabled_ui(!read_only, |ui| {
+                let mut fill = |resolution| self.result = conflict.resolved_text(text, resolution);
+                if ui.small_button("Use Current").clicked() {
+                    fill(Resolution::Current);
+                }
+                if ui.small_button("Use Incoming").clicked() {
+                    fill(Resolution::Incoming);
+                }
+                if ui.small_button("Use Both").clicked() {
+                    fill(Resolution::Both);
+                }
+                if conflict.base.is_some() && ui.small_button("Use Base").clicked() {
+                    fill(Resolution::Base);
+                } else if let (None, Some(base)) = (&conflict.base, &loaded_base) {
+                    if ui.small_button("Use Base").clicked() {
+                        self.result = base.clone();
+                    }
+                }
+            });
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.add_enabled(!read_only, egui::Button::new("Apply Result")).clicked() {
+                    action = Some(MergeAction::Resolve(self.current, self.result.clone()));
+                    self.result_for = None;
+                }
+            });
+        });
+
+        egui::ScrollArea::vertical().id_salt("merge_result").auto_shrink([false; 2]).show(ui, |ui| {
+            ui.add_enabled(
+                !read_only,
+                egui::TextEdit::multiline(&mut self.result)
+                    .font(font.clone())
+                    .code_editor()
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(8),
+            );
+        });
+
+        action
+    }
+}
+
+fn text_pane(ui: &mut egui::Ui, label: &str, text: &str, fill: Option<Color32>, font: &egui::FontId, height: f32) {
+    ui.strong(label);
+    egui::Frame::new().fill(fill.unwrap_or_default()).show(ui, |ui| {
+        egui::ScrollArea::both().id_salt(label).max_height(height).auto_shrink([false, true]).show(ui, |ui| {
+            ui.label(egui::RichText::new(text.trim_end_matches('\n')).font(font.clone()));
+        });
+    });
+}
@@ -1,3 +1,24 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod code_actions;
+pub mod completion_popup;
+pub mod diff_view;
 pub mod editor;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod format;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod git_diff;
+pub mod merge_view;
+pub mod notifications;
+pub mod outline;
+pub mod palette;
+pub mod problems;
+pub mod refactor;
+pub mod references;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod source_control;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod task_output;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod terminal;
 pub mod tree;
 pub mod theme;
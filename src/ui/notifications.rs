@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum NotificationLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A command offered as a button on a notification, handled by the app when clicked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationAction {
+    RetrySave(PathBuf),
+    OverwriteSave(PathBuf),
+    ReloadFile(PathBuf),
+    /// Restores the files a workspace edit wrote to disk.
+    #[cfg(not(target_arch = "wasm32"))]
+    UndoWorkspaceEdit,
+    Dismiss,
+}
+
+impl NotificationAction {
+    /// The file the action applies to, which has to still be the open one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::RetrySave(path) | Self::OverwriteSave(path) | Self::ReloadFile(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub message: String,
+    pub actions: Vec<(String, NotificationAction)>,
+    expires_at: Option<f64>,
+}
+
+#[derive(Default)]
+pub struct Notifications {
+    items: Vec<Notification>,
+}
+
+impl Notifications {
+    const INFO_TIMEOUT: f64 = 5.0;
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(NotificationLevel::Info, message.into());
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>) {
+        self.push(NotificationLevel::Warning, message.into());
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(NotificationLevel::Error, message.into());
+    }
+
+    pub fn push_with_actions(
+        &mut self,
+        level: NotificationLevel,
+        message: impl Into<String>,
+        actions: Vec<(String, NotificationAction)>,
+    ) {
+        self.push(level, message.into());
+        if let Some(item) = self.items.last_mut() {
+            item.actions = actions;
+        }
+    }
+
+    fn push(&mut self, level: NotificationLevel, message: String) {
+        log::log!(
+            match level {
+                NotificationLevel::Info => log::Level::Info,
+                NotificationLevel::Warning => log::Level::Warn,
+                NotificationLevel::Error => log::Level::Error,
+            },
+            "{}",
+            message
+        );
+
+        self.items.push(Notification {
+            level,
+            message,
+            actions: Vec::new(),
+            expires_at: None,
+        });
+    }
+
+    /// Draws the notification stack and returns the action the user clicked, if any.
+    pub fn render(&mut self, ctx: &egui::Context) -> Option<NotificationAction> {
+        if self.items.is_empty() {
+            return None;
+        }
+
+        let now = ctx.input(|i| i.time);
+        for item in &mut self.items {
+            if item.level == NotificationLevel::Info && item.actions.is_empty() && item.expires_at.is_none() {
+                item.expires_at = Some(now + Self::INFO_TIMEOUT);
+            }
+        }
+        self.items.retain(|item| item.expires_at.is_none_or(|t| t > now));
+
+        if let Some(next_expiry) = self.items.iter().filter_map(|item| item.expires_at).reduce(f64::min) {
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(next_expiry - now));
+        }
+
+        let mut dismissed = None;
+        let mut clicked = None;
+        egui::Area::new(egui::Id::new("zen_notifications"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                for (index, item) in self.items.iter().enumerate() {
+                    let (icon, accent) = match item.level {
+                        NotificationLevel::Info => ("ℹ", egui::Color32::from_rgb(96, 165, 250)),
+                        NotificationLevel::Warning => ("⚠", egui::Color32::from_rgb(250, 204, 21)),
+                        NotificationLevel::Error => ("⛔", egui::Color32::from_rgb(248, 113, 113)),
+                    };
+
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.set_max_width(360.0);
+                        ui.horizontal(|ui| {
+                            ui.colored_label(accent, icon);
+                            ui.add(egui::Label::new(&item.message).wrap());
+                            if ui.small_button("🗙").clicked() {
+                                dismissed = Some(index);
+                            }
+                        });
+                        if !item.actions.is_empty() {
+                            ui.horizontal(|ui| {
+                                for (label, action) in &item.actions {
+                                    if ui.button(label).clicked() {
+                                        dismissed = Some(index);
+                                        clicked = Some(action.clone());
+                                    }
+                                }
+                            });
+                        }
+                    });
+                    ui.add_space(4.0);
+                }
+            });
+
+        if let Some(index) = dismissed {
+            self.items.remove(index);
+        }
+
+        clicked
+    }
+}
@@ -0,0 +1,122 @@
+use crate::document::position::TextPosition;
+use crate::symbols::outline::{self, OutlineItem};
+use std::path::PathBuf;
+
+/// What the outline was built from, to tell when it is out of date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineSource {
+    pub path: PathBuf,
+    pub version: i32,
+    /// Whether a language server was running; once one is, its outline replaces the tags'.
+    pub server_running: bool,
+}
+
+/// The symbols of the open document as a tree that follows the cursor.
+#[derive(Default)]
+pub struct OutlinePanel {
+    pub items: Vec<OutlineItem>,
+    pub source: Option<OutlineSource>,
+    /// A `documentSymbol` request in flight, with the source it will describe.
+    pub request: Option<(i64, OutlineSource)>,
+    filter: String,
+    /// The item the cursor was in, as indices from the top level, to reveal it when it changes.
+    active: Vec<usize>,
+}
+
+impl OutlinePanel {
+    pub fn set_items(&mut self, items: Vec<OutlineItem>, source: OutlineSource) {
+        self.items = items;
+        self.source = Some(source);
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.source = None;
+        self.request = None;
+    }
+
+    /// Returns where the clicked symbol is.
+    pub fn render(&mut self, ui: &mut egui::Ui, cursor_line: usize) -> Option<TextPosition> {
+        ui.horizontal(|ui| {
+            ui.strong("Outline");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Filter").desired_width(f32::INFINITY));
+            });
+        });
+        ui.separator();
+
+        if self.items.is_empty() {
+            if self.source.is_some() {
+                ui.weak("No symbols found in this file");
+            } else {
+                ui.weak("Open a file to see its symbols");
+            }
+            return None;
+        }
+
+        let active = outline::path_at(&self.items, cursor_line);
+        let reveal = active != self.active;
+        self.active = active;
+
+        let mut clicked = None;
+        let view = OutlineView { active: &self.active, reveal, filter: self.filter.to_lowercase() };
+        egui::ScrollArea::both().auto_shrink([false; 2]).show(ui, |ui| {
+            view.show_items(ui, &self.items, &mut Vec::new(), &mut clicked);
+        });
+        clicked
+    }
+}
+
+struct OutlineView<'a> {
+    active: &'a [usize],
+    reveal: bool,
+    filter: String,
+}
+
+impl OutlineView<'_> {
+    fn show_items(&self, ui: &mut egui::Ui, items: &[OutlineItem], path: &mut Vec<usize>, clicked: &mut Option<TextPosition>) {
+        for (index, item) in items.iter().enumerate() {
+            if !self.filter.is_empty() && !matches_filter(item, &self.filter) {
+                continue;
+            }
+            path.push(index);
+
+            if item.children.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.add_space(ui.spacing().indent);
+                    self.show_row(ui, item, path, clicked);
+                });
+            } else {
+                let id = ui.make_persistent_id(("outline", path.as_slice()));
+                let mut state = egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true);
+                let contains_active = self.active.len() > path.len() && self.active.starts_with(path);
+                if (self.reveal && contains_active) || !self.filter.is_empty() {
+                    state.set_open(true);
+                }
+                state
+                    .show_header(ui, |ui| self.show_row(ui, item, path, clicked))
+                    .body(|ui| self.show_items(ui, &item.children, path, clicked));
+            }
+
+            path.pop();
+        }
+    }
+
+    fn show_row(&self, ui: &mut egui::Ui, item: &OutlineItem, path: &[usize], clicked: &mut Option<TextPosition>) {
+        let active = path == self.active;
+        let text = format!("{} {}", item.symbol.kind.icon(), item.label());
+        let response = ui
+            .selectable_label(active, text)
+            .on_hover_text(format!("{}: {}", item.symbol.kind.label(), item.symbol.signature));
+        if active && self.reveal {
+            response.scroll_to_me(Some(egui::Align::Center));
+        }
+        if response.clicked() {
+            *clicked = Some(item.symbol.position);
+        }
+    }
+}
+
+fn matches_filter(item: &OutlineItem, filter: &str) -> bool {
+    item.label().to_lowercase().contains(filter) || item.children.iter().any(|child| matches_filter(child, filter))
+}
@@ -0,0 +1,175 @@
+use crate::ui::theme::ZenTheme;
+use egui::text::{LayoutJob, TextFormat};
+use egui::Color32;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+
+const WIDTH: f32 = 560.0;
+const LIST_HEIGHT: f32 = 360.0;
+/// Rows shown at most, so a huge list stays cheap to filter and draw.
+const MAX_MATCHES: usize = 500;
+
+/// Something to pick in a palette.
+pub struct PaletteItem {
+    pub icon: &'static str,
+    pub label: String,
+    /// Shown faded after the label, such as where the item is.
+    pub detail: String,
+}
+
+pub enum PaletteResult {
+    /// The index of the item that was picked.
+    Picked(usize),
+    Closed,
+}
+
+/// A popup at the top of the window that narrows a list down as a query is typed and
+/// picks an item with Enter or a click.
+pub struct Palette {
+    hint: String,
+    query: String,
+    /// Matching item indices with the positions of the matched label characters, best first.
+    matches: Vec<(usize, Vec<usize>)>,
+    /// The query and item count the matches were computed for.
+    filtered: Option<(String, usize)>,
+    selected: usize,
+    reveal_selected: bool,
+}
+
+impl Palette {
+    pub fn new(hint: impl Into<String>) -> Self {
+        Self {
+            hint: hint.into(),
+            query: String::new(),
+            matches: Vec::new(),
+            filtered: None,
+            selected: 0,
+            reveal_selected: false,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, theme: &ZenTheme, items: &[PaletteItem]) -> Option<PaletteResult> {
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            )
+        });
+        if escape {
+            return Some(PaletteResult::Closed);
+        }
+
+        self.filter(items);
+        if up && self.selected > 0 {
+            self.selected -= 1;
+            self.reveal_selected = true;
+        }
+        if down && self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+            self.reveal_selected = true;
+        }
+        if enter {
+            return self.matches.get(self.selected).map(|(index, _)| PaletteResult::Picked(*index));
+        }
+
+        let mut result = None;
+        let area = egui::Area::new(egui::Id::new("palette"))
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 48.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(WIDTH);
+                    let input = ui.add(
+                        egui::TextEdit::singleline(&mut self.query)
+                            .hint_text(self.hint.as_str())
+                            .desired_width(f32::INFINITY),
+                    );
+                    input.request_focus();
+                    if input.changed() {
+                        self.selected = 0;
+                    }
+
+                    if self.matches.is_empty() {
+                        ui.weak("No matching results");
+                        return;
+                    }
+                    egui::ScrollArea::vertical().max_height(LIST_HEIGHT).show(ui, |ui| {
+                        ui.spacing_mut().item_spacing.y = 0.0;
+                        for (row, (index, positions)) in self.matches.iter().enumerate() {
+                            let job = row_layout(ui, theme, &items[*index], positions);
+                            let selected = row == self.selected;
+                            let response = ui.add_sized([WIDTH, 0.0], egui::Button::selectable(selected, job));
+                            if selected && self.reveal_selected {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                result = Some(PaletteResult::Picked(*index));
+                            }
+                        }
+                    });
+                    self.reveal_selected = false;
+                });
+            });
+
+        let clicked_outside = ctx.input(|i| {
+            i.pointer.any_pressed() && i.pointer.interact_pos().is_some_and(|pos| !area.response.rect.contains(pos))
+        });
+        if clicked_outside {
+            return Some(PaletteResult::Closed);
+        }
+        result
+    }
+
+    fn filter(&mut self, items: &[PaletteItem]) {
+        let key = (self.query.clone(), items.len());
+        if self.filtered.as_ref() == Some(&key) {
+            return;
+        }
+
+        let query = self.query.trim();
+        self.matches = if query.is_empty() {
+            items.iter().enumerate().take(MAX_MATCHES).map(|(index, _)| (index, Vec::new())).collect()
+        } else {
+            let matcher = SkimMatcherV2::default().smart_case();
+            let mut scored: Vec<(i64, usize, Vec<usize>)> = items
+                .iter()
+                .enumerate()
+                .filter_map(|(index, item)| {
+                    let (score, positions) = matcher.fuzzy_indices(&item.label, query)?;
+                    Some((score, index, positions))
+                })
+                .collect();
+            scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+            scored.truncate(MAX_MATCHES);
+            scored.into_iter().map(|(_, index, positions)| (index, positions)).collect()
+        };
+        self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+        self.filtered = Some(key);
+    }
+}
+
+/// The icon, the label with its matched characters highlighted, and the detail faded.
+fn row_layout(ui: &egui::Ui, theme: &ZenTheme, item: &PaletteItem, matched: &[usize]) -> LayoutJob {
+    let font = egui::FontId::monospace(theme.typography.code_font_size);
+    let plain = TextFormat::simple(font.clone(), ui.visuals().text_color());
+    let [r, g, b] = theme.syntax.keyword;
+    let highlight = TextFormat::simple(font.clone(), Color32::from_rgb(r, g, b));
+    let faded = TextFormat::simple(font, ui.visuals().weak_text_color());
+
+    let mut job = LayoutJob::default();
+    job.append(item.icon, 0.0, faded.clone());
+    job.append(" ", 0.0, plain.clone());
+    for (index, c) in item.label.chars().enumerate() {
+        let format = if matched.contains(&index) { highlight.clone() } else { plain.clone() };
+        job.append(c.encode_utf8(&mut [0; 4]), 0.0, format);
+    }
+    if !item.detail.is_empty() {
+        job.append(&item.detail, 12.0, faded);
+    }
+    job.wrap.max_rows = 1;
+    job.wrap.break_anywhere = true;
+    job.wrap.max_width = WIDTH - 16.0;
+    job
+}
@@ -0,0 +1,88 @@
+use crate::document::diagnostics::{Diagnostics, Severity};
+use crate::document::position::Location;
+use crate::ui::theme::ZenTheme;
+use std::path::{Path, PathBuf};
+
+/// Every problem reported in the workspace, grouped by file.
+#[derive(Default)]
+pub struct ProblemsPanel {
+    filter: String,
+}
+
+impl ProblemsPanel {
+    pub fn render(
+        &mut self,
+        ui: &mut egui::Ui,
+        theme: &ZenTheme,
+        diagnostics: &Diagnostics,
+        roots: &[PathBuf],
+    ) -> Option<Location> {
+        let mut clicked = None;
+
+        ui.horizontal(|ui| {
+            for severity in [Severity::Error, Severity::Warning, Severity::Info] {
+                let text = format!("{} {}", severity.icon(), diagnostics.count(severity));
+                ui.label(egui::RichText::new(text).color(theme.diagnostic_color(severity)));
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Filter").desired_width(160.0));
+            });
+        });
+        ui.separator();
+
+        if diagnostics.is_empty() {
+            ui.weak("No problems have been detected in the workspace");
+            return None;
+        }
+
+        let filter = self.filter.to_lowercase();
+        egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+            for (path, problems) in diagnostics.files() {
+                let problems: Vec<_> = problems
+                    .into_iter()
+                    .filter(|d| filter.is_empty() || d.description().to_lowercase().contains(&filter))
+                    .collect();
+                if problems.is_empty() {
+                    continue;
+                }
+
+                let header = format!("{}  ({})", display_path(path, roots), problems.len());
+                egui::CollapsingHeader::new(header)
+                    .id_salt(path)
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for diagnostic in problems {
+                            ui.horizontal(|ui| {
+                                let icon = egui::RichText::new(diagnostic.severity.icon())
+                                    .color(theme.diagnostic_color(diagnostic.severity));
+                                ui.label(icon).on_hover_text(diagnostic.severity.label());
+                                let text = format!(
+                                    "{}  [Ln {}, Col {}]",
+                                    diagnostic.description().replace('\n', " "),
+                                    diagnostic.start.line + 1,
+                                    diagnostic.start.column + 1
+                                );
+                                if ui.selectable_label(false, text).clicked() {
+                                    clicked = Some(Location::new(path, diagnostic.start));
+                                }
+                            });
+                        }
+                    });
+            }
+        });
+
+        clicked
+    }
+}
+
+/// The path relative to the workspace folder it is in, or in full when it is outside them.
+pub fn display_path(path: &Path, roots: &[PathBuf]) -> String {
+    let Some((root, relative)) = roots.iter().find_map(|root| Some((root, path.strip_prefix(root).ok()?))) else {
+        return path.display().to_string();
+    };
+    // With several roots, the root's folder name tells apart files at the same place in each.
+    match root.file_name().filter(|_| roots.len() > 1) {
+        Some(name) => Path::new(name).join(relative).display().to_string(),
+        None => relative.display().to_string(),
+    }
+}
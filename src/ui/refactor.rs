@@ -0,0 +1,355 @@
+use crate::document::edit::{self, FileChange, TextEdit, WorkspaceEdit};
+use crate::document::encoding::FileEncoding;
+use crate::document::line_ending::LineEnding;
+use crate::ui::diff_view::{DiffView, DiffViewAction};
+use crate::ui::problems::display_path;
+use crate::ui::theme::ZenTheme;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::document::line_ending;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::document::{encoding, writer};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::notifications::Notifications;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The F2 prompt for a new name for the identifier at a character index.
+struct RenamePrompt {
+    index: usize,
+    /// The buffer version the index is in; the prompt closes when the buffer changes.
+    version: i32,
+    name: String,
+    new_name: String,
+    focus: bool,
+}
+
+/// A rename the user confirmed in the prompt.
+pub struct Rename {
+    pub index: usize,
+    pub name: String,
+    pub new_name: String,
+}
+
+/// A workspace edit previewed in the diff view until it is applied or cancelled.
+pub struct PendingEdit {
+    pub label: String,
+    changes: Vec<FileChange>,
+    /// The edits to the open buffer, to keep the cursor on the same code.
+    buffer_edits: Vec<TextEdit>,
+    view: DiffView,
+    /// How each changed file other than the open one was stored, to write it back the same way.
+    disk: HashMap<PathBuf, DiskText>,
+    /// The `workspace/applyEdit` request to answer once the edit is applied or cancelled.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub reply: Option<(String, serde_json::Value)>,
+    /// The command of a code action, run on the server of a file after its edit is applied.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub command: Option<(PathBuf, lsp_types::Command)>,
+}
+
+impl PendingEdit {
+    /// The change to the open buffer and the edits that make it, if the edit changes it.
+    pub fn buffer_change(&self) -> Option<(&FileChange, &[TextEdit])> {
+        let change = self.changes.iter().find(|change| !self.disk.contains_key(&change.path))?;
+        Some((change, &self.buffer_edits))
+    }
+}
+
+/// What became of the previewed edit.
+pub enum PreviewResult {
+    Apply(PendingEdit),
+    Cancel(PendingEdit),
+}
+
+/// A file as read from disk for a workspace edit.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+struct DiskText {
+    bytes: Vec<u8>,
+    encoding: FileEncoding,
+    line_ending: LineEnding,
+}
+
+/// The files the last workspace edit wrote, as they were before and after, so it can be undone.
+#[cfg(not(target_arch = "wasm32"))]
+struct WrittenEdit {
+    label: String,
+    files: Vec<(PathBuf, Vec<u8>, Vec<u8>)>,
+}
+
+/// A rename or code action step waiting on a language server.
+#[cfg(not(target_arch = "wasm32"))]
+pub enum RefactorRequest {
+    /// The label of the rename, for its preview.
+    Rename(String),
+    Resolve,
+    /// The title of the code action whose command is running.
+    Command(String),
+}
+
+/// The open buffer, as edits to it see it.
+pub struct OpenBuffer<'a> {
+    /// The open file, unless it is too large to edit.
+    pub path: Option<&'a Path>,
+    pub code: &'a str,
+    pub read_only: bool,
+}
+
+/// Renames and workspace edits: the F2 prompt, the preview of an edit, the files the last
+/// one wrote and the language server step they wait on.
+#[derive(Default)]
+pub struct Refactoring {
+    rename_prompt: Option<RenamePrompt>,
+    pending_edit: Option<PendingEdit>,
+    #[cfg(not(target_arch = "wasm32"))]
+    written_edit: Option<WrittenEdit>,
+    #[cfg(not(target_arch = "wasm32"))]
+    request: Option<(i64, RefactorRequest)>,
+}
+
+impl Refactoring {
+    /// Asks for a new name for `name`, which starts at `index` in buffer `version`.
+    pub fn start_rename(&mut self, index: usize, version: i32, name: String) {
+        self.rename_prompt = Some(RenamePrompt {
+            index,
+            version,
+            new_name: name.clone(),
+            name,
+            focus: true,
+        });
+    }
+
+    pub fn is_renaming(&self) -> bool {
+        self.rename_prompt.is_some()
+    }
+
+    /// Shows the F2 prompt, which closes when the buffer is no longer at `version`.
+    /// Returns the rename once it is confirmed with a new name.
+    pub fn render_rename_prompt(&mut self, ctx: &egui::Context, version: i32) -> Option<Rename> {
+        let prompt = self.rename_prompt.as_mut()?;
+        if prompt.version != version {
+            self.rename_prompt = None;
+            return None;
+        }
+
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new("Rename Symbol")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 48.0))
+            .show(ctx, |ui| {
+                ui.label(format!("Rename `{}` to:", prompt.name));
+                let output = egui::TextEdit::singleline(&mut prompt.new_name).desired_width(280.0).show(ui);
+                if prompt.focus {
+                    prompt.focus = false;
+                    output.response.request_focus();
+                    let mut state = output.state;
+                    let all = egui::text::CCursorRange::two(
+                        egui::text::CCursor::new(0),
+                        egui::text::CCursor::new(prompt.new_name.chars().count()),
+                    );
+                    state.cursor.set_char_range(Some(all));
+                    state.store(ui.ctx(), output.response.id);
+                }
+                confirmed = output.response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                cancelled = ui.input(|i| i.key_pressed(egui::Key::Escape));
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    confirmed |= ui.button("Rename").clicked();
+                    cancelled |= ui.button("Cancel").clicked();
+                });
+            });
+
+        if cancelled {
+            self.rename_prompt = None;
+        }
+        if !confirmed {
+            return None;
+        }
+        let prompt = self.rename_prompt.take()?;
+        let new_name = prompt.new_name.trim();
+        if new_name.is_empty() || new_name == prompt.name {
+            return None;
+        }
+        Some(Rename { index: prompt.index, new_name: new_name.to_string(), name: prompt.name })
+    }
+
+    /// Works out what an edit changes in each file and shows it in the diff view, to be
+    /// applied or cancelled. Returns false when it changes nothing.
+    pub fn preview(&mut self, mut edit: WorkspaceEdit, buffer: &OpenBuffer<'_>, roots: &[PathBuf]) -> Result<bool, String> {
+        if self.pending_edit.is_some() {
+            return Err("another edit is waiting to be applied".to_string());
+        }
+        edit.merge_files();
+
+        let mut changes = Vec::new();
+        let mut buffer_edits = Vec::new();
+        let mut disk = HashMap::new();
+        for file in edit.files {
+            let old = if buffer.path == Some(file.path.as_path()) {
+                if buffer.read_only {
+                    return Err(format!("{} is open read-only", file.path.display()));
+                }
+                buffer_edits = file.edits.clone();
+                buffer.code.to_string()
+            } else {
+                let (text, stored) = read_for_edit(&file.path)?;
+                disk.insert(file.path.clone(), stored);
+                text
+            };
+            let new = edit::apply(&old, &file.edits).map_err(|e| format!("{}: {}", file.path.display(), e))?;
+            if new != old {
+                changes.push(FileChange { path: file.path, old, new });
+            }
+        }
+        if changes.is_empty() {
+            return Ok(false);
+        }
+
+        let title = match changes.len() {
+            1 => format!("{} (1 file)", edit.label),
+            count => format!("{} ({} files)", edit.label, count),
+        };
+        let files = changes.iter().map(|change| (display_path(&change.path, roots), change.old.as_str(), change.new.as_str()));
+        let view = DiffView::compare_files(title, files).with_confirm("Apply");
+        self.pending_edit = Some(PendingEdit {
+            label: edit.label,
+            changes,
+            buffer_edits,
+            view,
+            disk,
+            #[cfg(not(target_arch = "wasm32"))]
+            reply: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            command: None,
+        });
+        Ok(true)
+    }
+
+    pub fn is_previewing(&self) -> bool {
+        self.pending_edit.is_some()
+    }
+
+    /// The edit being previewed, to say what happens once it is applied.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pending_edit(&mut self) -> Option<&mut PendingEdit> {
+        self.pending_edit.as_mut()
+    }
+
+    /// Shows the preview in the diff view. Returns the edit once it is applied or cancelled.
+    pub fn render_preview(&mut self, ui: &mut egui::Ui, theme: &ZenTheme) -> Option<PreviewResult> {
+        let pending = self.pending_edit.as_mut()?;
+        match pending.view.render(ui, theme) {
+            Some(DiffViewAction::Confirm) => self.pending_edit.take().map(PreviewResult::Apply),
+            Some(DiffViewAction::Close) => self.pending_edit.take().map(PreviewResult::Cancel),
+            _ => None,
+        }
+    }
+
+    /// Writes the files of an edit other than the open one, remembering them for
+    /// [`Self::undo`]. Nothing is written if any of them was modified since the preview,
+    /// and files already written are restored if a later one fails. Returns the files
+    /// written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write_files(&mut self, pending: &PendingEdit) -> Result<Vec<PathBuf>, String> {
+        let disk_changes: Vec<&FileChange> =
+            pending.changes.iter().filter(|change| pending.disk.contains_key(&change.path)).collect();
+        for change in &disk_changes {
+            if std::fs::read(&change.path).ok().as_ref() != Some(&pending.disk[&change.path].bytes) {
+                return Err(format!("{} changed since the preview", change.path.display()));
+            }
+        }
+
+        let mut written = Vec::new();
+        for change in disk_changes {
+            let stored = &pending.disk[&change.path];
+            let result = stored
+                .encoding
+                .encode(&stored.line_ending.apply(&change.new))
+                .and_then(|bytes| writer::write_atomic(&change.path, &bytes).map(|_| bytes).map_err(|e| e.to_string()));
+            match result {
+                Ok(bytes) => written.push((change.path.clone(), stored.bytes.clone(), bytes)),
+                Err(e) => {
+                    for (path, before, _) in &written {
+                        if let Err(e) = writer::write_atomic(path, before) {
+                            log::warn!("Failed to restore {}: {}", path.display(), e);
+                        }
+                    }
+                    return Err(format!("{}: {}", change.path.display(), e));
+                }
+            }
+        }
+
+        let paths = written.iter().map(|(path, _, _)| path.clone()).collect();
+        if !written.is_empty() {
+            self.written_edit = Some(WrittenEdit { label: pending.label.clone(), files: written });
+        }
+        Ok(paths)
+    }
+
+    /// Puts back the files the last workspace edit wrote, unless they changed since.
+    /// Returns the files restored.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn undo(&mut self, notifications: &mut Notifications) -> Vec<PathBuf> {
+        let Some(edit) = self.written_edit.take() else {
+            return Vec::new();
+        };
+        let mut skipped = Vec::new();
+        let mut restored = Vec::new();
+        for (path, before, after) in edit.files {
+            if std::fs::read(&path).ok() != Some(after) {
+                skipped.push(path.display().to_string());
+                continue;
+            }
+            match writer::write_atomic(&path, &before) {
+                Ok(()) => restored.push(path),
+                Err(e) => notifications.error(format!("Failed to restore {}: {}", path.display(), e)),
+            }
+        }
+        if skipped.is_empty() {
+            notifications.info(format!("Undid {}", edit.label));
+        } else {
+            notifications.warning(format!("Undid {} except in files changed since: {}", edit.label, skipped.join(", ")));
+        }
+        restored
+    }
+
+    /// Waits for the language server to answer request `id`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn wait_for(&mut self, id: i64, request: RefactorRequest) {
+        self.request = Some((id, request));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn is_response(&self, id: i64) -> bool {
+        self.request.as_ref().is_some_and(|(request, _)| *request == id)
+    }
+
+    /// The step the language server answered with response `id`, if it was one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn take_request(&mut self, id: i64) -> Option<RefactorRequest> {
+        if !self.is_response(id) {
+            return None;
+        }
+        self.request.take().map(|(_, request)| request)
+    }
+}
+
+/// Reads a file that is not open, for an edit to be made to it.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_for_edit(path: &Path) -> Result<(String, DiskText), String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let decoded = encoding::decode(&bytes, None);
+    if decoded.had_errors {
+        return Err(format!("{} is not valid {}", path.display(), decoded.encoding.label()));
+    }
+    let line_ending = line_ending::detect(&decoded.text).line_ending;
+    let text = line_ending::normalize(decoded.text);
+    Ok((text, DiskText { bytes, encoding: decoded.encoding, line_ending }))
+}
+
+/// Files that are not open cannot be read in the browser.
+#[cfg(target_arch = "wasm32")]
+fn read_for_edit(path: &Path) -> Result<(String, DiskText), String> {
+    Err(format!("{} is not open", path.display()))
+}
@@ -0,0 +1,85 @@
+use crate::document::position::Location;
+use crate::symbols::Reference;
+use crate::ui::problems::display_path;
+use std::path::{Path, PathBuf};
+
+/// The results of the last "Find All References", or of a definition that has more
+/// than one candidate, grouped by file.
+#[derive(Default)]
+pub struct ReferencesPanel {
+    /// What was looked for, such as "references to `name`".
+    title: String,
+    results: Vec<Reference>,
+    searching: bool,
+}
+
+impl ReferencesPanel {
+    /// Clears the results for a new search; they arrive with [`Self::add_results`].
+    pub fn start(&mut self, title: String) {
+        self.title = title;
+        self.results.clear();
+        self.searching = true;
+    }
+
+    pub fn add_results(&mut self, results: Vec<Reference>) {
+        self.results.extend(results);
+        self.results.sort_by(|a, b| (&a.location.path, a.location.position).cmp(&(&b.location.path, b.location.position)));
+        self.results.dedup_by(|a, b| a.location == b.location);
+    }
+
+    pub fn finish(&mut self) {
+        self.searching = false;
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, roots: &[PathBuf]) -> Option<Location> {
+        let mut clicked = None;
+
+        ui.horizontal(|ui| {
+            if self.searching {
+                ui.spinner();
+                ui.label(format!("Searching for {}", self.title));
+            } else {
+                let count = self.results.len();
+                ui.label(format!("{} {}", count, self.title));
+            }
+        });
+        ui.separator();
+
+        if self.results.is_empty() {
+            if !self.searching {
+                ui.weak("Nothing was found");
+            }
+            return None;
+        }
+
+        egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+            for (path, results) in group_by_file(&self.results) {
+                let header = format!("{}  ({})", display_path(path, roots), results.len());
+                egui::CollapsingHeader::new(header)
+                    .id_salt(("references", path))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for reference in results {
+                            let position = reference.location.position;
+                            ui.horizontal(|ui| {
+                                ui.weak(format!("{}:{}", position.line + 1, position.column + 1));
+                                if ui.selectable_label(false, egui::RichText::new(&reference.preview).monospace()).clicked() {
+                                    clicked = Some(reference.location.clone());
+                                }
+                            });
+                        }
+                    });
+            }
+        });
+
+        clicked
+    }
+}
+
+/// Runs of results in the same file; the results are kept sorted by path.
+fn group_by_file(results: &[Reference]) -> Vec<(&Path, &[Reference])> {
+    results
+        .chunk_by(|a, b| a.location.path == b.location.path)
+        .map(|group| (group[0].location.path.as_path(), group))
+        .collect()
+}
@@ -0,0 +1,222 @@
+use crate::git::status::{FileStatus, GitStatus, RepoStatus, StatusEntry};
+use crate::ui::theme::GitColors;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A git operation requested from the panel. The editor runs these, since it
+/// owns the git monitor and has to refresh status and open diffs afterwards.
+pub enum ScmAction {
+    Stage { repo: PathBuf, path: PathBuf },
+    Unstage { repo: PathBuf, path: PathBuf },
+    StageAll(PathBuf),
+    UnstageAll(PathBuf),
+    Discard { repo: PathBuf, path: PathBuf, untracked: bool },
+    Commit { repo: PathBuf, message: String, amend: bool },
+    OpenDiff { repo: PathBuf, path: PathBuf, staged: bool, untracked: bool },
+}
+
+/// Staged and unstaged changes of every repository in the workspace, with a commit box per repository.
+#[derive(Default)]
+pub struct SourceControlPanel {
+    messages: HashMap<PathBuf, String>,
+    amend: HashMap<PathBuf, bool>,
+    /// The file whose discard button was pressed and is waiting for confirmation.
+    confirm_discard: Option<PathBuf>,
+}
+
+impl SourceControlPanel {
+    /// Forgets the message once a commit went through.
+    pub fn committed(&mut self, repo: &Path) {
+        self.messages.remove(repo);
+        self.amend.remove(repo);
+    }
+
+    /// `busy` is set while earlier actions are still running, so a commit is not sent twice.
+    pub fn render(&mut self, ui: &mut egui::Ui, status: &GitStatus, colors: &GitColors, busy: bool) -> Vec<ScmAction> {
+        let mut actions = Vec::new();
+
+        if status.repos.is_empty() {
+            ui.weak("No git repository in the open folders");
+            return actions;
+        }
+
+        egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+            for repo in &status.repos {
+                ui.push_id(&repo.root, |ui| {
+                    self.render_repo(ui, repo, colors, busy, &mut actions);
+                });
+                ui.add_space(8.0);
+            }
+        });
+
+        actions
+    }
+
+    fn render_repo(
+        &mut self,
+        ui: &mut egui::Ui,
+        repo: &RepoStatus,
+        colors: &GitColors,
+        busy: bool,
+        actions: &mut Vec<ScmAction>,
+    ) {
+        ui.horizontal(|ui| {
+            ui.strong(repo.name());
+            if let Some(branch) = &repo.branch {
+                ui.weak(format!("⎇ {}", branch));
+            }
+        });
+
+        let message = self.messages.entry(repo.root.clone()).or_default();
+        let editor = ui.add(
+            egui::TextEdit::multiline(message)
+                .hint_text("Message (Ctrl+Enter to commit)")
+                .desired_rows(3)
+                .desired_width(f32::INFINITY),
+        );
+        let submit = editor.has_focus() && ui.input(|i| i.modifiers.command && i.key_pressed(egui::Key::Enter));
+
+        let has_staged = repo.entries.iter().any(|entry| entry.staged.is_some());
+        let amend = self.amend.entry(repo.root.clone()).or_default();
+        let mut commit = submit && !busy;
+        ui.horizontal(|ui| {
+            if ui.checkbox(amend, "Amend").changed() && *amend && message.trim().is_empty() {
+                if let Some(previous) = crate::git::last_commit_message(&repo.root) {
+                    *message = previous;
+                }
+            }
+            let label = if *amend { "Amend Commit" } else { "Commit" };
+            commit |= ui.add_enabled(!busy && (has_staged || *amend), egui::Button::new(label)).clicked();
+            if busy {
+                ui.spinner();
+            }
+        });
+
+        if commit && (has_staged || *amend) {
+            if message.trim().is_empty() && !*amend {
+                ui.ctx().memory_mut(|m| m.request_focus(editor.id));
+            } else {
+                actions.push(ScmAction::Commit {
+                    repo: repo.root.clone(),
+                    message: message.clone(),
+                    amend: *amend,
+                });
+            }
+        }
+
+        let staged: Vec<&StatusEntry> = repo.entries.iter().filter(|entry| entry.staged.is_some()).collect();
+        let unstaged: Vec<&StatusEntry> = repo.entries.iter().filter(|entry| entry.unstaged.is_some()).collect();
+
+        if !staged.is_empty() {
+            self.render_group(ui, repo, &staged, true, colors, actions);
+        }
+        self.render_group(ui, repo, &unstaged, false, colors, actions);
+    }
+
+    fn render_group(
+        &mut self,
+        ui: &mut egui::Ui,
+        repo: &RepoStatus,
+        entries: &[&StatusEntry],
+        staged: bool,
+        colors: &GitColors,
+        actions: &mut Vec<ScmAction>,
+    ) {
+        let title = if staged { "Staged Changes" } else { "Changes" };
+        let header = egui::CollapsingHeader::new(format!("{} ({})", title, entries.len()))
+            .id_salt(title)
+            .default_open(true)
+            .show(ui, |ui| {
+                if entries.is_empty() {
+                    ui.weak("No changes");
+                }
+                for entry in entries {
+                    self.render_entry(ui, repo, entry, staged, colors, actions);
+                }
+            });
+
+        header.header_response.context_menu(|ui| {
+            let (label, action) = if staged {
+                ("Unstage All", ScmAction::UnstageAll(repo.root.clone()))
+            } else {
+                ("Stage All", ScmAction::StageAll(repo.root.clone()))
+            };
+            if ui.add_enabled(!entries.is_empty(), egui::Button::new(label)).clicked() {
+                actions.push(action);
+                ui.close();
+            }
+        });
+    }
+
+    fn render_entry(
+        &mut self,
+        ui: &mut egui::Ui,
+        repo: &RepoStatus,
+        entry: &StatusEntry,
+        staged: bool,
+        colors: &GitColors,
+        actions: &mut Vec<ScmAction>,
+    ) {
+        let Some(status) = (if staged { entry.staged } else { entry.unstaged }) else {
+            return;
+        };
+        let untracked = status == FileStatus::Untracked;
+        let name = entry.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let relative = entry
+            .path
+            .parent()
+            .and_then(|dir| dir.strip_prefix(&repo.root).ok())
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+
+        ui.push_id((&entry.path, staged), |ui| {
+            ui.horizontal(|ui| {
+                let label = egui::RichText::new(format!("{}  {}", name, status.badge())).color(colors.status_color(status));
+                let response = ui.selectable_label(false, label).on_hover_text(entry.path.display().to_string());
+                if !relative.is_empty() {
+                    ui.weak(&relative);
+                }
+                if response.clicked() {
+                    actions.push(ScmAction::OpenDiff {
+                        repo: repo.root.clone(),
+                        path: entry.path.clone(),
+                        staged,
+                        untracked,
+                    });
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if staged {
+                        if ui.small_button("−").on_hover_text("Unstage").clicked() {
+                            actions.push(ScmAction::Unstage { repo: repo.root.clone(), path: entry.path.clone() });
+                        }
+                        return;
+                    }
+
+                    if ui.small_button("+").on_hover_text("Stage").clicked() {
+                        actions.push(ScmAction::Stage { repo: repo.root.clone(), path: entry.path.clone() });
+                    }
+                    if status == FileStatus::Conflicted {
+                        return;
+                    }
+                    if self.confirm_discard.as_ref() == Some(&entry.path) {
+                        if ui.small_button("Cancel").clicked() {
+                            self.confirm_discard = None;
+                        }
+                        let label = if untracked { "Delete" } else { "Discard" };
+                        if ui.small_button(egui::RichText::new(label).color(colors.status_color(FileStatus::Deleted))).clicked() {
+                            self.confirm_discard = None;
+                            actions.push(ScmAction::Discard {
+                                repo: repo.root.clone(),
+                                path: entry.path.clone(),
+                                untracked,
+                            });
+                        }
+                    } else if ui.small_button("↺").on_hover_text("Discard Changes").clicked() {
+                        self.confirm_discard = Some(entry.path.clone());
+                    }
+                });
+            });
+        });
+    }
+}
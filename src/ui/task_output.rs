@@ -0,0 +1,193 @@
+use crate::document::diagnostics::{Diagnostics, Severity};
+use crate::document::position::Location;
+use crate::tasks::{self, Task, TaskRun, TaskStatus};
+use crate::ui::editor::file_name;
+use crate::ui::notifications::Notifications;
+use crate::ui::palette::PaletteItem;
+use crate::ui::problems::display_path;
+use crate::ui::theme::ZenTheme;
+use std::path::{Path, PathBuf};
+
+pub enum TaskOutputAction {
+    GoTo(Location),
+    Stop,
+    Rerun(Box<Task>),
+}
+
+/// The tasks defined for the project and the output of the last one that was run, with
+/// the problems in it clickable.
+#[derive(Default)]
+pub struct TaskOutputPanel {
+    /// The tasks listed in the Run Task palette while it is open.
+    tasks: Vec<Task>,
+    run: Option<TaskRun>,
+}
+
+impl TaskOutputPanel {
+    /// Reads the tasks defined under `roots` for the palette. Returns false, saying why,
+    /// when there are none.
+    pub fn load(&mut self, roots: &[PathBuf], notifications: &mut Notifications) -> bool {
+        let (tasks, errors) = tasks::load(roots);
+        for error in errors {
+            notifications.error(error);
+        }
+        if tasks.is_empty() {
+            notifications.info("No tasks are defined. Add them to .zen/tasks.json in the project folder");
+            return false;
+        }
+        self.tasks = tasks;
+        true
+    }
+
+    pub fn palette_items(&self, roots: &[PathBuf]) -> Vec<PaletteItem> {
+        self.tasks
+            .iter()
+            .map(|task| {
+                let place = match &task.root {
+                    Some(root) if roots.len() > 1 => format!("  ({})", file_name(root)),
+                    Some(_) => String::new(),
+                    None => "  (global)".to_string(),
+                };
+                PaletteItem {
+                    icon: "▶",
+                    label: task.config.label.clone(),
+                    detail: format!("{}{}", task.config.command, place),
+                }
+            })
+            .collect()
+    }
+
+    pub fn task(&self, index: usize) -> Option<Task> {
+        self.tasks.get(index).cloned()
+    }
+
+    /// Runs a task, stopping the one running before and taking back the problems both
+    /// reported earlier.
+    pub fn start(
+        &mut self,
+        task: Task,
+        root: Option<&Path>,
+        file: Option<&Path>,
+        diagnostics: &mut Diagnostics,
+        ctx: &egui::Context,
+    ) -> Result<(), String> {
+        if let Some(previous) = self.run.take() {
+            diagnostics.clear_provider(&provider(previous.label()));
+        }
+        diagnostics.clear_provider(&provider(&task.config.label));
+        self.run = Some(TaskRun::start(task, root, file, ctx)?);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(run) = &mut self.run {
+            run.stop();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.run.as_ref().is_some_and(TaskRun::is_running)
+    }
+
+    /// Takes in the task's new output and reports the problems found in it as they come in.
+    pub fn poll(&mut self, ctx: &egui::Context, diagnostics: &mut Diagnostics) {
+        let Some(run) = &mut self.run else {
+            return;
+        };
+        let changed = run.poll();
+        if run.is_exiting() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        let provider = provider(run.label());
+        for path in changed {
+            let problems = run.problems.get(&path).cloned().unwrap_or_default();
+            diagnostics.set(&provider, path, problems);
+        }
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, theme: &ZenTheme, roots: &[PathBuf]) -> Option<TaskOutputAction> {
+        let Some(run) = &self.run else {
+            ui.weak("No task has been run yet. Run one with Run Task... (Ctrl+Shift+B)");
+            return None;
+        };
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            match run.status {
+                TaskStatus::Running => {
+                    ui.spinner();
+                    ui.label(format!("Running {}", run.label()));
+                }
+                TaskStatus::Exited(Some(0)) => {
+                    ui.label(format!("{} finished", run.label()));
+                }
+                TaskStatus::Exited(Some(code)) => {
+                    let text = format!("{} exited with code {}", run.label(), code);
+                    ui.label(egui::RichText::new(text).color(theme.diagnostic_color(Severity::Error)));
+                }
+                TaskStatus::Exited(None) => {
+                    ui.label(format!("{} was ended by a signal", run.label()));
+                }
+                TaskStatus::Stopped => {
+                    ui.label(format!("{} was stopped", run.label()));
+                }
+            }
+            for severity in [Severity::Error, Severity::Warning] {
+                let count = run.problems.values().flatten().filter(|d| d.severity == severity).count();
+                if count > 0 {
+                    let text = format!("{} {}", severity.icon(), count);
+                    ui.label(egui::RichText::new(text).color(theme.diagnostic_color(severity)));
+                }
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if run.is_running() {
+                    if ui.button("Stop").clicked() {
+                        action = Some(TaskOutputAction::Stop);
+                    }
+                } else if ui.button("Run Again").clicked() {
+                    action = Some(TaskOutputAction::Rerun(Box::new(run.task.clone())));
+                }
+                ui.weak(display_path(&run.dir, roots));
+            });
+        });
+        ui.separator();
+
+        let font = egui::FontId::monospace(theme.typography.code_font_size);
+        let row_height = ui.fonts(|fonts| fonts.row_height(&font));
+        egui::ScrollArea::both()
+            .auto_shrink([false; 2])
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, run.lines.len(), |ui, rows| {
+                for line in run.lines.range(rows) {
+                    let text = egui::RichText::new(&line.text).font(font.clone());
+                    let Some((location, diagnostic)) = &line.problem else {
+                        ui.add(egui::Label::new(text).extend());
+                        continue;
+                    };
+                    let text = text.color(theme.diagnostic_color(diagnostic.severity));
+                    let response = ui
+                        .add(egui::Label::new(text).extend().sense(egui::Sense::click()))
+                        .on_hover_cursor(egui::CursorIcon::PointingHand)
+                        .on_hover_text(format!("Go to {}", describe(location, roots)));
+                    if response.hovered() {
+                        ui.painter().hline(response.rect.x_range(), response.rect.bottom(), ui.visuals().widgets.hovered.fg_stroke);
+                    }
+                    if response.clicked() {
+                        action = Some(TaskOutputAction::GoTo(location.clone()));
+                    }
+                }
+            });
+
+        action
+    }
+}
+
+fn describe(location: &Location, roots: &[PathBuf]) -> String {
+    format!("{}:{}:{}", display_path(&location.path, roots), location.position.line + 1, location.position.column + 1)
+}
+
+/// The diagnostics provider a task's problems are reported as, so each task replaces
+/// only what it reported itself.
+fn provider(label: &str) -> String {
+    format!("task: {}", label)
+}
@@ -0,0 +1,381 @@
+use crate::terminal::screen::{Cell, Color, Style};
+use crate::terminal::Terminal;
+use crate::ui::theme::ZenTheme;
+use egui::text::{LayoutJob, TextFormat};
+use egui::Color32;
+use std::path::Path;
+
+/// Space between the edge of the panel and the character grid.
+const PADDING: f32 = 4.0;
+
+/// What the terminal panel asks of the editor.
+pub enum TerminalAction {
+    /// Open another terminal.
+    New,
+    /// The last terminal was closed.
+    Closed,
+}
+
+struct TerminalTab {
+    terminal: Terminal,
+    /// How many lines the view is scrolled up into the scrollback.
+    scroll: usize,
+    /// Scrolling too small to move a whole line yet.
+    scroll_remainder: f32,
+    /// Where a selection drag started and where it is now, as line and column.
+    selection: Option<((usize, usize), (usize, usize))>,
+}
+
+impl TerminalTab {
+    /// The selection in reading order, unless it is empty.
+    fn selected(&self) -> Option<((usize, usize), (usize, usize))> {
+        let (anchor, head) = self.selection?;
+        (anchor != head).then(|| (anchor.min(head), anchor.max(head)))
+    }
+}
+
+/// Shells running in tabs below the editor. Keys go to the shell of the shown tab while
+/// it has focus; Ctrl+C copies when there is a selection and interrupts otherwise.
+#[derive(Default)]
+pub struct TerminalPanel {
+    tabs: Vec<TerminalTab>,
+    active: usize,
+    focus_requested: bool,
+    has_focus: bool,
+}
+
+impl TerminalPanel {
+    pub fn is_empty(&self) -> bool {
+        self.tabs.is_empty()
+    }
+
+    /// Whether typing goes to a terminal rather than the editor.
+    pub fn has_focus(&self) -> bool {
+        self.has_focus
+    }
+
+    /// Starts a shell in `dir` in a new tab and switches to it. It gets the real size of
+    /// the panel once drawn.
+    pub fn open(&mut self, dir: &Path, ctx: &egui::Context) -> Result<(), String> {
+        let terminal = Terminal::spawn(dir, 24, 80, ctx)?;
+        self.tabs.push(TerminalTab { terminal, scroll: 0, scroll_remainder: 0.0, selection: None });
+        self.active = self.tabs.len() - 1;
+        self.focus_requested = true;
+        Ok(())
+    }
+
+    /// Takes the output of every terminal, including those not shown, so that the
+    /// programs in them get their queries answered.
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        for tab in &mut self.tabs {
+            if tab.terminal.poll() {
+                ctx.request_repaint();
+            } else if tab.terminal.is_exiting() {
+                ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            }
+        }
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, theme: &ZenTheme) -> Option<TerminalAction> {
+        let mut action = None;
+        let mut close = None;
+        ui.horizontal(|ui| {
+            for (index, tab) in self.tabs.iter().enumerate() {
+                let title = match tab.terminal.exit_code {
+                    Some(code) => format!("{} (exited with {})", tab.terminal.title(), code),
+                    None => tab.terminal.title().to_string(),
+                };
+                if ui.selectable_label(index == self.active, title).clicked() {
+                    self.active = index;
+                    self.focus_requested = true;
+                }
+                if ui.small_button("🗙").on_hover_text("Kill Terminal").clicked() {
+                    close = Some(index);
+                }
+                ui.separator();
+            }
+            if ui.small_button("+").on_hover_text("New Terminal").clicked() {
+                action = Some(TerminalAction::New);
+            }
+        });
+
+        if let Some(index) = close {
+            self.tabs.remove(index);
+            self.active = self.active.min(self.tabs.len().saturating_sub(1));
+            if self.tabs.is_empty() {
+                return Some(TerminalAction::Closed);
+            }
+        }
+        if self.tabs.is_empty() {
+            self.has_focus = false;
+            ui.weak("No terminal is open");
+            return action;
+        }
+        self.render_terminal(ui, theme);
+        action
+    }
+
+    fn render_terminal(&mut self, ui: &mut egui::Ui, theme: &ZenTheme) {
+        let tab = &mut self.tabs[self.active];
+        let font = egui::FontId::monospace(theme.typography.code_font_size);
+        let (char_width, row_height) = ui.fonts(|f| (f.glyph_width(&font, 'M'), f.row_height(&font)));
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
+        let grid = rect.shrink(PADDING);
+        let rows = (grid.height() / row_height).floor().max(1.0) as usize;
+        let columns = (grid.width() / char_width).floor().max(1.0) as usize;
+        tab.terminal.resize(rows, columns);
+
+        if response.clicked() || response.drag_started() || std::mem::take(&mut self.focus_requested) {
+            response.request_focus();
+        }
+        self.has_focus = response.has_focus();
+        if self.has_focus {
+            ui.memory_mut(|memory| {
+                let filter = egui::EventFilter { tab: true, horizontal_arrows: true, vertical_arrows: true, escape: true };
+                memory.set_focus_lock_filter(response.id, filter);
+            });
+            handle_input(ui, tab, rows);
+        }
+
+        let scrollback = tab.terminal.screen.line_count() - rows;
+        if response.hovered() {
+            tab.scroll_remainder += ui.input(|i| i.smooth_scroll_delta.y) / row_height;
+            let lines = tab.scroll_remainder.trunc();
+            tab.scroll_remainder -= lines;
+            tab.scroll = (tab.scroll as isize + lines as isize).clamp(0, scrollback as isize) as usize;
+        }
+        tab.scroll = tab.scroll.min(scrollback);
+        let first_line = scrollback - tab.scroll;
+
+        let cell_at = |pos: egui::Pos2| {
+            let row = ((pos.y - grid.top()) / row_height).floor().clamp(0.0, (rows - 1) as f32) as usize;
+            let column = ((pos.x - grid.left()) / char_width).round().clamp(0.0, columns as f32) as usize;
+            (first_line + row, column)
+        };
+        if let Some(pos) = response.interact_pointer_pos() {
+            if response.drag_started() {
+                tab.selection = Some((cell_at(pos), cell_at(pos)));
+            } else if response.dragged() {
+                if let Some((_, head)) = &mut tab.selection {
+                    *head = cell_at(pos);
+                }
+            }
+        }
+        if response.clicked() {
+            tab.selection = None;
+        }
+        response.context_menu(|ui| {
+            if ui.add_enabled(tab.selected().is_some(), egui::Button::new("Copy")).clicked() {
+                if let Some((start, end)) = tab.selected() {
+                    ui.ctx().copy_text(tab.terminal.screen.text(start, end));
+                }
+                ui.close();
+            }
+        });
+
+        let painter = ui.painter_at(rect);
+        let [r, g, b] = theme.colors.editor_bg;
+        let background = Color32::from_rgb(r, g, b);
+        let [r, g, b] = theme.syntax.text;
+        let foreground = Color32::from_rgb(r, g, b);
+        painter.rect_filled(rect, 0.0, background);
+
+        let selection = tab.selected();
+        let [r, g, b] = theme.colors.selection;
+        let selection_color = Color32::from_rgb(r, g, b);
+        let screen = &tab.terminal.screen;
+        for row in 0..rows {
+            let index = first_line + row;
+            let top = grid.top() + row as f32 * row_height;
+            let cell_rect = |from: usize, to: usize| {
+                egui::Rect::from_min_max(
+                    egui::pos2(grid.left() + from as f32 * char_width, top),
+                    egui::pos2(grid.left() + to as f32 * char_width, top + row_height),
+                )
+            };
+
+            let line = screen.line(index);
+            let mut job = LayoutJob::default();
+            let mut column = 0;
+            for run in line.chunk_by(|a, b| a.style == b.style) {
+                let (fg, bg) = cell_colors(&run[0].style, theme, foreground, background);
+                if bg != background {
+                    painter.rect_filled(cell_rect(column, column + run.len()), 0.0, bg);
+                }
+                let text: String = run.iter().map(|cell: &Cell| cell.c).collect();
+                let mut format = TextFormat::simple(font.clone(), fg);
+                format.italics = run[0].style.italic;
+                if run[0].style.underline {
+                    format.underline = egui::Stroke::new(1.0, fg);
+                }
+                job.append(&text, 0.0, format);
+                column += run.len();
+            }
+
+            if let Some((start, end)) = selection.filter(|(start, end)| (start.0..=end.0).contains(&index)) {
+                let from = if index == start.0 { start.1 } else { 0 };
+                let to = if index == end.0 { end.1 } else { columns };
+                painter.rect_filled(cell_rect(from, to), 0.0, selection_color.gamma_multiply(0.6));
+            }
+            let galley = ui.fonts(|f| f.layout_job(job));
+            painter.galley(egui::pos2(grid.left(), top), galley, foreground);
+        }
+
+        let (cursor_line, cursor_column) = screen.cursor();
+        if screen.cursor_visible && tab.terminal.exit_code.is_none() && (first_line..first_line + rows).contains(&cursor_line) {
+            let top = grid.top() + (cursor_line - first_line) as f32 * row_height;
+            let left = grid.left() + cursor_column as f32 * char_width;
+            let cursor = egui::Rect::from_min_size(egui::pos2(left, top), egui::vec2(char_width, row_height));
+            if self.has_focus {
+                painter.rect_filled(cursor, 0.0, foreground.gamma_multiply(0.5));
+            } else {
+                painter.rect_stroke(cursor, 0.0, egui::Stroke::new(1.0, foreground), egui::StrokeKind::Inside);
+            }
+        }
+        if let Some(code) = tab.terminal.exit_code {
+            painter.text(
+                grid.right_bottom(),
+                egui::Align2::RIGHT_BOTTOM,
+                format!("The shell exited with code {}", code),
+                font,
+                ui.visuals().weak_text_color(),
+            );
+        }
+    }
+}
+
+/// Sends what was typed or pasted to the shell, and copies the selection on Ctrl+C.
+fn handle_input(ui: &egui::Ui, tab: &mut TerminalTab, rows: usize) {
+    let events = ui.input(|i| i.events.clone());
+    for event in events {
+        let typed = match event {
+            egui::Event::Text(text) => {
+                tab.terminal.write(text.as_bytes());
+                true
+            }
+            egui::Event::Paste(text) => {
+                tab.terminal.paste(&text);
+                true
+            }
+            egui::Event::Copy => match tab.selected() {
+                Some((start, end)) => {
+                    ui.ctx().copy_text(tab.terminal.screen.text(start, end));
+                    tab.selection = None;
+                    false
+                }
+                None => {
+                    tab.terminal.write(b"\x03");
+                    true
+                }
+            },
+            egui::Event::Cut => {
+                tab.terminal.write(b"\x18");
+                true
+            }
+            egui::Event::Key { key, pressed: true, modifiers, .. } => {
+                let page = rows.saturating_sub(1).max(1);
+                match key {
+                    egui::Key::PageUp if modifiers.shift_only() => {
+                        tab.scroll += page;
+                        false
+                    }
+                    egui::Key::PageDown if modifiers.shift_only() => {
+                        tab.scroll = tab.scroll.saturating_sub(page);
+                        false
+                    }
+                    _ => match key_sequence(key, modifiers, tab.terminal.screen.application_cursor) {
+                        Some(bytes) => {
+                            tab.terminal.write(&bytes);
+                            true
+                        }
+                        None => false,
+                    },
+                }
+            }
+            _ => false,
+        };
+        // Typing brings the view back to the prompt.
+        if typed {
+            tab.scroll = 0;
+            tab.selection = None;
+        }
+    }
+}
+
+/// What a key sends to the shell, for the keys that do not arrive as text.
+fn key_sequence(key: egui::Key, modifiers: egui::Modifiers, application_cursor: bool) -> Option<Vec<u8>> {
+    use egui::Key;
+
+    if modifiers.ctrl && !modifiers.alt {
+        let name = key.name();
+        if let [letter] = name.as_bytes() {
+            if letter.is_ascii_alphabetic() {
+                return Some(vec![letter.to_ascii_uppercase() & 0x1f]);
+            }
+        }
+        match key {
+            Key::Space => return Some(vec![0]),
+            Key::OpenBracket => return Some(vec![0x1b]),
+            Key::Backslash => return Some(vec![0x1c]),
+            Key::CloseBracket => return Some(vec![0x1d]),
+            _ => {}
+        }
+    }
+
+    let cursor = |c: char| if application_cursor { format!("\x1bO{}", c) } else { format!("\x1b[{}", c) };
+    let sequence = match key {
+        Key::Enter => "\r".to_string(),
+        Key::Backspace => "\x7f".to_string(),
+        Key::Tab if modifiers.shift => "\x1b[Z".to_string(),
+        Key::Tab => "\t".to_string(),
+        Key::Escape => "\x1b".to_string(),
+        Key::ArrowUp => cursor('A'),
+        Key::ArrowDown => cursor('B'),
+        Key::ArrowRight => cursor('C'),
+        Key::ArrowLeft => cursor('D'),
+        Key::Home => cursor('H'),
+        Key::End => cursor('F'),
+        Key::Insert => "\x1b[2~".to_string(),
+        Key::Delete => "\x1b[3~".to_string(),
+        Key::PageUp => "\x1b[5~".to_string(),
+        Key::PageDown => "\x1b[6~".to_string(),
+        Key::F1 => "\x1bOP".to_string(),
+        Key::F2 => "\x1bOQ".to_string(),
+        Key::F3 => "\x1bOR".to_string(),
+        Key::F4 => "\x1bOS".to_string(),
+        Key::F5 => "\x1b[15~".to_string(),
+        Key::F6 => "\x1b[17~".to_string(),
+        Key::F7 => "\x1b[18~".to_string(),
+        Key::F8 => "\x1b[19~".to_string(),
+        Key::F9 => "\x1b[20~".to_string(),
+        Key::F10 => "\x1b[21~".to_string(),
+        Key::F11 => "\x1b[23~".to_string(),
+        Key::F12 => "\x1b[24~".to_string(),
+        _ => return None,
+    };
+    Some(sequence.into_bytes())
+}
+
+/// The text and background colors of a cell. Bold text in one of the eight normal
+/// colors is drawn in its bright version, as most terminals do.
+fn cell_colors(style: &Style, theme: &ZenTheme, foreground: Color32, background: Color32) -> (Color32, Color32) {
+    let color = |color: Color, default: Color32| match color {
+        Color::Default => default,
+        Color::Indexed(index) if style.bold && index < 8 => theme.terminal.indexed(index + 8),
+        Color::Indexed(index) => theme.terminal.indexed(index),
+        Color::Rgb(r, g, b) => Color32::from_rgb(r, g, b),
+    };
+    let mut fg = color(style.foreground, foreground);
+    let bg = match style.background {
+        Color::Indexed(index) => theme.terminal.indexed(index),
+        other => color(other, background),
+    };
+    if style.dim {
+        fg = fg.gamma_multiply(0.6);
+    }
+    if style.inverse {
+        (bg, fg)
+    } else {
+        (fg, bg)
+    }
+}
@@ -1,3 +1,8 @@
+use crate::document::diagnostics::Severity;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::git::diff::LineChangeKind;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::git::status::FileStatus;
 use eframe::epaint::Stroke;
 use egui::Color32;
 use serde::{Deserialize, Serialize};
@@ -14,6 +19,10 @@ pub struct ZenTheme {
     pub spacing: ThemeSpacing,
     pub typography: ThemeTypography,
     pub syntax: SyntaxColors,
+    #[serde(default)]
+    pub git: GitColors,
+    #[serde(default)]
+    pub terminal: TerminalColors,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +39,25 @@ pub struct ThemeColors {
     pub button_active: [u8; 3],
     pub selection: [u8; 3],
     pub separator: [u8; 3],
+    /// Squiggles and gutter icons for problems, by severity.
+    #[serde(default = "default_error_color")]
+    pub error: [u8; 3],
+    #[serde(default = "default_warning_color")]
+    pub warning: [u8; 3],
+    #[serde(default = "default_info_color")]
+    pub info: [u8; 3],
+}
+
+fn default_error_color() -> [u8; 3] {
+    [232, 72, 72]
+}
+
+fn default_warning_color() -> [u8; 3] {
+    [222, 170, 50]
+}
+
+fn default_info_color() -> [u8; 3] {
+    [80, 150, 230]
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +73,107 @@ pub struct SyntaxColors {
     pub variables: [u8; 3],
 }
 
+/// Version control decorations in the file tree and editor gutter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GitColors {
+    pub added: [u8; 3],
+    pub modified: [u8; 3],
+    pub deleted: [u8; 3],
+    pub untracked: [u8; 3],
+    pub conflicted: [u8; 3],
+    pub ignored: [u8; 3],
+}
+
+impl Default for GitColors {
+    fn default() -> Self {
+        Self {
+            added: [87, 171, 90],
+            modified: [220, 165, 60],
+            deleted: [214, 80, 80],
+            untracked: [110, 180, 120],
+            conflicted: [230, 100, 160],
+            ignored: [110, 110, 110],
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GitColors {
+    pub fn status_color(&self, status: FileStatus) -> Color32 {
+        let [r, g, b] = match status {
+            FileStatus::Modified => self.modified,
+            FileStatus::Added => self.added,
+            FileStatus::Deleted => self.deleted,
+            FileStatus::Untracked => self.untracked,
+            FileStatus::Conflicted => self.conflicted,
+            FileStatus::Ignored => self.ignored,
+        };
+        Color32::from_rgb(r, g, b)
+    }
+
+    pub fn change_color(&self, kind: LineChangeKind) -> Color32 {
+        let [r, g, b] = match kind {
+            LineChangeKind::Added => self.added,
+            LineChangeKind::Modified => self.modified,
+            LineChangeKind::Deleted => self.deleted,
+        };
+        Color32::from_rgb(r, g, b)
+    }
+}
+
+/// The sixteen ANSI colors of the integrated terminal, normal then bright. Its default
+/// text and background are the editor's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerminalColors {
+    pub ansi: [[u8; 3]; 16],
+}
+
+impl Default for TerminalColors {
+    fn default() -> Self {
+        Self {
+            ansi: [
+                [40, 40, 40],
+                [205, 49, 49],
+                [13, 188, 121],
+                [229, 229, 16],
+                [36, 114, 200],
+                [188, 63, 188],
+                [17, 168, 205],
+                [229, 229, 229],
+                [102, 102, 102],
+                [241, 76, 76],
+                [35, 209, 139],
+                [245, 245, 67],
+                [59, 142, 234],
+                [214, 112, 214],
+                [41, 184, 219],
+                [255, 255, 255],
+            ],
+        }
+    }
+}
+
+impl TerminalColors {
+    /// One of the 256 indexed colors: the theme's sixteen, then a 6×6×6 color cube and
+    /// a ramp of greys.
+    pub fn indexed(&self, index: u8) -> Color32 {
+        match index {
+            0..=15 => {
+                let [r, g, b] = self.ansi[index as usize];
+                Color32::from_rgb(r, g, b)
+            }
+            16..=231 => {
+                let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+                let index = index - 16;
+                Color32::from_rgb(level(index / 36), level(index / 6 % 6), level(index % 6))
+            }
+            _ => Color32::from_gray(8 + (index - 232) * 10),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThemeSpacing {
     pub item_spacing: [f32; 2],
@@ -82,6 +211,9 @@ impl ZenTheme {
                 button_active: [80, 80, 80],
                 selection: [96, 96, 96],
                 separator: [48, 48, 48],
+                error: default_error_color(),
+                warning: default_warning_color(),
+                info: default_info_color(),
             },
             spacing: ThemeSpacing {
                 item_spacing: [8.0, 6.0],
@@ -104,6 +236,8 @@ impl ZenTheme {
                 types: [129, 140, 248],
                 variables: [248, 174, 76],
             },
+            git: GitColors::default(),
+            terminal: TerminalColors::default(),
         }
     }
 
@@ -123,6 +257,9 @@ impl ZenTheme {
                 button_active: [201, 203, 207],
                 selection: [13, 110, 253],
                 separator: [222, 226, 230],
+                error: [205, 30, 30],
+                warning: [190, 125, 0],
+                info: [20, 100, 200],
             },
             spacing: ThemeSpacing {
                 item_spacing: [8.0, 6.0],
@@ -145,6 +282,34 @@ impl ZenTheme {
                 types: [37, 99, 235],
                 variables: [217, 119, 6],
             },
+            git: GitColors {
+                added: [40, 130, 60],
+                modified: [180, 120, 0],
+                deleted: [200, 40, 40],
+                untracked: [50, 140, 80],
+                conflicted: [190, 40, 130],
+                ignored: [150, 150, 150],
+            },
+            terminal: TerminalColors {
+                ansi: [
+                    [0, 0, 0],
+                    [205, 49, 49],
+                    [0, 150, 0],
+                    [148, 152, 0],
+                    [4, 81, 165],
+                    [188, 5, 188],
+                    [5, 152, 188],
+                    [85, 85, 85],
+                    [102, 102, 102],
+                    [205, 49, 49],
+                    [20, 176, 20],
+                    [181, 186, 0],
+                    [4, 81, 165],
+                    [188, 5, 188],
+                    [5, 152, 188],
+                    [165, 165, 165],
+                ],
+            },
         }
     }
 
@@ -813,6 +978,17 @@ impl ZenTheme {
         )
     }
 
+    /// Problems tagged as hints are shown like other text so they stay unobtrusive.
+    pub fn diagnostic_color(&self, severity: Severity) -> Color32 {
+        let [r, g, b] = match severity {
+            Severity::Error => self.colors.error,
+            Severity::Warning => self.colors.warning,
+            Severity::Info => self.colors.info,
+            Severity::Hint => self.colors.text_secondary,
+        };
+        Color32::from_rgb(r, g, b)
+    }
+
     pub fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let json = std::fs::read_to_string(path)?;
         let theme: ZenTheme = serde_json::from_str(&json)?;
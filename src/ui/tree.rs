@@ -1,125 +1,759 @@
-use std::path::PathBuf;
+use crate::file_filter::{FileFilter, FileFilterConfig};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::git::status::GitStatus;
+use crate::ui::theme::GitColors;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::watcher::FsEvent;
+use crate::workspace::WorkspaceFolder;
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+
+/// Whether a directory's entries have been read yet. Directories are listed the
+/// first time they are expanded, so opening a large project only reads the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChildrenState {
+    Unloaded,
+    Loading,
+    Loaded,
+    Failed(String),
+}
 
 #[derive(Clone)]
 pub struct FileTreeNode {
     pub path: PathBuf,
     pub is_directory: bool,
     pub children: Vec<FileTreeNode>,
+    pub children_state: ChildrenState,
     pub expanded: bool,
+    /// Hidden by ignore files or exclude globs; only listed when showing ignored files.
+    pub ignored: bool,
+}
+
+impl FileTreeNode {
+    fn new(path: PathBuf, is_directory: bool) -> Self {
+        Self {
+            path,
+            is_directory,
+            children: Vec::new(),
+            children_state: ChildrenState::Unloaded,
+            expanded: false,
+            ignored: false,
+        }
+    }
+
+    /// Forgets the listing of this folder and every folder below it, keeping
+    /// what is expanded so the tree reloads into the same shape.
+    fn invalidate(&mut self) {
+        if self.is_directory && self.children_state != ChildrenState::Loading {
+            self.children_state = ChildrenState::Unloaded;
+        }
+        for child in &mut self.children {
+            child.invalidate();
+        }
+    }
+}
+
+/// A file operation requested from the explorer. The editor carries these out,
+/// since open buffers have to follow renames and moves.
+pub enum ExplorerCommand {
+    NewFile { dir: PathBuf, name: String },
+    NewFolder { dir: PathBuf, name: String },
+    Rename { path: PathBuf, name: String },
+    Move { path: PathBuf, into: PathBuf },
+    Duplicate(PathBuf),
+    Delete(PathBuf),
+    Reveal(PathBuf),
+    RemoveRoot(PathBuf),
+    Compare(PathBuf, PathBuf),
+}
+
+/// A name being typed into the tree, for a new entry or a rename.
+enum InlineEditKind {
+    NewFile(PathBuf),
+    NewFolder(PathBuf),
+    Rename(PathBuf),
+}
+
+struct InlineEdit {
+    kind: InlineEditKind,
+    text: String,
+    focused: bool,
+}
+
+impl InlineEdit {
+    fn new(kind: InlineEditKind, text: String) -> Self {
+        Self { kind, text, focused: false }
+    }
+
+    fn into_command(self) -> ExplorerCommand {
+        match self.kind {
+            InlineEditKind::NewFile(dir) => ExplorerCommand::NewFile { dir, name: self.text },
+            InlineEditKind::NewFolder(dir) => ExplorerCommand::NewFolder { dir, name: self.text },
+            InlineEditKind::Rename(path) => ExplorerCommand::Rename { path, name: self.text },
+        }
+    }
+
+    fn creates_in(&self, dir: &Path) -> bool {
+        matches!(&self.kind, InlineEditKind::NewFile(d) | InlineEditKind::NewFolder(d) if d == dir)
+    }
+
+    fn renames(&self, path: &Path) -> bool {
+        matches!(&self.kind, InlineEditKind::Rename(p) if p == path)
+    }
+}
+
+/// Per-frame state threaded through the recursive tree rendering.
+struct RenderContext<'a> {
+    root: &'a Path,
+    root_name: Option<&'a str>,
+    multi_root: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    git_status: &'a GitStatus,
+    git_colors: &'a GitColors,
+    selected_file: &'a mut Option<PathBuf>,
+    pending_file_load: &'a mut Option<PathBuf>,
+    compare_selection: &'a mut Vec<PathBuf>,
+    requested: &'a mut Vec<PathBuf>,
+    commands: &'a mut Vec<ExplorerCommand>,
+    inline_edit: &'a mut Option<InlineEdit>,
+}
+
+/// A top-level folder of the project or workspace.
+pub struct ExplorerRoot {
+    pub node: FileTreeNode,
+    pub name: Option<String>,
+    /// Folder-specific filter settings from the workspace file, if any.
+    files: Option<FileFilterConfig>,
+    filter: Arc<FileFilter>,
+}
+
+impl ExplorerRoot {
+    pub fn path(&self) -> &Path {
+        &self.node.path
+    }
+}
+
+/// The result of reading one directory off the UI thread.
+struct DirectoryListing {
+    path: PathBuf,
+    result: Result<Vec<FileTreeNode>, String>,
 }
 
 pub struct FileExplorer {
-    pub root: Option<FileTreeNode>,
+    pub roots: Vec<ExplorerRoot>,
     pub selected_file: Option<PathBuf>,
     pub pending_file_load: Option<PathBuf>,
+    /// Files picked with Ctrl+click for "Compare Selected"; holds at most two.
+    compare_selection: Vec<PathBuf>,
+    filter: Arc<FileFilter>,
+    commands: Vec<ExplorerCommand>,
+    inline_edit: Option<InlineEdit>,
+    #[cfg(not(target_arch = "wasm32"))]
+    git_status: Arc<GitStatus>,
+    listing_sender: mpsc::Sender<DirectoryListing>,
+    listing_receiver: mpsc::Receiver<DirectoryListing>,
 }
 
 impl Default for FileExplorer {
     fn default() -> Self {
+        let (listing_sender, listing_receiver) = mpsc::channel();
         Self {
-            root: None,
+            roots: Vec::new(),
             selected_file: None,
             pending_file_load: None,
+            compare_selection: Vec::new(),
+            filter: Arc::new(FileFilter::default()),
+            commands: Vec::new(),
+            inline_edit: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            git_status: Arc::new(GitStatus::default()),
+            listing_sender,
+            listing_receiver,
         }
     }
 }
 
 impl FileExplorer {
     pub fn open_project(&mut self, path: PathBuf) {
-        self.root = Some(self.build_file_tree(&path, false));
+        self.roots.clear();
+        self.add_root(WorkspaceFolder { path, ..Default::default() });
     }
 
-    fn build_file_tree(&self, dir: &PathBuf, expanded: bool) -> FileTreeNode {
+    pub fn open_workspace(&mut self, folders: Vec<WorkspaceFolder>) {
+        self.roots.clear();
+        for folder in folders {
+            self.add_root(folder);
+        }
+    }
+
+    /// Adds a root folder, unless it is already open.
+    pub fn add_root(&mut self, folder: WorkspaceFolder) -> bool {
+        if self.roots.iter().any(|root| root.path() == folder.path) {
+            return false;
+        }
+
+        let filter = match &folder.files {
+            Some(files) => Arc::new(FileFilter::new(files)),
+            None => self.filter.clone(),
+        };
+        self.roots.push(ExplorerRoot {
+            node: FileTreeNode::new(folder.path, true),
+            name: folder.name,
+            files: folder.files,
+            filter,
+        });
+        true
+    }
+
+    pub fn remove_root(&mut self, path: &Path) {
+        self.roots.retain(|root| root.path() != path);
+    }
+
+    pub fn root_paths(&self) -> Vec<PathBuf> {
+        self.roots.iter().map(|root| root.path().to_path_buf()).collect()
+    }
+
+    /// Each root with the filter that decides which of its files are part of the project.
+    pub fn filtered_roots(&self) -> Vec<(PathBuf, Arc<FileFilter>)> {
+        self.roots.iter().map(|root| (root.path().to_path_buf(), root.filter.clone())).collect()
+    }
+
+    pub fn workspace_folders(&self) -> Vec<WorkspaceFolder> {
+        self.roots
+            .iter()
+            .map(|root| WorkspaceFolder {
+                path: root.path().to_path_buf(),
+                name: root.name.clone(),
+                files: root.files.clone(),
+            })
+            .collect()
+    }
+
+    /// Replaces the ignore rules and relists every folder that has been read.
+    /// Roots with their own settings in the workspace file keep them.
+    pub fn set_filter(&mut self, filter: FileFilter) {
+        self.filter = Arc::new(filter);
+        for root in &mut self.roots {
+            if root.files.is_none() {
+                root.filter = self.filter.clone();
+            }
+            root.node.invalidate();
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_git_status(&mut self, status: Arc<GitStatus>) {
+        self.git_status = status;
+    }
+
+    /// The innermost root containing `path`.
+    fn root_for(&self, path: &Path) -> Option<&ExplorerRoot> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(root.path()))
+            .max_by_key(|root| root.path().components().count())
+    }
+
+    /// Lists the immediate entries of `dir`, without descending into subdirectories.
+    fn read_directory(filter: &FileFilter, root: &Path, dir: &Path) -> Result<Vec<FileTreeNode>, String> {
+        let entries = std::fs::read_dir(dir).map_err(|e| e.to_string())?;
+        let allowed = filter.allowed_entries(dir);
         let mut children = Vec::new();
 
-        if let Ok(entries) = std::fs::read_dir(dir) {
-            let mut entries: Vec<_> = entries.collect();
-            entries.sort_by(|a, b| {
-                let a = a.as_ref().unwrap();
-                let b = b.as_ref().unwrap();
-                let a_is_dir = a.path().is_dir();
-                let b_is_dir = b.path().is_dir();
-
-                match (a_is_dir, b_is_dir) {
-                    (true, false) => std::cmp::Ordering::Less,
-                    (false, true) => std::cmp::Ordering::Greater,
-                    _ => a.file_name().cmp(&b.file_name()),
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            let allowed_by_ignore_files = allowed.as_ref().is_none_or(|allowed| allowed.contains(&path));
+            let ignored = filter.is_ignored(root, &path, allowed_by_ignore_files);
+            if ignored && !filter.show_ignored {
+                continue;
+            }
+
+            let is_directory = match entry.file_type() {
+                Ok(file_type) if file_type.is_symlink() => path.is_dir(),
+                Ok(file_type) => file_type.is_dir(),
+                Err(_) => path.is_dir(),
+            };
+            let mut node = FileTreeNode::new(path, is_directory);
+            node.ignored = ignored;
+            children.push(node);
+        }
+
+        Self::sort_nodes(&mut children);
+        Ok(children)
+    }
+
+    fn filter_for(&self, path: &Path) -> (PathBuf, Arc<FileFilter>) {
+        match self.root_for(path) {
+            Some(root) => (root.path().to_path_buf(), root.filter.clone()),
+            None => (PathBuf::new(), self.filter.clone()),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn request_listing(&self, path: PathBuf, ctx: &egui::Context) {
+        let sender = self.listing_sender.clone();
+        let (root, filter) = self.filter_for(&path);
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let result = Self::read_directory(&filter, &root, &path);
+            let _ = sender.send(DirectoryListing { path, result });
+            ctx.request_repaint();
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn request_listing(&self, path: PathBuf, _ctx: &egui::Context) {
+        let (root, filter) = self.filter_for(&path);
+        let result = Self::read_directory(&filter, &root, &path);
+        let _ = self.listing_sender.send(DirectoryListing { path, result });
+    }
+
+    /// Moves finished directory listings into the tree. Listings for nodes that no
+    /// longer exist (the project was switched or the folder removed) are dropped.
+    fn apply_listings(&mut self) {
+        for listing in self.listing_receiver.try_iter().collect::<Vec<_>>() {
+            let Some(node) = self.find_in_roots(&listing.path) else {
+                continue;
+            };
+            if node.children_state != ChildrenState::Loading {
+                continue;
+            }
+
+            match listing.result {
+                Ok(mut children) => {
+                    // Keep the expansion and listings of folders that were already open.
+                    let mut previous = std::mem::take(&mut node.children);
+                    for child in &mut children {
+                        if let Some(old) = previous.iter_mut().find(|old| old.path == child.path) {
+                            child.expanded = old.expanded;
+                            child.children = std::mem::take(&mut old.children);
+                            child.children_state = old.children_state.clone();
+                        }
+                    }
+                    node.children = children;
+                    node.children_state = ChildrenState::Loaded;
                 }
-            });
+                Err(e) => {
+                    log::warn!("Failed to read {}: {}", listing.path.display(), e);
+                    node.children.clear();
+                    node.children_state = ChildrenState::Failed(e);
+                }
+            }
+        }
+    }
 
-            for entry in entries.into_iter().flatten() {
-                let path = entry.path();
-                let file_name = path.file_name().unwrap().to_string_lossy();
+    fn sort_nodes(nodes: &mut [FileTreeNode]) {
+        nodes.sort_by(|a, b| match (a.is_directory, b.is_directory) {
+            (true, false) => std::cmp::Ordering::Less,
+            (false, true) => std::cmp::Ordering::Greater,
+            _ => a.path.file_name().cmp(&b.path.file_name()),
+        });
+    }
 
-                if file_name.starts_with('.') || file_name == "target" {
-                    continue;
+    /// Patches the tree in place for changes reported by the file watcher.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn apply_fs_events(&mut self, events: &[FsEvent]) {
+        // What the ignore files allow in each folder, looked up once per batch.
+        let mut allowed = HashMap::new();
+        for event in events {
+            if Self::changes_ignore_rules(event) {
+                for root in &mut self.roots {
+                    root.node.invalidate();
                 }
+                return;
+            }
 
-                if path.is_dir() {
-                    children.push(self.build_file_tree(&path, false));
-                } else {
-                    children.push(FileTreeNode {
-                        path,
-                        is_directory: false,
-                        children: Vec::new(),
-                        expanded: false,
-                    });
+            match event {
+                FsEvent::Created(path) => self.insert_path(path, &mut allowed),
+                FsEvent::Removed(path) => self.remove_path(path),
+                FsEvent::Renamed(from, to) => {
+                    self.remove_path(from);
+                    self.insert_path(to, &mut allowed);
                 }
+                FsEvent::Modified(_) => {}
             }
         }
+    }
 
-        FileTreeNode {
-            path: dir.clone(),
-            is_directory: true,
-            children,
-            expanded,
+    #[cfg(not(target_arch = "wasm32"))]
+    fn changes_ignore_rules(event: &FsEvent) -> bool {
+        let is_ignore_file = |path: &Path| {
+            path.file_name()
+                .is_some_and(|name| name == ".gitignore" || name == ".ignore")
+        };
+        match event {
+            FsEvent::Created(path) | FsEvent::Removed(path) | FsEvent::Modified(path) => is_ignore_file(path),
+            FsEvent::Renamed(from, to) => is_ignore_file(from) || is_ignore_file(to),
         }
     }
 
-    pub fn render(&mut self, ui: &mut egui::Ui) {
+    /// Adds a created entry to its folder when that folder is listed. Only then are the
+    /// ignore files consulted, since that walks the folder.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn insert_path(&mut self, path: &Path, allowed: &mut HashMap<PathBuf, Option<HashSet<PathBuf>>>) {
+        let Some(parent_path) = path.parent() else {
+            return;
+        };
+        let Some(parent) = self.find_in_roots(parent_path) else {
+            return;
+        };
+        // Folders that were never listed pick the entry up when they are first expanded.
+        if parent.children_state != ChildrenState::Loaded {
+            return;
+        }
+        if parent.children.iter().any(|child| child.path == path) || !path.exists() {
+            return;
+        }
+
+        let (root, filter) = self.filter_for(path);
+        let allowed_by_ignore_files = allowed
+            .entry(parent_path.to_path_buf())
+            .or_insert_with(|| filter.allowed_entries(parent_path))
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(path));
+        let ignored = filter.is_ignored(&root, path, allowed_by_ignore_files);
+        if ignored && !filter.show_ignored {
+            return;
+        }
+
+        let Some(parent) = self.find_in_roots(parent_path) else {
+            return;
+        };
+        let mut node = FileTreeNode::new(path.to_path_buf(), path.is_dir());
+        node.ignored = ignored;
+        parent.children.push(node);
+        Self::sort_nodes(&mut parent.children);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn remove_path(&mut self, path: &Path) {
+        let Some(parent_path) = path.parent() else {
+            return;
+        };
+
+        if let Some(parent) = self.find_in_roots(parent_path) {
+            parent.children.retain(|child| child.path != path);
+        }
+        if self.selected_file.as_deref() == Some(path) {
+            self.selected_file = None;
+        }
+    }
+
+    fn find_in_roots(&mut self, path: &Path) -> Option<&mut FileTreeNode> {
+        self.roots
+            .iter_mut()
+            .filter(|root| path.starts_with(root.path()))
+            .max_by_key(|root| root.path().components().count())
+            .and_then(|root| Self::find_node_mut(&mut root.node, path))
+    }
+
+    fn find_node_mut<'a>(node: &'a mut FileTreeNode, path: &Path) -> Option<&'a mut FileTreeNode> {
+        if node.path == path {
+            return Some(node);
+        }
+        if !path.starts_with(&node.path) {
+            return None;
+        }
+
+        node.children
+            .iter_mut()
+            .filter(|child| child.is_directory)
+            .find_map(|child| Self::find_node_mut(child, path))
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, git_colors: &GitColors) {
+        self.apply_listings();
+
+        let mut requested = Vec::new();
+
+        // Roots are listed straight away so expanding them feels instant.
+        for root in &mut self.roots {
+            if root.node.children_state == ChildrenState::Unloaded {
+                root.node.children_state = ChildrenState::Loading;
+                requested.push(root.node.path.clone());
+            }
+        }
+
+        let multi_root = self.roots.len() > 1;
         egui::ScrollArea::both()
             .auto_shrink([false; 2])
             .show(ui, |ui| {
-                if let Some(tree) = &mut self.root {
-                    Self::render_node_static(tree, ui, 0, &mut self.selected_file, &mut self.pending_file_load);
+                for root in &mut self.roots {
+                    let root_path = root.node.path.clone();
+                    let mut context = RenderContext {
+                        root: &root_path,
+                        root_name: root.name.as_deref(),
+                        multi_root,
+                        #[cfg(not(target_arch = "wasm32"))]
+                        git_status: &self.git_status,
+                        git_colors,
+                        selected_file: &mut self.selected_file,
+                        pending_file_load: &mut self.pending_file_load,
+                        compare_selection: &mut self.compare_selection,
+                        requested: &mut requested,
+                        commands: &mut self.commands,
+                        inline_edit: &mut self.inline_edit,
+                    };
+                    Self::render_node_static(&mut root.node, ui, 0, &mut context);
                 }
             });
+
+        for path in requested {
+            self.request_listing(path, ui.ctx());
+        }
     }
 
-    fn render_node_static(
-        node: &mut FileTreeNode,
-        ui: &mut egui::Ui,
-        depth: usize,
-        selected_file: &mut Option<PathBuf>,
-        pending_file_load: &mut Option<PathBuf>
-    ) {
+    fn render_node_static(node: &mut FileTreeNode, ui: &mut egui::Ui, depth: usize, context: &mut RenderContext<'_>) {
         let indent = depth as f32 * 15.0;
 
         ui.horizontal(|ui| {
             ui.add_space(indent);
 
-            if node.is_directory {
+            if node.ignored {
+                ui.multiply_opacity(0.5);
+            }
+
+            if context.inline_edit.as_ref().is_some_and(|edit| edit.renames(&node.path)) {
+                let icon = if node.is_directory { "📁" } else { "📄" };
+                Self::render_inline_edit(ui, icon, context);
+                return;
+            }
+
+            let name = match context.root_name.filter(|_| depth == 0) {
+                Some(name) => name.into(),
+                None => node.path.file_name().unwrap_or(node.path.as_os_str()).to_string_lossy(),
+            };
+            let label = |icon: &str| Self::decorated_label(&node.path, &format!("{} {}", icon, name), context);
+            let response = if node.is_directory {
                 let icon = if node.expanded { "📂" } else { "📁" };
-                let name = node.path.file_name().unwrap().to_string_lossy();
-                if ui.selectable_label(false, format!("{} {}", icon, name)).clicked() {
+                let response = ui.selectable_label(false, label(icon));
+                if response.clicked() {
                     node.expanded = !node.expanded;
+
+                    // Expanding a folder that failed before retries the listing.
+                    if node.expanded && matches!(node.children_state, ChildrenState::Failed(_)) {
+                        node.children_state = ChildrenState::Unloaded;
+                    }
                 }
+                response
             } else {
-                let icon = "📄";
-                let name = node.path.file_name().unwrap().to_string_lossy();
-                if ui.selectable_label(
-                    selected_file.as_ref() == Some(&node.path),
-                    format!("{} {}", icon, name)
-                ).clicked() {
-                    *pending_file_load = Some(node.path.clone());
-                    *selected_file = Some(node.path.clone());
+                let selected = context.selected_file.as_ref() == Some(&node.path)
+                    || context.compare_selection.contains(&node.path);
+                let response = ui.selectable_label(selected, label("📄"));
+                if response.clicked() && ui.input(|i| i.modifiers.command) {
+                    Self::toggle_compare_selection(&node.path, context);
+                } else if response.clicked() {
+                    *context.pending_file_load = Some(node.path.clone());
+                    *context.selected_file = Some(node.path.clone());
+                    *context.compare_selection = vec![node.path.clone()];
                 }
+                response
+            };
+
+            let is_root = depth == 0;
+            let response = response.interact(egui::Sense::drag());
+            if !is_root {
+                response.dnd_set_drag_payload(node.path.clone());
             }
+            if node.is_directory {
+                Self::handle_drop(ui, &response, node, context);
+            }
+
+            response.context_menu(|ui| Self::render_context_menu(ui, node, is_root, context));
         });
 
-        if node.is_directory && node.expanded {
-            for child in &mut node.children {
-                Self::render_node_static(child, ui, depth + 1, selected_file, pending_file_load);
+        if !node.is_directory || !node.expanded {
+            return;
+        }
+
+        if node.children_state == ChildrenState::Unloaded {
+            node.children_state = ChildrenState::Loading;
+            context.requested.push(node.path.clone());
+        }
+
+        let child_indent = (depth + 1) as f32 * 15.0;
+
+        if let Some(edit) = context.inline_edit.as_ref().filter(|edit| edit.creates_in(&node.path)) {
+            let icon = if matches!(edit.kind, InlineEditKind::NewFolder(_)) { "📁" } else { "📄" };
+            ui.horizontal(|ui| {
+                ui.add_space(child_indent);
+                Self::render_inline_edit(ui, icon, context);
+            });
+        }
+
+        match &node.children_state {
+            // A folder being relisted keeps showing its old entries until the new ones arrive.
+            ChildrenState::Loading | ChildrenState::Unloaded if node.children.is_empty() => {
+                ui.horizontal(|ui| {
+                    ui.add_space(child_indent);
+                    ui.spinner();
+                    ui.weak("Loading…");
+                });
+            }
+            ChildrenState::Failed(error) => {
+                ui.horizontal(|ui| {
+                    ui.add_space(child_indent);
+                    ui.colored_label(ui.visuals().error_fg_color, format!("⚠ {}", error));
+                });
+            }
+            _ => {
+                for child in &mut node.children {
+                    Self::render_node_static(child, ui, depth + 1, context);
+                }
+            }
+        }
+    }
+
+    /// Colors an entry by its version control status and appends the status letter.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn decorated_label(path: &Path, text: &str, context: &RenderContext<'_>) -> egui::WidgetText {
+        match context.git_status.status_of(path) {
+            Some(status) => egui::RichText::new(format!("{}  {}", text, status.badge()))
+                .color(context.git_colors.status_color(status))
+                .into(),
+            None => text.into(),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn decorated_label(_path: &Path, text: &str, _context: &RenderContext<'_>) -> egui::WidgetText {
+        text.into()
+    }
+
+    /// Accepts an entry dragged onto a folder and asks for it to be moved there.
+    fn handle_drop(ui: &egui::Ui, response: &egui::Response, node: &FileTreeNode, context: &mut RenderContext<'_>) {
+        let accepts = |dragged: &Path| dragged.parent() != Some(node.path.as_path()) && !node.path.starts_with(dragged);
+
+        if response.dnd_hover_payload::<PathBuf>().is_some_and(|dragged| accepts(&dragged)) {
+            ui.painter().rect_stroke(
+                response.rect,
+                2.0,
+                ui.visuals().selection.stroke,
+                egui::StrokeKind::Inside,
+            );
+        }
+
+        if let Some(dragged) = response.dnd_release_payload::<PathBuf>() {
+            if accepts(&dragged) {
+                context.commands.push(ExplorerCommand::Move {
+                    path: dragged.as_ref().clone(),
+                    into: node.path.clone(),
+                });
+            }
+        }
+    }
+
+    fn render_context_menu(ui: &mut egui::Ui, node: &mut FileTreeNode, is_root: bool, context: &mut RenderContext<'_>) {
+        let dir = if node.is_directory {
+            node.path.clone()
+        } else {
+            node.path.parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+
+        if ui.button("New File…").clicked() {
+            node.expanded |= node.is_directory;
+            *context.inline_edit = Some(InlineEdit::new(InlineEditKind::NewFile(dir.clone()), String::new()));
+            ui.close();
+        }
+        if ui.button("New Folder…").clicked() {
+            node.expanded |= node.is_directory;
+            *context.inline_edit = Some(InlineEdit::new(InlineEditKind::NewFolder(dir), String::new()));
+            ui.close();
+        }
+
+        if !is_root {
+            ui.separator();
+
+            if ui.button("Rename…").clicked() {
+                let name = node.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                *context.inline_edit = Some(InlineEdit::new(InlineEditKind::Rename(node.path.clone()), name));
+                ui.close();
+            }
+            if ui.button("Duplicate").clicked() {
+                context.commands.push(ExplorerCommand::Duplicate(node.path.clone()));
+                ui.close();
+            }
+            if ui.button("Delete…").clicked() {
+                context.commands.push(ExplorerCommand::Delete(node.path.clone()));
+                ui.close();
+            }
+        }
+
+        if !node.is_directory {
+            ui.separator();
+
+            let selection = match context.compare_selection.as_slice() {
+                [a, b] if node.path == *a || node.path == *b => Some((a.clone(), b.clone())),
+                [other] if *other != node.path => Some((other.clone(), node.path.clone())),
+                _ => None,
+            };
+            let button = ui
+                .add_enabled(selection.is_some(), egui::Button::new("Compare Selected"))
+                .on_disabled_hover_text("Ctrl+click two files to compare them");
+            if let (true, Some((old, new))) = (button.clicked(), selection) {
+                context.commands.push(ExplorerCommand::Compare(old, new));
+                ui.close();
+            }
+        }
+
+        if is_root && context.multi_root {
+            ui.separator();
+
+            if ui.button("Remove Folder from Workspace").clicked() {
+                context.commands.push(ExplorerCommand::RemoveRoot(node.path.clone()));
+                ui.close();
+            }
+        }
+
+        ui.separator();
+
+        if ui.button("Copy Path").clicked() {
+            ui.ctx().copy_text(node.path.display().to_string());
+            ui.close();
+        }
+        if ui.button("Copy Relative Path").clicked() {
+            let relative = node.path.strip_prefix(context.root).unwrap_or(&node.path);
+            ui.ctx().copy_text(relative.display().to_string());
+            ui.close();
+        }
+        if ui.button("Reveal in File Manager").clicked() {
+            context.commands.push(ExplorerCommand::Reveal(node.path.clone()));
+            ui.close();
+        }
+    }
+
+    /// Adds a file to the compare selection or takes it out again, keeping the two most recent picks.
+    fn toggle_compare_selection(path: &Path, context: &mut RenderContext<'_>) {
+        if let Some(index) = context.compare_selection.iter().position(|p| p == path) {
+            context.compare_selection.remove(index);
+            return;
+        }
+        if context.compare_selection.len() == 2 {
+            context.compare_selection.remove(0);
+        }
+        context.compare_selection.push(path.to_path_buf());
+    }
+
+    /// Draws the name field of an inline edit. Enter confirms; Escape or clicking away cancels.
+    fn render_inline_edit(ui: &mut egui::Ui, icon: &str, context: &mut RenderContext<'_>) {
+        let Some(edit) = context.inline_edit.as_mut() else {
+            return;
+        };
+
+        ui.label(icon);
+        let response = ui.add(egui::TextEdit::singleline(&mut edit.text).desired_width(f32::INFINITY));
+        if !edit.focused {
+            response.request_focus();
+            edit.focused = true;
+        }
+
+        if response.lost_focus() {
+            let confirmed = ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if let Some(edit) = context.inline_edit.take().filter(|_| confirmed) {
+                context.commands.push(edit.into_command());
             }
         }
     }
@@ -127,4 +761,14 @@ impl FileExplorer {
     pub fn take_pending_file(&mut self) -> Option<PathBuf> {
         self.pending_file_load.take()
     }
-}
\ No newline at end of file
+
+    pub fn take_commands(&mut self) -> Vec<ExplorerCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
+    /// Selects and opens a file, e.g. one just created from the context menu.
+    pub fn open_file(&mut self, path: PathBuf) {
+        self.selected_file = Some(path.clone());
+        self.pending_file_load = Some(path);
+    }
+}
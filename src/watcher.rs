@@ -0,0 +1,218 @@
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// A change on disk, reduced to what the file tree and open buffers care about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsEvent {
+    Created(PathBuf),
+    Removed(PathBuf),
+    Modified(PathBuf),
+    Renamed(PathBuf, PathBuf),
+}
+
+/// Watches the project roots recursively and the open document's folder, using the
+/// platform's native backend (inotify on Linux) and falling back to polling for any
+/// folder the native backend cannot watch, such as past the inotify watch limit.
+///
+/// Setting up a recursive watch walks the whole tree, so it happens on a background
+/// thread; this side only sends it what to watch.
+pub struct FileWatcher {
+    commands: mpsc::Sender<Command>,
+    receiver: mpsc::Receiver<FsEvent>,
+    roots: Vec<PathBuf>,
+    document_dir: Option<PathBuf>,
+}
+
+enum Command {
+    Watch(PathBuf, RecursiveMode),
+    Unwatch(PathBuf),
+}
+
+impl FileWatcher {
+    const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+    pub fn new(ctx: egui::Context) -> Result<Self, String> {
+        let (sender, receiver) = mpsc::channel();
+        let (commands, command_receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("file-watcher".to_string())
+            .spawn(move || {
+                let mut backends = Backends::new(sender, ctx);
+                for command in command_receiver {
+                    match command {
+                        Command::Watch(path, mode) => backends.watch(path, mode),
+                        Command::Unwatch(path) => backends.unwatch(&path),
+                    }
+                }
+            })
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            commands,
+            receiver,
+            roots: Vec::new(),
+            document_dir: None,
+        })
+    }
+
+    /// Starts and stops watching so exactly `roots` are watched.
+    pub fn watch_roots(&mut self, roots: &[PathBuf]) {
+        if self.roots == roots {
+            return;
+        }
+
+        for old in self.roots.iter().filter(|old| !roots.contains(old)) {
+            let _ = self.commands.send(Command::Unwatch(old.clone()));
+        }
+        for root in roots.iter().filter(|root| !self.roots.contains(root)) {
+            let _ = self.commands.send(Command::Watch(root.clone(), RecursiveMode::Recursive));
+        }
+
+        // Roots that fail to watch are remembered too, so they are not retried every frame.
+        self.roots = roots.to_vec();
+    }
+
+    /// Watches the folder of the open document when it lives outside every project root.
+    pub fn watch_document(&mut self, path: Option<&Path>) {
+        let dir = path
+            .filter(|p| !self.roots.iter().any(|root| p.starts_with(root)))
+            .and_then(Path::parent)
+            .map(Path::to_path_buf);
+
+        if dir == self.document_dir {
+            return;
+        }
+
+        if let Some(old) = self.document_dir.take() {
+            let _ = self.commands.send(Command::Unwatch(old));
+        }
+        if let Some(dir) = dir {
+            let _ = self.commands.send(Command::Watch(dir.clone(), RecursiveMode::NonRecursive));
+            self.document_dir = Some(dir);
+        }
+    }
+
+    pub fn drain(&self) -> Vec<FsEvent> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+/// The watchers on the background thread, with which one watches each path.
+struct Backends {
+    native: Option<Box<dyn Watcher + Send>>,
+    /// Created the first time a path cannot be watched natively.
+    poller: Option<notify::PollWatcher>,
+    /// Each watched path, and whether the native watcher has it rather than the poller.
+    watched: HashMap<PathBuf, bool>,
+    sender: mpsc::Sender<FsEvent>,
+    ctx: egui::Context,
+}
+
+impl Backends {
+    fn new(sender: mpsc::Sender<FsEvent>, ctx: egui::Context) -> Self {
+        let native_sender = sender.clone();
+        let native_ctx = ctx.clone();
+        let native = match notify::recommended_watcher(move |event| forward(event, &native_sender, &native_ctx)) {
+            Ok(watcher) => Some(Box::new(watcher) as Box<dyn Watcher + Send>),
+            Err(e) => {
+                log::warn!("Native file watching unavailable ({}), falling back to polling", e);
+                None
+            }
+        };
+        Self {
+            native,
+            poller: None,
+            watched: HashMap::new(),
+            sender,
+            ctx,
+        }
+    }
+
+    fn watch(&mut self, path: PathBuf, mode: RecursiveMode) {
+        if let Some(native) = &mut self.native {
+            match native.watch(&path, mode) {
+                Ok(()) => {
+                    self.watched.insert(path, true);
+                    return;
+                }
+                Err(e) => {
+                    log::warn!("Failed to watch {} natively ({}), polling it instead", path.display(), e);
+                    // A recursive watch can fail halfway, leaving watches on some subfolders.
+                    let _ = native.unwatch(&path);
+                }
+            }
+        }
+
+        let poller = match &mut self.poller {
+            Some(poller) => poller,
+            None => {
+                let sender = self.sender.clone();
+                let ctx = self.ctx.clone();
+                let config = notify::Config::default().with_poll_interval(FileWatcher::POLL_INTERVAL);
+                match notify::PollWatcher::new(move |event| forward(event, &sender, &ctx), config) {
+                    Ok(poller) => self.poller.insert(poller),
+                    Err(e) => {
+                        log::warn!("Failed to watch {}: {}", path.display(), e);
+                        return;
+                    }
+                }
+            }
+        };
+        match poller.watch(&path, mode) {
+            Ok(()) => {
+                self.watched.insert(path, false);
+            }
+            Err(e) => log::warn!("Failed to watch {}: {}", path.display(), e),
+        }
+    }
+
+    fn unwatch(&mut self, path: &Path) {
+        let result = match self.watched.remove(path) {
+            Some(true) => self.native.as_mut().map(|native| native.unwatch(path)),
+            Some(false) => self.poller.as_mut().map(|poller| poller.unwatch(path)),
+            None => None,
+        };
+        if let Some(Err(e)) = result {
+            log::debug!("Failed to stop watching {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn forward(event: notify::Result<Event>, sender: &mpsc::Sender<FsEvent>, ctx: &egui::Context) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            log::warn!("File watcher error: {}", e);
+            return;
+        }
+    };
+
+    let mut paths = event.paths.into_iter();
+    let events: Vec<FsEvent> = match event.kind {
+        EventKind::Create(_) => paths.map(FsEvent::Created).collect(),
+        EventKind::Remove(_) => paths.map(FsEvent::Removed).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => match (paths.next(), paths.next()) {
+            (Some(from), Some(to)) => vec![FsEvent::Renamed(from, to)],
+            _ => Vec::new(),
+        },
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => paths.map(FsEvent::Removed).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => paths.map(FsEvent::Created).collect(),
+        EventKind::Modify(ModifyKind::Name(_)) => paths
+            .map(|p| if p.exists() { FsEvent::Created(p) } else { FsEvent::Removed(p) })
+            .collect(),
+        EventKind::Modify(_) => paths.map(FsEvent::Modified).collect(),
+        _ => Vec::new(),
+    };
+
+    if events.is_empty() {
+        return;
+    }
+    for event in events {
+        let _ = sender.send(event);
+    }
+    ctx.request_repaint();
+}
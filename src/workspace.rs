@@ -0,0 +1,83 @@
+use crate::file_filter::FileFilterConfig;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+pub const WORKSPACE_EXTENSION: &str = "zen-workspace";
+
+/// Drops `.` and folds `..` in a path without touching the file system, so the same
+/// folder reached two ways compares equal.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// One root folder of a workspace.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceFolder {
+    /// Absolute, or relative to the workspace file.
+    pub path: PathBuf,
+    /// Shown in the explorer instead of the folder name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Replaces the global file filter settings for this folder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<FileFilterConfig>,
+}
+
+/// A `*.zen-workspace` file: several project folders opened together.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Workspace {
+    pub folders: Vec<WorkspaceFolder>,
+}
+
+impl Workspace {
+    pub fn is_workspace_file(path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == WORKSPACE_EXTENSION)
+    }
+
+    /// Reads a workspace file, resolving folder paths against its location.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut workspace: Workspace = serde_json::from_str(&content)
+            .map_err(|e| format!("{} is not a valid workspace file: {}", path.display(), e))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for folder in &mut workspace.folders {
+            if folder.path.is_relative() {
+                folder.path = normalize(&base.join(&folder.path));
+            }
+        }
+        Ok(workspace)
+    }
+
+    /// Writes the workspace, storing folders relative to the file when they sit below it.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let base = path.parent().unwrap_or(Path::new(""));
+        let mut workspace = self.clone();
+        for folder in &mut workspace.folders {
+            if let Ok(relative) = folder.path.strip_prefix(base) {
+                folder.path = if relative.as_os_str().is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    relative.to_path_buf()
+                };
+            }
+        }
+
+        let json = serde_json::to_string_pretty(&workspace).map_err(|e| e.to_string())?;
+        crate::document::writer::write_atomic(path, json.as_bytes())
+            .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+    }
+}
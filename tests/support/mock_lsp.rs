@@ -0,0 +1,138 @@
+//! A language server that speaks just enough of the protocol for the client's tests.
+//!
+//! It keeps the text of open documents, applying incremental changes itself, and
+//! reports it back as a `mock/text` notification after every `didOpen` and `didChange`.
+//! Every message it receives is appended to a log as `<pid> <method>` so tests can check
+//! what was sent and in which order; `exit` is logged as `exit 0` after a `shutdown` and
+//! `exit 1` without one.
+//!
+//! Usage: `mock-lsp <log> [--shutdown-delay <ms>]`. A `mock/crash` notification makes it
+//! exit at once, like a server that crashed.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::time::Duration;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let log_path = args.next().expect("usage: mock-lsp <log> [--shutdown-delay <ms>]");
+    let mut shutdown_delay = Duration::ZERO;
+    while let Some(arg) = args.next() {
+        if arg == "--shutdown-delay" {
+            let millis = args.next().and_then(|ms| ms.parse().ok()).unwrap_or(0);
+            shutdown_delay = Duration::from_millis(millis);
+        }
+    }
+
+    let mut log = std::fs::OpenOptions::new().create(true).append(true).open(log_path).unwrap();
+    let mut record = |entry: &str| {
+        writeln!(log, "{} {}", std::process::id(), entry).unwrap();
+    };
+
+    let mut reader = BufReader::new(std::io::stdin().lock());
+    let mut stdout = std::io::stdout().lock();
+    let mut documents: HashMap<String, String> = HashMap::new();
+    let mut shut_down = false;
+
+    while let Some(message) = read_message(&mut reader) {
+        let method = message["method"].as_str().unwrap_or("").to_string();
+        record(&method);
+        let params = &message["params"];
+        match method.as_str() {
+            "initialize" => {
+                let capabilities = json!({ "textDocumentSync": 2, "hoverProvider": true });
+                reply(&mut stdout, &message["id"], json!({ "capabilities": capabilities }));
+            }
+            "shutdown" => {
+                std::thread::sleep(shutdown_delay);
+                shut_down = true;
+                reply(&mut stdout, &message["id"], Value::Null);
+            }
+            "exit" => {
+                record(if shut_down { "exit 0" } else { "exit 1" });
+                std::process::exit(if shut_down { 0 } else { 1 });
+            }
+            "mock/crash" => std::process::exit(101),
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+                let text = params["textDocument"]["text"].as_str().unwrap_or("").to_string();
+                documents.insert(uri.clone(), text);
+                report_text(&mut stdout, &uri, &documents[&uri]);
+            }
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+                let Some(text) = documents.get_mut(&uri) else {
+                    continue;
+                };
+                for change in params["contentChanges"].as_array().into_iter().flatten() {
+                    let new_text = change["text"].as_str().unwrap_or("");
+                    match change.get("range") {
+                        Some(range) => {
+                            record("incremental");
+                            let start = offset(text, &range["start"]);
+                            let end = offset(text, &range["end"]);
+                            text.replace_range(start..end, new_text);
+                        }
+                        None => *text = new_text.to_string(),
+                    }
+                }
+                report_text(&mut stdout, &uri, text);
+            }
+            _ => {
+                if !message["id"].is_null() {
+                    reply(&mut stdout, &message["id"], Value::Null);
+                }
+            }
+        }
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+    let mut body = vec![0; length?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+fn send(stdout: &mut impl Write, message: Value) {
+    let body = message.to_string();
+    write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    stdout.flush().unwrap();
+}
+
+fn reply(stdout: &mut impl Write, id: &Value, result: Value) {
+    send(stdout, json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+}
+
+fn report_text(stdout: &mut impl Write, uri: &str, text: &str) {
+    send(stdout, json!({ "jsonrpc": "2.0", "method": "mock/text", "params": { "uri": uri, "text": text } }));
+}
+
+/// The byte offset of an LSP position, whose column counts UTF-16 code units.
+fn offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+    let line_start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let mut units = 0;
+    for (index, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}